@@ -0,0 +1,35 @@
+use std::env;
+use std::process::Command;
+
+fn git_commit() -> Option<String> {
+    if let Ok(commit) = env::var("ABSH_GIT_COMMIT") {
+        return Some(commit);
+    }
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_owned())
+}
+
+fn features() -> String {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| k.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase()))
+        .collect();
+    features.sort();
+    features.join(",")
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=ABSH_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let commit = git_commit().unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=ABSH_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=ABSH_FEATURES={}", features());
+}
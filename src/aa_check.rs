@@ -0,0 +1,202 @@
+//! A/A check with `--aa-check`: two copies of the baseline are compared before
+//! the real benchmark, and any significant difference between them is noise.
+
+use std::fmt;
+use std::fmt::Write as _;
+
+use crate::ansi;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Stat;
+use crate::math::ratio::Verdict;
+use crate::math::significance::SignificanceTest;
+use crate::measure::key::MeasureKey;
+use crate::run_log::RunLog;
+use crate::runner::Runner;
+use crate::runner::RunnerOptions;
+use crate::student::ConfInterval;
+
+/// Iterations of the A/A check.
+pub const AA_CHECK_ITERATIONS: u64 = 10;
+
+/// Two copies of `test` without samples, named like `A-aa1` and `A-aa2`.
+pub fn aa_experiments(test: &Experiment) -> ExperimentMap<Experiment> {
+    let mut experiments = ExperimentMap::default();
+    for copy in 1..=2 {
+        let name = ExperimentName::intern(&format!("{}-aa{}", test.name, copy));
+        let mut experiment = Experiment::new(name, test.warmup.clone(), test.run.clone());
+        experiment.when = test.when.clone();
+        experiment.env = test.env.clone();
+        experiment.dir = test.dir.clone();
+        experiments.insert(name, experiment);
+    }
+    experiments
+}
+
+/// Wall time comparison of the two copies.
+#[derive(Copy, Clone, Debug)]
+pub struct AaCheck {
+    pub interval: RatioInterval,
+    pub p: Option<f64>,
+    /// Test of `p`.
+    pub significance: SignificanceTest,
+    /// Statistic and level of `interval`.
+    pub stat: Stat,
+    pub conf: ConfInterval,
+}
+
+impl AaCheck {
+    /// Compare copies made by `aa_experiments`, `None` with fewer than two runs of either.
+    pub fn compare(
+        experiments: &ExperimentMap<Experiment>,
+        significance: SignificanceTest,
+        stat: Stat,
+        conf: ConfInterval,
+    ) -> Option<AaCheck> {
+        let mut numbers = experiments
+            .values()
+            .map(|t| &t.measures[MeasureKey::WallTime]);
+        let (a, b) = (numbers.next()?, numbers.next()?);
+        if a.len() < 2 || b.len() < 2 {
+            return None;
+        }
+        Some(AaCheck {
+            interval: RatioInterval::compare(stat, conf, a, b),
+            p: significance.p_value(a, b),
+            significance,
+            stat,
+            conf,
+        })
+    }
+
+    /// Identical scripts were reported as different.
+    pub fn false_positive(&self) -> bool {
+        self.interval.verdict() != Verdict::NoDifference
+    }
+}
+
+impl fmt::Display for AaCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ratio {:.3}, {} conf {:.3}..{:.3}, p={} ({})",
+            self.stat.name(),
+            self.interval.ratio,
+            self.conf,
+            self.interval.min,
+            self.interval.max,
+            self.p.map_or("?".to_owned(), |p| format!("{:.4}", p)),
+            self.significance.name(),
+        )
+    }
+}
+
+/// Run two copies of `test` and report whether they were found different.
+pub fn run_aa_check(
+    log: &mut RunLog,
+    options: RunnerOptions,
+    test: &Experiment,
+    significance: SignificanceTest,
+    stat: Stat,
+    conf: ConfInterval,
+) -> anyhow::Result<()> {
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "A/A check: running {} against itself for {} iterations",
+        test.name.name_colored(),
+        AA_CHECK_ITERATIONS
+    )?;
+    let mut runner = Runner::new(aa_experiments(test), options)?;
+    for _ in 0..AA_CHECK_ITERATIONS {
+        runner.run_iteration(log)?;
+    }
+    writeln!(log.both_log_and_stderr())?;
+    match AaCheck::compare(&runner.experiments, significance, stat, conf) {
+        Some(check) if check.false_positive() => writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}A/A check failed: identical scripts differ, {}{reset}\n\
+             {yellow}The environment is too noisy, differences of this size are not meaningful.{reset}",
+            check,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?,
+        Some(check) => writeln!(
+            log.both_log_and_stderr(),
+            "A/A check passed: no difference detected, {}",
+            check
+        )?,
+        None => writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}A/A check inconclusive: fewer than two successful runs{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_check::AaCheck;
+    use crate::aa_check::aa_experiments;
+    use crate::experiment::Experiment;
+    use crate::experiment_name::ExperimentName;
+    use crate::math::ratio::Stat;
+    use crate::math::significance::SignificanceTest;
+    use crate::measure::key::MeasureKey;
+    use crate::student::TWO_SIDED_95;
+
+    #[test]
+    fn compare() {
+        let name = ExperimentName::intern("aa-test");
+        let mut test = Experiment::new(name, "w".to_owned(), "r".to_owned());
+        test.env.push(("X".to_owned(), "1".to_owned()));
+        let mut experiments = aa_experiments(&test);
+        let names: Vec<&str> = experiments.keys().map(|n| n.name()).collect();
+        assert_eq!(vec!["aa-test-aa1", "aa-test-aa2"], names);
+        assert!(experiments.values().all(|t| t.env == test.env));
+        assert!(
+            AaCheck::compare(
+                &experiments,
+                SignificanceTest::Welch,
+                Stat::Mean,
+                TWO_SIDED_95
+            )
+            .is_none()
+        );
+
+        let samples = [[100, 102, 101, 99], [101, 99, 100, 102]];
+        for (t, samples) in experiments.values_mut().zip(samples.iter()) {
+            for &s in samples {
+                t.measures[MeasureKey::WallTime].push(s);
+            }
+        }
+        let check = AaCheck::compare(
+            &experiments,
+            SignificanceTest::Welch,
+            Stat::Mean,
+            TWO_SIDED_95,
+        )
+        .unwrap();
+        assert!(!check.false_positive(), "{}", check);
+
+        let b = experiments.values_mut().nth(1).unwrap();
+        b.clear_samples();
+        for &s in [300, 301, 302, 300].iter() {
+            b.measures[MeasureKey::WallTime].push(s);
+        }
+        assert!(
+            AaCheck::compare(
+                &experiments,
+                SignificanceTest::Welch,
+                Stat::Mean,
+                TWO_SIDED_95
+            )
+            .unwrap()
+            .false_positive()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Samples this many times the running median are anomalous, given as `2x-median`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AlertThreshold(pub f64);
+
+/// Running median needs this many samples to be meaningful.
+const MIN_SAMPLES: usize = 3;
+
+impl AlertThreshold {
+    /// Ratio of the sample to the median of `previous` samples if it is above the threshold.
+    pub fn check(&self, sample: u64, previous_median: Option<u64>, previous: usize) -> Option<f64> {
+        let median = previous_median.filter(|&m| m > 0 && previous >= MIN_SAMPLES)?;
+        let ratio = sample as f64 / median as f64;
+        (ratio > self.0).then_some(ratio)
+    }
+}
+
+impl FromStr for AlertThreshold {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<AlertThreshold> {
+        let factor = s.strip_suffix("-median").unwrap_or(s);
+        let factor = factor.strip_suffix('x').unwrap_or(factor);
+        match factor.parse::<f64>() {
+            Ok(f) if f > 1.0 => Ok(AlertThreshold(f)),
+            _ => Err(anyhow::anyhow!(
+                "expecting factor above 1 like `2x-median`: `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AlertThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x-median", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alert::AlertThreshold;
+
+    #[test]
+    fn parse() {
+        assert_eq!(AlertThreshold(2.0), "2x-median".parse().unwrap());
+        assert_eq!(AlertThreshold(1.5), "1.5x".parse().unwrap());
+        assert_eq!(AlertThreshold(3.0), "3".parse().unwrap());
+        assert!("0.5x".parse::<AlertThreshold>().is_err());
+        assert!("median".parse::<AlertThreshold>().is_err());
+        assert_eq!("2x-median", AlertThreshold(2.0).to_string());
+    }
+
+    #[test]
+    fn check() {
+        let t = AlertThreshold(2.0);
+        assert_eq!(Some(2.5), t.check(250, Some(100), 5));
+        assert_eq!(None, t.check(150, Some(100), 5));
+        assert_eq!(None, t.check(250, Some(100), 2));
+        assert_eq!(None, t.check(250, None, 0));
+    }
+}
@@ -0,0 +1,119 @@
+//! Automatic warmup with `--auto-warmup`: runs of each variant are excluded from
+//! statistics until its wall time stabilizes.
+
+use std::fmt::Write;
+
+use crate::ansi;
+use crate::experiment_map::ExperimentMap;
+use crate::measure::key::MeasureKey;
+use crate::percent::Percent;
+use crate::run_log::RunLog;
+use crate::runner::Runner;
+
+/// Consecutive runs whose wall time must vary less than the threshold.
+pub const AUTO_WARMUP_WINDOW: usize = 5;
+/// Iterations after which variants are considered warmed up even if not stable.
+pub const AUTO_WARMUP_MAX_ITERATIONS: u64 = 50;
+
+/// Coefficient of variation of the last `AUTO_WARMUP_WINDOW` samples, `None` with fewer.
+pub fn window_cv(samples: &[u64]) -> Option<f64> {
+    if samples.len() < AUTO_WARMUP_WINDOW {
+        return None;
+    }
+    let window = &samples[samples.len() - AUTO_WARMUP_WINDOW..];
+    let n = window.len() as f64;
+    let mean = window.iter().map(|&s| s as f64).sum::<f64>() / n;
+    let var = window
+        .iter()
+        .map(|&s| (s as f64 - mean).powi(2))
+        .sum::<f64>()
+        / (n - 1.0);
+    match mean > 0.0 {
+        true => Some(var.sqrt() / mean),
+        false => None,
+    }
+}
+
+/// Run iterations until the last runs of every variant vary less than `max_cv`, and drop
+/// the runs before them.
+pub fn auto_warmup(log: &mut RunLog, runner: &mut Runner, max_cv: Percent) -> anyhow::Result<()> {
+    // Runs treated as warmup, for variants that are stable.
+    let mut warmup_runs: ExperimentMap<usize> = ExperimentMap::default();
+    let first = runner.iteration();
+    while warmup_runs.count() < runner.experiments.count() {
+        if runner.iteration() - first >= AUTO_WARMUP_MAX_ITERATIONS {
+            for (name, test) in runner.experiments.iter() {
+                if warmup_runs.get(name).is_none() {
+                    writeln!(
+                        log.both_log_and_stderr(),
+                        "{yellow}{} did not stabilize within {} iterations.{reset}",
+                        name,
+                        AUTO_WARMUP_MAX_ITERATIONS,
+                        yellow = ansi::YELLOW,
+                        reset = ansi::RESET,
+                    )?;
+                    let runs = test.runs().saturating_sub(AUTO_WARMUP_WINDOW);
+                    warmup_runs.insert(name, runs);
+                }
+            }
+            break;
+        }
+        runner.run_iteration(log)?;
+        for (name, test) in runner.experiments.iter() {
+            if warmup_runs.get(name).is_some() {
+                continue;
+            }
+            match window_cv(test.measures[MeasureKey::WallTime].raw()) {
+                Some(cv) if cv < max_cv.0 => {
+                    warmup_runs.insert(name, test.runs() - AUTO_WARMUP_WINDOW);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    writeln!(log.both_log_and_stderr())?;
+    for (name, test) in runner.experiments.iter_mut() {
+        let runs = warmup_runs[name];
+        *test = test.without_first_runs(runs);
+        writeln!(log.log_only(), "{}.warmup_runs: {}", name, runs)?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "{} warmed up after {} runs.",
+            test.name.name_colored(),
+            runs
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auto_warmup::window_cv;
+    use crate::experiment::Experiment;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+
+    #[test]
+    fn cv() {
+        assert_eq!(None, window_cv(&[100, 100, 100, 100]));
+        assert_eq!(Some(0.0), window_cv(&[900, 100, 100, 100, 100, 100]));
+        let cv = window_cv(&[90, 110, 90, 110, 100]).unwrap();
+        assert!((cv - 0.1).abs() < 0.001, "{}", cv);
+    }
+
+    #[test]
+    fn warmup_runs_trim_metrics() {
+        let name = ExperimentName::intern("warmup-metrics");
+        let mut test = Experiment::new(name, String::new(), String::new());
+        for (i, t) in [900, 500, 100, 100, 100].iter().enumerate() {
+            for key in MeasureKey::ALL.iter().copied() {
+                test.measures[key].push(*t);
+            }
+            test.push_metric("cold".to_owned(), i as u64);
+        }
+        let test = test.without_first_runs(2);
+        assert_eq!(&[100, 100, 100], test.measures[MeasureKey::WallTime].raw());
+        assert_eq!(&[2, 3, 4], test.metrics["cold"].raw());
+    }
+}
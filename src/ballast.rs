@@ -0,0 +1,73 @@
+use std::convert::TryInto;
+use std::env;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+
+use anyhow::Context;
+
+use crate::mem_usage::MemUsage;
+
+/// Name of the hidden subcommand executed in the helper process.
+pub const BALLAST_SUBCOMMAND: &str = "internal-ballast";
+
+const READY: &str = "ready";
+
+/// Helper process holding a fixed amount of memory while it is alive.
+pub struct Ballast {
+    child: Child,
+}
+
+impl Ballast {
+    pub fn spawn(size: MemUsage) -> anyhow::Result<Ballast> {
+        let mut child = Command::new(env::current_exe()?)
+            .arg(BALLAST_SUBCOMMAND)
+            .arg(size.bytes().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("spawning ballast process")?;
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line)?;
+        if line.trim() != READY {
+            let _ = child.kill();
+            let status = child.wait()?;
+            return Err(anyhow::anyhow!(
+                "ballast process failed to allocate memory: {}",
+                status
+            ));
+        }
+        Ok(Ballast { child })
+    }
+}
+
+impl Drop for Ballast {
+    fn drop(&mut self) {
+        // Closing stdin tells the helper to exit, kill it in case it is stuck.
+        drop(self.child.stdin.take());
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Body of the helper process: allocate and touch memory, then wait until stdin is closed.
+pub fn hold_ballast(bytes: u64) -> anyhow::Result<()> {
+    const PAGE: usize = 4096;
+    let mut ballast = vec![0u8; bytes.try_into()?];
+    // Write every page so the memory is actually resident.
+    for i in (0..ballast.len()).step_by(PAGE) {
+        ballast[i] = 1;
+    }
+    println!("{}", READY);
+    io::stdout().flush()?;
+
+    let mut buf = [0; 1];
+    while io::stdin().read(&mut buf)? != 0 {}
+    drop(ballast);
+    Ok(())
+}
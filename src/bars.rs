@@ -182,14 +182,27 @@ pub fn plot_halves_u64(values: &[u64], max: u64, highlight: &PlotHighlight) -> S
     s
 }
 
+/// Plot with separately colored bars, `colors[i]` is used for `values[i]`.
+pub fn plot_u64_colored(values: &[u64], max: u64, colors: &[&str], reset: &str) -> String {
+    assert_eq!(values.len(), colors.len());
+    let mut s = String::new();
+    for (v, color) in values.iter().zip(colors) {
+        s.push_str(color);
+        s.push(bar_char_0_8_range(*v as f64, 0.0, max as f64));
+        s.push_str(reset);
+    }
+    s
+}
+
 #[cfg(test)]
 mod test {
     use crate::bars::_plot;
+    use crate::bars::_plot_halves;
+    use crate::bars::PlotHighlight;
     use crate::bars::f64_to_bucket;
     use crate::bars::plot_halves_u64;
     use crate::bars::plot_u64;
-    use crate::bars::PlotHighlight;
-    use crate::bars::_plot_halves;
+    use crate::bars::plot_u64_colored;
 
     #[test]
     fn test_f64_to_range() {
@@ -232,6 +245,11 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_plot_u64_colored() {
+        assert_eq!("<█!>▄!", plot_u64_colored(&[20, 10], 20, &["<", ">"], "!"));
+    }
+
     #[test]
     fn test_plot_halves() {
         assert_eq!(
@@ -0,0 +1,176 @@
+//! SLO-style view of wall time with `--buckets 100ms,500ms,1s`: the share of runs of each
+//! variant under each threshold, compared to the first variant.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::ansi;
+use crate::duration::Duration;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::math::proportion::Proportion;
+use crate::math::proportion::compare_proportions;
+use crate::measure::key::MeasureKey;
+use crate::student::ConfInterval;
+
+/// Wall time thresholds in increasing order, with labels as given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Buckets(pub Vec<(String, Duration)>);
+
+impl FromStr for Buckets {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Buckets> {
+        let mut buckets = Vec::new();
+        for b in s.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+            let limit: Duration = b.parse()?;
+            if limit.nanos() == 0 {
+                return Err(anyhow::anyhow!(
+                    "bucket threshold must be positive: `{}`",
+                    b
+                ));
+            }
+            buckets.push((b.to_owned(), limit));
+        }
+        if buckets.is_empty() {
+            return Err(anyhow::anyhow!("no bucket thresholds in `{}`", s));
+        }
+        buckets.sort_by_key(|&(_, limit)| limit);
+        buckets.dedup_by_key(|(_, limit)| *limit);
+        Ok(Buckets(buckets))
+    }
+}
+
+/// Runs of `test` with wall time under `limit`.
+fn under(test: &Experiment, limit: Duration) -> Proportion {
+    let times = &test.measures[MeasureKey::WallTime];
+    Proportion {
+        successes: times.iter().filter(|&t| t < limit.nanos()).count() as u64,
+        total: times.len() as u64,
+    }
+}
+
+/// Table of the share of runs under each threshold, with p-values of the difference of
+/// each variant from the first one, highlighted when significant at `conf`, empty without
+/// thresholds.
+pub fn render_buckets(
+    tests: &ExperimentMap<Experiment>,
+    buckets: &Buckets,
+    conf: ConfInterval,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    if buckets.0.is_empty() {
+        return Ok(r);
+    }
+    let a = tests.values().next().unwrap();
+    let rows: Vec<(String, String, Vec<String>)> = tests
+        .values()
+        .map(|t| {
+            let cells = buckets.0.iter().map(|&(_, limit)| {
+                let p = under(t, limit);
+                match p.total {
+                    0 => "-".to_owned(),
+                    _ => format!("{:.1}%", p.ratio() * 100.0),
+                }
+            });
+            (
+                t.name.name().to_owned(),
+                t.name.name_colored(),
+                cells.collect(),
+            )
+        })
+        .chain(tests.values().skip(1).map(|b| {
+            let cells = buckets.0.iter().map(|&(_, limit)| {
+                match compare_proportions(under(a, limit), under(b, limit)) {
+                    Some(test) if test.p() < conf.alpha() => {
+                        format!("{}p={:.4}{}", ansi::RED, test.p(), ansi::RESET)
+                    }
+                    Some(test) => format!("p={:.4}", test.p()),
+                    None => "?".to_owned(),
+                }
+            });
+            let name = format!("{}/{}", b.name, a.name);
+            (name.clone(), name, cells.collect())
+        }))
+        .collect();
+
+    let name_width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap();
+    let headers: Vec<String> = buckets
+        .0
+        .iter()
+        .map(|(label, _)| format!("<{}", label))
+        .collect();
+    let width = |i: usize| {
+        let cells = rows
+            .iter()
+            .map(|(_, _, cells)| ansi::strip_csi(&cells[i]).len());
+        cells.chain([headers[i].len()]).max().unwrap()
+    };
+    let widths: Vec<usize> = (0..headers.len()).map(width).collect();
+
+    writeln!(r)?;
+    writeln!(r, "Runs under wall time thresholds:")?;
+    write!(r, "{}", " ".repeat(name_width))?;
+    for (header, &w) in headers.iter().zip(&widths) {
+        write!(r, "  {:>w$}", header, w = w)?;
+    }
+    writeln!(r)?;
+    for (name, colored, cells) in &rows {
+        write!(r, "{}{}", colored, " ".repeat(name_width - name.len()))?;
+        for (cell, &w) in cells.iter().zip(&widths) {
+            let pad = w - ansi::strip_csi(cell).len();
+            write!(r, "  {}{}", " ".repeat(pad), cell)?;
+        }
+        writeln!(r)?;
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::strip_csi;
+    use crate::buckets::Buckets;
+    use crate::buckets::render_buckets;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+    use crate::student::TWO_SIDED_95;
+
+    #[test]
+    fn parse() {
+        let buckets: Buckets = "1s, 100ms,500ms,1000ms".parse().unwrap();
+        let labels: Vec<&str> = buckets.0.iter().map(|(l, _)| l.as_str()).collect();
+        assert_eq!(vec!["100ms", "500ms", "1s"], labels);
+        assert!("".parse::<Buckets>().is_err());
+        assert!("0ms".parse::<Buckets>().is_err());
+        assert!("fast".parse::<Buckets>().is_err());
+    }
+
+    #[test]
+    fn render() {
+        let mut tests = ExperimentMap::default();
+        // Names not used in other tests, so they are interned in this order.
+        for (name, millis) in [("bucket-a", 50), ("bucket-b", 300)] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for _ in 0..20 {
+                test.measures[MeasureKey::WallTime].push(millis * 1_000_000);
+            }
+            tests.insert(name, test);
+        }
+        let buckets: Buckets = "100ms,1s".parse().unwrap();
+        let table = strip_csi(&render_buckets(&tests, &buckets, TWO_SIDED_95).unwrap());
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!("Runs under wall time thresholds:", lines[1]);
+        assert_eq!("                     <100ms       <1s", lines[2]);
+        assert_eq!("bucket-a             100.0%    100.0%", lines[3]);
+        assert_eq!("bucket-b               0.0%    100.0%", lines[4]);
+        assert_eq!("bucket-b/bucket-a  p=0.0000  p=1.0000", lines[5]);
+        assert!(
+            render_buckets(&tests, &Buckets::default(), TWO_SIDED_95)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
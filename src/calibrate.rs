@@ -0,0 +1,174 @@
+//! Calibration with `--calibrate-to`: `ABSH_SCALE` is raised until the first variant
+//! runs for about the target time, and all variants then run with that scale.
+
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::process;
+use std::time::Instant;
+
+use crate::duration::Duration;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::run_log::RunLog;
+use crate::sh::Shell;
+
+/// Calibrate `ABSH_SCALE` on the first variant and set it for all of them.
+pub fn calibrate_scale(
+    log: &mut RunLog,
+    shell: &Shell,
+    experiments: &mut ExperimentMap<Experiment>,
+    target: Duration,
+) -> anyhow::Result<u64> {
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "Calibrating ABSH_SCALE on {} to run for {:3} s",
+        experiments.keys().next().unwrap().name_colored(),
+        target
+    )?;
+    let scale = calibrate(log, shell, experiments.values().next().unwrap(), target)?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "Using ABSH_SCALE={} for all variants",
+        scale
+    )?;
+    for test in experiments.values_mut() {
+        test.env.push(("ABSH_SCALE".to_owned(), scale.to_string()));
+    }
+    Ok(scale)
+}
+
+/// Find `ABSH_SCALE` value for which the test runs for about `target`.
+fn calibrate(
+    log: &mut RunLog,
+    shell: &Shell,
+    test: &Experiment,
+    target: Duration,
+) -> anyhow::Result<u64> {
+    grow_scale(target, |scale| {
+        let env = [("ABSH_SCALE".to_owned(), scale.to_string())];
+        let command = |script: &str| -> anyhow::Result<process::Command> {
+            let mut command = shell.command(script)?;
+            command.envs(test.env.iter().cloned().chain(env.clone()));
+            if let Some(dir) = &test.dir {
+                command.current_dir(dir);
+            }
+            Ok(command)
+        };
+        if !test.warmup.is_empty() {
+            let status = command(&test.warmup)?.status()?;
+            if !status.success() {
+                return Err(anyhow::anyhow!(
+                    "warmup failed during calibration: {}",
+                    status
+                ));
+            }
+        }
+
+        let start = Instant::now();
+        let status = command(&test.run)?.status()?;
+        let elapsed = Duration::from_nanos(start.elapsed().as_nanos().try_into()?);
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "script failed during calibration: {}",
+                status
+            ));
+        }
+
+        writeln!(
+            log.both_log_and_stderr(),
+            "calibration: ABSH_SCALE={} finished in {:3} s",
+            scale,
+            elapsed
+        )?;
+        Ok(elapsed)
+    })
+}
+
+fn does_not_react(scale: u64) -> anyhow::Error {
+    anyhow::anyhow!(
+        "script does not react to ABSH_SCALE: it runs as long with ABSH_SCALE={} as with 1",
+        scale
+    )
+}
+
+/// Raise the scale, starting at 1, until `run` with it takes at least 80% of `target`.
+fn grow_scale(
+    target: Duration,
+    mut run: impl FnMut(u64) -> anyhow::Result<Duration>,
+) -> anyhow::Result<u64> {
+    let mut scale: u64 = 1;
+    let mut first_elapsed = None;
+    for _ in 0..30 {
+        let elapsed = run(scale)?;
+
+        // Close enough: at least 80% of the target.
+        if elapsed.nanos() >= target.nanos() / 10 * 8 {
+            return Ok(scale);
+        }
+        // A thousand times more work should take well over 10% longer.
+        let first_elapsed = *first_elapsed.get_or_insert(elapsed);
+        if scale >= 1000 && elapsed.nanos() <= first_elapsed.nanos() / 10 * 11 {
+            return Err(does_not_react(scale));
+        }
+        let estimate = if elapsed.nanos() == 0 {
+            scale.saturating_mul(10)
+        } else {
+            (scale as f64 * (target / elapsed)).round() as u64
+        };
+        let min = scale.checked_mul(2).ok_or_else(|| does_not_react(scale))?;
+        scale = estimate.clamp(min, scale.saturating_mul(10));
+    }
+    Err(anyhow::anyhow!(
+        "calibration did not reach {:3} s, last ABSH_SCALE={}",
+        target,
+        scale
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calibrate::grow_scale;
+    use crate::duration::Duration;
+
+    #[test]
+    fn grows_until_close_to_target() {
+        let mut tried = Vec::new();
+        let scale = grow_scale(Duration::from_millis(1000), |scale| {
+            tried.push(scale);
+            Ok(Duration::from_millis(scale * 3))
+        })
+        .unwrap();
+        // At most 10x at a time, then straight to the estimate.
+        assert_eq!(vec![1, 10, 100, 333], tried);
+        assert_eq!(333, scale);
+    }
+
+    #[test]
+    fn grows_at_least_twice() {
+        let mut tried = Vec::new();
+        let scale = grow_scale(Duration::from_millis(1000), |scale| {
+            tried.push(scale);
+            Ok(Duration::from_millis(500 + scale))
+        })
+        .unwrap();
+        assert_eq!(vec![1, 2, 4, 8, 16, 32, 64, 128, 256, 512], tried);
+        assert_eq!(512, scale);
+    }
+
+    #[test]
+    fn script_ignoring_scale() {
+        let err = grow_scale(Duration::from_millis(2000), |_| {
+            Ok(Duration::from_millis(1))
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("does not react to ABSH_SCALE"));
+    }
+
+    #[test]
+    fn script_taking_no_time() {
+        let err =
+            grow_scale(Duration::from_millis(2000), |_| Ok(Duration::from_nanos(0))).unwrap_err();
+        assert!(err.to_string().contains("does not react to ABSH_SCALE"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Compile time benchmarks with `--cargo-compare`: every variant builds into its own
+//! target directory, which is cleaned or touched before each run.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::experiment::Experiment;
+use crate::experiment_name::ExperimentName;
+
+/// Build state before each run, given with `--cargo-compare`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CargoCompare {
+    /// Build from scratch: clean the target directory, without incremental compilation or
+    /// a compiler wrapper like `sccache`.
+    Clean,
+    /// Build from scratch, but keep the compiler wrapper and its cache.
+    Cached,
+    /// Rebuild workspace crates: touch their sources, with incremental compilation.
+    Incremental,
+}
+
+impl CargoCompare {
+    /// Target directory of `name`, relative to its working directory.
+    pub fn target_dir(name: ExperimentName) -> String {
+        format!("target/absh-{}", name)
+    }
+
+    /// Setup script preparing the build before `setup` of the variant.
+    ///
+    /// Setup runs before every run, unlike warmup with `--warmup-mode`.
+    pub fn wrap_setup(&self, setup: &str) -> String {
+        let prepare = match self {
+            CargoCompare::Clean | CargoCompare::Cached => "cargo clean --quiet",
+            CargoCompare::Incremental => {
+                "find . -path ./target -prune -o -name '*.rs' -exec touch {} +"
+            }
+        };
+        match setup.is_empty() {
+            true => prepare.to_owned(),
+            false => format!("{}\n{}", prepare, setup),
+        }
+    }
+
+    /// Environment of scripts of `name`, overridden by variables given for the variant.
+    pub fn env(&self, name: ExperimentName) -> Vec<(String, String)> {
+        let mut env = vec![(
+            "CARGO_TARGET_DIR".to_owned(),
+            CargoCompare::target_dir(name),
+        )];
+        let incremental = match self {
+            CargoCompare::Incremental => "1",
+            CargoCompare::Clean | CargoCompare::Cached => "0",
+        };
+        env.push(("CARGO_INCREMENTAL".to_owned(), incremental.to_owned()));
+        if *self == CargoCompare::Clean {
+            env.push(("RUSTC_WRAPPER".to_owned(), String::new()));
+        }
+        env
+    }
+
+    /// Add the build preparation and environment to scripts of `test`.
+    pub fn apply(&self, test: &mut Experiment) {
+        test.setup = self.wrap_setup(&test.setup);
+        test.env.splice(0..0, self.env(test.name));
+    }
+}
+
+impl FromStr for CargoCompare {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<CargoCompare> {
+        match s {
+            "clean" => Ok(CargoCompare::Clean),
+            "cached" => Ok(CargoCompare::Cached),
+            "incremental" => Ok(CargoCompare::Incremental),
+            s => Err(anyhow::anyhow!(
+                "unknown cargo build state: `{}`, expecting `clean`, `cached` or `incremental`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CargoCompare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CargoCompare::Clean => write!(f, "clean"),
+            CargoCompare::Cached => write!(f, "cached"),
+            CargoCompare::Incremental => write!(f, "incremental"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::env;
+    #[cfg(unix)]
+    use std::fs;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+    #[cfg(unix)]
+    use std::process;
+
+    use crate::cargo_compare::CargoCompare;
+    use crate::experiment::Experiment;
+    #[cfg(unix)]
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    #[cfg(unix)]
+    use crate::run_log::RunLog;
+    #[cfg(unix)]
+    use crate::runner::Runner;
+    #[cfg(unix)]
+    use crate::runner::RunnerOptions;
+    #[cfg(unix)]
+    use crate::verbosity::Verbosity;
+    #[cfg(unix)]
+    use crate::warmup_mode::WarmupMode;
+
+    #[test]
+    fn apply() {
+        let name = ExperimentName::intern("cargo-a");
+        let mut test = Experiment::new(name, "./warm".to_owned(), "cargo build".to_owned());
+        test.setup = "./prepare".to_owned();
+        test.env
+            .push(("CARGO_INCREMENTAL".to_owned(), "1".to_owned()));
+        "clean".parse::<CargoCompare>().unwrap().apply(&mut test);
+        assert_eq!("cargo clean --quiet\n./prepare", test.setup);
+        assert_eq!("./warm", test.warmup);
+        let env: Vec<String> = test
+            .env
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        assert_eq!(
+            vec![
+                "CARGO_TARGET_DIR=target/absh-cargo-a",
+                "CARGO_INCREMENTAL=0",
+                "RUSTC_WRAPPER=",
+                "CARGO_INCREMENTAL=1",
+            ],
+            env
+        );
+        assert!("warm".parse::<CargoCompare>().is_err());
+    }
+
+    /// Cleaning runs before every run, also when the warmup runs only once.
+    #[cfg(unix)]
+    #[test]
+    fn clean_with_warmup_once() {
+        let dir = env::temp_dir().join(format!("absh-cargo-compare-{}", process::id()));
+        let bin = dir.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let cargo = bin.join("cargo");
+        fs::write(
+            &cargo,
+            "#!/bin/sh\necho \"$@\" >> \"$ABSH_TEST_DIR/cargo\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&cargo, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let name = ExperimentName::intern("cargo-once");
+        let mut test = Experiment::new(
+            name,
+            "echo warmup >> \"$ABSH_TEST_DIR/warmup\"".to_owned(),
+            "true".to_owned(),
+        );
+        test.env.push((
+            "PATH".to_owned(),
+            format!("{}:{}", bin.display(), env::var("PATH").unwrap()),
+        ));
+        test.env
+            .push(("ABSH_TEST_DIR".to_owned(), dir.display().to_string()));
+        CargoCompare::Clean.apply(&mut test);
+        let mut experiments = ExperimentMap::default();
+        experiments.insert(name, test);
+        let mut runner = Runner::new(
+            experiments,
+            RunnerOptions {
+                warmup_mode: WarmupMode::Once,
+                verbosity: Verbosity::Quiet,
+                ..RunnerOptions::default()
+            },
+        )
+        .unwrap();
+        let mut log = RunLog::open(&dir.join("logs"));
+        for _ in 0..3 {
+            runner.run_iteration(&mut log).unwrap();
+        }
+        assert_eq!(3, runner.min_runs());
+        let read = |file| fs::read_to_string(dir.join(file)).unwrap();
+        assert_eq!("clean --quiet\n".repeat(3), read("cargo"));
+        assert_eq!("warmup\n", read("warmup"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! Per-run resource accounting with cgroup v2 on Linux.
+//!
+//! Each script runs in a fresh child cgroup of the cgroup of absh, which covers
+//! the whole process tree of the script. Creating child cgroups requires write
+//! access to the current cgroup, for example when it is delegated by systemd.
+
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs;
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+
+use crate::ansi;
+use crate::duration::Duration;
+
+/// Directory of the cgroup v2 hierarchy, from mount info like
+/// `42 32 0:38 / /sys/fs/cgroup rw,relatime - cgroup2 cgroup2 rw`.
+fn parse_cgroup2_mount(mountinfo: &str) -> Option<PathBuf> {
+    mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        if fs.split(' ').next()? != "cgroup2" {
+            return None;
+        }
+        Some(PathBuf::from(mount.split(' ').nth(4)?))
+    })
+}
+
+/// Path of the cgroup v2 of a process relative to the hierarchy, from `/proc/PID/cgroup`.
+fn parse_own_cgroup(cgroup: &str) -> Option<&str> {
+    cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim_start_matches('/'))
+}
+
+/// Directory of the cgroup of this process.
+fn find_own_cgroup() -> anyhow::Result<PathBuf> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    let root = parse_cgroup2_mount(&mountinfo)
+        .ok_or_else(|| anyhow::anyhow!("cgroup v2 is not mounted"))?;
+    let own = fs::read_to_string("/proc/self/cgroup")?;
+    let own =
+        parse_own_cgroup(&own).ok_or_else(|| anyhow::anyhow!("process is not in a cgroup v2"))?;
+    Ok(root.join(own))
+}
+
+/// Directory of the cgroup of this process, found once rather than before every run.
+pub(crate) fn own_cgroup() -> anyhow::Result<&'static Path> {
+    // `anyhow::Error` is not `Clone`, so the error is kept as a message.
+    static OWN: Lazy<Result<PathBuf, String>> =
+        Lazy::new(|| find_own_cgroup().map_err(|e| format!("{:#}", e)));
+    match &*OWN {
+        Ok(path) => Ok(path),
+        Err(e) => Err(anyhow::anyhow!("{}", e)),
+    }
+}
+
+/// Resource totals of a cgroup.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CgroupStats {
+    pub user_time: Duration,
+    pub sys_time: Duration,
+    /// Peak memory in bytes, if the memory controller is enabled.
+    pub memory_peak: Option<u64>,
+    /// Bytes read and written, if the io controller is enabled.
+    pub io: Option<(u64, u64)>,
+}
+
+/// Value of `key` in lines like `user_usec 1234`.
+fn stat_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k != key {
+            return None;
+        }
+        v.trim().parse().ok()
+    })
+}
+
+/// Sum of `rbytes` and `wbytes` over devices in `io.stat` lines like
+/// `8:0 rbytes=1024 wbytes=0 rios=1 wios=0 dbytes=0 dios=0`.
+fn parse_io_stat(content: &str) -> (u64, u64) {
+    let (mut read, mut written) = (0, 0);
+    for field in content.split_whitespace() {
+        match field.split_once('=') {
+            Some(("rbytes", n)) => read += n.parse().unwrap_or(0),
+            Some(("wbytes", n)) => written += n.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    (read, written)
+}
+
+/// Transient cgroup for one script run, removed when dropped.
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Create empty cgroup under the cgroup of this process.
+    pub fn create() -> anyhow::Result<Cgroup> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let path = own_cgroup()?.join(format!(
+            "absh-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path).with_context(|| format!("creating cgroup {}", path.display()))?;
+        Ok(Cgroup { path })
+    }
+
+    /// Check that scripts can be run in transient cgroups.
+    pub fn check() -> anyhow::Result<()> {
+        let cgroup = Cgroup::create()?;
+        let mut command = Command::new("true");
+        command.stdin(Stdio::null());
+        cgroup.join_on_spawn(&mut command)?;
+        let status = command.status().context("running process in cgroup")?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("process in cgroup failed: {}", status));
+        }
+        Ok(())
+    }
+
+    /// Make the spawned process join this cgroup before exec.
+    #[cfg(unix)]
+    pub fn join_on_spawn(&self, command: &mut Command) -> anyhow::Result<()> {
+        let procs = CString::new(self.path.join("cgroup.procs").as_os_str().as_bytes())?;
+        // Only async-signal-safe calls between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // `0` is the writing process.
+                let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                match written {
+                    1 => Ok(()),
+                    _ => Err(error),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn join_on_spawn(&self, _command: &mut Command) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("cgroups are only supported on Linux"))
+    }
+
+    /// Resource totals of all processes which were in this cgroup.
+    pub fn stats(&self) -> anyhow::Result<CgroupStats> {
+        let read = |name: &str| fs::read_to_string(self.path.join(name));
+        let cpu = read("cpu.stat").context("reading cgroup cpu.stat")?;
+        let micros = |key: &str| Duration::from_nanos(stat_value(&cpu, key).unwrap_or(0) * 1000);
+        Ok(CgroupStats {
+            user_time: micros("user_usec"),
+            sys_time: micros("system_usec"),
+            memory_peak: read("memory.peak").ok().and_then(|p| p.trim().parse().ok()),
+            io: read("io.stat").ok().map(|s| parse_io_stat(&s)),
+        })
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // Fails if processes started by the script are still running, the cgroup is left then.
+        if let Err(e) = fs::remove_dir(&self.path) {
+            eprintln!(
+                "{yellow}warning: leaving cgroup {}, processes of the script may still run: {}{reset}",
+                self.path.display(),
+                e,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::cgroup::parse_cgroup2_mount;
+    use crate::cgroup::parse_io_stat;
+    use crate::cgroup::parse_own_cgroup;
+    use crate::cgroup::stat_value;
+
+    #[test]
+    fn mount() {
+        let mountinfo = "\
+25 1 0:23 / / rw - ext4 /dev/sda1 rw
+42 32 0:38 / /sys/fs/cgroup/unified rw,relatime - cgroup2 cgroup2 rw
+";
+        assert_eq!(
+            Some(PathBuf::from("/sys/fs/cgroup/unified")),
+            parse_cgroup2_mount(mountinfo)
+        );
+        assert_eq!(
+            None,
+            parse_cgroup2_mount("25 1 0:23 / / rw - ext4 /dev/sda1 rw")
+        );
+    }
+
+    #[test]
+    fn own() {
+        assert_eq!(
+            Some("user.slice/session-1.scope"),
+            parse_own_cgroup("1:cpu:/\n0::/user.slice/session-1.scope\n")
+        );
+        assert_eq!(Some(""), parse_own_cgroup("0::/\n"));
+        assert_eq!(None, parse_own_cgroup("1:cpu:/\n"));
+    }
+
+    #[test]
+    fn stats() {
+        let cpu = "usage_usec 300\nuser_usec 200\nsystem_usec 100\n";
+        assert_eq!(Some(200), stat_value(cpu, "user_usec"));
+        assert_eq!(None, stat_value(cpu, "nr_periods"));
+        let io = "8:0 rbytes=1024 wbytes=10 rios=1 wios=1\n8:16 rbytes=1 wbytes=0 rios=1 wios=0\n";
+        assert_eq!((1025, 10), parse_io_stat(io));
+    }
+}
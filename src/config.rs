@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::experiment::EnvVar;
+use crate::experiment::NamedDir;
+use crate::experiment::NamedEnvVar;
+use crate::experiment::NamedScript;
+use crate::experiment_name::ExperimentName;
+use crate::metadata::Metadata;
+
+/// Benchmark definition loaded with `--config absh.toml`.
+///
+/// ```toml
+/// iterations = 100
+/// mem = true
+///
+/// [[test]]
+/// name = "baseline"
+/// run = "make bench"
+///
+/// [[test]]
+/// name = "lto"
+/// warmup = "make clean"
+/// run = "make bench LTO=1"
+/// when = "test -e /sys/fs/cgroup/cgroup.controllers"
+/// ```
+///
+/// Snippets defined in `[scripts]` are substituted for `${scripts.NAME}` in
+/// `run`, `warmup` and `when` when the config is parsed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Variants, the first is the baseline.
+    #[serde(default)]
+    pub test: Vec<ConfigTest>,
+    pub iterations: Option<u32>,
+    #[serde(default)]
+    pub random_order: bool,
+    #[serde(default)]
+    pub ignore_first: bool,
+    #[serde(default)]
+    pub mem: bool,
+    #[serde(default)]
+    pub cpu: bool,
+    #[serde(default)]
+    pub paired: bool,
+    #[serde(default)]
+    pub details: bool,
+    #[serde(default)]
+    pub record_sensors: bool,
+    #[serde(default)]
+    pub line_latency: bool,
+    #[serde(default)]
+    pub body_time: bool,
+    #[serde(default)]
+    pub mem_tree: bool,
+    #[serde(default)]
+    pub threads_fds: bool,
+    /// What the whole benchmark measures, copied into reports.
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    /// Issues or documents about the benchmark.
+    #[serde(default)]
+    pub links: Vec<String>,
+    /// Script snippets referenced as `${scripts.NAME}`.
+    #[serde(default)]
+    pub scripts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigTest {
+    pub name: String,
+    pub run: String,
+    pub warmup: Option<String>,
+    /// Skip the variant in iterations where this script exits non-zero.
+    pub when: Option<String>,
+    /// Environment variables of the variant scripts.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Working directory of the variant scripts.
+    pub dir: Option<PathBuf>,
+    /// What the variant is, copied into reports.
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub links: Vec<String>,
+}
+
+impl ConfigTest {
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            description: self.description.clone(),
+            owner: self.owner.clone(),
+            links: self.links.clone(),
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(content: &str) -> anyhow::Result<Config> {
+        let mut config: Config = toml::from_str(content)?;
+        if config.test.is_empty() {
+            return Err(anyhow::anyhow!("no `[[test]]` in config"));
+        }
+        for test in &mut config.test {
+            ExperimentName::validate(&test.name)?;
+            let (scripts, name) = (&config.scripts, test.name.clone());
+            let expand = |script: &str| {
+                expand_scripts(script, scripts).with_context(|| format!("in test `{}`", name))
+            };
+            test.run = expand(&test.run)?;
+            test.warmup = test.warmup.as_deref().map(expand).transpose()?;
+            test.when = test.when.as_deref().map(expand).transpose()?;
+        }
+        Ok(config)
+    }
+
+    /// Load the config file at `path`, relative `dir` of tests is relative to its directory.
+    pub fn load(path: &Path) -> anyhow::Result<Config> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut config =
+            Config::parse(&content).with_context(|| format!("in {}", path.display()))?;
+        if let Some(base) = path.parent() {
+            for dir in config.test.iter_mut().filter_map(|t| t.dir.as_mut()) {
+                *dir = base.join(&*dir);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Variant scripts and warmup scripts, like given with `--test` and `--warmup`.
+    pub fn scripts(&self) -> (Vec<NamedScript>, Vec<NamedScript>) {
+        let tests = self
+            .test
+            .iter()
+            .map(|t| NamedScript {
+                name: t.name.clone(),
+                script: t.run.clone(),
+            })
+            .collect();
+        let warmups = self
+            .test
+            .iter()
+            .filter_map(|t| {
+                Some(NamedScript {
+                    name: t.name.clone(),
+                    script: t.warmup.clone()?,
+                })
+            })
+            .collect();
+        (tests, warmups)
+    }
+
+    /// Condition scripts, like given with `--when`.
+    pub fn conditions(&self) -> Vec<NamedScript> {
+        self.test
+            .iter()
+            .filter_map(|t| {
+                Some(NamedScript {
+                    name: t.name.clone(),
+                    script: t.when.clone()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Environment variables, like given with `--env`.
+    pub fn env_vars(&self) -> Vec<NamedEnvVar> {
+        self.test
+            .iter()
+            .flat_map(|t| {
+                t.env.iter().map(move |(name, value)| NamedEnvVar {
+                    name: t.name.clone(),
+                    var: EnvVar {
+                        name: name.clone(),
+                        value: value.clone(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Description, owner and links of variants which have any, by variant name.
+    pub fn metadata(&self) -> Vec<(String, Metadata)> {
+        self.test
+            .iter()
+            .map(|t| (t.name.clone(), t.metadata()))
+            .filter(|(_, m)| !m.is_empty())
+            .collect()
+    }
+
+    /// Description, owner and links of the whole benchmark.
+    pub fn suite_metadata(&self) -> Metadata {
+        Metadata {
+            description: self.description.clone(),
+            owner: self.owner.clone(),
+            links: self.links.clone(),
+        }
+    }
+
+    /// Working directories, like given with `--dir`.
+    pub fn dirs(&self) -> Vec<NamedDir> {
+        self.test
+            .iter()
+            .filter_map(|t| {
+                Some(NamedDir {
+                    name: t.name.clone(),
+                    dir: t.dir.clone()?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Notices changes of the config file, for `--reload-config`.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> ConfigWatcher {
+        ConfigWatcher {
+            path: path.to_owned(),
+            modified: modified(path),
+        }
+    }
+
+    /// Config loaded again if the file was modified since the previous check.
+    pub fn poll(&mut self) -> Option<anyhow::Result<Config>> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(&self.path))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Replace `${scripts.NAME}` references with snippets from `scripts`.
+fn expand_scripts(script: &str, scripts: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    const PREFIX: &str = "${scripts.";
+    let mut r = String::new();
+    let mut rem = script;
+    while let Some(start) = rem.find(PREFIX) {
+        r.push_str(&rem[..start]);
+        rem = &rem[start + PREFIX.len()..];
+        let end = rem
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unterminated `{}` in `{}`", PREFIX, script))?;
+        let name = &rem[..end];
+        let snippet = scripts
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("unknown script `{}` in `[scripts]`", name))?;
+        if snippet.contains(PREFIX) {
+            return Err(anyhow::anyhow!(
+                "script `{}` references other scripts, which is not supported",
+                name
+            ));
+        }
+        r.push_str(snippet);
+        rem = &rem[end + 1..];
+    }
+    r.push_str(rem);
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::config::Config;
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(
+            r#"
+iterations = 10
+mem = true
+description = "Startup time"
+links = ["https://example.com/issues/1"]
+
+[[test]]
+name = "baseline"
+run = "sleep 1"
+owner = "alice"
+
+[[test]]
+name = "fast"
+warmup = "true"
+run = "sleep 0.5"
+when = "test -d /tmp"
+dir = "build"
+env = { RUST_LOG = "info" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(Some(10), config.iterations);
+        assert!(config.mem);
+        assert!(!config.random_order);
+        let (tests, warmups) = config.scripts();
+        assert_eq!(
+            vec!["baseline", "fast"],
+            tests.iter().map(|t| t.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!("sleep 0.5", tests[1].script);
+        assert_eq!(1, warmups.len());
+        assert_eq!("fast", warmups[0].name);
+        let conditions = config.conditions();
+        assert_eq!(1, conditions.len());
+        assert_eq!("test -d /tmp", conditions[0].script);
+        let env_vars = config.env_vars();
+        assert_eq!(1, env_vars.len());
+        assert_eq!("fast", env_vars[0].name);
+        assert_eq!("RUST_LOG", env_vars[0].var.name);
+        assert_eq!("info", env_vars[0].var.value);
+        let dirs = config.dirs();
+        assert_eq!(1, dirs.len());
+        assert_eq!("build", dirs[0].dir.to_str().unwrap());
+        let suite = config.suite_metadata();
+        assert_eq!(Some("Startup time"), suite.description.as_deref());
+        assert_eq!(1, suite.links.len());
+        let metadata = config.metadata();
+        assert_eq!(1, metadata.len());
+        assert_eq!(
+            ("baseline", Some("alice")),
+            (&*metadata[0].0, metadata[0].1.owner.as_deref())
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Config::parse("iterations = 10").is_err());
+        assert!(Config::parse("[[test]]\nname = \"a b\"\nrun = \"true\"").is_err());
+        assert!(Config::parse("itrations = 10\n[[test]]\nname = \"a\"\nrun = \"true\"").is_err());
+    }
+
+    #[test]
+    fn scripts() {
+        let config = Config::parse(
+            r#"
+[scripts]
+build = "make -j8"
+clean = "rm -rf out"
+
+[[test]]
+name = "a"
+warmup = "${scripts.clean}"
+run = "${scripts.build} && ./bench ${HOME}"
+"#,
+        )
+        .unwrap();
+        assert_eq!("make -j8 && ./bench ${HOME}", config.test[0].run);
+        assert_eq!(Some("rm -rf out"), config.test[0].warmup.as_deref());
+        assert!(Config::parse("[[test]]\nname = \"a\"\nrun = \"${scripts.x}\"").is_err());
+        assert!(Config::parse("[[test]]\nname = \"a\"\nrun = \"${scripts.x\"").is_err());
+    }
+
+    #[test]
+    fn load_relative_dir() {
+        let dir = env::temp_dir().join(format!("absh-config-test-{}", std::process::id()));
+        let path = dir.join("sub/absh.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"
+[[test]]
+name = "rel"
+run = "make"
+dir = "build"
+
+[[test]]
+name = "abs"
+run = "make"
+dir = "/srv/build"
+"#,
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        let dirs: Vec<PathBuf> = config.dirs().into_iter().map(|d| d.dir).collect();
+        assert_eq!(
+            vec![dir.join("sub/build"), PathBuf::from("/srv/build")],
+            dirs
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -7,7 +7,8 @@ pub struct ConsoleWriter {
     color: bool,
 }
 
-fn is_tty() -> bool {
+/// Stderr is a terminal.
+pub fn is_tty() -> bool {
     !cfg!(windows) && atty::is(atty::Stream::Stderr)
 }
 
@@ -0,0 +1,165 @@
+//! Pinning scripts to CPUs with `--cpu` and changing their priority with `--nice`.
+
+use std::fmt;
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::process::Stdio;
+use std::str::FromStr;
+
+use anyhow::Context;
+
+/// Highest niceness, lowest priority.
+pub const MAX_NICE: i32 = 19;
+/// Lowest niceness, needs root.
+pub const MIN_NICE: i32 = -20;
+
+/// CPUs given as a list like `0-3,6`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CpuList(pub Vec<usize>);
+
+impl FromStr for CpuList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<CpuList> {
+        let mut cpus = Vec::new();
+        for part in s.split(',') {
+            let parse = |cpu: &str| {
+                cpu.trim()
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("invalid CPU: `{}`, expecting like `0-3,6`", cpu))
+            };
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse(first)?, parse(last)?);
+                    if first > last {
+                        return Err(anyhow::anyhow!("empty CPU range: `{}`", part));
+                    }
+                    cpus.extend(first..=last);
+                }
+                None => cpus.push(parse(part)?),
+            }
+        }
+        cpus.sort_unstable();
+        cpus.dedup();
+        Ok(CpuList(cpus))
+    }
+}
+
+impl fmt::Display for CpuList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Consecutive CPUs as ranges.
+        let mut i = 0;
+        while i < self.0.len() {
+            let mut j = i + 1;
+            while j < self.0.len() && self.0[j] == self.0[j - 1] + 1 {
+                j += 1;
+            }
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            match j - i {
+                1 => write!(f, "{}", self.0[i])?,
+                _ => write!(f, "{}-{}", self.0[i], self.0[j - 1])?,
+            }
+            i = j;
+        }
+        Ok(())
+    }
+}
+
+/// Make the spawned process, and so its children, run on `cpus` with niceness `nice`.
+pub fn pin_on_spawn(
+    command: &mut Command,
+    cpus: Option<&CpuList>,
+    nice: Option<i32>,
+) -> anyhow::Result<()> {
+    if let Some(cpus) = cpus {
+        set_affinity_on_spawn(command, cpus)?;
+    }
+    if let Some(nice) = nice {
+        set_nice_on_spawn(command, nice)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_nice_on_spawn(command: &mut Command, nice: i32) -> anyhow::Result<()> {
+    unsafe {
+        command.pre_exec(move || {
+            // `0` is the calling process.
+            match libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_nice_on_spawn(_command: &mut Command, _nice: i32) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("--nice is only supported on Unix"))
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity_on_spawn(command: &mut Command, cpus: &CpuList) -> anyhow::Result<()> {
+    // The set is built before fork, only async-signal-safe calls between fork and exec.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in &cpus.0 {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(anyhow::anyhow!("CPU {} is out of range", cpu));
+        }
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    unsafe {
+        command.pre_exec(move || {
+            match libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity_on_spawn(_command: &mut Command, _cpus: &CpuList) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("--cpu is only supported on Linux"))
+}
+
+/// Fail early if scripts cannot be pinned to `cpus` or given niceness `nice`.
+pub fn check_pinning(cpus: Option<&CpuList>, nice: Option<i32>) -> anyhow::Result<()> {
+    let mut command = Command::new("true");
+    command.stdin(Stdio::null());
+    pin_on_spawn(&mut command, cpus, nice)?;
+    let status = command.status().with_context(|| match nice {
+        Some(nice) if nice < 0 => format!(
+            "running process on CPUs and with niceness {}, negative niceness needs root",
+            nice
+        ),
+        _ => "running process on CPUs, they may be offline or not allowed".to_owned(),
+    })?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("pinned process failed: {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu_pinning::CpuList;
+
+    #[test]
+    fn parse() {
+        let cpus: CpuList = "6,0-3,2".parse().unwrap();
+        assert_eq!(vec![0, 1, 2, 3, 6], cpus.0);
+        assert_eq!("0-3,6", cpus.to_string());
+        assert_eq!("1", "1".parse::<CpuList>().unwrap().to_string());
+        assert!("3-1".parse::<CpuList>().is_err());
+        assert!("a".parse::<CpuList>().is_err());
+        assert!("".parse::<CpuList>().is_err());
+    }
+}
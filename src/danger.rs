@@ -0,0 +1,167 @@
+use std::io;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+
+/// Patterns of obviously destructive commands with their descriptions.
+static PATTERNS: Lazy<Vec<(Regex, &'static str)>> = Lazy::new(|| {
+    [
+        (
+            r#"\brm\s+(-\S+\s+)*(/|~|\$HOME|"\$HOME")(\s|/?\*?(\s|$)|$)"#,
+            "removes root or home directory",
+        ),
+        (
+            r"\brm\s[^\n;|&]*[^\x22\x27\w]\$\{?[A-Za-z_]\w*",
+            "removes path with unquoted variable",
+        ),
+        (r"\bmkfs(\.\w+)?\b", "creates a file system"),
+        (r"\bdd\b[^\n;|&]*\bof=/dev/", "writes to a device with dd"),
+        (r">\s*/dev/(sd|hd|nvme|vd|disk)", "writes to a disk device"),
+        (r":\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}\s*;\s*:", "fork bomb"),
+    ]
+    .iter()
+    .map(|(re, what)| (Regex::new(re).unwrap(), *what))
+    .collect()
+});
+
+/// Describe destructive-looking commands in a script.
+///
+/// This is a heuristic to catch mistakes, not a sandbox.
+pub fn find_destructive(script: &str) -> Vec<&'static str> {
+    PATTERNS
+        .iter()
+        .filter(|(re, _)| re.is_match(script))
+        .map(|(_, what)| *what)
+        .collect()
+}
+
+/// Describe destructive-looking commands in all scripts of `test`.
+pub fn find_destructive_in(test: &Experiment) -> Vec<String> {
+    let scripts = [
+        ("setup", &test.setup),
+        ("when", &test.when),
+        ("warmup", &test.warmup),
+        ("script", &test.run),
+        ("teardown", &test.teardown),
+    ];
+    let mut found = Vec::new();
+    for (what, script) in scripts.iter() {
+        for danger in find_destructive(script) {
+            found.push(format!("{} {}: {}", test.name, what, danger));
+        }
+    }
+    found
+}
+
+/// Describe destructive-looking commands in scripts of `experiments` and in `session`
+/// scripts like `--setup`, given with their names.
+fn find_destructive_all(
+    experiments: &ExperimentMap<Experiment>,
+    session: &[(&str, &str)],
+) -> Vec<String> {
+    let mut found = Vec::new();
+    for (what, script) in session {
+        for danger in find_destructive(script) {
+            found.push(format!("{}: {}", what, danger));
+        }
+    }
+    for test in experiments.values() {
+        found.extend(find_destructive_in(test));
+    }
+    found
+}
+
+/// Ask for confirmation if any script looks destructive.
+pub fn confirm_destructive(
+    experiments: &ExperimentMap<Experiment>,
+    session: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    let found = find_destructive_all(experiments, session);
+    if found.is_empty() {
+        return Ok(());
+    }
+    for f in &found {
+        eprintln!("warning: {}", f);
+    }
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(anyhow::anyhow!(
+            "scripts look destructive, use `--no-safety-check` to run them anyway"
+        ));
+    }
+    eprint!("Scripts will be executed many times. Run them anyway? [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => Err(anyhow::anyhow!("not confirmed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::danger::find_destructive;
+    use crate::danger::find_destructive_all;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+
+    #[test]
+    fn destructive() {
+        assert_eq!(
+            vec!["removes root or home directory"],
+            find_destructive("rm -rf /")
+        );
+        assert_eq!(
+            vec!["removes root or home directory"],
+            find_destructive("rm -r -f ~/")
+        );
+        assert_eq!(
+            vec!["removes root or home directory"],
+            find_destructive("rm -rf /*")
+        );
+        assert_eq!(
+            vec!["removes path with unquoted variable"],
+            find_destructive("rm -rf $BUILD/out")
+        );
+        assert_eq!(
+            vec!["creates a file system"],
+            find_destructive("mkfs.ext4 /dev/sdb1")
+        );
+        assert_eq!(
+            vec!["writes to a device with dd"],
+            find_destructive("dd if=/dev/zero of=/dev/sda bs=1M")
+        );
+    }
+
+    #[test]
+    fn harmless() {
+        assert!(find_destructive("rm -rf /tmp/build").is_empty());
+        assert!(find_destructive("rm -rf \"$BUILD/out\"").is_empty());
+        assert!(find_destructive("rm -f out.txt; make -j8").is_empty());
+        assert!(find_destructive("dd if=/dev/zero of=/tmp/x bs=1M count=10").is_empty());
+        assert!(find_destructive("echo mkfsx").is_empty());
+    }
+
+    #[test]
+    fn all_scripts() {
+        let name = ExperimentName::intern("danger-hooks");
+        let mut test = Experiment::new(name, "make".to_owned(), "./bench".to_owned());
+        test.setup = "rm -rf ~/*".to_owned();
+        test.when = "mkfs.ext4 /dev/sdb1".to_owned();
+        test.teardown = "rm -rf $OUT".to_owned();
+        let mut experiments = ExperimentMap::default();
+        experiments.insert(name, test);
+        assert_eq!(
+            vec![
+                "teardown: removes root or home directory",
+                "danger-hooks setup: removes root or home directory",
+                "danger-hooks when: creates a file system",
+                "danger-hooks teardown: removes path with unquoted variable",
+            ],
+            find_destructive_all(&experiments, &[("setup", "make"), ("teardown", "rm -rf /")])
+        );
+    }
+}
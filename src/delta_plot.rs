@@ -0,0 +1,109 @@
+use crate::ansi;
+use crate::bars::plot_u64_colored;
+use crate::math::numbers::Numbers;
+
+/// Per-iteration differences `b - a`, samples are paired by iteration.
+pub(crate) fn paired_deltas(a: &Numbers, b: &Numbers) -> Vec<i64> {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| b as i64 - a as i64)
+        .collect()
+}
+
+/// Per-iteration differences `b - a` of series with one sample per successful run.
+///
+/// Iterations where either test failed are skipped, so both tests exclude the same iterations.
+pub(crate) fn paired_deltas_by_iteration(
+    a_iterations: &[u64],
+    a: &Numbers,
+    b_iterations: &[u64],
+    b: &Numbers,
+) -> Vec<i64> {
+    let b: Vec<(u64, u64)> = b_iterations.iter().copied().zip(b.iter()).collect();
+    a_iterations
+        .iter()
+        .zip(a.iter())
+        .filter_map(|(i, a)| {
+            let (_, b) = b.iter().find(|(j, _)| j == i)?;
+            Some(*b as i64 - a as i64)
+        })
+        .collect()
+}
+
+/// Histogram over the range symmetric around zero.
+///
+/// Number of buckets is odd, so the middle bucket is centered at zero.
+fn delta_distr(deltas: &[i64], width: usize) -> Vec<u64> {
+    let width = (width.max(1) - 1) / 2 * 2 + 1;
+    let mut counts = vec![0; width];
+    let max_abs = deltas.iter().map(|d| d.abs()).max().unwrap_or(0);
+    for &d in deltas {
+        let idx = if max_abs == 0 {
+            width / 2
+        } else {
+            let d_0_1 = (d + max_abs) as f64 / (2 * max_abs) as f64;
+            ((d_0_1 * width as f64) as usize).min(width - 1)
+        };
+        counts[idx] += 1;
+    }
+    counts
+}
+
+/// Plot deltas, negative buckets are green, positive are red.
+pub(crate) fn make_delta_plot(deltas: &[i64], width: usize) -> String {
+    let counts = delta_distr(deltas, width);
+    let max = counts.iter().max().cloned().unwrap_or(0);
+    let middle = counts.len() / 2;
+    let colors: Vec<&str> = (0..counts.len())
+        .map(|i| match i {
+            i if i < middle => ansi::GREEN,
+            i if i > middle => ansi::RED,
+            _ => "",
+        })
+        .collect();
+    plot_u64_colored(&counts, max, &colors, ansi::RESET)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delta_plot::delta_distr;
+    use crate::delta_plot::paired_deltas;
+    use crate::delta_plot::paired_deltas_by_iteration;
+    use crate::math::numbers::Numbers;
+
+    #[test]
+    fn test_paired_deltas() {
+        let mut a = Numbers::default();
+        let mut b = Numbers::default();
+        for (x, y) in [(10, 12), (10, 7), (5, 5)] {
+            a.push(x);
+            b.push(y);
+        }
+        b.push(100);
+        assert_eq!(vec![2, -3, 0], paired_deltas(&a, &b));
+    }
+
+    #[test]
+    fn test_paired_deltas_by_iteration() {
+        let mut a = Numbers::default();
+        let mut b = Numbers::default();
+        for x in [10, 20, 30] {
+            a.push(x);
+        }
+        for y in [21, 33] {
+            b.push(y);
+        }
+        // B failed in iteration 1.
+        assert_eq!(
+            vec![1, 3],
+            paired_deltas_by_iteration(&[1, 2, 3], &a, &[2, 3], &b)
+        );
+    }
+
+    #[test]
+    fn test_delta_distr() {
+        assert_eq!(vec![1, 0, 2, 0, 1], delta_distr(&[-10, 0, 1, 10], 5));
+        assert_eq!(vec![0, 1, 0], delta_distr(&[0], 4));
+        assert_eq!(vec![1, 1, 1], delta_distr(&[-3, 0, 3], 3));
+    }
+}
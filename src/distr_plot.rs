@@ -1,47 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+
 use crate::bars::plot_halves_u64;
 use crate::bars::plot_u64;
+use crate::duration::Duration;
 use crate::experiment::Experiment;
 use crate::experiment_map::ExperimentMap;
 use crate::math::numbers::Numbers;
+use crate::mem_usage::MemUsage;
+
+/// Which distribution plots to draw.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PlotMode {
+    /// Halves plot when bars are low, full plot otherwise.
+    #[default]
+    Auto,
+    /// Two buckets per character, for few samples.
+    Halves,
+    /// One bucket per character with bar height.
+    Full,
+    /// Both halves and full plots.
+    Both,
+}
+
+impl FromStr for PlotMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<PlotMode> {
+        match s {
+            "auto" => Ok(PlotMode::Auto),
+            "halves" => Ok(PlotMode::Halves),
+            "full" => Ok(PlotMode::Full),
+            "both" => Ok(PlotMode::Both),
+            s => Err(anyhow::anyhow!(
+                "unknown plot: `{}`, expecting `auto`, `halves`, `full` or `both`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PlotMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlotMode::Auto => write!(f, "auto"),
+            PlotMode::Halves => write!(f, "halves"),
+            PlotMode::Full => write!(f, "full"),
+            PlotMode::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// Reference value drawn under distribution plots, given with `--mark`, like `time=1.5s`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlotMark {
+    /// Id of the measure, like `wall-time`.
+    pub measure: String,
+    /// In units of the measure's samples, like nanoseconds.
+    pub value: u64,
+}
+
+impl FromStr for PlotMark {
+    type Err = anyhow::Error;
 
+    fn from_str(s: &str) -> anyhow::Result<PlotMark> {
+        let (measure, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expecting `MEASURE=VALUE`: `{}`", s))?;
+        let (measure, value) = match measure {
+            "time" | "wall-time" => ("wall-time", value.parse::<Duration>()?.nanos()),
+            "user-time" | "sys-time" => (measure, value.parse::<Duration>()?.nanos()),
+            "rss" | "max-rss" => ("max-rss", value.parse::<MemUsage>()?.bytes()),
+            m if m.starts_with("metric-") => (
+                m,
+                value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid metric value: `{}`", value))?,
+            ),
+            m => {
+                return Err(anyhow::anyhow!(
+                    "unknown measure: `{}`, expecting `time`, `user-time`, `sys-time`, `rss` \
+                     or `metric-NAME`",
+                    m
+                ));
+            }
+        };
+        Ok(PlotMark {
+            measure: measure.to_owned(),
+            value,
+        })
+    }
+}
+
+/// Distribution plots of a measure.
+pub struct DistrPlots {
+    /// Plot of each experiment, one map per drawn plot kind.
+    pub plots: Vec<ExperimentMap<String>>,
+    /// Character of the plots where the mark is.
+    pub mark_column: Option<usize>,
+}
+
+/// Plots of each experiment, with a range including `mark`.
 pub(crate) fn make_distr_plots(
     tests: &ExperimentMap<Experiment>,
     width: usize,
+    mode: PlotMode,
+    mark: Option<u64>,
     numbers: impl Fn(&Experiment) -> &Numbers,
-) -> anyhow::Result<ExperimentMap<String>> {
+) -> anyhow::Result<DistrPlots> {
     let min = tests
         .values()
         .map(|t| numbers(t).min().unwrap())
+        .chain(mark)
         .min()
         .unwrap();
     let max = tests
         .values()
         .map(|t| numbers(t).max().unwrap())
+        .chain(mark)
         .max()
         .unwrap();
+    // Same bucket as `Numbers::distr`.
+    let mark_column = mark.map(|mark| match max - min {
+        0 => 0,
+        range => ((mark - min) as f64 / range as f64 * (width - 1) as f64).round() as usize,
+    });
 
-    let distr_halves: ExperimentMap<_> =
-        tests.map(|t| (t, numbers(t).distr(width * 2, min.clone(), max.clone())));
+    let distr_halves: ExperimentMap<_> = tests.map(|t| (t, numbers(t).distr(width * 2, min, max)));
 
-    let distr: ExperimentMap<_> =
-        tests.map(|t| (t, numbers(t).distr(width, min.clone(), max.clone())));
+    let distr: ExperimentMap<_> = tests.map(|t| (t, numbers(t).distr(width, min, max)));
 
-    let max_height_halves = distr_halves
-        .values()
-        .map(|(_, d)| d.max())
-        .max()
-        .unwrap()
-        .clone();
-    let max_height = distr.values().map(|(_, d)| d.max()).max().unwrap().clone();
+    let max_height_halves = distr_halves.values().map(|(_, d)| d.max()).max().unwrap();
+    let max_height = distr.values().map(|(_, d)| d.max()).max().unwrap();
 
     let distr_plots = distr.map(|(t, d)| plot_u64(&d.counts, max_height, &t.plot_highlights()));
 
     let distr_halves_plots = distr_halves
         .map(|(t, d)| plot_halves_u64(&d.counts, max_height_halves, &t.plot_halves_highlights()));
 
-    if max_height_halves <= 2 {
-        Ok(distr_halves_plots)
-    } else {
-        Ok(distr_plots)
+    let plots = match mode {
+        PlotMode::Auto if max_height_halves <= 2 => vec![distr_halves_plots],
+        PlotMode::Auto => vec![distr_plots],
+        PlotMode::Halves => vec![distr_halves_plots],
+        PlotMode::Full => vec![distr_plots],
+        PlotMode::Both => vec![distr_halves_plots, distr_plots],
+    };
+    Ok(DistrPlots { plots, mark_column })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distr_plot::PlotMark;
+    use crate::distr_plot::PlotMode;
+
+    #[test]
+    fn parse() {
+        for mode in [
+            PlotMode::Auto,
+            PlotMode::Halves,
+            PlotMode::Full,
+            PlotMode::Both,
+        ] {
+            assert_eq!(mode, mode.to_string().parse().unwrap());
+        }
+        assert!("half".parse::<PlotMode>().is_err());
+    }
+
+    #[test]
+    fn parse_mark() {
+        let mark = |s: &str| s.parse::<PlotMark>().unwrap();
+        let time = mark("time=1.5s");
+        assert_eq!("wall-time", time.measure);
+        assert_eq!(1_500_000_000, time.value);
+        assert_eq!(200 << 20, mark("rss=200M").value);
+        assert_eq!("metric-alloc", mark("metric-alloc=3").measure);
+        assert!("time".parse::<PlotMark>().is_err());
+        assert!("speed=3".parse::<PlotMark>().is_err());
+        assert!("metric-alloc=1.5".parse::<PlotMark>().is_err());
     }
 }
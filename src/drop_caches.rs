@@ -0,0 +1,50 @@
+//! Dropping the page cache before runs with `--drop-caches`, Linux only.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use anyhow::Context;
+
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+/// Fail early if caches cannot be dropped.
+pub fn check_drop_caches() -> anyhow::Result<()> {
+    if !cfg!(target_os = "linux") {
+        return Err(anyhow::anyhow!("--drop-caches is only supported on Linux"));
+    }
+    if !is_root() {
+        return Err(anyhow::anyhow!(
+            "--drop-caches needs root to write to {}, run absh with sudo",
+            DROP_CACHES
+        ));
+    }
+    OpenOptions::new()
+        .write(true)
+        .open(DROP_CACHES)
+        .with_context(|| format!("--drop-caches cannot open {}", DROP_CACHES))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+/// Write dirty pages to disk, then drop the page cache, dentries and inodes.
+pub fn drop_caches() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    unsafe {
+        libc::sync()
+    };
+    OpenOptions::new()
+        .write(true)
+        .open(DROP_CACHES)
+        .and_then(|mut file| file.write_all(b"3"))
+        .with_context(|| format!("writing to {}", DROP_CACHES))?;
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How far samples are written after each iteration, given with `--durability`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Durability {
+    /// Left to the OS: kept if absh is killed, may be lost if the machine crashes.
+    None,
+    /// Data of the log and raw sample files is written to disk.
+    Flush,
+    /// Like `Flush`, and the log directory too, so replaced raw sample files are kept.
+    #[default]
+    Fsync,
+}
+
+impl FromStr for Durability {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Durability> {
+        match s {
+            "none" => Ok(Durability::None),
+            "flush" => Ok(Durability::Flush),
+            "fsync" => Ok(Durability::Fsync),
+            s => Err(anyhow::anyhow!(
+                "unknown durability: `{}`, expecting `none`, `flush` or `fsync`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Durability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Durability::None => write!(f, "none"),
+            Durability::Flush => write!(f, "flush"),
+            Durability::Fsync => write!(f, "fsync"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::durability::Durability;
+
+    #[test]
+    fn parse() {
+        for s in ["none", "flush", "fsync"].iter() {
+            assert_eq!(*s, s.parse::<Durability>().unwrap().to_string());
+        }
+        assert!("sync".parse::<Durability>().is_err());
+    }
+}
@@ -4,6 +4,9 @@ use std::ops::Add;
 use std::ops::AddAssign;
 use std::ops::Div;
 use std::ops::Sub;
+use std::str::FromStr;
+
+use anyhow::Context;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Default, Debug)]
 pub struct Duration {
@@ -130,3 +133,52 @@ impl fmt::Display for Duration {
         write!(f, "{}.{:03}", self.millis() / 1000, self.millis() % 1000)
     }
 }
+
+impl FromStr for Duration {
+    type Err = anyhow::Error;
+
+    /// Parse duration like `1.5s`, `300ms`, `100us`, `2m` or `2` (seconds).
+    fn from_str(s: &str) -> anyhow::Result<Duration> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("invalid duration: {:?}", s))?;
+        let nanos_per_unit = match unit.trim() {
+            "ns" => 1.0,
+            "us" => 1_000.0,
+            "ms" => 1_000_000.0,
+            "" | "s" => 1_000_000_000.0,
+            "m" => 60_000_000_000.0,
+            "h" => 3_600_000_000_000.0,
+            unit => {
+                return Err(anyhow::anyhow!(
+                    "unknown duration unit {:?} in {:?}",
+                    unit,
+                    s
+                ));
+            }
+        };
+        Ok(Duration::from_nanos_f64(number * nanos_per_unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duration::Duration;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Duration::from_millis(2000), "2s".parse().unwrap());
+        assert_eq!(Duration::from_millis(2000), "2".parse().unwrap());
+        assert_eq!(Duration::from_millis(1500), "1.5s".parse().unwrap());
+        assert_eq!(Duration::from_millis(300), "300ms".parse().unwrap());
+        assert_eq!(Duration::from_nanos(100_000), "100us".parse().unwrap());
+        assert_eq!(Duration::from_millis(120_000), "2m".parse().unwrap());
+        assert!("2x".parse::<Duration>().is_err());
+        assert!("s".parse::<Duration>().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Pre-flight checks with `--check-env` for machine settings which make benchmark
+//! results noisy, read from `/proc` and `/sys` on Linux.
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_owned())
+}
+
+/// Contents of `file` in entries of `dir` whose names start with `entry_prefix`.
+fn read_entries(dir: &Path, entry_prefix: &str, file: &str) -> Vec<String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut values: Vec<(String, String)> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(entry_prefix))
+        .filter_map(|e| {
+            let value = read_trimmed(&e.path().join(file))?;
+            Some((e.file_name().to_string_lossy().into_owned(), value))
+        })
+        .collect();
+    values.sort();
+    values.into_iter().map(|(_, value)| value).collect()
+}
+
+/// Load average above which other processes likely compete with the benchmark.
+fn max_load(cpus: usize) -> f64 {
+    (cpus as f64 / 10.0).max(1.0)
+}
+
+/// Problems of the machine under `root`, normally `/`, as warnings.
+pub fn check_environment_at(root: &Path, cpus: usize) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let cpu_dir = root.join("sys/devices/system/cpu");
+    let governors = read_entries(&cpu_dir, "cpu", "cpufreq/scaling_governor");
+    let slow = governors.iter().filter(|g| *g != "performance").count();
+    if slow != 0 {
+        let governor = governors.iter().find(|g| *g != "performance").unwrap();
+        warnings.push(format!(
+            "CPU frequency governor is `{}` on {} of {} CPUs, not `performance`",
+            governor,
+            slow,
+            governors.len()
+        ));
+    }
+
+    let no_turbo = read_trimmed(&cpu_dir.join("intel_pstate/no_turbo"));
+    let boost = read_trimmed(&cpu_dir.join("cpufreq/boost"));
+    if no_turbo.as_deref() == Some("0") || boost.as_deref() == Some("1") {
+        warnings.push("turbo boost is enabled, CPU frequency depends on temperature".to_owned());
+    }
+
+    if let Some(loadavg) = read_trimmed(&root.join("proc/loadavg")) {
+        let load = loadavg
+            .split_whitespace()
+            .next()
+            .and_then(|l| l.parse::<f64>().ok());
+        if let Some(load) = load.filter(|&l| l > max_load(cpus)) {
+            warnings.push(format!(
+                "load average is {:.2} on {} CPUs, other processes compete for CPU time",
+                load, cpus
+            ));
+        }
+    }
+
+    let power_dir = root.join("sys/class/power_supply");
+    let types = read_entries(&power_dir, "", "type");
+    let statuses = read_entries(&power_dir, "", "status");
+    let on_battery =
+        types.iter().any(|t| t == "Battery") && statuses.iter().any(|s| s == "Discharging");
+    if on_battery {
+        warnings.push("running on battery, power saving may throttle CPUs".to_owned());
+    }
+
+    match read_trimmed(&root.join("proc/sys/kernel/randomize_va_space")).as_deref() {
+        None | Some("0") => {}
+        Some(aslr) => warnings.push(format!(
+            "ASLR is enabled (randomize_va_space={}), memory layout differs between runs",
+            aslr
+        )),
+    }
+    warnings
+}
+
+/// Problems of this machine, as warnings.
+pub fn check_environment() -> Vec<String> {
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    check_environment_at(Path::new("/"), cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::env_check::check_environment_at;
+
+    #[test]
+    fn check() {
+        let root = env::temp_dir().join(format!("absh-env-check-test-{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write(
+            "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "performance\n",
+        );
+        write(
+            "sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "performance\n",
+        );
+        write("sys/devices/system/cpu/intel_pstate/no_turbo", "1\n");
+        write("proc/loadavg", "0.50 0.40 0.30 1/100 1000\n");
+        write("proc/sys/kernel/randomize_va_space", "0\n");
+        write("sys/class/power_supply/AC/type", "Mains\n");
+        assert!(check_environment_at(&root, 2).is_empty());
+
+        write(
+            "sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "powersave\n",
+        );
+        write("sys/devices/system/cpu/intel_pstate/no_turbo", "0\n");
+        write("proc/loadavg", "3.00 0.40 0.30 1/100 1000\n");
+        write("proc/sys/kernel/randomize_va_space", "2\n");
+        write("sys/class/power_supply/BAT0/type", "Battery\n");
+        write("sys/class/power_supply/BAT0/status", "Discharging\n");
+        assert_eq!(
+            vec![
+                "CPU frequency governor is `powersave` on 1 of 2 CPUs, not `performance`",
+                "turbo boost is enabled, CPU frequency depends on temperature",
+                "load average is 3.00 on 2 CPUs, other processes compete for CPU time",
+                "running on battery, power saving may throttle CPUs",
+                "ASLR is enabled (randomize_va_space=2), memory layout differs between runs",
+            ],
+            check_environment_at(&root, 2)
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
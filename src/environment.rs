@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::thread;
+
+use crate::version::version_long;
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_owned())
+}
+
+/// Description of the machine absh runs on, as `(key, value)` pairs.
+pub fn describe_environment() -> Vec<(&'static str, String)> {
+    let mut r = vec![
+        ("absh", version_long()),
+        ("os", format!("{} {}", env::consts::OS, env::consts::ARCH)),
+    ];
+    if let Some(kernel) = read_trimmed("/proc/sys/kernel/osrelease") {
+        r.push(("kernel", kernel));
+    }
+    if let Some(hostname) = read_trimmed("/proc/sys/kernel/hostname") {
+        r.push(("hostname", hostname));
+    }
+    if let Ok(cpus) = thread::available_parallelism() {
+        r.push(("cpus", cpus.to_string()));
+    }
+    if let Some(loadavg) = read_trimmed("/proc/loadavg") {
+        let loadavg: Vec<&str> = loadavg.split_whitespace().take(3).collect();
+        r.push(("loadavg", loadavg.join(" ")));
+    }
+    r
+}
+
+/// Environment properties which do not change between runs on the same machine, like
+/// `absh=0.1.0 os=linux x86_64 kernel=6.1.0 hostname=bench cpus=8`.
+pub fn fingerprint() -> String {
+    describe_environment()
+        .into_iter()
+        .filter(|(key, _)| *key != "loadavg")
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,67 @@
+//! Exit status of a benchmark, so wrappers can branch on the outcome without parsing
+//! the output.
+//!
+//! Errors exit with 1 and invalid arguments with 2, as usual.
+
+use crate::math::ratio::Verdict;
+
+/// Exit code of `absh` when the benchmark completes or stops.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExitCode {
+    /// No variant differs significantly from A in time, or a command other than
+    /// a benchmark succeeded.
+    Success = 0,
+    /// Some variant is significantly slower than A.
+    Regression = 3,
+    /// Some variant is significantly faster than A, and none is slower.
+    Improvement = 4,
+    /// Stopped early by `--abort-if-ratio-above`, or because of failed runs.
+    Aborted = 5,
+    /// Ended before every variant had `--min-samples` samples, without statistics.
+    TooFewSamples = 6,
+    /// Stopped by Ctrl-C, like processes killed by `SIGINT`.
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    /// Outcome of time comparisons of every variant to A.
+    pub fn from_verdicts(verdicts: impl IntoIterator<Item = Verdict>) -> ExitCode {
+        let verdicts: Vec<Verdict> = verdicts.into_iter().collect();
+        if verdicts.contains(&Verdict::Higher) {
+            ExitCode::Regression
+        } else if verdicts.contains(&Verdict::Lower) {
+            ExitCode::Improvement
+        } else {
+            ExitCode::Success
+        }
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> std::process::ExitCode {
+        std::process::ExitCode::from(code as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exit_code::ExitCode;
+    use crate::math::ratio::Verdict;
+
+    #[test]
+    fn from_verdicts() {
+        assert_eq!(ExitCode::Success, ExitCode::from_verdicts([]));
+        assert_eq!(
+            ExitCode::Success,
+            ExitCode::from_verdicts([Verdict::NoDifference])
+        );
+        assert_eq!(
+            ExitCode::Improvement,
+            ExitCode::from_verdicts([Verdict::Lower, Verdict::NoDifference])
+        );
+        assert_eq!(
+            ExitCode::Regression,
+            ExitCode::from_verdicts([Verdict::Lower, Verdict::Higher])
+        );
+    }
+}
@@ -1,34 +1,483 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use crate::ansi;
 use crate::bars::PlotHighlight;
+use crate::duration::Duration;
 use crate::experiment_name::ExperimentName;
 use crate::math::numbers::Numbers;
+use crate::measure::key::MeasureKey;
 use crate::measure::map::MeasureMap;
+use crate::metadata::Metadata;
+use crate::sensors::SensorSample;
+
+/// Script with a variant name, like `lto=make lto`.
+#[derive(Clone, Debug)]
+pub struct NamedScript {
+    pub name: String,
+    pub script: String,
+}
+
+impl FromStr for NamedScript {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<NamedScript> {
+        let (name, script) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expecting `NAME=SCRIPT`: `{}`", s))?;
+        ExperimentName::validate(name)?;
+        Ok(NamedScript {
+            name: name.to_owned(),
+            script: script.to_owned(),
+        })
+    }
+}
+
+/// Environment variable assignment, like `RUST_LOG=info`.
+#[derive(Clone, Debug)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for EnvVar {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<EnvVar> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok(EnvVar {
+                name: name.to_owned(),
+                value: value.to_owned(),
+            }),
+            _ => Err(anyhow::anyhow!("expecting `KEY=VALUE`: `{}`", s)),
+        }
+    }
+}
+
+/// Environment variable for a named variant, like `lto=RUST_LOG=info`.
+#[derive(Clone, Debug)]
+pub struct NamedEnvVar {
+    pub name: String,
+    pub var: EnvVar,
+}
+
+impl FromStr for NamedEnvVar {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<NamedEnvVar> {
+        let named: NamedScript = s
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expecting `NAME=KEY=VALUE`: `{}`", s))?;
+        Ok(NamedEnvVar {
+            name: named.name,
+            var: named.script.parse()?,
+        })
+    }
+}
+
+/// Working directory for a named variant, like `lto=build-lto`.
+#[derive(Clone, Debug)]
+pub struct NamedDir {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+impl FromStr for NamedDir {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<NamedDir> {
+        let named: NamedScript = s
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expecting `NAME=PATH`: `{}`", s))?;
+        Ok(NamedDir {
+            name: named.name,
+            dir: PathBuf::from(named.script),
+        })
+    }
+}
 
 pub struct Experiment {
     pub name: ExperimentName,
     pub warmup: String,
     pub run: String,
+    /// Script deciding whether to run this experiment in an iteration, empty to always run.
+    pub when: String,
+    /// Script run before each run, before the warmup, empty for none.
+    pub setup: String,
+    /// Script run after each run, also after failed runs, empty for none.
+    pub teardown: String,
+    /// Extra environment variables for warmup and run scripts.
+    pub env: Vec<(String, String)>,
+    /// Working directory of all scripts, `None` for the current directory.
+    pub dir: Option<PathBuf>,
+    /// Description, owner and links from the config.
+    pub metadata: Metadata,
     pub measures: MeasureMap<Numbers>,
+    /// Metrics reported by the script, by metric name.
+    pub metrics: BTreeMap<String, Numbers>,
+    /// Run of each metric sample, as an index into `measures` samples, by metric name.
+    ///
+    /// Missing for metrics of logs which did not record it.
+    pub metric_runs: BTreeMap<String, Vec<u64>>,
+    /// Number of failed warmup or script runs.
+    pub failures: u64,
+    /// Number of failures caused by the OOM killer.
+    pub oom_kills: u64,
+    /// Number of failures caused by `SIGKILL` without confirmed OOM kill.
+    pub suspected_oom_kills: u64,
+    /// Number of failures caused by scripts running longer than the timeout.
+    pub timeouts: u64,
+    /// Number of iterations skipped because `when` exited non-zero.
+    pub skips: u64,
+    /// Number of successful warmup runs.
+    pub warmups: u64,
+    /// Sensor readings of successful runs when recording sensors.
+    pub sensors: Vec<SensorSample>,
+    /// Iteration of each successful run, counting from 1.
+    pub iterations: Vec<u64>,
+    /// Number of runs started at each position within their iteration, first one first.
+    pub positions: Vec<u64>,
 }
 
 impl Experiment {
+    pub fn new(name: ExperimentName, warmup: String, run: String) -> Experiment {
+        Experiment {
+            name,
+            warmup,
+            run,
+            when: String::new(),
+            setup: String::new(),
+            teardown: String::new(),
+            env: Vec::new(),
+            dir: None,
+            metadata: Metadata::default(),
+            measures: MeasureMap::new_all_default(),
+            metrics: BTreeMap::new(),
+            metric_runs: BTreeMap::new(),
+            failures: 0,
+            oom_kills: 0,
+            suspected_oom_kills: 0,
+            timeouts: 0,
+            skips: 0,
+            warmups: 0,
+            sensors: Vec::new(),
+            iterations: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// Log lines with scripts and settings of the experiment.
+    pub fn log_lines(&self) -> Vec<String> {
+        let n = self.name;
+        let mut lines = vec![format!("{}.run: {}", n, self.run)];
+        if !self.warmup.is_empty() {
+            lines.push(format!("{}.warmup: {}", n, self.warmup));
+        }
+        if !self.when.is_empty() {
+            lines.push(format!("{}.when: {}", n, self.when));
+        }
+        if !self.setup.is_empty() {
+            lines.push(format!("{}.setup: {}", n, self.setup));
+        }
+        if !self.teardown.is_empty() {
+            lines.push(format!("{}.teardown: {}", n, self.teardown));
+        }
+        if let Some(dir) = &self.dir {
+            lines.push(format!("{}.dir: {}", n, dir.display()));
+        }
+        for (name, value) in &self.env {
+            lines.push(format!("{}.var: {}={}", n, name, value));
+        }
+        lines
+    }
+
+    /// Copy for reporting on another thread while more samples are collected.
+    ///
+    /// Samples are shared with this experiment until it changes, so this is cheap.
+    pub fn snapshot(&self) -> Experiment {
+        let mut measures = MeasureMap::default();
+        for (key, numbers) in self.measures.iter() {
+            measures.insert(key, numbers.clone());
+        }
+        Experiment {
+            name: self.name,
+            warmup: self.warmup.clone(),
+            run: self.run.clone(),
+            when: self.when.clone(),
+            setup: self.setup.clone(),
+            teardown: self.teardown.clone(),
+            env: self.env.clone(),
+            dir: self.dir.clone(),
+            metadata: self.metadata.clone(),
+            measures,
+            metrics: self.metrics.clone(),
+            metric_runs: self.metric_runs.clone(),
+            failures: self.failures,
+            oom_kills: self.oom_kills,
+            suspected_oom_kills: self.suspected_oom_kills,
+            timeouts: self.timeouts,
+            skips: self.skips,
+            warmups: self.warmups,
+            sensors: self.sensors.clone(),
+            iterations: self.iterations.clone(),
+            positions: self.positions.clone(),
+        }
+    }
+
+    /// Copy without runs in which any of `keys` measures is outside of its Tukey fences.
+    pub fn without_outliers(&self, keys: &[MeasureKey]) -> Experiment {
+        let runs = self.measures[MeasureKey::WallTime].len();
+        let fences: Vec<(&Numbers, (f64, f64))> = keys
+            .iter()
+            .map(|&key| &self.measures[key])
+            .filter(|numbers| numbers.len() == runs)
+            .filter_map(|numbers| Some((numbers, numbers.tukey_fences()?)))
+            .collect();
+        let keep: Vec<bool> = (0..runs)
+            .map(|i| {
+                fences.iter().all(|(numbers, (lo, hi))| {
+                    let n = numbers.raw()[i] as f64;
+                    *lo <= n && n <= *hi
+                })
+            })
+            .collect();
+        self.retain_runs(&keep)
+    }
+
+    /// Copy without runs with given indices, like samples excluded in `absh review`.
+    pub fn without_runs(&self, excluded: &[usize]) -> Experiment {
+        let runs = self.measures[MeasureKey::WallTime].len();
+        let keep: Vec<bool> = (0..runs).map(|i| !excluded.contains(&i)).collect();
+        self.retain_runs(&keep)
+    }
+
+    /// Copy without the first `runs` runs, like warmup runs.
+    pub fn without_first_runs(&self, runs: usize) -> Experiment {
+        let keep: Vec<bool> = (0..self.runs()).map(|i| i >= runs).collect();
+        self.retain_runs(&keep)
+    }
+
+    /// Record `value` of `metric` reported by the last run.
+    pub fn push_metric(&mut self, metric: String, value: u64) {
+        let run = self.runs() as u64 - 1;
+        self.metric_runs
+            .entry(metric.clone())
+            .or_default()
+            .push(run);
+        self.metrics.entry(metric).or_default().push(value);
+    }
+
+    /// Run of each sample of `metric`, `None` if unknown.
+    ///
+    /// Metrics reported in every run of logs without run indices belong to runs in order.
+    fn metric_run_indices(&self, metric: &str) -> Option<Vec<u64>> {
+        let len = self.metrics.get(metric)?.len();
+        match self.metric_runs.get(metric) {
+            Some(runs) if runs.len() == len => Some(runs.clone()),
+            Some(_) => None,
+            None if len == self.runs() => Some((0..len as u64).collect()),
+            None => None,
+        }
+    }
+
+    /// Copy with runs for which `keep` is true.
+    ///
+    /// Metrics are filtered by their run indices, and kept as is if those are unknown.
+    fn retain_runs(&self, keep: &[bool]) -> Experiment {
+        let runs = keep.len();
+        let kept = |len: usize, i: usize| len != runs || keep[i];
+
+        let mut r = self.snapshot();
+        for (key, numbers) in self.measures.iter() {
+            let mut filtered = Numbers::new();
+            for (i, n) in numbers.iter().enumerate() {
+                if kept(numbers.len(), i) {
+                    filtered.push(n);
+                }
+            }
+            r.measures[key] = filtered;
+        }
+        let sensors = self.sensors.iter().enumerate();
+        r.sensors = sensors
+            .filter(|(i, _)| kept(self.sensors.len(), *i))
+            .map(|(_, &s)| s)
+            .collect();
+        let iterations = self.iterations.iter().enumerate();
+        r.iterations = iterations
+            .filter(|(i, _)| kept(self.iterations.len(), *i))
+            .map(|(_, &n)| n)
+            .collect();
+
+        // Index of each kept run among kept runs.
+        let new_index: Vec<u64> = keep
+            .iter()
+            .scan(0, |next, &k| {
+                let index = *next;
+                *next += k as u64;
+                Some(index)
+            })
+            .collect();
+        for (metric, numbers) in &self.metrics {
+            let runs = match self.metric_run_indices(metric) {
+                Some(runs) => runs,
+                None => continue,
+            };
+            let mut filtered = Numbers::new();
+            let mut filtered_runs = Vec::new();
+            for (n, run) in numbers.iter().zip(runs) {
+                if keep.get(run as usize).copied().unwrap_or(true) {
+                    filtered.push(n);
+                    filtered_runs.push(new_index.get(run as usize).copied().unwrap_or(run));
+                }
+            }
+            r.metrics.insert(metric.clone(), filtered);
+            r.metric_runs.insert(metric.clone(), filtered_runs);
+        }
+        r
+    }
+
+    /// Add samples of `keys` measures and all metrics of `other` after samples of this one.
+    pub fn append_samples(&mut self, other: &Experiment, keys: &[MeasureKey]) {
+        let offset = self.runs() as u64;
+        for (metric, numbers) in &other.metrics {
+            let own = match self.metrics.get(metric) {
+                Some(_) => self.metric_run_indices(metric),
+                None => Some(Vec::new()),
+            };
+            match (own, other.metric_run_indices(metric)) {
+                (Some(mut runs), Some(other_runs)) => {
+                    runs.extend(other_runs.iter().map(|&run| run + offset));
+                    self.metric_runs.insert(metric.clone(), runs);
+                }
+                _ => {
+                    self.metric_runs.remove(metric);
+                }
+            }
+            let target = self.metrics.entry(metric.clone()).or_default();
+            for n in numbers.iter() {
+                target.push(n);
+            }
+        }
+        for &key in keys {
+            for n in other.measures[key].iter() {
+                self.measures[key].push(n);
+            }
+        }
+    }
+
+    /// Forget all samples collected so far.
+    pub fn clear_samples(&mut self) {
+        for numbers in self.measures.values_mut() {
+            numbers.clear();
+        }
+        for numbers in self.metrics.values_mut() {
+            numbers.clear();
+        }
+        self.metric_runs.clear();
+        self.sensors.clear();
+        self.iterations.clear();
+        self.positions.clear();
+    }
+
     pub fn plot_highlights(&self) -> PlotHighlight {
         PlotHighlight {
-            non_zero: format!("{}", self.name.color().to_owned()),
-            zero: format!("{}", ansi::WHITE_BG),
+            non_zero: self.name.color().to_owned(),
+            zero: ansi::WHITE_BG.to_owned(),
             reset: ansi::RESET.to_owned(),
         }
     }
 
     pub fn plot_halves_highlights(&self) -> PlotHighlight {
         PlotHighlight {
-            non_zero: format!("{}", self.name.color().to_owned()),
+            non_zero: self.name.color().to_owned(),
             zero: "".to_owned(),
             reset: ansi::RESET.to_owned(),
         }
     }
 
+    /// Environment variables describing the previous successful run of this experiment,
+    /// like `ABSH_PREV_DURATION_MS`.
+    pub fn prev_sample_env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if let Some(&nanos) = self.measures[MeasureKey::WallTime].raw().last() {
+            env.push((
+                "ABSH_PREV_DURATION_MS".to_owned(),
+                Duration::from_nanos(nanos).millis().to_string(),
+            ));
+        }
+        if let Some(&bytes) = self.measures[MeasureKey::MaxRss].raw().last() {
+            env.push(("ABSH_PREV_MAX_RSS_KB".to_owned(), (bytes >> 10).to_string()));
+        }
+        for (name, numbers) in &self.metrics {
+            if let Some(value) = numbers.raw().last() {
+                let var = name
+                    .chars()
+                    .map(|c| match c {
+                        c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                        _ => '_',
+                    })
+                    .collect::<String>();
+                env.push((format!("ABSH_PREV_METRIC_{}", var), value.to_string()));
+            }
+        }
+        env
+    }
+
     pub fn runs(&self) -> usize {
         self.measures.values().next().unwrap().len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::experiment::Experiment;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+
+    /// Experiment with wall times `times` and metric `m` reported by runs with odd times.
+    fn experiment(times: &[u64]) -> Experiment {
+        let name = ExperimentName::intern("retain");
+        let mut test = Experiment::new(name, String::new(), String::new());
+        for &t in times {
+            for key in MeasureKey::ALL.iter().copied() {
+                test.measures[key].push(t);
+            }
+            if t % 2 == 1 {
+                test.push_metric("m".to_owned(), t * 10);
+            }
+        }
+        test
+    }
+
+    #[test]
+    fn without_runs_filters_metrics() {
+        let test = experiment(&[1, 2, 3, 4, 5]).without_runs(&[0, 3]);
+        assert_eq!(&[2, 3, 5], test.measures[MeasureKey::WallTime].raw());
+        assert_eq!(&[30, 50], test.metrics["m"].raw());
+        assert_eq!(vec![1, 2], test.metric_runs["m"]);
+
+        let test = test.without_runs(&[1]);
+        assert_eq!(&[50], test.metrics["m"].raw());
+        assert_eq!(vec![1], test.metric_runs["m"]);
+    }
+
+    #[test]
+    fn append_samples_offsets_metric_runs() {
+        let mut test = experiment(&[1, 2]);
+        test.append_samples(&experiment(&[4, 5]), MeasureKey::ALL);
+        assert_eq!(&[10, 50], test.metrics["m"].raw());
+        assert_eq!(vec![0, 3], test.metric_runs["m"]);
+    }
+
+    #[test]
+    fn metrics_of_logs_without_runs() {
+        let mut test = experiment(&[1, 3, 5]);
+        test.metric_runs.clear();
+        assert_eq!(&[10, 50], test.without_runs(&[1]).metrics["m"].raw());
+
+        let mut test = experiment(&[1, 2, 3]);
+        test.metric_runs.clear();
+        assert_eq!(&[10, 30], test.without_runs(&[0]).metrics["m"].raw());
+    }
+}
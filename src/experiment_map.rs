@@ -30,6 +30,10 @@ impl<A> ExperimentMap<A> {
         self.values.insert(exp.index(), value);
     }
 
+    pub fn remove(&mut self, exp: ExperimentName) -> Option<A> {
+        self.values.remove(exp.index())
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (ExperimentName, &A)> {
         self.values
             .iter()
@@ -67,7 +71,7 @@ impl<A> ExperimentMap<A> {
     pub fn zip<'a, B>(
         &'a self,
         other: &'a ExperimentMap<B>,
-    ) -> impl Iterator<Item = (ExperimentName, &A, &B)> + 'a {
+    ) -> impl Iterator<Item = (ExperimentName, &'a A, &'a B)> + 'a {
         self.values
             .zip(&other.values)
             .map(|(k, a, b)| (ExperimentName::from_index(k), a, b))
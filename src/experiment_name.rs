@@ -1,56 +1,96 @@
 use std::fmt;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
 
 use crate::ansi;
 
+/// Names of all experiments known to this process, indexed by `ExperimentName`.
+static NAMES: Lazy<Mutex<Vec<&'static str>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+const COLORS: &[&str] = &[
+    ansi::RED,
+    ansi::GREEN,
+    ansi::BLUE,
+    ansi::MAGENTA,
+    ansi::CYAN,
+    ansi::YELLOW,
+];
+
+/// Experiment name, like `A` or `baseline`.
+///
+/// Names are interned, so this is a cheap index.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum ExperimentName {
-    A,
-    B,
-    C,
-    D,
-    E,
-}
+pub struct ExperimentName(usize);
 
 impl ExperimentName {
-    pub fn index(&self) -> usize {
-        match self {
-            ExperimentName::A => 0,
-            ExperimentName::B => 1,
-            ExperimentName::C => 2,
-            ExperimentName::D => 3,
-            ExperimentName::E => 4,
+    /// Name for the string, registering it if it is new.
+    pub fn intern(name: &str) -> ExperimentName {
+        let mut names = NAMES.lock().unwrap();
+        if let Some(index) = names.iter().position(|n| *n == name) {
+            return ExperimentName(index);
         }
+        names.push(Box::leak(name.to_owned().into_boxed_str()));
+        ExperimentName(names.len() - 1)
     }
 
-    pub fn from_index(index: usize) -> ExperimentName {
-        match index {
-            0 => ExperimentName::A,
-            1 => ExperimentName::B,
-            2 => ExperimentName::C,
-            3 => ExperimentName::D,
-            4 => ExperimentName::E,
-            _ => panic!("Invalid index: {}", index),
-        }
+    /// Name of a variant given with `-a`, `-b` etc: `A` for 0, `B` for 1.
+    pub fn letter(index: usize) -> ExperimentName {
+        assert!(index < 26, "Invalid index: {}", index);
+        ExperimentName::intern(&((b'A' + index as u8) as char).to_string())
     }
 
-    pub fn name(&self) -> &str {
-        match self {
-            ExperimentName::A => "A",
-            ExperimentName::B => "B",
-            ExperimentName::C => "C",
-            ExperimentName::D => "D",
-            ExperimentName::E => "E",
+    /// Check the string can be used as an experiment name.
+    pub fn validate(name: &str) -> anyhow::Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(anyhow::anyhow!(
+                "invalid name: `{}`, expecting letters, digits, `_`, `-` or `.`",
+                name
+            ));
         }
+        Ok(())
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+
+    pub(crate) fn from_index(index: usize) -> ExperimentName {
+        assert!(
+            index < NAMES.lock().unwrap().len(),
+            "Invalid index: {}",
+            index
+        );
+        ExperimentName(index)
+    }
+
+    /// Find already registered name.
+    pub fn from_name(name: &str) -> Option<ExperimentName> {
+        NAMES
+            .lock()
+            .unwrap()
+            .iter()
+            .position(|n| *n == name)
+            .map(ExperimentName)
+    }
+
+    pub fn name(&self) -> &'static str {
+        NAMES.lock().unwrap()[self.0]
+    }
+
+    /// Name given in a flag like `--input VARIANT:NAME=PATH` refers to this experiment.
+    /// Single letter names are case-insensitive.
+    pub fn is_named(&self, name: &str) -> bool {
+        let own = self.name();
+        name == own || (name.len() == 1 && name.eq_ignore_ascii_case(own))
     }
 
     pub fn color(&self) -> &'static str {
-        match self {
-            ExperimentName::A => ansi::RED,
-            ExperimentName::B => ansi::GREEN,
-            ExperimentName::C => ansi::BLUE,
-            ExperimentName::D => ansi::MAGENTA,
-            ExperimentName::E => ansi::CYAN,
-        }
+        COLORS[self.0 % COLORS.len()]
     }
 
     pub fn name_colored(&self) -> String {
@@ -63,3 +103,26 @@ impl fmt::Display for ExperimentName {
         write!(f, "{}", self.name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::experiment_name::ExperimentName;
+
+    #[test]
+    fn intern() {
+        let pgo = ExperimentName::intern("pgo");
+        assert_eq!(pgo, ExperimentName::intern("pgo"));
+        assert_eq!(Some(pgo), ExperimentName::from_name("pgo"));
+        assert_eq!("pgo", pgo.name());
+        assert_eq!(None, ExperimentName::from_name("never-interned"));
+        assert_eq!("C", ExperimentName::letter(2).name());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(ExperimentName::validate("lto-1.2_x").is_ok());
+        assert!(ExperimentName::validate("").is_err());
+        assert!(ExperimentName::validate("a b").is_err());
+        assert!(ExperimentName::validate("a:b").is_err());
+    }
+}
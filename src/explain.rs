@@ -0,0 +1,280 @@
+//! Plain-English explanation of comparisons printed with `--explain`.
+
+use std::fmt::Display;
+use std::fmt::Write;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::ks::ks_test;
+use crate::math::numbers::Numbers;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Stat;
+use crate::math::ratio::Verdict;
+use crate::math::stats::Stats;
+use crate::render_stats::RenderOptions;
+
+/// Fewer runs than this make intervals unreliable.
+const FEW_RUNS: u64 = 10;
+/// Standard deviation above this fraction of the mean is noisy.
+const NOISY_CV: f64 = 0.1;
+
+fn percent(ratio: f64) -> f64 {
+    (ratio - 1.0).abs() * 100.0
+}
+
+/// Warnings about data of one variant which weaken the comparison.
+fn variant_caveats(
+    name: ExperimentName,
+    test: &Experiment,
+    numbers: &Numbers,
+    stats: &Stats<u64>,
+) -> Vec<String> {
+    let mut r = Vec::new();
+    if stats.count < FEW_RUNS {
+        r.push(format!(
+            "{} has only {} runs. With so few runs the interval is wide and the normal \
+             approximation behind it may not hold; {} or more runs are better.",
+            name, stats.count, FEW_RUNS
+        ));
+    }
+    if stats.mean != 0 && stats.std as f64 / stats.mean as f64 > NOISY_CV {
+        r.push(format!(
+            "Runs of {} vary a lot: the standard deviation is {:.0}% of the mean. \
+             Background activity on the machine may be adding noise.",
+            name,
+            stats.std as f64 / stats.mean as f64 * 100.0
+        ));
+    }
+    if let Some((lo, hi)) = numbers.tukey_fences() {
+        let outliers = numbers
+            .iter()
+            .filter(|&n| (n as f64) < lo || (n as f64) > hi)
+            .count();
+        if outliers != 0 {
+            r.push(format!(
+                "{} of {} runs of {} are outliers, far from the others. They pull the mean; \
+                 `--filter-outliers` or `absh review` can exclude them.",
+                outliers, stats.count, name
+            ));
+        }
+    }
+    if test.failures != 0 {
+        r.push(format!(
+            "{} runs of {} failed and are not counted. If failures depend on the change, \
+             the remaining runs are not a fair comparison.",
+            test.failures, name
+        ));
+    }
+    r
+}
+
+/// Explanation of the comparison of each variant with A.
+pub(crate) fn explain_measure<D: Display>(
+    name: &str,
+    words: (&str, &str),
+    tests: &ExperimentMap<Experiment>,
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+    display: impl Fn(u64) -> D,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
+    let mut iter = tests.iter().zip(stats.values());
+    let ((a_name, a), stats_a) = iter.next().unwrap();
+    for ((b_name, b), stats_b) in iter {
+        let interval = RatioInterval::compare(opts.stat, opts.conf, numbers(a), numbers(b));
+        let value = |stats: &Stats<u64>| match opts.stat {
+            Stat::Mean => stats.mean,
+            Stat::Median => stats.med,
+        };
+        writeln!(r, "{}, {}/{}:", name, b_name, a_name)?;
+        writeln!(
+            r,
+            "  The {} of {} is {} and of {} is {}, so their ratio {}/{} is {:.3}.",
+            opts.stat.name(),
+            b_name,
+            display(value(stats_b)),
+            a_name,
+            display(value(stats_a)),
+            b_name,
+            a_name,
+            interval.ratio
+        )?;
+        writeln!(
+            r,
+            "  The {conf} confidence interval {min:.3}..{max:.3} is the range of ratios consistent \
+             with the measurements: if the benchmark were repeated many times, intervals \
+             computed this way would contain the true ratio {conf} of the time.",
+            conf = opts.conf,
+            min = interval.min,
+            max = interval.max,
+        )?;
+        match interval.verdict() {
+            Verdict::Lower => writeln!(
+                r,
+                "  The whole interval is below 1, so {} is {} than {}, by {:.1}% to {:.1}%.",
+                b_name,
+                words.0,
+                a_name,
+                percent(interval.max),
+                percent(interval.min)
+            )?,
+            Verdict::Higher => writeln!(
+                r,
+                "  The whole interval is above 1, so {} is {} than {}, by {:.1}% to {:.1}%.",
+                b_name,
+                words.1,
+                a_name,
+                percent(interval.min),
+                percent(interval.max)
+            )?,
+            Verdict::NoDifference if interval.max < interval.min => writeln!(
+                r,
+                "  The runs of {} vary too much for the interval to be bounded, so nothing can \
+                 be said about the ratio yet.",
+                a_name
+            )?,
+            Verdict::NoDifference => writeln!(
+                r,
+                "  The interval includes 1 (no change), so the measurements cannot tell whether \
+                 {} is {} or {}: anything from {:.1}% {} to {:.1}% {} is still plausible. \
+                 This is not proof that they are the same.",
+                b_name,
+                words.0,
+                words.1,
+                percent(interval.min),
+                words.0,
+                percent(interval.max),
+                words.1
+            )?,
+        }
+        if let Some(p) = opts.significance.p_value(numbers(a), numbers(b)) {
+            writeln!(
+                r,
+                "  The p-value {:.4} ({}) is the chance of seeing a difference at least this \
+                 large if {} and {} were actually the same; \
+                 below {} is called significant at the {} confidence level.",
+                p,
+                opts.significance.long_name(),
+                a_name,
+                b_name,
+                opts.conf.alpha(),
+                opts.conf
+            )?;
+        }
+        writeln!(
+            r,
+            "  n={}/{} are the numbers of successful runs of {} and {}; \
+             more runs make the interval narrower.",
+            stats_a.count, stats_b.count, a_name, b_name
+        )?;
+
+        let mut caveats = Vec::new();
+        caveats.extend(variant_caveats(a_name, a, numbers(a), stats_a));
+        caveats.extend(variant_caveats(b_name, b, numbers(b), stats_b));
+        if let Some(ks) = ks_test(numbers(a).sorted(), numbers(b).sorted()) {
+            if ks.p < opts.conf.alpha() && interval.verdict() == Verdict::NoDifference {
+                caveats.push(format!(
+                    "Although the {}s do not differ significantly, the distributions do \
+                     (Kolmogorov-Smirnov p={:.4}): {} may have a different shape, like a \
+                     second mode or a longer tail. Compare the distribution plots.",
+                    opts.stat.name(),
+                    ks.p,
+                    b_name
+                ));
+            }
+        }
+        if !caveats.is_empty() {
+            writeln!(r, "  Caveats:")?;
+            for caveat in caveats {
+                writeln!(r, "  - {}", caveat)?;
+            }
+        }
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duration::Duration;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::explain::explain_measure;
+    use crate::math::ratio::Stat;
+    use crate::measure::key::MeasureKey;
+    use crate::render_stats::RenderOptions;
+
+    fn explain(samples: [&[u64]; 2], failures: u64) -> String {
+        explain_stat(samples, failures, Stat::Mean)
+    }
+
+    fn explain_stat(samples: [&[u64]; 2], failures: u64, stat: Stat) -> String {
+        let mut tests = ExperimentMap::default();
+        for (name, samples) in ["explain-a", "explain-b"].iter().zip(samples.iter()) {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for &t in samples.iter() {
+                test.measures[MeasureKey::WallTime].push(t * 1_000_000);
+            }
+            test.failures = failures;
+            tests.insert(name, test);
+        }
+        explain_measure(
+            "Time (in seconds)",
+            ("faster", "slower"),
+            &tests,
+            &RenderOptions {
+                stat,
+                ..RenderOptions::default()
+            },
+            |t| &t.measures[MeasureKey::WallTime],
+            Duration::from_nanos,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn slower() {
+        let a = [100, 101, 99, 100, 102, 98, 100, 101, 99, 100];
+        let b = [120, 121, 119, 120, 122, 118, 120, 121, 119, 120];
+        let text = explain([&a, &b], 0);
+        assert!(
+            text.starts_with("Time (in seconds), explain-b/explain-a:\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("so explain-b is slower than explain-a, by "),
+            "{}",
+            text
+        );
+        assert!(!text.contains("Caveats"), "{}", text);
+
+        let text = explain_stat([&a, &b], 0, Stat::Median);
+        assert!(
+            text.contains("The median of explain-b is 0.120 and of explain-a is 0.100"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn caveats() {
+        let text = explain([&[100, 140, 100, 100], &[100, 101, 99, 100]], 2);
+        assert!(
+            text.contains("cannot tell whether explain-b is faster or slower"),
+            "{}",
+            text
+        );
+        assert!(text.contains("explain-a has only 4 runs."), "{}", text);
+        assert!(text.contains("Runs of explain-a vary a lot"), "{}", text);
+        assert!(
+            text.contains("1 of 4 runs of explain-a are outliers"),
+            "{}",
+            text
+        );
+        assert!(text.contains("2 runs of explain-b failed"), "{}", text);
+    }
+}
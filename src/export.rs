@@ -0,0 +1,78 @@
+//! JSON and HTML reports with `--json` and `--html`, rewritten while the benchmark runs.
+
+use std::path::PathBuf;
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::html_report::write_html_report;
+use crate::json_report::write_json_report;
+use crate::measure::key::MeasureKey;
+use crate::measure::tr::AllMeasures;
+use crate::metadata::Metadata;
+use crate::render_stats::RenderOptions;
+
+/// Reports written on another thread, so the measurement loop does not wait for them.
+pub struct Export {
+    json: Option<PathBuf>,
+    html: Option<PathBuf>,
+    running: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl Export {
+    pub fn new(json: Option<PathBuf>, html: Option<PathBuf>) -> Export {
+        Export {
+            json,
+            html,
+            running: None,
+        }
+    }
+
+    /// Write reports of `experiments`.
+    ///
+    /// Skipped while the previous export is still running, except the `last` one.
+    pub fn start(
+        &mut self,
+        experiments: &ExperimentMap<Experiment>,
+        measure_keys: &[MeasureKey],
+        proportions: &[String],
+        suite: &Metadata,
+        render_opts: &RenderOptions,
+        last: bool,
+    ) -> anyhow::Result<()> {
+        if self.json.is_none() && self.html.is_none() {
+            return Ok(());
+        }
+        if !last && self.running.as_ref().is_some_and(|e| !e.is_finished()) {
+            return Ok(());
+        }
+        self.join()?;
+        let snapshot = experiments.map(Experiment::snapshot);
+        let keys = measure_keys.to_vec();
+        let proportions = proportions.to_vec();
+        let (json, html) = (self.json.clone(), self.html.clone());
+        let (suite, render_opts) = (suite.clone(), render_opts.clone());
+        self.running = Some(thread::spawn(move || {
+            let measures = AllMeasures::new(&keys, &proportions, &snapshot);
+            if let Some(path) = json {
+                write_json_report(&path, &measures, &snapshot, &suite, &render_opts)?;
+            }
+            if let Some(path) = html {
+                write_html_report(&path, &measures, &snapshot, &suite, &render_opts)?;
+            }
+            Ok(())
+        }));
+        Ok(())
+    }
+
+    /// Wait for the running export, if any.
+    pub fn join(&mut self) -> anyhow::Result<()> {
+        match self.running.take() {
+            Some(running) => running
+                .join()
+                .map_err(|_| anyhow::anyhow!("export thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
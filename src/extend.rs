@@ -0,0 +1,210 @@
+//! Benchmarks continued with more iterations by `absh extend`.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::ansi;
+use crate::environment::fingerprint;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::input::PinnedInputs;
+use crate::run_log_reader::LoadedRunLog;
+use crate::run_log_reader::read_run_log;
+use crate::shell::shell_split;
+
+/// Earlier benchmark continued by `absh extend`.
+pub struct Extend {
+    pub dir: PathBuf,
+    /// Command line of the earlier benchmark.
+    pub args: Vec<String>,
+    /// Text of the earlier log.
+    pub text: String,
+    pub log: LoadedRunLog,
+    pub iterations: u32,
+}
+
+impl Extend {
+    /// Command line of the benchmark recorded in `dir`.
+    pub fn read_args(dir: &Path) -> anyhow::Result<Vec<String>> {
+        let path = dir.join("args.txt");
+        let args =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        shell_split(&args)
+    }
+
+    /// Benchmark recorded in `dir` with command line `args`, to run `iterations` more.
+    pub fn read(dir: &Path, args: Vec<String>, iterations: u32) -> anyhow::Result<Extend> {
+        let text = fs::read_to_string(dir.join("log"))
+            .with_context(|| format!("reading {}", dir.join("log").display()))?;
+        let log = read_run_log(dir)?.without_excluded();
+        Ok(Extend {
+            dir: dir.to_owned(),
+            args,
+            text,
+            log,
+            iterations,
+        })
+    }
+
+    /// Last iteration the earlier benchmark started.
+    pub fn last_iteration(&self) -> u64 {
+        self.text
+            .lines()
+            .filter_map(|l| l.strip_prefix("iteration: ")?.parse().ok())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Fail unless variants would run as in the earlier benchmark, on a similar machine.
+    pub fn check(
+        &self,
+        experiments: &ExperimentMap<Experiment>,
+        inputs: &PinnedInputs,
+    ) -> anyhow::Result<()> {
+        let dir = self.dir.display();
+        if !experiments.keys().eq(self.log.experiments.keys()) {
+            return Err(anyhow::anyhow!("variants differ from those in {}", dir));
+        }
+        let recorded = |prefix: &str| -> Vec<&str> {
+            self.text
+                .lines()
+                .filter(|l| l.starts_with(prefix))
+                .collect()
+        };
+        for t in experiments.values() {
+            let prefixes: Vec<String> =
+                ["run", "warmup", "when", "setup", "teardown", "dir", "var"]
+                    .iter()
+                    .map(|k| format!("{}.{}: ", t.name, k))
+                    .collect();
+            let old: Vec<&str> = self
+                .text
+                .lines()
+                .filter(|l| prefixes.iter().any(|p| l.starts_with(p)))
+                .collect();
+            if old != t.log_lines() {
+                return Err(anyhow::anyhow!(
+                    "scripts or settings of {} differ from those in {}",
+                    t.name,
+                    dir
+                ));
+            }
+        }
+        let input_lines: Vec<String> = inputs
+            .lines()
+            .iter()
+            .map(|l| format!("input: {}", l))
+            .collect();
+        if recorded("input: ") != input_lines {
+            return Err(anyhow::anyhow!("input files differ from those in {}", dir));
+        }
+        match recorded("fingerprint: ").first() {
+            Some(old) => {
+                let now = format!("fingerprint: {}", fingerprint());
+                if *old != now {
+                    return Err(anyhow::anyhow!(
+                        "environment differs from that of {}: was `{}`, now `{}`",
+                        dir,
+                        &old["fingerprint: ".len()..],
+                        &now["fingerprint: ".len()..]
+                    ));
+                }
+            }
+            None => eprintln!(
+                "{yellow}{} has no environment fingerprint, not checking it{reset}",
+                dir,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fmt::Write as _;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use crate::environment::fingerprint;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::extend::Extend;
+    use crate::input::PinnedInputs;
+    use crate::measure::key::MeasureKey;
+    use crate::run_log::RunLog;
+
+    fn experiments(names: &[&str], run: &str) -> ExperimentMap<Experiment> {
+        let mut experiments = ExperimentMap::default();
+        for name in names {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, "make".to_owned(), run.to_owned());
+            test.setup = "mkdir -p out".to_owned();
+            test.env.push(("N".to_owned(), "1".to_owned()));
+            experiments.insert(name, test);
+        }
+        experiments
+    }
+
+    /// Write a run log under `logs_dir` like a benchmark does, returns its directory.
+    fn write_log(logs_dir: &Path, experiments: &ExperimentMap<Experiment>) -> PathBuf {
+        let mut log = RunLog::open(logs_dir);
+        let args = ["absh", "-a", "sleep 0.1"].map(str::to_owned);
+        log.write_args(Some(&args)).unwrap();
+        writeln!(log.log_only(), "fingerprint: {}", fingerprint()).unwrap();
+        for test in experiments.values() {
+            log.write_experiment(test).unwrap();
+        }
+        for iteration in 1..=2 {
+            writeln!(log.log_only(), "iteration: {}", iteration).unwrap();
+        }
+        let rows: Vec<(ExperimentName, &[u64])> =
+            experiments.keys().map(|n| (n, &[10, 20][..])).collect();
+        log.write_raw_rows(MeasureKey::WallTime.id(), rows).unwrap();
+        log.name().to_owned()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let logs_dir = env::temp_dir().join(format!("absh-extend-test-{}", std::process::id()));
+        let recorded = experiments(&["A", "B"], "./bench");
+        let dir = write_log(&logs_dir, &recorded);
+        let inputs = PinnedInputs { inputs: Vec::new() };
+
+        let args = Extend::read_args(&dir).unwrap();
+        assert_eq!(vec!["absh", "-a", "sleep 0.1"], args);
+        let extend = Extend::read(&dir, args, 5).unwrap();
+        assert_eq!(2, extend.last_iteration());
+        assert_eq!(
+            &[10, 20],
+            extend.log.experiments[ExperimentName::intern("B")].measures[MeasureKey::WallTime]
+                .raw()
+        );
+        extend.check(&recorded, &inputs).unwrap();
+
+        let check = |experiments: &ExperimentMap<Experiment>| {
+            extend
+                .check(experiments, &inputs)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert!(check(&experiments(&["A", "B"], "./bench2")).contains("scripts or settings"));
+        assert!(check(&experiments(&["A", "B", "C"], "./bench")).contains("variants differ"));
+
+        let log = fs::read_to_string(dir.join("log")).unwrap();
+        let log = log.replace(&fingerprint(), "cpus=1 other=machine");
+        fs::write(dir.join("log"), log).unwrap();
+        let extend = Extend::read(&dir, Vec::new(), 5).unwrap();
+        let e = extend.check(&recorded, &inputs).err().unwrap();
+        assert!(e.to_string().contains("environment differs"), "{}", e);
+        fs::remove_dir_all(&logs_dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What happens when a run fails, given with `--on-failure`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FailurePolicy {
+    /// Count the failure and continue with the next run.
+    #[default]
+    Skip,
+    /// Stop the benchmark and report runs completed so far.
+    Abort,
+    /// Run again up to this many times before counting the run as skipped.
+    Retry(u32),
+}
+
+impl FailurePolicy {
+    /// How many times a failed run is run again.
+    pub fn max_retries(&self) -> u32 {
+        match *self {
+            FailurePolicy::Retry(n) => n,
+            FailurePolicy::Skip | FailurePolicy::Abort => 0,
+        }
+    }
+}
+
+impl FromStr for FailurePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<FailurePolicy> {
+        match s {
+            "skip" => Ok(FailurePolicy::Skip),
+            "abort" => Ok(FailurePolicy::Abort),
+            s => match s.strip_prefix("retry=") {
+                Some(n) => match n.parse() {
+                    Ok(n) if n > 0 => Ok(FailurePolicy::Retry(n)),
+                    _ => Err(anyhow::anyhow!("invalid retry count: `{}`", n)),
+                },
+                None => Err(anyhow::anyhow!(
+                    "unknown failure policy: `{}`, expecting `skip`, `abort` or `retry=N`",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for FailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailurePolicy::Skip => write!(f, "skip"),
+            FailurePolicy::Abort => write!(f, "abort"),
+            FailurePolicy::Retry(n) => write!(f, "retry={}", n),
+        }
+    }
+}
+
+/// Error returned when the benchmark stops because of failed runs.
+#[derive(Debug)]
+pub struct FailureAbort(pub String);
+
+impl fmt::Display for FailureAbort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FailureAbort {}
+
+#[cfg(test)]
+mod tests {
+    use crate::failure_policy::FailurePolicy;
+
+    #[test]
+    fn parse() {
+        let policy = |s: &str| s.parse::<FailurePolicy>().unwrap();
+        assert_eq!(FailurePolicy::Skip, policy("skip"));
+        assert_eq!(FailurePolicy::Abort, policy("abort"));
+        assert_eq!(2, policy("retry=2").max_retries());
+        assert_eq!(0, policy("abort").max_retries());
+        assert!("retry=0".parse::<FailurePolicy>().is_err());
+        assert!("ignore".parse::<FailurePolicy>().is_err());
+        assert_eq!("retry=2", policy("retry=2").to_string());
+    }
+}
@@ -0,0 +1,259 @@
+//! Flamegraphs of extra profiled runs of each variant with `--flamegraph`.
+//!
+//! After the benchmark each variant is run a few more times under `perf record`
+//! (or `dtrace` on macOS), and the fastest and the slowest of these runs are
+//! rendered with [inferno](https://github.com/jonhoo/inferno), so the profiles
+//! show where slow runs spend their time compared to fast ones.
+
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Instant;
+
+use anyhow::Context;
+
+use crate::duration::Duration;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::run_log::RunLog;
+use crate::sh::Shell;
+use crate::sh::sh_command;
+use crate::shell::shell_quote;
+use crate::shell::shell_quote_args;
+
+/// Profiled runs of each variant.
+pub const FLAMEGRAPH_RUNS: usize = 5;
+
+/// Sampling frequency, not a multiple of common timer frequencies.
+const FREQUENCY: u32 = 997;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Profiler {
+    Perf,
+    Dtrace,
+}
+
+impl Profiler {
+    /// Profiler available on this OS.
+    pub fn native() -> Profiler {
+        match cfg!(target_os = "macos") {
+            true => Profiler::Dtrace,
+            false => Profiler::Perf,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Profiler::Perf => "perf",
+            Profiler::Dtrace => "dtrace",
+        }
+    }
+
+    /// Command recording stacks of `argv` and the processes it starts to `output`.
+    fn record_argv(self, argv: &[String], output: &Path) -> Vec<String> {
+        let output = output.display().to_string();
+        match self {
+            Profiler::Perf => {
+                let mut args: Vec<String> = vec![
+                    "perf".to_owned(),
+                    "record".to_owned(),
+                    "-F".to_owned(),
+                    FREQUENCY.to_string(),
+                    "-g".to_owned(),
+                    "-q".to_owned(),
+                    "-o".to_owned(),
+                    output,
+                    "--".to_owned(),
+                ];
+                args.extend(argv.iter().cloned());
+                args
+            }
+            Profiler::Dtrace => vec![
+                "dtrace".to_owned(),
+                "-q".to_owned(),
+                "-x".to_owned(),
+                "ustackframes=100".to_owned(),
+                "-n".to_owned(),
+                format!(
+                    "profile-{} /pid == $target || progenyof($target)/ {{ @[ustack()] = count(); }}",
+                    FREQUENCY
+                ),
+                "-o".to_owned(),
+                output,
+                // `dtrace -c` splits the command on spaces without any quoting.
+                "-c".to_owned(),
+                argv.join(" "),
+            ],
+        }
+    }
+
+    /// Shell pipeline rendering recorded `data` to `svg`.
+    fn render_script(self, data: &Path, svg: &Path, title: &str) -> String {
+        let data = shell_quote(&data.display().to_string());
+        let collapse = match self {
+            Profiler::Perf => format!("perf script -i {} | inferno-collapse-perf", data),
+            Profiler::Dtrace => format!("inferno-collapse-dtrace {}", data),
+        };
+        format!(
+            "{} | {} > {}",
+            collapse,
+            shell_quote_args(["inferno-flamegraph", "--title", title]),
+            shell_quote(&svg.display().to_string())
+        )
+    }
+}
+
+/// Check that the profiler and inferno are installed.
+pub fn check_flamegraph(profiler: Profiler) -> anyhow::Result<()> {
+    for program in [profiler.name(), "inferno-flamegraph"] {
+        let found = sh_command(&format!("command -v {} >/dev/null", program))
+            .status()?
+            .success();
+        if !found {
+            return Err(anyhow::anyhow!(
+                "`--flamegraph` needs `{}` in PATH; inferno is installed with `cargo install inferno`",
+                program
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Rendered flamegraph of one profiled run.
+pub struct Flamegraph {
+    pub duration: Duration,
+    pub path: PathBuf,
+}
+
+/// Profile `test` `FLAMEGRAPH_RUNS` times and render the fastest and the slowest run
+/// into `dir`.
+pub fn record_flamegraphs(
+    test: &Experiment,
+    shell: &Shell,
+    profiler: Profiler,
+    dir: &Path,
+) -> anyhow::Result<(Flamegraph, Flamegraph)> {
+    let command = |argv: &[String]| {
+        let mut command = Command::new(&argv[0]);
+        command
+            .args(&argv[1..])
+            .envs(test.env.iter().cloned())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        if let Some(dir) = &test.dir {
+            command.current_dir(dir);
+        }
+        command
+    };
+
+    let mut runs = Vec::new();
+    for i in 0..FLAMEGRAPH_RUNS {
+        if !test.warmup.is_empty() {
+            let output = command(&shell.argv(&test.warmup)?).output()?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!("warmup failed: {}", output.status));
+            }
+        }
+        let data = dir.join(format!("flamegraph-{}-{}.data", test.name, i));
+        let argv = profiler.record_argv(&shell.argv(&test.run)?, &data);
+        let start = Instant::now();
+        let output = command(&argv)
+            .output()
+            .with_context(|| format!("running {}", profiler.name()))?;
+        let duration = Duration::from_nanos(start.elapsed().as_nanos().try_into()?);
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "profiled run failed: {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        runs.push((duration, data));
+    }
+
+    runs.sort_by_key(|(duration, _)| *duration);
+    let render = |(duration, data): &(Duration, PathBuf), kind: &str| -> anyhow::Result<_> {
+        let path = dir.join(format!("flamegraph-{}-{}.svg", test.name, kind));
+        let title = format!(
+            "{} {} of {} profiled runs, {} s",
+            test.name, kind, FLAMEGRAPH_RUNS, duration
+        );
+        let output = sh_command(&profiler.render_script(data, &path, &title)).output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "rendering flamegraph failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(Flamegraph {
+            duration: *duration,
+            path,
+        })
+    };
+    let fastest = render(runs.first().unwrap(), "fastest")?;
+    let slowest = render(runs.last().unwrap(), "slowest")?;
+    for (_, data) in &runs {
+        fs::remove_file(data).with_context(|| format!("removing {}", data.display()))?;
+    }
+    Ok((fastest, slowest))
+}
+
+/// Profile extra runs of each variant and save flamegraphs to the log directory.
+pub fn write_flamegraphs(
+    log: &mut RunLog,
+    shell: &Shell,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "Recording flamegraphs of {} profiled runs of each variant",
+        FLAMEGRAPH_RUNS
+    )?;
+    let dir = log.name().to_owned();
+    for test in experiments.values() {
+        let (fastest, slowest) = record_flamegraphs(test, shell, Profiler::native(), &dir)
+            .with_context(|| format!("recording flamegraphs of {}", test.name))?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "{}: fastest {:3} s {}, slowest {:3} s {}",
+            test.name.name_colored(),
+            fastest.duration,
+            fastest.path.display(),
+            slowest.duration,
+            slowest.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::flamegraph::Profiler;
+
+    #[test]
+    fn perf() {
+        let argv = vec!["/bin/sh".to_owned(), "-ec".to_owned(), "make x".to_owned()];
+        assert_eq!(
+            "perf record -F 997 -g -q -o /tmp/p.data -- /bin/sh -ec make x",
+            Profiler::Perf
+                .record_argv(&argv, Path::new("/tmp/p.data"))
+                .join(" ")
+        );
+        assert_eq!(
+            "perf script -i /tmp/p.data | inferno-collapse-perf | \
+             inferno-flamegraph --title 'A fastest' > '/tmp/my dir/a.svg'",
+            Profiler::Perf.render_script(
+                Path::new("/tmp/p.data"),
+                Path::new("/tmp/my dir/a.svg"),
+                "A fastest"
+            )
+        );
+    }
+}
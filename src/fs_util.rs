@@ -1,20 +1,54 @@
 use std::fs;
+use std::fs::File;
+use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::Context;
 
-pub(crate) fn write_using_temp(
-    path: impl AsRef<Path>,
-    contents: impl AsRef<[u8]>,
-) -> anyhow::Result<()> {
-    let path = path.as_ref();
+/// Hidden file next to `path` to write before renaming it to `path`.
+fn temp_path(path: &Path) -> anyhow::Result<PathBuf> {
     let file_name = path
         .file_name()
         .context("no file name")?
         .to_str()
         .context("file name not UTF-8")?;
-    let temp_path = path.with_file_name(format!(".{}!", file_name));
+    Ok(path.with_file_name(format!(".{}!", file_name)))
+}
+
+pub(crate) fn write_using_temp(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let temp_path = temp_path(path)?;
     fs::write(&temp_path, contents)?;
     fs::rename(temp_path, path)?;
     Ok(())
 }
+
+/// Like `write_using_temp`, but the contents are on disk before the rename.
+///
+/// The rename itself is on disk after `sync_dir_of`.
+pub(crate) fn write_using_temp_synced(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let temp_path = temp_path(path)?;
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_data()?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Write the directory entry of `path` to disk.
+pub(crate) fn sync_dir_of(path: &Path) -> anyhow::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+//! Scripts run once per benchmark, given with `--setup` and `--teardown`.
+
+use std::fmt::Write as _;
+
+use anyhow::Context;
+
+use crate::run_log::RunLog;
+use crate::sh::Shell;
+
+/// Run the setup script before the first iteration, the benchmark fails if it fails.
+pub fn run_setup(log: &mut RunLog, shell: &Shell, script: &str) -> anyhow::Result<()> {
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(log.both_log_and_stderr(), "running setup script:")?;
+    for line in script.lines() {
+        writeln!(log.both_log_and_stderr(), "    {}", line)?;
+    }
+    let status = shell
+        .command(script)?
+        .status()
+        .context("running setup script")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("setup script failed: {}", status));
+    }
+    Ok(())
+}
+
+/// Runs the teardown script when dropped, so it also runs when the benchmark fails
+/// or is interrupted.
+pub struct Teardown {
+    pub shell: Shell,
+    pub script: String,
+}
+
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        eprintln!();
+        eprintln!("running teardown script:");
+        for line in self.script.lines() {
+            eprintln!("    {}", line);
+        }
+        let status = self
+            .shell
+            .command(&self.script)
+            .and_then(|mut command| Ok(command.status()?));
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("teardown script failed: {}", status),
+            Err(e) => eprintln!("teardown script failed: {}", e),
+        }
+    }
+}
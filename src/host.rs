@@ -0,0 +1,110 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// POSIX shell quoting. Unlike `shell_quote` it does not use bash `$'...'` syntax,
+/// so multiline scripts work with any `sh`.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Machine to run scripts on: `local` or `ssh://[user@]host[:port]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Host {
+    Local,
+    Ssh { target: String, port: Option<u16> },
+}
+
+impl Host {
+    /// Shell script which runs `script` on this host.
+    pub fn wrap_script(&self, script: &str) -> String {
+        match self {
+            Host::Local => script.to_owned(),
+            Host::Ssh { target, port } => {
+                let remote = format!("sh -ec {}", quote(script));
+                let port = match port {
+                    Some(port) => format!(" -p {}", port),
+                    None => String::new(),
+                };
+                format!(
+                    "ssh -o BatchMode=yes{} {} {}",
+                    port,
+                    quote(target),
+                    quote(&remote)
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for Host {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Host> {
+        if s == "local" {
+            return Ok(Host::Local);
+        }
+        let rem = s
+            .strip_prefix("ssh://")
+            .ok_or_else(|| anyhow::anyhow!("expecting `local` or `ssh://host`: `{}`", s))?;
+        let (target, port) = match rem.rsplit_once(':') {
+            Some((target, port)) => (target, Some(port.parse()?)),
+            None => (rem, None),
+        };
+        if target.is_empty() || target.ends_with('@') || target.contains('/') {
+            return Err(anyhow::anyhow!("invalid host: `{}`", s));
+        }
+        Ok(Host::Ssh {
+            target: target.to_owned(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Local => write!(f, "local"),
+            Host::Ssh { target, port: None } => write!(f, "ssh://{}", target),
+            Host::Ssh {
+                target,
+                port: Some(port),
+            } => write!(f, "ssh://{}:{}", target, port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::host::Host;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Host::Local, "local".parse().unwrap());
+        assert_eq!(
+            Host::Ssh {
+                target: "me@h1".to_owned(),
+                port: Some(2222)
+            },
+            "ssh://me@h1:2222".parse().unwrap()
+        );
+        assert_eq!("ssh://h2", "ssh://h2".parse::<Host>().unwrap().to_string());
+        assert!("h1".parse::<Host>().is_err());
+        assert!("ssh://".parse::<Host>().is_err());
+        assert!("ssh://h1:x".parse::<Host>().is_err());
+    }
+
+    #[test]
+    fn wrap_script() {
+        assert_eq!("make", Host::Local.wrap_script("make"));
+        let host: Host = "ssh://h1".parse().unwrap();
+        assert_eq!(
+            r"ssh -o BatchMode=yes 'h1' 'sh -ec '\''make'\'''",
+            host.wrap_script("make")
+        );
+        let host: Host = "ssh://h1:22".parse().unwrap();
+        assert_eq!(
+            "ssh -o BatchMode=yes -p 22 'h1' 'sh -ec '\\''a\nb'\\'''",
+            host.wrap_script("a\nb")
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Standalone HTML report written with `--html`: summary tables and box plots
+//! with samples, which show their values on hover, and the samples to download.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::fs_util::write_using_temp;
+use crate::json_report::JsonComparison;
+use crate::json_report::JsonVariant;
+use crate::json_report::json_report;
+use crate::json_report::samples_csv;
+use crate::math::sorted::NumbersSorted;
+use crate::measure::tr::AllMeasures;
+use crate::measure::tr::MeasureDyn;
+use crate::metadata::Metadata;
+use crate::render_stats::RenderOptions;
+use crate::version::version_long;
+
+/// Colors of variants, in order.
+const COLORS: &[&str] = &[
+    "#d62728", "#2ca02c", "#1f77b4", "#9467bd", "#ff7f0e", "#17becf", "#8c564b", "#e377c2",
+];
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+svg text { font-size: 12px; font-family: sans-serif; }
+circle:hover { stroke: #000; stroke-width: 2; }
+";
+
+const PLOT_WIDTH: f64 = 640.0;
+const ROW_HEIGHT: f64 = 40.0;
+/// Space for variant names on the left.
+const LEFT: f64 = 100.0;
+const RIGHT: f64 = 20.0;
+/// Space for the axis at the bottom.
+const BOTTOM: f64 = 24.0;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Description, owner and links like [`Metadata::summary`], with web links clickable.
+fn metadata_html(metadata: &Metadata) -> String {
+    let mut details: Vec<String> = metadata
+        .owner
+        .iter()
+        .map(|o| format!("owner: {}", escape(o)))
+        .collect();
+    for link in &metadata.links {
+        details.push(
+            match link.starts_with("https://") || link.starts_with("http://") {
+                true => format!(r#"<a href="{0}">{0}</a>"#, escape(link)),
+                false => escape(link),
+            },
+        );
+    }
+    let description = escape(metadata.description.as_deref().unwrap_or(""));
+    match (description.is_empty(), details.is_empty()) {
+        (_, true) => description,
+        (true, false) => details.join("; "),
+        (false, false) => format!("{} ({})", description, details.join("; ")),
+    }
+}
+
+/// `data:` URL of `contents`, so downloads need nothing but the page.
+fn data_url(mime: &str, contents: &str) -> String {
+    let mut url = format!("data:{};charset=utf-8,", mime);
+    for b in contents.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(b as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    url
+}
+
+/// Quantile of sorted samples with linear interpolation, like percentiles in the report.
+fn quantile(sorted: &[u64], q: f64) -> f64 {
+    NumbersSorted(sorted).percentile_f64(q * 100.0).unwrap()
+}
+
+/// Horizontal box plot of each variant with quartiles, Tukey whiskers and all samples.
+fn box_plots(measure: &dyn MeasureDyn, variants: &[JsonVariant]) -> anyhow::Result<String> {
+    let min = variants.iter().map(|v| v.min).min().unwrap() as f64;
+    let max = variants.iter().map(|v| v.max).max().unwrap() as f64;
+    let span = if max > min { max - min } else { 1.0 };
+    let x = |n: f64| LEFT + (n - min) / span * (PLOT_WIDTH - LEFT - RIGHT);
+    let height = ROW_HEIGHT * variants.len() as f64 + BOTTOM;
+
+    let mut r = String::new();
+    writeln!(
+        r,
+        r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = PLOT_WIDTH,
+        h = height
+    )?;
+    for (i, variant) in variants.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let y = ROW_HEIGHT * i as f64 + ROW_HEIGHT / 2.0;
+        let mut sorted = variant.samples.clone();
+        sorted.sort_unstable();
+        let (q1, med, q3) = (
+            quantile(&sorted, 0.25),
+            quantile(&sorted, 0.5),
+            quantile(&sorted, 0.75),
+        );
+        let iqr = q3 - q1;
+        let values = sorted.iter().map(|&n| n as f64);
+        let lo = values.clone().find(|&n| n >= q1 - 1.5 * iqr).unwrap_or(q1);
+        let hi = values.rev().find(|&n| n <= q3 + 1.5 * iqr).unwrap_or(q3);
+
+        writeln!(
+            r,
+            r#"<text x="{}" y="{}" dominant-baseline="middle" fill="{}">{}</text>"#,
+            4,
+            y,
+            color,
+            escape(&variant.name)
+        )?;
+        writeln!(
+            r,
+            r#"<line x1="{:.1}" x2="{:.1}" y1="{y}" y2="{y}" stroke="{c}"/>"#,
+            x(lo),
+            x(hi),
+            y = y,
+            c = color
+        )?;
+        writeln!(
+            r,
+            r#"<rect x="{:.1}" y="{}" width="{:.1}" height="{}" fill="{c}" fill-opacity="0.2" stroke="{c}"><title>q1 {} med {} q3 {}</title></rect>"#,
+            x(q1),
+            y - ROW_HEIGHT / 4.0,
+            x(q3) - x(q1),
+            ROW_HEIGHT / 2.0,
+            measure.display_number(q1 as u64),
+            measure.display_number(med as u64),
+            measure.display_number(q3 as u64),
+            c = color
+        )?;
+        writeln!(
+            r,
+            r#"<line x1="{x:.1}" x2="{x:.1}" y1="{}" y2="{}" stroke="{c}" stroke-width="2"/>"#,
+            y - ROW_HEIGHT / 4.0,
+            y + ROW_HEIGHT / 4.0,
+            x = x(med),
+            c = color
+        )?;
+        for (index, &n) in variant.samples.iter().enumerate() {
+            // Spread samples vertically, so equal values stay visible.
+            let jitter = ((index * 7) % 11) as f64 / 10.0 - 0.5;
+            writeln!(
+                r,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}" fill-opacity="0.6"><title>sample {}: {}</title></circle>"#,
+                x(n as f64),
+                y + jitter * ROW_HEIGHT / 3.0,
+                color,
+                index,
+                measure.display_number(n)
+            )?;
+        }
+    }
+    let axis_y = ROW_HEIGHT * variants.len() as f64;
+    writeln!(
+        r,
+        r##"<line x1="{}" x2="{}" y1="{y}" y2="{y}" stroke="#888"/>"##,
+        LEFT,
+        PLOT_WIDTH - RIGHT,
+        y = axis_y
+    )?;
+    for (value, anchor) in [(min, "start"), (max, "end")] {
+        writeln!(
+            r,
+            r#"<text x="{:.1}" y="{}" text-anchor="{}">{}</text>"#,
+            x(value),
+            axis_y + 16.0,
+            anchor,
+            measure.display_number(value as u64)
+        )?;
+    }
+    writeln!(r, "</svg>")?;
+    Ok(r)
+}
+
+fn comparisons_table(
+    comparisons: &[JsonComparison],
+    opts: &RenderOptions,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "<table>")?;
+    writeln!(
+        r,
+        "<tr><th></th><th>ratio</th><th>{} conf</th><th>p ({})</th><th>D (KS)</th><th>p (KS)</th></tr>",
+        opts.conf,
+        opts.significance.name()
+    )?;
+    for c in comparisons {
+        writeln!(
+            r,
+            "<tr><td>{}/{}</td><td>{:.3}</td><td>{:.3}..{:.3}</td><td>{}</td><td>{:.3}</td><td>{:.4}</td></tr>",
+            escape(&c.variant),
+            escape(&c.baseline),
+            c.ratio,
+            c.ratio_min,
+            c.ratio_max,
+            c.p_value.map_or("?".to_owned(), |p| format!("{:.4}", p)),
+            c.ks_d,
+            c.ks_p_value,
+        )?;
+    }
+    writeln!(r, "</table>")?;
+    Ok(r)
+}
+
+fn render_measure(
+    measure: &dyn MeasureDyn,
+    tests: &ExperimentMap<Experiment>,
+    opts: &RenderOptions,
+) -> anyhow::Result<String> {
+    let json = measure.json(tests, opts);
+    let mut r = String::new();
+    writeln!(r, "<h2>{}</h2>", escape(measure.name()))?;
+    writeln!(r, "<table>")?;
+    if json.proportion {
+        writeln!(r, "<tr><th></th><th>successes</th><th>rate</th></tr>")?;
+        for v in &json.variants {
+            let successes: u64 = v.samples.iter().sum();
+            writeln!(
+                r,
+                "<tr><td>{}</td><td>{}/{}</td><td>{:.1}%</td></tr>",
+                escape(&v.name),
+                successes,
+                v.count,
+                successes as f64 / v.count as f64 * 100.0
+            )?;
+        }
+        writeln!(r, "</table>")?;
+        return Ok(r);
+    }
+    writeln!(
+        r,
+        "<tr><th></th><th>n</th><th>mean</th><th>med</th><th>std</th><th>min</th><th>max</th></tr>"
+    )?;
+    for v in &json.variants {
+        writeln!(
+            r,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&v.name),
+            v.count,
+            measure.display_number(v.mean),
+            measure.display_number(v.med),
+            measure.display_number(v.std),
+            measure.display_number(v.min),
+            measure.display_number(v.max),
+        )?;
+    }
+    writeln!(r, "</table>")?;
+    if !json.comparisons.is_empty() {
+        r.push_str(&comparisons_table(&json.comparisons, opts)?);
+    }
+    r.push_str(&box_plots(measure, &json.variants)?);
+    Ok(r)
+}
+
+/// Standalone HTML page with measures which have enough samples for statistics.
+pub fn html_report(
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    suite: &Metadata,
+    opts: &RenderOptions,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "<!DOCTYPE html>")?;
+    writeln!(r, "<html>")?;
+    writeln!(r, "<head>")?;
+    writeln!(r, r#"<meta charset="utf-8">"#)?;
+    writeln!(r, "<title>absh report</title>")?;
+    writeln!(r, "<style>\n{}</style>", STYLE)?;
+    writeln!(r, "</head>")?;
+    writeln!(r, "<body>")?;
+    writeln!(r, "<h1>absh report</h1>")?;
+    if !suite.is_empty() {
+        writeln!(r, "<p>{}</p>", metadata_html(suite))?;
+    }
+    let with_metadata = tests.values().any(|t| !t.metadata.is_empty());
+    writeln!(r, "<table>")?;
+    match with_metadata {
+        true => writeln!(r, "<tr><th>variant</th><th>script</th><th>about</th></tr>")?,
+        false => writeln!(r, "<tr><th>variant</th><th>script</th></tr>")?,
+    }
+    for (i, test) in tests.values().enumerate() {
+        write!(
+            r,
+            r#"<tr><td><span style="color: {}">&#9632;</span> {}</td><td><code>{}</code></td>"#,
+            COLORS[i % COLORS.len()],
+            escape(test.name.name()),
+            escape(&test.run)
+        )?;
+        if with_metadata {
+            write!(r, "<td>{}</td>", metadata_html(&test.metadata))?;
+        }
+        writeln!(r, "</tr>")?;
+    }
+    writeln!(r, "</table>")?;
+    for measure in measures.0.iter().filter(|m| m.has_stats(tests)) {
+        r.push_str(&render_measure(measure.as_ref(), tests, opts)?);
+    }
+    let report = json_report(measures, tests, suite, opts);
+    writeln!(r, "<h2>Raw data</h2>")?;
+    writeln!(
+        r,
+        r#"<p>Samples in raw units, like nanoseconds or bytes: <a download="absh-samples.csv" href="{}">absh-samples.csv</a>, or with statistics and scripts: <a download="absh-report.json" href="{}">absh-report.json</a>.</p>"#,
+        data_url("text/csv", &samples_csv(&report.measures)),
+        data_url("application/json", &serde_json::to_string_pretty(&report)?),
+    )?;
+    writeln!(r, "<p><small>absh {}</small></p>", escape(&version_long()))?;
+    writeln!(r, "</body>")?;
+    writeln!(r, "</html>")?;
+    Ok(r)
+}
+
+pub fn write_html_report(
+    path: &Path,
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    suite: &Metadata,
+    opts: &RenderOptions,
+) -> anyhow::Result<()> {
+    write_using_temp(path, html_report(measures, tests, suite, opts)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::html_report::html_report;
+    use crate::html_report::quantile;
+    use crate::measure::key::MeasureKey;
+    use crate::measure::tr::AllMeasures;
+    use crate::metadata::Metadata;
+    use crate::render_stats::RenderOptions;
+
+    #[test]
+    fn quantiles() {
+        assert_eq!(2.0, quantile(&[1, 2, 3], 0.5));
+        assert_eq!(1.75, quantile(&[1, 2, 3, 4], 0.25));
+    }
+
+    #[test]
+    fn report() {
+        let mut tests = ExperimentMap::default();
+        for (name, times) in [("html-a", [10, 12, 11]), ("html-b", [20, 22, 21])] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), "echo '<x>'".to_owned());
+            for &t in times.iter() {
+                test.measures[MeasureKey::WallTime].push(t * 1_000_000);
+            }
+            tests.insert(name, test);
+        }
+        tests[ExperimentName::intern("html-b")].metadata.links = vec![
+            "https://example.com/?a=1&b=2".to_owned(),
+            "javascript:x".to_owned(),
+        ];
+        let suite = Metadata {
+            description: Some("Startup <time>".to_owned()),
+            ..Metadata::default()
+        };
+        let measures = AllMeasures::new(&[MeasureKey::WallTime], &[], &tests);
+        let html = html_report(&measures, &tests, &suite, &RenderOptions::default()).unwrap();
+        assert!(html.contains("<p>Startup &lt;time&gt;</p>"), "{}", html);
+        let url = "https://example.com/?a=1&amp;b=2";
+        let links = format!(r#"<td><a href="{0}">{0}</a>; javascript:x</td>"#, url);
+        assert!(html.contains(&links), "{}", html);
+        assert!(html.contains("<code>echo '&lt;x&gt;'</code>"), "{}", html);
+        assert!(html.contains("<h2>Time (in seconds)</h2>"), "{}", html);
+        assert!(html.contains("<title>sample 2: 0.021</title>"), "{}", html);
+        assert_eq!(6, html.matches("<circle").count());
+        assert!(
+            html.contains(r#"<span style="color: #2ca02c">&#9632;</span> html-b"#),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("data:text/csv;charset=utf-8,variant%2Cmeasure"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("%0Ahtml-b%2Cwall-time%2C3%2C21000000%0A"),
+            "{}",
+            html
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::experiment_name::ExperimentName;
+
+/// Input file declared with `--input [VARIANT:]NAME=PATH`.
+#[derive(Clone, Debug)]
+pub struct InputSpec {
+    /// Variant using the input, all variants if `None`.
+    pub variant: Option<String>,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl InputSpec {
+    /// Input is used by the variant. Single letter variant names are case-insensitive.
+    pub fn used_by(&self, name: ExperimentName) -> bool {
+        match &self.variant {
+            Some(variant) => name.is_named(variant),
+            None => true,
+        }
+    }
+
+    /// Environment variable with the input path passed to scripts.
+    pub fn env_var(&self) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                _ => '_',
+            })
+            .collect();
+        format!("ABSH_INPUT_{}", name)
+    }
+}
+
+impl FromStr for InputSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<InputSpec> {
+        let (name, path) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expecting `NAME=PATH`: `{}`", s))?;
+        let (variant, name) = match name.split_once(':') {
+            Some((variant, name)) => {
+                ExperimentName::validate(variant)?;
+                (Some(variant.to_owned()), name)
+            }
+            None => (None, name),
+        };
+        if name.is_empty() || path.is_empty() {
+            return Err(anyhow::anyhow!("expecting `NAME=PATH`: `{}`", s));
+        }
+        Ok(InputSpec {
+            variant,
+            name: name.to_owned(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// Hex SHA-256 of file content.
+pub fn sha256_file(path: &PathBuf) -> anyhow::Result<String> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("reading {}", path.display()))?;
+    let mut hex = String::new();
+    for b in hasher.finalize() {
+        write!(hex, "{:02x}", b)?;
+    }
+    Ok(hex)
+}
+
+/// Input files with checksums taken before the benchmark.
+pub struct PinnedInputs {
+    pub inputs: Vec<(InputSpec, String)>,
+}
+
+impl PinnedInputs {
+    pub fn pin(specs: &[InputSpec]) -> anyhow::Result<PinnedInputs> {
+        let inputs = specs
+            .iter()
+            .map(|spec| Ok((spec.clone(), sha256_file(&spec.path)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(PinnedInputs { inputs })
+    }
+
+    /// Inputs like `[VARIANT:]NAME sha256=CHECKSUM PATH`, as recorded in run logs.
+    pub fn lines(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .map(|(spec, checksum)| {
+                let variant = match &spec.variant {
+                    Some(variant) => format!("{}:", variant),
+                    None => String::new(),
+                };
+                format!(
+                    "{}{} sha256={} {}",
+                    variant,
+                    spec.name,
+                    checksum,
+                    spec.path.display()
+                )
+            })
+            .collect()
+    }
+
+    /// Fail if any input changed since it was pinned.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        for (spec, checksum) in &self.inputs {
+            let current = sha256_file(&spec.path)?;
+            if &current != checksum {
+                return Err(anyhow::anyhow!(
+                    "input `{}` ({}) changed during the benchmark: sha256 was {}, now {}",
+                    spec.name,
+                    spec.path.display(),
+                    checksum,
+                    current
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::experiment_name::ExperimentName;
+    use crate::input::InputSpec;
+    use crate::input::PinnedInputs;
+
+    #[test]
+    fn parse() {
+        let spec: InputSpec = "data=/tmp/x.csv".parse().unwrap();
+        assert_eq!(None, spec.variant);
+        assert_eq!("data", spec.name);
+        assert_eq!("ABSH_INPUT_DATA", spec.env_var());
+        let spec: InputSpec = "b:big-data=x".parse().unwrap();
+        assert!(spec.used_by(ExperimentName::letter(1)));
+        assert!(!spec.used_by(ExperimentName::letter(0)));
+        assert_eq!("ABSH_INPUT_BIG_DATA", spec.env_var());
+        assert!("data".parse::<InputSpec>().is_err());
+        assert!("a b:data=x".parse::<InputSpec>().is_err());
+    }
+
+    #[test]
+    fn verify() {
+        let path = env::temp_dir().join(format!("absh-input-test-{}", std::process::id()));
+        fs::write(&path, "abc").unwrap();
+        let spec: InputSpec = format!("x={}", path.display()).parse().unwrap();
+        let pinned = PinnedInputs::pin(&[spec]).unwrap();
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            pinned.inputs[0].1
+        );
+        pinned.verify().unwrap();
+        fs::write(&path, "abd").unwrap();
+        assert!(pinned.verify().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
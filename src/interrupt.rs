@@ -0,0 +1,114 @@
+//! Ctrl-C handling: the first `SIGINT` stops measuring so the statistics collected
+//! so far are still reported, the second one exits immediately.
+//!
+//! On Windows Ctrl-C is handled with a console control handler instead, and scripts
+//! attached to the same console receive it themselves.
+
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
+
+#[cfg(windows)]
+use windows_sys::Win32::System::Console::CTRL_C_EVENT;
+#[cfg(windows)]
+use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+use crate::exit_code::ExitCode;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Process group of the running script if it is not in our group, or zero.
+#[cfg(unix)]
+static SCRIPT_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// Error returned when a run was stopped by `SIGINT`.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_: libc::c_int) {
+    // Only async-signal-safe calls here.
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(ExitCode::Interrupted as libc::c_int) };
+    }
+    // Terminal sends `SIGINT` to the foreground group only, so scripts in their own group
+    // would keep running.
+    let group = SCRIPT_GROUP.load(Ordering::SeqCst);
+    if group != 0 {
+        unsafe { libc::kill(-group, libc::SIGKILL) };
+    }
+}
+
+/// Handle `SIGINT` in this process instead of exiting.
+#[cfg(unix)]
+pub fn install_interrupt_handler() -> anyhow::Result<()> {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_ctrl_c(ctrl_type: u32) -> i32 {
+    if ctrl_type != CTRL_C_EVENT {
+        // Other events are left to the default handler, which exits.
+        return 0;
+    }
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        std::process::exit(ExitCode::Interrupted as i32);
+    }
+    1
+}
+
+/// Handle Ctrl-C in this process instead of exiting.
+#[cfg(windows)]
+pub fn install_interrupt_handler() -> anyhow::Result<()> {
+    if unsafe { SetConsoleCtrlHandler(Some(on_ctrl_c), 1) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// `Interrupted` error if `SIGINT` was received.
+pub fn check_interrupted() -> anyhow::Result<()> {
+    match interrupted() {
+        true => Err(Interrupted.into()),
+        false => Ok(()),
+    }
+}
+
+/// Kill process group `pgid` on `SIGINT` until the returned guard is dropped.
+#[cfg(unix)]
+pub fn kill_group_on_interrupt(pgid: u32) -> ScriptGroupGuard {
+    SCRIPT_GROUP.store(pgid as i32, Ordering::SeqCst);
+    ScriptGroupGuard(())
+}
+
+#[cfg(unix)]
+pub struct ScriptGroupGuard(());
+
+#[cfg(unix)]
+impl Drop for ScriptGroupGuard {
+    fn drop(&mut self) {
+        SCRIPT_GROUP.store(0, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,176 @@
+//! Per-run resource accounting with Job Objects on Windows.
+//!
+//! Each script runs in a fresh Job Object, which covers every process the script
+//! starts. The job provides CPU time and peak memory of the whole tree, and
+//! terminates it when the script runs longer than the timeout.
+
+use std::io;
+use std::mem;
+use std::os::windows::io::AsRawHandle;
+use std::os::windows::process::CommandExt;
+use std::process::Child;
+use std::process::Command;
+use std::ptr;
+
+use windows_sys::Win32::Foundation::CloseHandle;
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+use windows_sys::Win32::System::Diagnostics::ToolHelp::CreateToolhelp32Snapshot;
+use windows_sys::Win32::System::Diagnostics::ToolHelp::TH32CS_SNAPTHREAD;
+use windows_sys::Win32::System::Diagnostics::ToolHelp::THREADENTRY32;
+use windows_sys::Win32::System::Diagnostics::ToolHelp::Thread32First;
+use windows_sys::Win32::System::Diagnostics::ToolHelp::Thread32Next;
+use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+use windows_sys::Win32::System::JobObjects::CreateJobObjectW;
+use windows_sys::Win32::System::JobObjects::JOBOBJECT_BASIC_ACCOUNTING_INFORMATION;
+use windows_sys::Win32::System::JobObjects::JOBOBJECT_EXTENDED_LIMIT_INFORMATION;
+use windows_sys::Win32::System::JobObjects::JobObjectBasicAccountingInformation;
+use windows_sys::Win32::System::JobObjects::JobObjectExtendedLimitInformation;
+use windows_sys::Win32::System::JobObjects::QueryInformationJobObject;
+use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+use windows_sys::Win32::System::Threading::CREATE_SUSPENDED;
+use windows_sys::Win32::System::Threading::OpenThread;
+use windows_sys::Win32::System::Threading::ResumeThread;
+use windows_sys::Win32::System::Threading::THREAD_SUSPEND_RESUME;
+
+use crate::duration::Duration;
+
+/// Exit code of processes terminated by `Job::terminate`, like `128 + SIGKILL` of `sh`.
+const TERMINATED_EXIT_CODE: u32 = 137;
+
+/// Resource totals of all processes which were in a job.
+pub struct JobStats {
+    pub user_time: Duration,
+    pub sys_time: Duration,
+    /// Peak committed memory of the largest process, in bytes.
+    pub peak_process_memory: u64,
+    /// Peak committed memory of all processes together, in bytes.
+    pub peak_job_memory: u64,
+    /// Bytes read and written by all processes.
+    pub io: (u64, u64),
+}
+
+/// Job Object which exists until dropped.
+pub struct Job {
+    handle: HANDLE,
+}
+
+// Job handles may be used from any thread.
+unsafe impl Send for Job {}
+unsafe impl Sync for Job {}
+
+impl Job {
+    pub fn create() -> anyhow::Result<Job> {
+        let handle = unsafe { CreateJobObjectW(ptr::null(), ptr::null()) };
+        if handle.is_null() {
+            return Err(anyhow::anyhow!(
+                "creating job object: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Job { handle })
+    }
+
+    /// Make `command` start suspended, so it can be assigned to the job with `spawn`
+    /// before it starts any process of its own.
+    pub fn suspend_on_spawn(command: &mut Command) {
+        command.creation_flags(CREATE_SUSPENDED);
+    }
+
+    /// Assign a process started with `suspend_on_spawn` to this job, then resume it.
+    pub fn assign(&self, process: &Child) -> anyhow::Result<()> {
+        let assigned =
+            unsafe { AssignProcessToJobObject(self.handle, process.as_raw_handle() as HANDLE) };
+        if assigned == 0 {
+            return Err(anyhow::anyhow!(
+                "assigning process to job object: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        resume_threads(process.id())?;
+        Ok(())
+    }
+
+    /// Terminate all processes in the job.
+    pub fn terminate(&self) {
+        unsafe { TerminateJobObject(self.handle, TERMINATED_EXIT_CODE) };
+    }
+
+    fn query<T>(&self, class: i32) -> anyhow::Result<T> {
+        let mut info: T = unsafe { mem::zeroed() };
+        let ok = unsafe {
+            QueryInformationJobObject(
+                self.handle,
+                class,
+                &mut info as *mut T as *mut _,
+                mem::size_of::<T>() as u32,
+                ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(anyhow::anyhow!(
+                "querying job object: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(info)
+    }
+
+    /// Resource totals of all processes which were in this job.
+    pub fn stats(&self) -> anyhow::Result<JobStats> {
+        let accounting: JOBOBJECT_BASIC_ACCOUNTING_INFORMATION =
+            self.query(JobObjectBasicAccountingInformation)?;
+        let limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION =
+            self.query(JobObjectExtendedLimitInformation)?;
+        // Times are in 100 ns units.
+        Ok(JobStats {
+            user_time: Duration::from_nanos(accounting.TotalUserTime as u64 * 100),
+            sys_time: Duration::from_nanos(accounting.TotalKernelTime as u64 * 100),
+            peak_process_memory: limits.PeakProcessMemoryUsed as u64,
+            peak_job_memory: limits.PeakJobMemoryUsed as u64,
+            io: (
+                limits.IoInfo.ReadTransferCount,
+                limits.IoInfo.WriteTransferCount,
+            ),
+        })
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // Processes keep running when the last handle to their job is closed.
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// Resume the threads of process `pid`, which was started suspended.
+fn resume_threads(pid: u32) -> anyhow::Result<()> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Err(anyhow::anyhow!(
+            "listing threads: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    let mut entry: THREADENTRY32 = unsafe { mem::zeroed() };
+    entry.dwSize = mem::size_of::<THREADENTRY32>() as u32;
+    let mut resumed = 0;
+    let mut more = unsafe { Thread32First(snapshot, &mut entry) } != 0;
+    while more {
+        if entry.th32OwnerProcessID == pid {
+            let thread = unsafe { OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID) };
+            if !thread.is_null() {
+                if unsafe { ResumeThread(thread) } != u32::MAX {
+                    resumed += 1;
+                }
+                unsafe { CloseHandle(thread) };
+            }
+        }
+        more = unsafe { Thread32Next(snapshot, &mut entry) } != 0;
+    }
+    unsafe { CloseHandle(snapshot) };
+    match resumed {
+        0 => Err(anyhow::anyhow!("no thread of process {} to resume", pid)),
+        _ => Ok(()),
+    }
+}
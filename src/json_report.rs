@@ -0,0 +1,441 @@
+//! Machine-readable summary written with `--json`.
+//!
+//! Numbers are in measure units: nanoseconds for time, bytes for max RSS.
+//!
+//! The format is described by `schema/report.schema.json`; incompatible changes
+//! must bump [`SCHEMA_VERSION`].
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::fs_util::write_using_temp;
+use crate::math::ks::ks_test;
+use crate::math::numbers::Numbers;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Verdict;
+use crate::measure::tr::AllMeasures;
+use crate::metadata::Metadata;
+use crate::render_stats::RenderOptions;
+use crate::version::version_long;
+
+/// Version of the report format, `schema_version` in the report.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct JsonReport {
+    pub schema_version: u32,
+    pub absh_version: String,
+    /// Description, owner and links of the whole benchmark from the config.
+    pub suite: Metadata,
+    pub experiments: Vec<JsonExperiment>,
+    pub measures: Vec<JsonMeasure>,
+    /// Human-readable problems which make results less reliable, like failed runs.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct JsonExperiment {
+    pub name: String,
+    pub warmup: String,
+    pub run: String,
+    /// Condition script, empty if the experiment always runs.
+    pub when: String,
+    pub metadata: Metadata,
+    /// Number of successful runs.
+    pub runs: u64,
+    pub failures: u64,
+    pub oom_kills: u64,
+    pub suspected_oom_kills: u64,
+    pub timeouts: u64,
+    /// Number of iterations skipped because the condition script failed.
+    pub skips: u64,
+}
+
+#[derive(Serialize)]
+pub struct JsonMeasure {
+    pub id: String,
+    pub name: String,
+    /// Values are booleans, comparisons are omitted.
+    pub proportion: bool,
+    pub variants: Vec<JsonVariant>,
+    /// Each variant compared to the first one.
+    pub comparisons: Vec<JsonComparison>,
+}
+
+#[derive(Serialize)]
+pub struct JsonVariant {
+    pub name: String,
+    pub count: u64,
+    pub mean: u64,
+    pub med: u64,
+    pub std: u64,
+    pub se: u64,
+    pub min: u64,
+    pub max: u64,
+    pub samples: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct JsonComparison {
+    pub variant: String,
+    pub baseline: String,
+    /// Ratio of `stat`, variant/baseline.
+    pub ratio: f64,
+    /// Confidence interval of the ratio at level `confidence`.
+    pub ratio_min: f64,
+    pub ratio_max: f64,
+    /// Two-sided level of the interval, like `0.95`.
+    pub confidence: f64,
+    /// Compared statistic: `mean` or `median`.
+    pub stat: &'static str,
+    /// `lower`, `higher` or `no_difference`.
+    pub verdict: &'static str,
+    /// P-value of the difference, `null` if it is undefined, like when neither varies.
+    pub p_value: Option<f64>,
+    /// Test of `p_value`: `t`, `welch`, `mannwhitney` or `permutation`.
+    pub p_value_test: String,
+    /// Kolmogorov–Smirnov statistic: largest distance between distribution functions.
+    pub ks_d: f64,
+    /// Kolmogorov–Smirnov test p-value for the difference of distributions.
+    pub ks_p_value: f64,
+}
+
+pub(crate) fn json_measure(
+    id: &str,
+    name: &str,
+    proportion: bool,
+    tests: &ExperimentMap<Experiment>,
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> JsonMeasure {
+    let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
+    let variants = tests
+        .iter()
+        .zip(stats.values())
+        .map(|((name, test), stats)| JsonVariant {
+            name: name.to_string(),
+            count: stats.count,
+            mean: stats.mean,
+            med: stats.med,
+            std: stats.std,
+            se: stats.se,
+            min: stats.min,
+            max: stats.max,
+            samples: numbers(test).raw().to_vec(),
+        })
+        .collect();
+
+    let mut comparisons = Vec::new();
+    if !proportion {
+        let mut names = tests.keys();
+        let a_name = names.next().unwrap();
+        for b_name in names {
+            let interval = RatioInterval::compare(
+                opts.stat,
+                opts.conf,
+                numbers(&tests[a_name]),
+                numbers(&tests[b_name]),
+            );
+            let sorted = |name| numbers(tests.get(name).unwrap()).sorted();
+            // Both samples are not empty, because they have stats.
+            let ks = ks_test(sorted(a_name), sorted(b_name)).unwrap();
+            comparisons.push(JsonComparison {
+                variant: b_name.to_string(),
+                baseline: a_name.to_string(),
+                ratio: interval.ratio,
+                ratio_min: interval.min,
+                ratio_max: interval.max,
+                confidence: opts.conf.level(),
+                stat: opts.stat.name(),
+                verdict: match interval.verdict() {
+                    Verdict::Lower => "lower",
+                    Verdict::Higher => "higher",
+                    Verdict::NoDifference => "no_difference",
+                },
+                p_value: opts
+                    .significance
+                    .p_value(numbers(&tests[a_name]), numbers(&tests[b_name])),
+                p_value_test: opts.significance.to_string(),
+                ks_d: ks.d,
+                ks_p_value: ks.p,
+            });
+        }
+    }
+
+    JsonMeasure {
+        id: id.to_owned(),
+        name: name.to_owned(),
+        proportion,
+        variants,
+        comparisons,
+    }
+}
+
+fn json_experiment(test: &Experiment) -> JsonExperiment {
+    JsonExperiment {
+        name: test.name.to_string(),
+        warmup: test.warmup.clone(),
+        run: test.run.clone(),
+        when: test.when.clone(),
+        metadata: test.metadata.clone(),
+        runs: test.runs() as u64,
+        failures: test.failures,
+        oom_kills: test.oom_kills,
+        suspected_oom_kills: test.suspected_oom_kills,
+        timeouts: test.timeouts,
+        skips: test.skips,
+    }
+}
+
+fn warnings(tests: &ExperimentMap<Experiment>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for test in tests.values() {
+        if test.failures != 0 {
+            warnings.push(format!(
+                "{}: {} failed runs ({} out of memory, {} timed out)",
+                test.name, test.failures, test.oom_kills, test.timeouts
+            ));
+        }
+    }
+    warnings
+}
+
+/// Summary of measures which have enough samples for statistics.
+pub fn json_report(
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    suite: &Metadata,
+    opts: &RenderOptions,
+) -> JsonReport {
+    JsonReport {
+        schema_version: SCHEMA_VERSION,
+        absh_version: version_long(),
+        suite: suite.clone(),
+        experiments: tests.values().map(json_experiment).collect(),
+        measures: measures
+            .0
+            .iter()
+            .filter(|m| m.has_stats(tests))
+            .map(|m| m.json(tests, opts))
+            .collect(),
+        warnings: warnings(tests),
+    }
+}
+
+/// Samples of `measures` as CSV with columns `variant,measure,sample,value`, with values in
+/// raw units, like nanoseconds or bytes.
+pub fn samples_csv(measures: &[JsonMeasure]) -> String {
+    let mut csv = "variant,measure,sample,value\n".to_owned();
+    for measure in measures {
+        for variant in &measure.variants {
+            for (i, value) in variant.samples.iter().enumerate() {
+                csv.push_str(&format!(
+                    "{},{},{},{}\n",
+                    csv_field(&variant.name),
+                    measure.id,
+                    i + 1,
+                    value
+                ));
+            }
+        }
+    }
+    csv
+}
+
+/// Quote `s` if it would not be read back as one CSV field.
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_owned(),
+    }
+}
+
+pub fn write_json_report(
+    path: &Path,
+    measures: &AllMeasures,
+    tests: &ExperimentMap<Experiment>,
+    suite: &Metadata,
+    opts: &RenderOptions,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&json_report(measures, tests, suite, opts))?;
+    write_using_temp(path, json + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::json_report::json_report;
+    use crate::json_report::samples_csv;
+    use crate::math::ratio::Stat;
+    use crate::math::significance::SignificanceTest;
+    use crate::measure::key::MeasureKey;
+    use crate::measure::tr::AllMeasures;
+    use crate::metadata::Metadata;
+    use crate::render_stats::RenderOptions;
+    use crate::student::ConfInterval;
+
+    const SCHEMA: &str = include_str!("../schema/report.schema.json");
+
+    /// Check `value` against the subset of JSON Schema used in the report schema.
+    fn validate(value: &Value, schema: &Value, root: &Value, path: &str) -> Result<(), String> {
+        if let Some(r) = schema["$ref"].as_str() {
+            let name = r.strip_prefix("#/$defs/").unwrap();
+            return validate(value, &root["$defs"][name], root, path);
+        }
+        let type_matches = |t: &Value| match t.as_str().unwrap() {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "null" => value.is_null(),
+            t => panic!("unsupported type: {}", t),
+        };
+        let type_ok = match &schema["type"] {
+            Value::Array(types) => types.iter().any(type_matches),
+            Value::Null => true,
+            t => type_matches(t),
+        };
+        if !type_ok {
+            return Err(format!(
+                "{}: expecting {}, got {}",
+                path, schema["type"], value
+            ));
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{}: {} is not one of {:?}", path, value, values));
+            }
+        }
+        if let (Some(min), Some(n)) = (schema["minimum"].as_f64(), value.as_f64()) {
+            if n < min {
+                return Err(format!("{}: {} is less than {}", path, n, min));
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(required.as_str().unwrap()) {
+                    return Err(format!("{}: missing {}", path, required));
+                }
+            }
+            for (key, v) in object {
+                match schema["properties"].get(key) {
+                    Some(s) => validate(v, s, root, &format!("{}.{}", path, key))?,
+                    None if schema["additionalProperties"] == Value::Bool(false) => {
+                        return Err(format!("{}: unexpected {}", path, key));
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let (Some(array), Some(items)) = (value.as_array(), schema.get("items")) {
+            for (i, v) in array.iter().enumerate() {
+                validate(v, items, root, &format!("{}[{}]", path, i))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn schema() {
+        let mut tests = ExperimentMap::default();
+        for (name, times, hit) in [
+            ("json-schema-a", [10, 12, 11], [1, 0, 1]),
+            ("json-schema-b", [20, 22, 21], [1, 1, 0]),
+        ] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), "true".to_owned());
+            for (&t, &h) in times.iter().zip(hit.iter()) {
+                test.measures[MeasureKey::WallTime].push(t);
+                test.metrics.entry("hit".to_owned()).or_default().push(h);
+                test.metrics.entry("ops".to_owned()).or_default().push(0);
+            }
+            test.failures = 1;
+            test.metadata.owner = Some("alice".to_owned());
+            tests.insert(name, test);
+        }
+        let measures = AllMeasures::new(&[MeasureKey::WallTime], &["hit".to_owned()], &tests);
+        let suite = Metadata {
+            description: Some("Startup".to_owned()),
+            ..Metadata::default()
+        };
+        let report = json_report(&measures, &tests, &suite, &RenderOptions::default());
+        let report = serde_json::to_value(report).unwrap();
+        assert_eq!(3, report["measures"].as_array().unwrap().len());
+        assert_eq!(2, report["warnings"].as_array().unwrap().len());
+
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        validate(&report, &schema, &schema, "report").unwrap();
+
+        let mut broken = report.clone();
+        broken["measures"][0]["variants"][0]["samples"][0] = Value::from(-1);
+        assert!(validate(&broken, &schema, &schema, "report").is_err());
+        let mut broken = report;
+        broken["experiments"][0]["extra"] = Value::from(1);
+        assert!(validate(&broken, &schema, &schema, "report").is_err());
+    }
+
+    #[test]
+    fn report() {
+        let mut tests = ExperimentMap::default();
+        // Names not used in other tests, so they are interned in this order.
+        for (name, times) in [("json-a", [10, 12, 11]), ("json-b", [20, 22, 21])] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for t in times {
+                test.measures[MeasureKey::WallTime].push(t);
+            }
+            tests.insert(name, test);
+        }
+        let measures = AllMeasures::new(&[MeasureKey::WallTime], &[], &tests);
+        let suite = Metadata::default();
+        let opts = RenderOptions {
+            significance: SignificanceTest::MannWhitney,
+            ..RenderOptions::default()
+        };
+        let report = json_report(&measures, &tests, &suite, &opts);
+        assert_eq!(1, report.measures.len());
+        let measure = &report.measures[0];
+        assert_eq!(vec![10, 12, 11], measure.variants[0].samples);
+        assert_eq!(21, measure.variants[1].mean);
+        assert_eq!(1, measure.comparisons.len());
+        assert_eq!("json-b", measure.comparisons[0].variant);
+        assert_eq!("json-a", measure.comparisons[0].baseline);
+        assert!((measure.comparisons[0].ratio - 21.0 / 11.0).abs() < 1e-9);
+        assert_eq!("higher", measure.comparisons[0].verdict);
+        assert_eq!("mannwhitney", measure.comparisons[0].p_value_test);
+        assert_eq!(0.95, measure.comparisons[0].confidence);
+        assert_eq!("mean", measure.comparisons[0].stat);
+        let opts_99 = RenderOptions {
+            conf: ConfInterval::C_99_5_99_0,
+            ..opts
+        };
+        let report_99 = json_report(&measures, &tests, &suite, &opts_99);
+        let comparison_99 = &report_99.measures[0].comparisons[0];
+        assert_eq!(0.99, comparison_99.confidence);
+        assert!(comparison_99.ratio_min < measure.comparisons[0].ratio_min);
+        assert!(comparison_99.ratio_max > measure.comparisons[0].ratio_max);
+        let opts_median = RenderOptions {
+            stat: Stat::Median,
+            ..opts_99
+        };
+        let report_median = json_report(&measures, &tests, &suite, &opts_median);
+        let comparison_median = &report_median.measures[0].comparisons[0];
+        assert_eq!("median", comparison_median.stat);
+        assert!((comparison_median.ratio - 21.0 / 11.0).abs() < 1e-9);
+        let csv = samples_csv(&report.measures);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!("variant,measure,sample,value", lines[0]);
+        assert_eq!("json-a,wall-time,2,12", lines[2]);
+        assert_eq!(7, lines.len());
+    }
+}
@@ -1,19 +1,80 @@
+pub mod aa_check;
+pub mod alert;
 pub mod ansi;
+pub mod auto_warmup;
+pub mod ballast;
 mod bars;
+pub mod buckets;
+pub mod calibrate;
+pub mod cargo_compare;
+pub mod cgroup;
+pub mod config;
 pub mod console_writer;
+pub mod cpu_pinning;
+pub mod danger;
+mod delta_plot;
 pub mod distr_plot;
+pub mod drop_caches;
+pub mod durability;
 pub mod duration;
+pub mod env_check;
+pub mod environment;
+pub mod exit_code;
 pub mod experiment;
 pub mod experiment_map;
 pub mod experiment_name;
+mod explain;
+pub mod export;
+pub mod extend;
+pub mod failure_policy;
+pub mod flamegraph;
 pub mod fs_util;
+pub mod hooks;
+pub mod host;
+pub mod html_report;
+pub mod input;
+pub mod interrupt;
+#[cfg(windows)]
+pub mod job;
+pub mod json_report;
 pub mod linear_map;
+mod markdown_report;
 pub mod math;
 pub mod maybe_strip_csi_writer;
 pub mod measure;
 pub mod mem_usage;
+pub mod metadata;
+pub mod metric;
+pub mod missing_command;
+pub mod noise;
+pub mod oom;
+pub mod pause_window;
+pub mod percent;
+pub mod perf;
+pub mod pilot;
+pub mod pin_frequency;
+pub mod prime;
+pub mod progress;
+pub mod raw_format;
+pub mod reload;
 pub mod render_stats;
+pub mod report;
+pub mod repro;
+pub mod review;
+pub mod rss_poll;
 pub mod run_log;
+pub mod run_log_reader;
+pub mod run_order;
+pub mod runner;
+pub mod scheduling;
+pub mod sensors;
 pub mod sh;
+pub mod shard;
 pub mod shell;
 pub mod student;
+pub mod sweep;
+pub mod verbosity;
+pub mod version;
+pub mod warmup_mode;
+pub mod worker;
+pub mod wrap;
@@ -27,6 +27,10 @@ impl<A> LinearMap<A> {
         self.values[index] = Some(value);
     }
 
+    pub fn remove(&mut self, index: usize) -> Option<A> {
+        self.values.get_mut(index).and_then(|v| v.take())
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (usize, &A)> {
         self.values
             .iter()
@@ -68,7 +72,7 @@ impl<A> LinearMap<A> {
     pub fn zip<'a, B>(
         &'a self,
         other: &'a LinearMap<B>,
-    ) -> impl Iterator<Item = (usize, &A, &B)> + 'a {
+    ) -> impl Iterator<Item = (usize, &'a A, &'a B)> + 'a {
         self.iter().zip(other.iter()).map(|((ka, va), (kb, vb))| {
             assert_eq!(ka, kb);
             (ka, va, vb)
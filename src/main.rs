@@ -1,37 +1,161 @@
-use std::convert::TryInto;
+use std::collections::BTreeMap;
+use std::env;
 use std::fmt::Write as _;
-use std::time::Instant;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 
+use absh::aa_check::run_aa_check;
+use absh::alert::AlertThreshold;
 use absh::ansi;
+use absh::auto_warmup::auto_warmup;
+use absh::ballast::BALLAST_SUBCOMMAND;
+use absh::ballast::Ballast;
+use absh::ballast::hold_ballast;
+use absh::buckets::Buckets;
+use absh::calibrate::calibrate_scale;
+use absh::cargo_compare::CargoCompare;
+use absh::cgroup::Cgroup;
+use absh::config::Config;
+use absh::config::ConfigWatcher;
+use absh::console_writer::is_tty;
+use absh::cpu_pinning::CpuList;
+use absh::cpu_pinning::MAX_NICE;
+use absh::cpu_pinning::MIN_NICE;
+use absh::cpu_pinning::check_pinning;
+use absh::danger::confirm_destructive;
+use absh::distr_plot::PlotMark;
+use absh::distr_plot::PlotMode;
+use absh::drop_caches::check_drop_caches;
+use absh::durability::Durability;
 use absh::duration::Duration;
+use absh::env_check::check_environment;
+use absh::environment::fingerprint;
+use absh::exit_code::ExitCode;
 use absh::experiment::Experiment;
+use absh::experiment::NamedDir;
+use absh::experiment::NamedEnvVar;
+use absh::experiment::NamedScript;
 use absh::experiment_map::ExperimentMap;
 use absh::experiment_name::ExperimentName;
+use absh::export::Export;
+use absh::extend::Extend;
+use absh::failure_policy::FailureAbort;
+use absh::failure_policy::FailurePolicy;
+use absh::flamegraph::Profiler;
+use absh::flamegraph::check_flamegraph;
+use absh::flamegraph::write_flamegraphs;
+use absh::hooks::Teardown;
+use absh::hooks::run_setup;
+use absh::host::Host;
+use absh::input::InputSpec;
+use absh::input::PinnedInputs;
+use absh::interrupt::Interrupted;
+use absh::interrupt::install_interrupt_handler;
+use absh::interrupt::interrupted;
+use absh::math::ratio::Stat;
+use absh::math::significance::SignificanceTest;
+use absh::math::stats::Percentiles;
 use absh::measure::key::MeasureKey;
-use absh::measure::map::MeasureMap;
 use absh::measure::tr::AllMeasures;
-use absh::measure::tr::MaxRss;
-use absh::measure::tr::MeasureDyn;
-use absh::measure::tr::WallTime;
 use absh::mem_usage::MemUsage;
+use absh::metadata::Metadata;
+use absh::metric::MetricAggSpec;
+use absh::missing_command::check_commands;
+use absh::noise::NOISE_SUBCOMMAND;
+use absh::noise::Noise;
+use absh::noise::NoiseSpec;
+use absh::noise::make_noise;
+use absh::pause_window::PauseWindow;
+use absh::percent::Percent;
+use absh::perf::check_perf;
+use absh::pilot::run_pilot;
+use absh::pin_frequency::PinnedFrequency;
+use absh::prime::PrimeFiles;
+use absh::progress::Progress;
+use absh::raw_format::RAW_FORMAT_VERSION;
+use absh::raw_format::upgrade_raw_files;
+use absh::reload::ReloadOptions;
+use absh::reload::reload_config;
+use absh::render_stats::RenderOptions;
+use absh::render_stats::SortBy;
+use absh::render_stats::render_failures;
+use absh::render_stats::render_outliers;
+use absh::render_stats::render_sensors;
+use absh::report::ReportOpts;
+use absh::report::analyze_logs;
+use absh::report::compare_logs;
+use absh::report::ratio_above;
+use absh::report::render_final_report;
+use absh::report::report_logs;
+use absh::report::sweep_logs;
+use absh::report::wall_time_intervals;
+use absh::report::widest_interval;
+use absh::repro::repro_script;
+use absh::review::EXCLUDED_FILE;
+use absh::review::review;
+use absh::review::write_excluded;
+use absh::rss_poll::RssPoller;
 use absh::run_log::RunLog;
-use absh::sh::spawn_sh;
+use absh::run_log_reader::read_run_log;
+use absh::run_order::unbalanced_order;
+use absh::runner::MaxRssSource;
+use absh::runner::Runner;
+use absh::runner::RunnerOptions;
+use absh::scheduling::Scheduling;
+use absh::sh::Shell;
+use absh::shard::Shard;
+use absh::student::ConfInterval;
+use absh::verbosity::Verbosity;
+use absh::version::version_long;
+use absh::warmup_mode::WarmupMode;
+use absh::worker::WORKER_SUBCOMMAND;
+use absh::worker::serve_worker;
+use absh::wrap::terminal_width;
+use absh::wrap::wrap_text;
+use anyhow::Context;
 use clap::Parser;
-use rand::prelude::SliceRandom;
-use wait4::Wait4;
+use once_cell::sync::Lazy;
+
+static VERSION: Lazy<String> = Lazy::new(version_long);
 
 #[derive(clap::Parser, Debug)]
-#[command(about = "A/B testing for shell scripts")]
+#[command(about = "A/B testing for shell scripts", version = VERSION.as_str())]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Opts {
-    #[clap(short, help = "A variant shell script")]
-    a: String,
-    #[clap(short, help = "B variant shell script")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[clap(
+        short,
+        required_unless_present_any = ["run", "test", "config"],
+        conflicts_with_all = ["run", "test"],
+        help = "A variant shell script"
+    )]
+    a: Option<String>,
+    #[clap(
+        short,
+        conflicts_with_all = ["run", "test"],
+        help = "B variant shell script"
+    )]
     b: Option<String>,
-    #[clap(short, help = "C variant shell script")]
+    #[clap(
+        short,
+        conflicts_with_all = ["run", "test"],
+        help = "C variant shell script"
+    )]
     c: Option<String>,
-    #[clap(short, help = "D variant shell script")]
+    #[clap(
+        short,
+        conflicts_with_all = ["run", "test"],
+        help = "D variant shell script"
+    )]
     d: Option<String>,
-    #[clap(short, help = "E variant shell script")]
+    #[clap(
+        short,
+        conflicts_with_all = ["run", "test"],
+        help = "E variant shell script"
+    )]
     e: Option<String>,
     #[clap(short = 'A', long = "a-warmup", help = "A variant warmup shell script")]
     aw: Option<String>,
@@ -43,10 +167,79 @@ struct Opts {
     dw: Option<String>,
     #[clap(short = 'E', long = "e-warmup", help = "E variant warmup shell script")]
     ew: Option<String>,
-    #[clap(short = 'r', help = "Randomise test execution order")]
+    #[clap(
+        long,
+        value_name = "SCRIPT",
+        help = "Script run once before the first iteration"
+    )]
+    setup: Option<String>,
+    #[clap(
+        long,
+        value_name = "SCRIPT",
+        help = "Script run once after the benchmark ends, also after errors and Ctrl-C"
+    )]
+    teardown: Option<String>,
+    #[clap(
+        short = 'r',
+        help = "Randomise test execution order, like `--scheduling random`"
+    )]
     random_order: bool,
-    #[clap(short = 'i', help = "Ignore the results of the first iteration")]
-    ignore_first: bool,
+    #[clap(
+        long,
+        default_value = "interleaved",
+        value_name = "interleaved|blocked[:N]|latin-square|random",
+        conflicts_with = "random_order",
+        help = "Order of runs: ABAB, AABB for blocks of N (default 5) iterations, order rotated every iteration, or shuffled every iteration"
+    )]
+    scheduling: Scheduling,
+    #[clap(
+        long,
+        help = "With random order, switch to latin-square scheduling when some variant happened to run at some position much more often than others"
+    )]
+    balance_order: bool,
+    #[clap(
+        long,
+        default_value = "each",
+        value_name = "each|once|first-n=N",
+        help = "Run warmup scripts before every run, or only until one or N warmups of the variant succeed"
+    )]
+    warmup_mode: WarmupMode,
+    #[clap(
+        short = 'i',
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Ignore the results of the first N iterations, 1 if N is not given"
+    )]
+    ignore_first: Option<u64>,
+    #[clap(
+        long,
+        conflicts_with = "ignore_first",
+        help = "Ignore the results of each variant until its wall time is stable, like an automatic `-i`"
+    )]
+    auto_warmup: bool,
+    #[clap(
+        long,
+        default_value = "5%",
+        value_name = "PERCENT",
+        help = "Coefficient of variation below which `--auto-warmup` considers the last runs stable"
+    )]
+    warmup_cv: Percent,
+    #[clap(
+        long,
+        value_name = "clean|cached|incremental",
+        help = "Compile time benchmark: build each variant into its own target directory, cleaned before each run, or with sources touched for `incremental`"
+    )]
+    cargo_compare: Option<CargoCompare>,
+    #[clap(
+        long,
+        default_value = "fsync",
+        value_name = "none|flush|fsync",
+        help = "Samples written to disk after each iteration: left to the OS, file data synced, or directory synced too"
+    )]
+    durability: Durability,
     #[clap(
         short = 'n',
         help = "Stop after n successful iterations (run forever if not specified)"
@@ -54,162 +247,1126 @@ struct Opts {
     iterations: Option<u32>,
     #[clap(short = 'm', long, help = "Also measure max resident set size")]
     mem: bool,
+    #[clap(
+        long,
+        help = "Measure max RSS of all processes the script starts, even if their parents exit first; implies `-m`"
+    )]
+    mem_tree: bool,
+    #[clap(
+        long,
+        help = "Record peak thread and open file descriptor counts of the script's processes as metrics"
+    )]
+    threads_fds: bool,
+    #[clap(long, help = "Also measure user and system CPU time")]
+    cpu: bool,
+    #[clap(
+        long,
+        value_name = "LIST",
+        help = "Pin scripts and their children to CPUs like `0-3,6`, so the scheduler does not migrate them (Linux)"
+    )]
+    cpu_list: Option<CpuList>,
+    #[clap(
+        long,
+        value_name = "N",
+        allow_negative_numbers = true,
+        value_parser = clap::value_parser!(i32).range(MIN_NICE as i64..=MAX_NICE as i64),
+        help = "Run scripts and their children with this niceness, from -20 to 19; negative needs root"
+    )]
+    nice: Option<i32>,
+    #[clap(
+        long,
+        help = "Run scripts under `perf stat` and record instructions, cycles, cache misses and branch misses as metrics (Linux)"
+    )]
+    perf: bool,
+    #[clap(
+        long,
+        help = "After the benchmark, profile a few more runs of each variant under `perf record` (`dtrace` on macOS) and save flamegraphs of the fastest and slowest to the log directory"
+    )]
+    flamegraph: bool,
+    #[clap(
+        long,
+        requires = "iterations",
+        help = "Run only shard K of M of the iterations, like `2/5`"
+    )]
+    shard: Option<Shard>,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        help = "Before benchmarking, find `ABSH_SCALE` for which A runs about this long, like `2s`"
+    )]
+    calibrate_to: Option<Duration>,
+    #[clap(
+        long,
+        help = "Before benchmarking, run A against itself for a few iterations and report whether a difference would be (falsely) detected"
+    )]
+    aa_check: bool,
+    #[clap(
+        long,
+        value_name = "WIDTH",
+        help = "Wrap or truncate output lines to this width (default is terminal width)"
+    )]
+    max_line_width: Option<usize>,
+    #[clap(
+        short,
+        long,
+        conflicts_with = "verbose",
+        help = "Do not print scripts and statistics after each iteration, only the final report"
+    )]
+    quiet: bool,
+    #[clap(
+        short,
+        long,
+        visible_alias = "show-output",
+        help = "Also pass stdout of scripts through"
+    )]
+    verbose: bool,
+    #[clap(
+        long,
+        value_name = "SIZE",
+        help = "Hold this much memory in a helper process during all runs, like `2G`"
+    )]
+    ballast: Option<MemUsage>,
+    #[clap(
+        long,
+        help = "After the last iteration, print full statistics, plots, failures and environment after the summary"
+    )]
+    details: bool,
+    #[clap(
+        long,
+        value_name = "K",
+        default_value = "1",
+        help = "Print statistics every K iterations, or only at the end with 0"
+    )]
+    report_every: u64,
+    #[clap(
+        long,
+        help = "Explain in plain English what the interval, p-value and n mean for each result, with caveats"
+    )]
+    explain: bool,
+    #[clap(long, help = "Also plot per-iteration differences against A")]
+    paired: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(100..),
+        help = "Also print percentile bootstrap intervals of mean and median ratios, computed from N resamples"
+    )]
+    bootstrap: Option<u32>,
+    #[clap(
+        long,
+        default_value = "auto",
+        value_name = "auto|halves|full|both",
+        help = "Distribution plots to print: halves plot for few samples, full plot, both, or pick by bar height"
+    )]
+    plot: PlotMode,
+    #[clap(
+        long,
+        value_name = "MEASURE=VALUE",
+        help = "Mark a reference value under distribution plots, like `time=1.5s`, `rss=200M` or `metric-NAME=N`, may be given many times"
+    )]
+    mark: Vec<PlotMark>,
+    #[clap(
+        long,
+        value_name = "DURATIONS",
+        help = "Report the share of runs of each variant with wall time under each threshold, like `100ms,500ms,1s`, and whether it differs from the first variant"
+    )]
+    buckets: Option<Buckets>,
+    #[clap(
+        long,
+        value_name = "name|mean|median|ratio",
+        help = "Order of variants in statistics and plots, lowest first; the baseline stays first with `ratio`"
+    )]
+    sort_by: Option<SortBy>,
+    #[clap(
+        long,
+        default_value = "mean",
+        value_name = "mean|median",
+        help = "Compare variants by mean, or by median which rare slow runs do not move"
+    )]
+    stat: Stat,
+    #[clap(
+        long,
+        default_value = "75,90,99",
+        value_name = "P,...",
+        help = "Percentiles printed with statistics, empty for none"
+    )]
+    percentiles: Percentiles,
+    #[clap(
+        long,
+        default_value = "0.95",
+        value_name = "LEVEL",
+        help = "Confidence level of intervals: 0.90, 0.95, 0.99 or 0.999"
+    )]
+    confidence: ConfInterval,
+    #[clap(
+        long,
+        default_value = "welch",
+        value_name = "t|welch|mannwhitney|permutation",
+        help = "Hypothesis test of p-values of comparisons: Student's t, Welch's t, Mann-Whitney U or permutation test"
+    )]
+    significance_test: SignificanceTest,
+    #[clap(
+        long,
+        help = "Exclude runs outside of Tukey fences from statistics, raw data in the log keeps them"
+    )]
+    filter_outliers: bool,
+    #[clap(
+        long,
+        value_name = "NAME=AGG",
+        help = "Combine a metric reported several times in one run with last, sum, mean, min, max or count, like `query_ms=max` (default is last)"
+    )]
+    metric_agg: Vec<MetricAggSpec>,
+    #[clap(
+        long,
+        value_name = "METRIC",
+        help = "Report a metric as the rate of runs reporting `1` (or `true`) and compare rates between variants, may be given many times"
+    )]
+    proportion: Vec<String>,
+    #[clap(
+        long,
+        help = "Pass a file path in `ABSH_METRIC_FILE` to scripts, and record metrics written there like `12345` or `ops=12345`"
+    )]
+    custom_metric: bool,
+    #[clap(
+        long,
+        help = "Read `absh-metric:` and `absh-phase:` lines from the descriptor in `ABSH_METRICS_FD` instead of script output"
+    )]
+    metrics_fd: bool,
+    #[clap(
+        long,
+        help = "Record CPU frequency and temperature around each run and report their correlation with run time"
+    )]
+    record_sensors: bool,
+    #[clap(
+        long,
+        requires = "host_a",
+        help = "Shell script to run on every host given with `--host-a` and `--host-b`"
+    )]
+    run: Option<String>,
+    #[clap(
+        long,
+        requires = "run",
+        value_name = "HOST",
+        help = "Run `--run` script as variant A on this host: `local` or `ssh://[user@]host[:port]`"
+    )]
+    host_a: Option<Host>,
+    #[clap(
+        long,
+        requires = "run",
+        value_name = "HOST",
+        help = "Run `--run` script as variant B on this host"
+    )]
+    host_b: Option<Host>,
+    #[clap(
+        long,
+        help = "Run scripts from a separate absh worker process to isolate measurements from the main process"
+    )]
+    worker: bool,
+    #[clap(
+        long,
+        help = "Do not run scripts in transient cgroups, take CPU time from `wait4` instead"
+    )]
+    no_cgroup: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Run scripts with this shell instead of /bin/sh (cmd.exe on Windows), like bash, zsh, fish, cmd.exe or powershell"
+    )]
+    shell: Option<String>,
+    #[clap(
+        long,
+        conflicts_with = "shell",
+        help = "Split scripts into arguments and execute them directly, without shell startup overhead"
+    )]
+    exec: bool,
+    #[clap(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["iterations", "shard"],
+        value_parser = clap::value_parser!(u32).range(2..),
+        help = "Run K iterations first, then suggest the number of iterations for `--precision`"
+    )]
+    pilot: Option<u32>,
+    #[clap(
+        long,
+        default_value = "2%",
+        help = "Target precision of B/A time for `--pilot`"
+    )]
+    precision: Percent,
+    #[clap(
+        long,
+        requires = "pilot",
+        help = "Continue with the suggested number of iterations after `--pilot` without asking"
+    )]
+    auto: bool,
+    #[clap(
+        long,
+        help = "Do not ask for confirmation when scripts look destructive, like `rm -rf /`"
+    )]
+    no_safety_check: bool,
+    #[clap(
+        long,
+        value_name = "[VARIANT:]NAME=PATH",
+        help = "Input file which must not change during the benchmark, passed to scripts as `ABSH_INPUT_<NAME>`"
+    )]
+    input: Vec<InputSpec>,
+    #[clap(
+        long,
+        value_name = "[VARIANT:]GLOB",
+        help = "Read files matching shell glob into the page cache before each measured run, not timed; may be given many times"
+    )]
+    prime_files: Vec<PrimeFiles>,
+    #[clap(
+        long,
+        help = "Sync and drop the page cache, dentries and inodes before each run, not timed; Linux, needs root"
+    )]
+    drop_caches: bool,
+    #[clap(
+        long,
+        help = "Before starting, warn about settings which make results noisy: CPU governor, turbo boost, load, battery, ASLR (Linux)"
+    )]
+    check_env: bool,
+    #[clap(
+        long,
+        help = "Set the `performance` CPU governor and disable turbo boost while the benchmark runs, restoring them after (Linux, needs root)"
+    )]
+    pin_frequency: bool,
+    #[clap(
+        long,
+        help = "Record when script output lines appear: time to first line, to 50% and 90% of lines, and line count"
+    )]
+    line_latency: bool,
+    #[clap(
+        long,
+        help = "Record shell startup and script body time of each run separately, as metrics `setup_us` and `body_us`"
+    )]
+    body_time: bool,
+    #[clap(
+        long,
+        value_name = "NAME=SCRIPT",
+        conflicts_with = "run",
+        help = "Named variant shell script, may be given many times, the first is the baseline"
+    )]
+    test: Vec<NamedScript>,
+    #[clap(
+        long,
+        value_name = "NAME=SCRIPT",
+        conflicts_with_all = ["a", "run"],
+        help = "Warmup shell script for the variant given with `--test NAME=...`"
+    )]
+    warmup: Vec<NamedScript>,
+    #[clap(
+        long,
+        value_name = "NAME=SCRIPT",
+        conflicts_with_all = ["a", "run"],
+        help = "Condition shell script for the variant given with `--test NAME=...`, the variant is skipped in iterations where it exits non-zero"
+    )]
+    when: Vec<NamedScript>,
+    #[clap(
+        long,
+        value_name = "NAME=KEY=VALUE",
+        conflicts_with = "run",
+        help = "Environment variable for scripts of a variant, like `B=RUST_LOG=debug`, may be given many times"
+    )]
+    env: Vec<NamedEnvVar>,
+    #[clap(
+        long,
+        value_name = "NAME=PATH",
+        conflicts_with = "run",
+        help = "Working directory of scripts of a variant, like `B=build-new`"
+    )]
+    dir: Vec<NamedDir>,
+    #[clap(
+        long,
+        value_name = "NAME=SCRIPT",
+        conflicts_with = "run",
+        help = "Script run before each run of a variant, like `A=make clean`, before its warmup, not measured"
+    )]
+    variant_setup: Vec<NamedScript>,
+    #[clap(
+        long,
+        value_name = "NAME=SCRIPT",
+        conflicts_with = "run",
+        help = "Script run after each run of a variant, like `A=rm -rf out`, not measured"
+    )]
+    variant_teardown: Vec<NamedScript>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["a", "b", "c", "d", "e", "run", "test"],
+        help = "Load variants, warmups, iteration count and measurement flags from a TOML file"
+    )]
+    config: Option<PathBuf>,
+    #[clap(
+        long,
+        requires = "config",
+        help = "Re-read the config between iterations and add or remove variants, keeping samples of the others"
+    )]
+    reload_config: bool,
+    /// Script snippets of the config, recorded in the log.
+    #[clap(skip)]
+    scripts: BTreeMap<String, String>,
+    /// Description, owner and links of the benchmark from the config.
+    #[clap(skip)]
+    suite: Metadata,
+    /// Description, owner and links of variants from the config, by variant name.
+    #[clap(skip)]
+    metadata: Vec<(String, Metadata)>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write statistics, samples and confidence intervals as JSON to this file after every iteration"
+    )]
+    json: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write standalone HTML report with statistics and box plots of samples to this file after every iteration"
+    )]
+    html: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "2",
+        value_parser = clap::value_parser!(u32).range(2..),
+        help = "Print only raw values, without confidence intervals or verdicts, until every variant has N samples; a benchmark ending with fewer exits with code 6"
+    )]
+    min_samples: u32,
+    #[clap(
+        long,
+        value_name = "WIDTH",
+        conflicts_with = "pilot",
+        help = "Stop when the confidence interval of time of every variant to A is narrower than WIDTH, like `0.02`, or after `-n` iterations"
+    )]
+    max_ci_width: Option<Percent>,
+    #[clap(
+        long,
+        value_name = "RATIO",
+        help = "Stop early when the confidence interval of time of any variant to A is entirely above RATIO, like `3.0`"
+    )]
+    abort_if_ratio_above: Option<f64>,
+    #[clap(
+        long,
+        default_value = "skip",
+        value_name = "skip|abort|retry=N",
+        help = "When a run fails: count it and continue, stop and report runs completed so far, or run it again up to N times"
+    )]
+    on_failure: FailurePolicy,
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Stop and report runs completed so far when more than N runs failed in total"
+    )]
+    max_failures: Option<u64>,
+    #[clap(
+        long,
+        value_name = "cpu:N|io|net",
+        help = "Generate background load during all runs: N busy threads, disk writes or loopback network traffic, may be given many times"
+    )]
+    noise: Vec<NoiseSpec>,
+    #[clap(
+        long,
+        value_name = "HH:MM-HH:MM",
+        help = "Do not measure during this daily local time window, like `02:00-03:00`, may be given many times"
+    )]
+    pause_between: Vec<PauseWindow>,
+    #[clap(
+        long,
+        value_name = "DURATION",
+        help = "Kill the process group of a warmup or run script running longer than this, like `30s`, and count the run as failed"
+    )]
+    timeout: Option<Duration>,
+    #[clap(
+        long,
+        default_value = "300ms",
+        value_name = "DURATION",
+        help = "Idle time between the end of one run and the start of the next, not included in any measurement"
+    )]
+    gap: Duration,
+    #[clap(
+        long,
+        value_name = "FACTOR",
+        help = "Highlight runs slower than FACTOR times the running median of the variant, like `2x-median`"
+    )]
+    alert_if_above: Option<AlertThreshold>,
+    #[clap(
+        long,
+        requires = "alert_if_above",
+        help = "Wait for Enter after a run highlighted by `--alert-if-above`"
+    )]
+    pause_on_alert: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write run logs to this directory (default is `ABSH_LOG_DIR`, `$XDG_STATE_HOME/absh/logs` or `~/.absh/logs`)"
+    )]
+    log_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Print a shell script reproducing one measured run of each variant and exit"
+    )]
+    print_repro: bool,
 }
 
-fn run_test(log: &mut RunLog, test: &mut Experiment) -> anyhow::Result<()> {
-    writeln!(log.both_log_and_stderr())?;
-    writeln!(
-        log.both_log_and_stderr(),
-        "running test: {}",
-        test.name.name_colored()
-    )?;
-    let warmup_lines = test.warmup.lines().collect::<Vec<_>>();
-    if !warmup_lines.is_empty() {
-        writeln!(log.both_log_and_stderr(), "running warmup script:")?;
-        for line in &warmup_lines {
-            writeln!(log.both_log_and_stderr(), "    {}", line)?;
-        }
+impl Opts {
+    fn max_line_width(&self) -> Option<usize> {
+        self.max_line_width.or_else(terminal_width)
     }
 
-    let mut process = spawn_sh(&test.warmup)?;
-    let status = process.wait4()?;
-    if !status.status.success() {
-        writeln!(
-            log.both_log_and_stderr(),
-            "warmup failed: {}",
-            status.status
-        )?;
-        return Ok(());
+    fn verbosity(&self) -> Verbosity {
+        Verbosity::from_flags(self.quiet, self.verbose)
     }
 
-    writeln!(log.both_log_and_stderr(), "running script:")?;
-    let lines = test.run.lines().collect::<Vec<_>>();
-    for line in &lines {
-        writeln!(log.both_log_and_stderr(), "    {}", line)?;
+    fn scheduling(&self) -> Scheduling {
+        match self.random_order {
+            true => Scheduling::Random,
+            false => self.scheduling,
+        }
     }
 
-    let start = Instant::now();
+    fn shell(&self) -> Shell {
+        Shell::from_flags(self.shell.as_deref(), self.exec)
+    }
 
-    let mut process = spawn_sh(&test.run)?;
-    let status = process.wait4()?;
-    if !status.status.success() {
-        writeln!(
-            log.both_log_and_stderr(),
-            "script failed: {}",
-            status.status
-        )?;
-        return Ok(());
+    fn runner_options(&self) -> RunnerOptions {
+        RunnerOptions {
+            scheduling: self.scheduling(),
+            warmup_mode: self.warmup_mode,
+            verbosity: self.verbosity(),
+            on_failure: self.on_failure,
+            max_failures: self.max_failures,
+            max_line_width: self.max_line_width(),
+            metric_agg: self.metric_agg.clone(),
+            proportions: self.proportion.clone(),
+            custom_metric: self.custom_metric,
+            perf: self.perf,
+            prime_files: self.prime_files.clone(),
+            drop_caches: self.drop_caches,
+            record_sensors: self.record_sensors,
+            line_latency: self.line_latency,
+            body_time: self.body_time,
+            poll_rss: false,
+            mem_tree: self.mem_tree,
+            threads_fds: self.threads_fds,
+            metrics_fd: self.metrics_fd,
+            cpus: self.cpu_list.clone(),
+            nice: self.nice,
+            worker: self.worker,
+            cgroup: false,
+            shell: self.shell(),
+            pause_windows: self.pause_between.clone(),
+            timeout: self.timeout,
+            gap: self.gap,
+            alert_if_above: self.alert_if_above,
+            pause_on_alert: self.pause_on_alert,
+        }
     }
 
-    let duration = Duration::from_nanos(start.elapsed().as_nanos().try_into()?);
-    if status.rusage.maxrss == 0 {
-        return Err(anyhow::anyhow!("maxrss not available"));
+    /// Take variants from the config, and flags not given on the command line.
+    fn apply_config(&mut self, config: Config) {
+        let (test, warmup) = config.scripts();
+        self.test = test;
+        self.warmup.extend(warmup);
+        self.when.extend(config.conditions());
+        self.env.extend(config.env_vars());
+        self.dir.extend(config.dirs());
+        self.metadata = config.metadata();
+        self.suite = config.suite_metadata();
+        if self.pilot.is_none() {
+            self.iterations = self.iterations.or(config.iterations);
+        }
+        self.random_order |= config.random_order;
+        if config.ignore_first {
+            self.ignore_first = self.ignore_first.or(Some(1));
+        }
+        self.mem |= config.mem;
+        self.cpu |= config.cpu;
+        self.paired |= config.paired;
+        self.details |= config.details;
+        self.record_sensors |= config.record_sensors;
+        self.line_latency |= config.line_latency;
+        self.body_time |= config.body_time;
+        self.mem_tree |= config.mem_tree;
+        self.threads_fds |= config.threads_fds;
+        self.scripts = config.scripts;
     }
-    let max_rss = MemUsage::from_bytes(status.rusage.maxrss);
+}
 
-    writeln!(
-        log.both_log_and_stderr(),
-        "{} finished in {:3} s, max rss {} MiB",
-        test.name.name_colored(),
-        duration,
-        max_rss.mib(),
-    )?;
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    #[command(about = "Print statistics of previously recorded runs")]
+    Report(ReportOpts),
+    #[command(
+        about = "Recompute and print all statistics, plots and intervals of recorded runs without running anything"
+    )]
+    Analyze(ReportOpts),
+    #[command(
+        about = "Compare the first variants of separately recorded run logs, as variants A, B, ..."
+    )]
+    Compare(ReportOpts),
+    #[command(
+        about = "Combine run logs of one benchmark recorded at different parameter values into one ratio per variant"
+    )]
+    Sweep(ReportOpts),
+    #[command(about = "Maintain previously recorded run logs")]
+    Logs {
+        #[command(subcommand)]
+        command: LogsCommand,
+    },
+    #[command(
+        about = "Run more iterations of a recorded benchmark with the same settings and report old and new runs"
+    )]
+    Extend {
+        #[clap(help = "Run log directory")]
+        dir: PathBuf,
+        #[clap(short = 'n', value_name = "N", help = "Number of iterations to add")]
+        iterations: u32,
+    },
+    #[command(
+        about = "Step through outlier samples of a run log, exclude some and print statistics"
+    )]
+    Review {
+        #[clap(help = "Run log directory")]
+        dir: PathBuf,
+    },
+    #[command(name = BALLAST_SUBCOMMAND, hide = true)]
+    Ballast { bytes: u64 },
+    #[command(name = NOISE_SUBCOMMAND, hide = true)]
+    Noise { spec: NoiseSpec },
+    #[command(name = WORKER_SUBCOMMAND, hide = true)]
+    Worker,
+}
 
-    test.measures[MeasureKey::WallTime].push(duration.nanos());
-    test.measures[MeasureKey::MaxRss].push(max_rss.bytes());
-    Ok(())
+#[derive(clap::Subcommand, Debug)]
+enum LogsCommand {
+    #[command(about = "Rewrite raw data files of run logs in the current format")]
+    Upgrade {
+        #[clap(required = true, help = "Run log directories")]
+        dirs: Vec<PathBuf>,
+    },
 }
 
-fn run_pair(
-    log: &mut RunLog,
-    opts: &Opts,
-    tests: &mut ExperimentMap<Experiment>,
-) -> anyhow::Result<()> {
-    let mut indices: Vec<ExperimentName> = tests.keys().collect();
-    if opts.random_order {
-        indices.shuffle(&mut rand::thread_rng());
+fn review_log(dir: &Path) -> anyhow::Result<()> {
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(anyhow::anyhow!("stdin is not a terminal"));
     }
-    for &index in &indices {
-        run_test(log, tests.get_mut(index).unwrap())?;
+    let log = read_run_log(dir)?;
+    let excluded = review(&log, &mut io::stdin().lock(), &mut io::stderr())?;
+    write_excluded(dir, &excluded)?;
+    eprintln!();
+    eprintln!(
+        "{} samples excluded, listed in {}",
+        excluded.len(),
+        dir.join(EXCLUDED_FILE).display()
+    );
+    eprintln!();
+    report_logs(&ReportOpts {
+        merge: false,
+        details: true,
+        explain: false,
+        paired: false,
+        bootstrap: None,
+        plot: PlotMode::Auto,
+        mark: Vec::new(),
+        buckets: None,
+        proportion: Vec::new(),
+        sort_by: None,
+        stat: Stat::Mean,
+        percentiles: Percentiles::default(),
+        confidence: ConfInterval::default(),
+        significance_test: SignificanceTest::default(),
+        filter_outliers: false,
+        md: false,
+        dirs: vec![dir.to_owned()],
+    })
+}
+
+fn upgrade_logs(dirs: &[PathBuf]) -> anyhow::Result<()> {
+    for dir in dirs {
+        let upgraded = upgrade_raw_files(dir)?;
+        if upgraded.is_empty() {
+            eprintln!(
+                "{}: already in format version {}",
+                dir.display(),
+                RAW_FORMAT_VERSION
+            );
+        }
+        for path in upgraded {
+            eprintln!("upgraded {}", path.display());
+        }
     }
     Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<process::ExitCode> {
+    Ok(run()?.into())
+}
+
+fn run() -> anyhow::Result<ExitCode> {
     let opts: Opts = Opts::parse();
 
-    let mut log = RunLog::open();
+    let success = |()| ExitCode::Success;
+    match &opts.command {
+        Some(Command::Report(report_opts)) => return report_logs(report_opts).map(success),
+        Some(Command::Analyze(report_opts)) => return analyze_logs(report_opts).map(success),
+        Some(Command::Compare(report_opts)) => return compare_logs(report_opts).map(success),
+        Some(Command::Sweep(report_opts)) => return sweep_logs(report_opts),
+        Some(Command::Review { dir }) => return review_log(dir).map(success),
+        Some(Command::Extend { dir, iterations }) => return extend(dir, *iterations),
+        Some(Command::Logs {
+            command: LogsCommand::Upgrade { dirs },
+        }) => return upgrade_logs(dirs).map(success),
+        Some(Command::Ballast { bytes }) => return hold_ballast(*bytes).map(success),
+        Some(Command::Noise { spec }) => return make_noise(*spec).map(success),
+        Some(Command::Worker) => return serve_worker().map(success),
+        None => {}
+    }
+
+    benchmark(opts, None)
+}
+
+/// Continue the benchmark recorded in `dir` with `iterations` more iterations.
+fn extend(dir: &Path, iterations: u32) -> anyhow::Result<ExitCode> {
+    let args = Extend::read_args(dir)?;
+    let opts = Opts::try_parse_from(&args)
+        .with_context(|| format!("parsing command line of {}", dir.display()))?;
+    if opts.command.is_some() {
+        return Err(anyhow::anyhow!(
+            "{} is not a log of a benchmark",
+            dir.display()
+        ));
+    }
+    if opts.calibrate_to.is_some() {
+        return Err(anyhow::anyhow!(
+            "benchmarks calibrated with `--calibrate-to` cannot be extended"
+        ));
+    }
+    benchmark(opts, Some(Extend::read(dir, args, iterations)?))
+}
+
+/// Variant `name` for a per-variant setting like `--env`, an error if there is none.
+fn named_variant<'a>(
+    experiments: &'a mut ExperimentMap<Experiment>,
+    name: &str,
+    what: &str,
+) -> anyhow::Result<&'a mut Experiment> {
+    experiments
+        .values_mut()
+        .find(|t| t.name.name() == name)
+        .ok_or_else(|| anyhow::anyhow!("{} for unknown variant `{}`", what, name))
+}
+
+/// Run the benchmark given on the command line, or continue an earlier one.
+fn benchmark(mut opts: Opts, extend: Option<Extend>) -> anyhow::Result<ExitCode> {
+    if extend.is_some() {
+        // Only measure, like in the remaining iterations of the earlier benchmark.
+        opts.pilot = None;
+        opts.aa_check = false;
+        opts.flamegraph = false;
+        opts.print_repro = false;
+    }
+
+    if let Some(path) = &opts.config {
+        let config = Config::load(path)?;
+        opts.apply_config(config);
+    }
+
+    let logs_dir = opts
+        .log_dir
+        .clone()
+        .unwrap_or_else(RunLog::default_logs_dir);
+    let mut log = RunLog::open(&logs_dir);
 
     let mut experiments = ExperimentMap::default();
-    experiments.insert(
-        ExperimentName::A,
-        Experiment {
-            name: ExperimentName::A,
-            warmup: opts.aw.clone().unwrap_or(String::new()),
-            run: opts.a.clone(),
-            measures: MeasureMap::new_all_default(),
-        },
-    );
+    if let Some(run) = &opts.run {
+        for (index, host, warmup) in [(0, &opts.host_a, &opts.aw), (1, &opts.host_b, &opts.bw)] {
+            if let Some(host) = host {
+                let name = ExperimentName::letter(index);
+                writeln!(log.log_only(), "{}.host: {}", name, host)?;
+                let warmup = match warmup {
+                    Some(warmup) => host.wrap_script(warmup),
+                    None => String::new(),
+                };
+                experiments.insert(name, Experiment::new(name, warmup, host.wrap_script(run)));
+            }
+        }
+    } else if !opts.test.is_empty() {
+        for test in &opts.test {
+            let name = ExperimentName::intern(&test.name);
+            if experiments.get(name).is_some() {
+                return Err(anyhow::anyhow!("test `{}` is given more than once", name));
+            }
+            let warmup = opts
+                .warmup
+                .iter()
+                .rev()
+                .find(|w| w.name == test.name)
+                .map_or(String::new(), |w| w.script.clone());
+            let mut experiment = Experiment::new(name, warmup, test.script.clone());
+            if let Some(when) = opts.when.iter().rev().find(|w| w.name == test.name) {
+                experiment.when = when.script.clone();
+            }
+            experiments.insert(name, experiment);
+        }
+        for warmup in &opts.warmup {
+            if !opts.test.iter().any(|t| t.name == warmup.name) {
+                return Err(anyhow::anyhow!("warmup for unknown test `{}`", warmup.name));
+            }
+        }
+        for when in &opts.when {
+            if !opts.test.iter().any(|t| t.name == when.name) {
+                return Err(anyhow::anyhow!(
+                    "condition for unknown test `{}`",
+                    when.name
+                ));
+            }
+        }
+    } else {
+        let scripts = [
+            (&opts.a, &opts.aw),
+            (&opts.b, &opts.bw),
+            (&opts.c, &opts.cw),
+            (&opts.d, &opts.dw),
+            (&opts.e, &opts.ew),
+        ];
+        for (index, (run, warmup)) in scripts.iter().enumerate() {
+            // Intern all letters, so variants keep their colors when some are skipped.
+            let name = ExperimentName::letter(index);
+            if let Some(run) = run {
+                let warmup = warmup.as_deref().unwrap_or("").to_owned();
+                experiments.insert(name, Experiment::new(name, warmup, run.clone()));
+            }
+        }
+    }
 
-    fn parse_opt_test(
-        tests: &mut ExperimentMap<Experiment>,
-        name: ExperimentName,
-        run: &Option<String>,
-        warmup: &Option<String>,
-    ) {
-        if let Some(run) = run.clone() {
-            tests.insert(
-                name,
-                Experiment {
-                    name,
-                    warmup: warmup.clone().unwrap_or(String::new()),
-                    run,
-                    measures: MeasureMap::new_all_default(),
-                },
-            );
+    for var in &opts.env {
+        let test = named_variant(&mut experiments, &var.name, "environment")?;
+        test.env.push((var.var.name.clone(), var.var.value.clone()));
+    }
+    for dir in &opts.dir {
+        named_variant(&mut experiments, &dir.name, "directory")?.dir = Some(dir.dir.clone());
+    }
+    for setup in &opts.variant_setup {
+        named_variant(&mut experiments, &setup.name, "setup")?.setup = setup.script.clone();
+    }
+    for teardown in &opts.variant_teardown {
+        let test = named_variant(&mut experiments, &teardown.name, "teardown")?;
+        test.teardown = teardown.script.clone();
+    }
+    for (name, metadata) in &opts.metadata {
+        if let Some((_, test)) = experiments.iter_mut().find(|(n, _)| n.name() == name) {
+            test.metadata = metadata.clone();
+        }
+    }
+    for test in experiments.values() {
+        if let Some(dir) = test.dir.as_ref().filter(|d| !d.is_dir()) {
+            return Err(anyhow::anyhow!(
+                "working directory of {} is not a directory: {}",
+                test.name,
+                dir.display()
+            ));
+        }
+    }
+
+    if let Some(cargo) = opts.cargo_compare {
+        if !opts.shell().is_posix() {
+            return Err(anyhow::anyhow!("--cargo-compare needs a POSIX shell"));
+        }
+        for test in experiments.values_mut() {
+            cargo.apply(test);
         }
     }
-    parse_opt_test(&mut experiments, ExperimentName::B, &opts.b, &opts.bw);
-    parse_opt_test(&mut experiments, ExperimentName::C, &opts.c, &opts.cw);
-    parse_opt_test(&mut experiments, ExperimentName::D, &opts.d, &opts.dw);
-    parse_opt_test(&mut experiments, ExperimentName::E, &opts.e, &opts.ew);
+
+    if opts.perf {
+        check_perf()?;
+    }
+    if opts.drop_caches {
+        check_drop_caches()?;
+    }
+    if opts.cpu_list.is_some() || opts.nice.is_some() {
+        check_pinning(opts.cpu_list.as_ref(), opts.nice)?;
+    }
+    if opts.flamegraph {
+        check_flamegraph(Profiler::native())?;
+    }
+    if opts.body_time && !opts.shell().is_posix() {
+        return Err(anyhow::anyhow!(
+            "--body-time needs a POSIX shell to report the body start"
+        ));
+    }
+    // On Windows the Job Object of the script covers the whole tree.
+    if opts.mem_tree && !cfg!(windows) && !RssPoller::available() {
+        return Err(anyhow::anyhow!(
+            "--mem-tree needs /proc to sample processes"
+        ));
+    }
+    if opts.threads_fds && !RssPoller::available() {
+        return Err(anyhow::anyhow!(
+            "--threads-fds needs /proc to sample processes"
+        ));
+    }
+    if !opts.no_safety_check {
+        let session: Vec<(&str, &str)> = [("setup", &opts.setup), ("teardown", &opts.teardown)]
+            .iter()
+            .filter_map(|(what, script)| Some((*what, script.as_deref()?)))
+            .collect();
+        confirm_destructive(&experiments, &session)?;
+    }
+    check_commands(&opts.shell(), &experiments)?;
+
+    let inputs = PinnedInputs::pin(&opts.input)?;
+    for (spec, _) in &inputs.inputs {
+        for (name, test) in experiments.iter_mut() {
+            if spec.used_by(name) {
+                test.env
+                    .push((spec.env_var(), spec.path.display().to_string()));
+            }
+        }
+    }
+    if let Some(extend) = &extend {
+        extend.check(&experiments, &inputs)?;
+    }
 
     eprintln!("Writing absh data to {}/", log.name().display());
     if let Some(last) = log.last() {
         eprintln!("Log symlink is {}", last.display());
     }
 
-    log.write_args()?;
+    log.write_args(extend.as_ref().map(|e| &e.args[..]))?;
+    log.write_version()?;
+    writeln!(log.log_only(), "fingerprint: {}", fingerprint())?;
+    if opts.check_env {
+        for warning in check_environment() {
+            writeln!(log.log_only(), "env_check: {}", warning)?;
+            eprintln!(
+                "{yellow}warning: {}{reset}",
+                warning,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            );
+        }
+    }
+    if let Some(extend) = &extend {
+        writeln!(log.log_only(), "extends: {}", extend.dir.display())?;
+    }
+    log.write_inputs(&inputs)?;
+    if let Some(shard) = opts.shard {
+        log.write_shard(shard)?;
+    }
 
     writeln!(log.log_only(), "random_order: {}", opts.random_order)?;
-    for (n, t) in experiments.iter_mut() {
-        writeln!(log.log_only(), "{}.run: {}", n, t.run)?;
-        if !t.warmup.is_empty() {
-            writeln!(log.log_only(), "{}.warmup: {}", n, t.warmup)?;
+    writeln!(log.log_only(), "scheduling: {}", opts.scheduling())?;
+    writeln!(log.log_only(), "warmup_mode: {}", opts.warmup_mode)?;
+    writeln!(log.log_only(), "on_failure: {}", opts.on_failure)?;
+    if let Some(max) = opts.max_failures {
+        writeln!(log.log_only(), "max_failures: {}", max)?;
+    }
+    writeln!(log.log_only(), "durability: {}", opts.durability)?;
+    writeln!(
+        log.log_only(),
+        "significance_test: {}",
+        opts.significance_test
+    )?;
+    log.set_durability(opts.durability);
+    if let Some(cargo) = opts.cargo_compare {
+        writeln!(log.log_only(), "cargo_compare: {}", cargo)?;
+    }
+    if let Some(cpus) = &opts.cpu_list {
+        writeln!(log.log_only(), "cpu_list: {}", cpus)?;
+    }
+    if let Some(nice) = opts.nice {
+        writeln!(log.log_only(), "nice: {}", nice)?;
+    }
+    for (name, script) in &opts.scripts {
+        writeln!(log.log_only(), "scripts.{}: {}", name, script)?;
+    }
+    for line in opts.suite.log_lines("") {
+        writeln!(log.log_only(), "{}", line)?;
+    }
+    for metric in &opts.proportion {
+        writeln!(log.log_only(), "proportion: {}", metric)?;
+    }
+    for (what, script) in [("setup", &opts.setup), ("teardown", &opts.teardown)].iter() {
+        if let Some(script) = script {
+            writeln!(log.log_only(), "{}: {}", what, script)?;
+        }
+    }
+    for t in experiments.values() {
+        log.write_experiment(t)?;
+    }
+
+    let _ballast = match opts.ballast {
+        Some(size) => {
+            writeln!(
+                log.both_log_and_stderr(),
+                "Holding {} MiB of memory ballast in a helper process",
+                size.mib()
+            )?;
+            Some(Ballast::spawn(size)?)
+        }
+        None => None,
+    };
+
+    let mut _noise = Vec::new();
+    for &spec in &opts.noise {
+        writeln!(
+            log.both_log_and_stderr(),
+            "Generating {} noise in a helper process",
+            spec
+        )?;
+        _noise.push(Noise::spawn(spec)?);
+    }
+
+    let calibrated_scale = match opts.calibrate_to {
+        Some(target) => Some(calibrate_scale(
+            &mut log,
+            &opts.shell(),
+            &mut experiments,
+            target,
+        )?),
+        None => None,
+    };
+
+    if opts.print_repro {
+        print!("{}", repro_script(&experiments, &env::current_dir()?));
+        return Ok(ExitCode::Success);
+    }
+
+    let mut runner_options = opts.runner_options();
+    if !opts.no_cgroup {
+        match Cgroup::check() {
+            Ok(()) => runner_options.cgroup = true,
+            Err(e) => writeln!(log.log_only(), "cgroup unavailable: {:#}", e)?,
+        }
+    }
+    writeln!(
+        log.log_only(),
+        "resource accounting: {}",
+        if runner_options.cgroup {
+            "cgroup"
+        } else if cfg!(windows) {
+            "job object"
+        } else {
+            "wait4"
+        }
+    )?;
+    match &runner_options.shell {
+        Shell::Path(path) => writeln!(log.log_only(), "shell: {}", path)?,
+        Shell::Exec => writeln!(log.log_only(), "shell: none, exec")?,
+    }
+    let mut mem = opts.mem || opts.mem_tree;
+    if opts.mem_tree && cfg!(windows) {
+        writeln!(log.log_only(), "max rss: job object")?;
+    } else if opts.mem_tree {
+        writeln!(log.log_only(), "max rss: process group polled from /proc")?;
+    } else if mem {
+        match runner_options.check_max_rss()? {
+            MaxRssSource::Reported => writeln!(log.log_only(), "max rss: reported")?,
+            MaxRssSource::Polled => {
+                writeln!(log.log_only(), "max rss: polled from /proc")?;
+                writeln!(
+                    log.both_log_and_stderr(),
+                    "{yellow}Max RSS is not reported by the OS, sampling it from /proc; short peaks may be missed.{reset}",
+                    yellow = ansi::YELLOW,
+                    reset = ansi::RESET,
+                )?;
+            }
+            MaxRssSource::Unavailable => {
+                writeln!(
+                    log.both_log_and_stderr(),
+                    "{yellow}Max RSS is not available on this system, ignoring `-m`.{reset}",
+                    yellow = ansi::YELLOW,
+                    reset = ansi::RESET,
+                )?;
+                mem = false;
+            }
         }
     }
 
-    if opts.ignore_first {
-        run_pair(&mut log, &opts, &mut experiments)?;
+    install_interrupt_handler()?;
 
-        for (_n, test) in experiments.iter_mut() {
-            for numbers in test.measures.values_mut() {
-                numbers.clear();
+    let _pinned_frequency = match opts.pin_frequency {
+        true => {
+            let pinned = PinnedFrequency::pin()?;
+            for setting in pinned.describe() {
+                writeln!(log.log_only(), "pin_frequency: {}", setting)?;
             }
+            Some(pinned)
         }
+        false => None,
+    };
+    // Created before the setup runs, so a failed setup is cleaned up too.
+    let _teardown = opts.teardown.as_ref().map(|script| Teardown {
+        shell: opts.shell(),
+        script: script.clone(),
+    });
+    if let Some(setup) = &opts.setup {
+        run_setup(&mut log, &opts.shell(), setup)?;
+    }
 
-        writeln!(log.both_log_and_stderr(), "")?;
+    if opts.aa_check {
+        run_aa_check(
+            &mut log,
+            runner_options.clone(),
+            experiments.values().next().unwrap(),
+            opts.significance_test,
+            opts.stat,
+            opts.confidence,
+        )?;
+    }
+
+    let mut runner = Runner::new(experiments, runner_options)?;
+    if let Some(extend) = &extend {
+        runner.skip_iterations(extend.last_iteration());
+    }
+
+    if let Some(ignore_first) = opts.ignore_first {
+        let last_ignored = runner.iteration() + ignore_first;
+        while runner.iteration() < last_ignored {
+            runner.run_iteration(&mut log)?;
+        }
+
+        // Samples are excluded by iteration, so all tests lose the same iterations
+        // even if some of them failed.
         writeln!(
-            log.both_log_and_stderr(),
-            "Ignoring first run pair results."
+            log.log_only(),
+            "excluded iterations: {}",
+            runner.iteration()
         )?;
+        for (name, test) in runner.experiments.iter() {
+            writeln!(
+                log.log_only(),
+                "{}.excluded_samples: {}",
+                name,
+                test.iterations.len()
+            )?;
+        }
+        runner.clear_samples();
+
+        writeln!(log.both_log_and_stderr())?;
+        match ignore_first {
+            1 => writeln!(
+                log.both_log_and_stderr(),
+                "Ignoring first run pair results."
+            )?,
+            n => writeln!(
+                log.both_log_and_stderr(),
+                "Ignoring results of the first {} iterations.",
+                n
+            )?,
+        }
         writeln!(log.both_log_and_stderr(), "Now collecting the results.")?;
         writeln!(
             log.both_log_and_stderr(),
-            "Statistics will be printed after the second successful iteration."
+            "Statistics will be printed after {} successful iterations.",
+            opts.min_samples
         )?;
+    } else if opts.auto_warmup {
+        writeln!(log.log_only(), "warmup_cv: {}", opts.warmup_cv)?;
+        auto_warmup(&mut log, &mut runner, opts.warmup_cv)?;
+        writeln!(log.both_log_and_stderr(), "Now collecting the results.")?;
     } else {
-        writeln!(log.both_log_and_stderr(), "")?;
+        writeln!(log.both_log_and_stderr())?;
         writeln!(
             log.both_log_and_stderr(),
             "{yellow}First run pair results will be used in statistics.{reset}",
@@ -230,37 +1387,332 @@ fn main() -> anyhow::Result<()> {
         )?;
     }
 
-    let mut measures: Vec<Box<dyn MeasureDyn>> = Vec::new();
-    measures.push(Box::new(WallTime));
-    if opts.mem {
-        measures.push(Box::new(MaxRss));
+    let mut measure_keys = vec![MeasureKey::WallTime];
+    if mem {
+        measure_keys.push(MeasureKey::MaxRss);
+    }
+    if opts.cpu {
+        measure_keys.push(MeasureKey::UserTime);
+        measure_keys.push(MeasureKey::SysTime);
     }
-    let measures = AllMeasures(measures);
+
+    let mut iterations = match opts.shard {
+        Some(shard) => opts.iterations.map(|n| shard.iterations(n)),
+        None => opts.iterations,
+    };
+
+    if let Some(extend) = &extend {
+        for (name, test) in runner.experiments.iter_mut() {
+            test.append_samples(&extend.log.experiments[name], &extend.log.measures);
+        }
+        let recorded = runner.min_runs() as u32;
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "Extending {}: running {} more iterations after {} recorded runs of each variant",
+            extend.dir.display(),
+            extend.iterations,
+            recorded
+        )?;
+        iterations = Some(recorded + extend.iterations);
+    }
+
+    if let Some(pilot) = opts.pilot {
+        let n = run_pilot(
+            &mut log,
+            &mut runner,
+            &inputs,
+            pilot,
+            opts.precision,
+            opts.confidence,
+            opts.auto,
+        )?;
+        iterations = Some(n);
+    }
+
+    let progress = match is_tty() {
+        true => iterations.map(|n| Progress::new(n, runner.min_runs())),
+        false => None,
+    };
+
+    let mut export = Export::new(opts.json.clone(), opts.html.clone());
+    // Statistics of the previous printout, to show how they moved.
+    let mut prev_printout = None;
+    // Iteration of the last statistics printout, for `--report-every`.
+    let mut reported_iteration = runner.iteration();
+    let mut exit_code = ExitCode::Success;
+    let mut config_watcher = match (&opts.config, opts.reload_config) {
+        (Some(path), true) => Some(ConfigWatcher::new(path)),
+        _ => None,
+    };
 
     loop {
-        run_pair(&mut log, &opts, &mut experiments)?;
+        if let Some(watcher) = &mut config_watcher {
+            if let Some(config) = watcher.poll() {
+                let next_iteration = runner.iteration() + 1;
+                reload_config(
+                    &mut log,
+                    config,
+                    &mut runner.experiments,
+                    next_iteration,
+                    &ReloadOptions {
+                        cargo_compare: opts.cargo_compare,
+                        no_safety_check: opts.no_safety_check,
+                        inputs: &inputs,
+                        scale: calibrated_scale,
+                    },
+                )?;
+            }
+        }
+        inputs.verify()?;
+        let mut failure_abort = None;
+        let interrupted = match runner.run_iteration(&mut log) {
+            Err(e) if e.is::<Interrupted>() => true,
+            Err(e) if e.is::<FailureAbort>() => {
+                failure_abort = Some(e);
+                false
+            }
+            r => r.map(|()| false)?,
+        };
+        if interrupted {
+            writeln!(log.both_log_and_stderr())?;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{yellow}Interrupted, reporting runs completed so far{reset}",
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+        }
+        if opts.balance_order && runner.options.scheduling == Scheduling::Random {
+            if let Some(warning) = unbalanced_order(&runner.experiments) {
+                runner.options.scheduling = Scheduling::LatinSquare;
+                writeln!(
+                    log.both_log_and_stderr(),
+                    "{yellow}Switching to latin-square scheduling: {}{reset}",
+                    warning,
+                    yellow = ansi::YELLOW,
+                    reset = ansi::RESET,
+                )?;
+            }
+        }
+        let experiments = &runner.experiments;
 
-        let min_count = experiments.values_mut().map(|t| t.runs()).min().unwrap();
-        if Some(min_count) == opts.iterations.map(|n| n as usize) {
-            break;
+        let measures = AllMeasures::new(&measure_keys, &opts.proportion, experiments);
+        measures.write_raw(experiments, &mut log)?;
+        log.sync()?;
+
+        let min_count = runner.min_runs();
+        let mut done = interrupted || iterations.is_some_and(|n| min_count >= n as usize);
+
+        if let Some(e) = failure_abort {
+            writeln!(log.both_log_and_stderr())?;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{red}Stopping: {}{reset}",
+                e,
+                red = ansi::RED,
+                reset = ansi::RESET,
+            )?;
+            done = true;
+            exit_code = ExitCode::Aborted;
         }
 
-        if min_count < 2 {
-            continue;
+        if let Some(limit) = opts.abort_if_ratio_above {
+            if min_count >= opts.min_samples as usize {
+                if let Some((name, interval)) =
+                    ratio_above(experiments, opts.stat, opts.confidence, limit)
+                {
+                    writeln!(log.both_log_and_stderr())?;
+                    writeln!(
+                        log.both_log_and_stderr(),
+                        "{red}Stopping early: {name}/{a} time {min:.3}..{max:.3} ({conf} conf) is above {limit}{reset}",
+                        conf = opts.confidence,
+                        a = experiments.keys().next().unwrap(),
+                        min = interval.min,
+                        max = interval.max,
+                        red = ansi::RED,
+                        reset = ansi::RESET,
+                    )?;
+                    done = true;
+                    exit_code = ExitCode::Aborted;
+                }
+            }
         }
 
-        writeln!(log.both_log_and_stderr(), "")?;
+        if let Some(width) = opts.max_ci_width {
+            if min_count >= opts.min_samples as usize && !done {
+                if let Some(widest) = widest_interval(experiments, opts.stat, opts.confidence)
+                    .filter(|w| *w <= width.0)
+                {
+                    writeln!(log.both_log_and_stderr())?;
+                    writeln!(
+                        log.both_log_and_stderr(),
+                        "Stopping: widest time interval {:.3} is narrower than {}",
+                        widest,
+                        width.0
+                    )?;
+                    done = true;
+                }
+            }
+        }
 
-        let graph_full = measures.render_stats(&experiments, true)?;
-        let graph_short = measures.render_stats(&experiments, false)?;
+        let prints_runs = opts.verbosity().prints_runs();
+        let reports = done
+            || (opts.report_every != 0
+                && runner.iteration() >= reported_iteration + opts.report_every);
+        if reports {
+            reported_iteration = runner.iteration();
+        }
+        if reports && min_count >= opts.min_samples as usize {
+            writeln!(log.log_and_stderr_if(done || prints_runs))?;
 
-        write!(log.stderr_only(), "{}", graph_full)?;
-        write!(log.log_only(), "{}", graph_short,)?;
+            let filtered;
+            let (experiments, outliers) = match opts.filter_outliers {
+                true => {
+                    filtered = experiments.map(|t| t.without_outliers(&measure_keys));
+                    (&filtered, render_outliers(experiments, &filtered)?)
+                }
+                false => (experiments, String::new()),
+            };
 
-        log.write_graph(&graph_full)?;
+            let max_width = opts.max_line_width();
+            let render_opts = RenderOptions {
+                include_distr: true,
+                max_width,
+                paired: opts.paired,
+                bootstrap: opts.bootstrap,
+                plot: opts.plot,
+                sort_by: opts.sort_by,
+                stat: opts.stat,
+                percentiles: opts.percentiles.clone(),
+                conf: opts.confidence,
+                significance: opts.significance_test,
+                marks: opts.mark.clone(),
+                buckets: opts.buckets.clone().unwrap_or_default(),
+            };
+            let failures =
+                render_failures(experiments)? + &render_sensors(experiments)? + &outliers;
+            let graph_full = wrap_text(
+                &(measures.render_stats(experiments, &render_opts)? + &failures),
+                max_width,
+            );
+            let graph_short = wrap_text(
+                &(measures.render_stats(
+                    experiments,
+                    &RenderOptions {
+                        include_distr: false,
+                        ..render_opts.clone()
+                    },
+                )? + &failures),
+                max_width,
+            );
+
+            if done && exit_code == ExitCode::Success {
+                exit_code = ExitCode::from_verdicts(
+                    wall_time_intervals(experiments, opts.stat, opts.confidence)
+                        .iter()
+                        .map(|(_, interval)| interval.verdict()),
+                );
+            }
+            if done {
+                let report = render_final_report(
+                    &measures,
+                    experiments,
+                    &render_opts,
+                    opts.details,
+                    opts.explain,
+                    true,
+                )? + &outliers;
+                write!(
+                    log.both_log_and_stderr(),
+                    "{}",
+                    wrap_text(&report, max_width)
+                )?;
+                let unbalanced = match runner.options.scheduling {
+                    Scheduling::Random => unbalanced_order(&runner.experiments),
+                    _ => None,
+                };
+                if let Some(warning) = unbalanced {
+                    writeln!(
+                        log.both_log_and_stderr(),
+                        "{yellow}warning: random order is unbalanced, {}; \
+                         consider `--scheduling latin-square` or `--balance-order`{reset}",
+                        warning,
+                        yellow = ansi::YELLOW,
+                        reset = ansi::RESET,
+                    )?;
+                }
+            } else {
+                if prints_runs {
+                    write!(log.stderr_only(), "{}", graph_full)?;
+                }
+                write!(log.log_only(), "{}", graph_short,)?;
+                if let (Some(prev), true) = (&prev_printout, prints_runs) {
+                    let changes = measures.render_changes(prev, experiments, &render_opts)?;
+                    write!(log.stderr_only(), "{}", wrap_text(&changes, max_width))?;
+                }
+                // Only statistics, a snapshot would make every push copy the samples.
+                prev_printout = Some(measures.printout_stats(experiments, &render_opts));
+            }
 
-        measures.write_raw(&experiments, &mut log)?;
+            log.write_graph(&graph_full)?;
+            export.start(
+                experiments,
+                &measure_keys,
+                &opts.proportion,
+                &opts.suite,
+                &render_opts,
+                done,
+            )?;
+        } else if reports && min_count >= 1 {
+            writeln!(log.log_and_stderr_if(prints_runs))?;
+            let raw = format!(
+                "{}Statistics need {} samples of each variant, have {}.\n",
+                measures.render_raw(experiments)?,
+                opts.min_samples,
+                min_count,
+            );
+            write!(
+                log.log_and_stderr_if(prints_runs),
+                "{}",
+                wrap_text(&raw, opts.max_line_width())
+            )?;
+        }
+
+        if done {
+            break;
+        }
+        if let Some(progress) = &progress {
+            let runs: Vec<_> = experiments
+                .iter()
+                .map(|(name, t)| (name, t.runs()))
+                .collect();
+            writeln!(log.stderr_only(), "{}", progress.line(&runs))?;
+        }
     }
 
-    Ok(())
+    export.join()?;
+
+    let min_count = runner.min_runs();
+    if exit_code == ExitCode::Success && min_count < opts.min_samples as usize && !interrupted() {
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}No statistics: --min-samples needs {} samples of each variant, have {}.{reset}",
+            opts.min_samples,
+            min_count,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        exit_code = ExitCode::TooFewSamples;
+    }
+
+    if opts.flamegraph && !interrupted() {
+        write_flamegraphs(&mut log, &opts.shell(), &runner.experiments)?;
+    }
+
+    match interrupted() {
+        true => Ok(ExitCode::Interrupted),
+        false => Ok(exit_code),
+    }
 }
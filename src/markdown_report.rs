@@ -0,0 +1,320 @@
+//! GitHub-flavored markdown summary printed with `absh report --md`, for pasting into PRs.
+
+use std::fmt::Display;
+use std::fmt::Write;
+
+use crate::bars::PlotHighlight;
+use crate::bars::plot_u64;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::math::numbers::Numbers;
+use crate::math::proportion::Proportion;
+use crate::math::proportion::ProportionTest;
+use crate::math::proportion::compare_proportions;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Verdict;
+use crate::metadata::Metadata;
+use crate::render_stats::RenderOptions;
+use crate::render_stats::render_rate_difference;
+use crate::version::version_long;
+
+/// Width of distribution sparklines.
+const SPARKLINE_WIDTH: usize = 24;
+
+/// Distribution plot of each experiment on a common scale.
+fn sparklines(
+    tests: &ExperimentMap<Experiment>,
+    numbers: &impl Fn(&Experiment) -> &Numbers,
+) -> ExperimentMap<String> {
+    let min = tests.values().map(|t| numbers(t).min().unwrap()).min();
+    let max = tests.values().map(|t| numbers(t).max().unwrap()).max();
+    let distr = tests.map(|t| numbers(t).distr(SPARKLINE_WIDTH, min.unwrap(), max.unwrap()));
+    let max_height = distr.values().map(|d| d.max()).max().unwrap();
+    distr.map(|d| {
+        // Markdown strips spaces at both ends of code spans, but not non-breaking spaces.
+        plot_u64(&d.counts, max_height, &PlotHighlight::no()).replace(' ', "\u{a0}")
+    })
+}
+
+fn verdict(name: impl Display, verdict: Verdict, ratio: f64, words: (&str, &str)) -> String {
+    match verdict {
+        Verdict::Lower => format!("{} is {:.1}% {}", name, (1.0 - ratio) * 100.0, words.0),
+        Verdict::Higher => format!("{} is {:.1}% {}", name, (ratio - 1.0) * 100.0, words.1),
+        Verdict::NoDifference => "no significant difference".to_owned(),
+    }
+}
+
+/// Version of absh, description of the benchmark and table of variants with their scripts
+/// and descriptions.
+pub(crate) fn markdown_header(
+    tests: &ExperimentMap<Experiment>,
+    suite: &Metadata,
+) -> anyhow::Result<String> {
+    // Pipes would end the cell even in code spans, and newlines the row.
+    let cell = |s: &str| s.replace('|', "\\|").replace('\n', "; ");
+    let mut r = String::new();
+    writeln!(r, "<sub>absh {}</sub>", version_long())?;
+    writeln!(r)?;
+    if !suite.is_empty() {
+        writeln!(r, "{}", suite.summary())?;
+        writeln!(r)?;
+    }
+    let with_metadata = tests.values().any(|t| !t.metadata.is_empty());
+    match with_metadata {
+        true => {
+            writeln!(r, "| variant | script | about |")?;
+            writeln!(r, "|---|---|---|")?;
+        }
+        false => {
+            writeln!(r, "| variant | script |")?;
+            writeln!(r, "|---|---|")?;
+        }
+    }
+    for (name, test) in tests.iter() {
+        write!(r, "| {} | `{}` |", name, cell(&test.run))?;
+        if with_metadata {
+            write!(r, " {} |", cell(&test.metadata.summary()))?;
+        }
+        writeln!(r)?;
+    }
+    Ok(r)
+}
+
+/// Samples as CSV in a collapsed block, for reanalysis without the run log.
+pub(crate) fn markdown_raw_data(csv: &str) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "<details>")?;
+    writeln!(r, "<summary>Raw samples (CSV)</summary>")?;
+    writeln!(r)?;
+    writeln!(r, "```csv")?;
+    write!(r, "{}", csv)?;
+    writeln!(r, "```")?;
+    writeln!(r)?;
+    writeln!(r, "</details>")?;
+    Ok(r)
+}
+
+/// Statistics table and comparisons with A of a measure.
+pub(crate) fn markdown_measure<D: Display>(
+    name: &str,
+    words: (&str, &str),
+    tests: &ExperimentMap<Experiment>,
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+    display: impl Fn(u64) -> D,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "### {}", name)?;
+    writeln!(r)?;
+    writeln!(r, "| | n | mean | med | std | min | max | distribution |")?;
+    writeln!(r, "|---|--:|--:|--:|--:|--:|--:|---|")?;
+    let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
+    let sparklines = sparklines(tests, &numbers);
+    for ((name, stats), plot) in stats.iter().zip(sparklines.values()) {
+        writeln!(
+            r,
+            "| {} | {} | {} | {} | {} | {} | {} | `{}` |",
+            name,
+            stats.count,
+            display(stats.mean),
+            display(stats.med),
+            display(stats.std),
+            display(stats.min),
+            display(stats.max),
+            plot,
+        )?;
+    }
+
+    if tests.count() > 1 {
+        writeln!(r)?;
+        writeln!(
+            r,
+            "| | {} ratio | {} conf | p ({}) | |",
+            opts.stat.name(),
+            opts.conf,
+            opts.significance.name()
+        )?;
+        writeln!(r, "|---|--:|--:|--:|---|")?;
+        let mut names = tests.keys();
+        let a_name = names.next().unwrap();
+        for b_name in names {
+            let interval = RatioInterval::compare(
+                opts.stat,
+                opts.conf,
+                numbers(&tests[a_name]),
+                numbers(&tests[b_name]),
+            );
+            let p = opts
+                .significance
+                .p_value(numbers(&tests[a_name]), numbers(&tests[b_name]));
+            writeln!(
+                r,
+                "| {b_name}/{a_name} | {ratio:.3} | {min:.3}..{max:.3} | {p} | {verdict} |",
+                ratio = interval.ratio,
+                min = interval.min,
+                max = interval.max,
+                p = p.map_or("?".to_owned(), |p| format!("{:.4}", p)),
+                verdict = verdict(b_name, interval.verdict(), interval.ratio, words),
+            )?;
+        }
+    }
+    Ok(r)
+}
+
+/// Success rates and comparisons with A of a measure with boolean values.
+pub(crate) fn markdown_proportions(
+    name: &str,
+    tests: &ExperimentMap<Experiment>,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "### {} (proportion)", name)?;
+    writeln!(r)?;
+    writeln!(r, "| | successes | rate |")?;
+    writeln!(r, "|---|--:|--:|")?;
+    let proportions: ExperimentMap<Proportion> = tests.map(|t| Proportion {
+        successes: numbers(t).sum(),
+        total: numbers(t).len() as u64,
+    });
+    for (name, p) in proportions.iter() {
+        writeln!(
+            r,
+            "| {} | {}/{} | {:.1}% |",
+            name,
+            p.successes,
+            p.total,
+            p.ratio() * 100.0
+        )?;
+    }
+
+    if tests.count() > 1 {
+        writeln!(r)?;
+        writeln!(r, "| | difference | test |")?;
+        writeln!(r, "|---|--:|---|")?;
+        let mut iter = proportions.iter();
+        let (a_name, a) = iter.next().unwrap();
+        for (b_name, b) in iter {
+            let test = match compare_proportions(*a, *b) {
+                Some(ProportionTest::ChiSquared { statistic, p }) => {
+                    format!("chi-squared={:.3} p={:.4}", statistic, p)
+                }
+                Some(ProportionTest::FisherExact { p }) => format!("Fisher exact p={:.4}", p),
+                None => "no test".to_owned(),
+            };
+            writeln!(
+                r,
+                "| {}-{} | {} | {} |",
+                b_name,
+                a_name,
+                render_rate_difference(*a, *b),
+                test
+            )?;
+        }
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duration::Duration;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::markdown_report::markdown_header;
+    use crate::markdown_report::markdown_measure;
+    use crate::markdown_report::markdown_raw_data;
+    use crate::math::ratio::Stat;
+    use crate::measure::key::MeasureKey;
+    use crate::metadata::Metadata;
+    use crate::render_stats::RenderOptions;
+    use crate::student::ConfInterval;
+    use crate::student::TWO_SIDED_95;
+    use crate::version::version_long;
+
+    #[test]
+    fn measure() {
+        let mut tests = ExperimentMap::default();
+        // Names not used in other tests, so they are interned in this order.
+        for (name, times) in [("md-a", [10, 12, 11]), ("md-b", [20, 22, 21])] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for &t in times.iter() {
+                test.measures[MeasureKey::WallTime].push(t * 1_000_000);
+            }
+            tests.insert(name, test);
+        }
+        let md = |conf, stat| {
+            let opts = RenderOptions {
+                conf,
+                stat,
+                ..RenderOptions::default()
+            };
+            markdown_measure(
+                "Time (in seconds)",
+                ("faster", "slower"),
+                &tests,
+                &opts,
+                |t| &t.measures[MeasureKey::WallTime],
+                Duration::from_nanos,
+            )
+            .unwrap()
+        };
+        let md_99 = md(ConfInterval::C_99_5_99_0, Stat::Mean);
+        let md_median = md(TWO_SIDED_95, Stat::Median);
+        let md = md(TWO_SIDED_95, Stat::Mean);
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!("### Time (in seconds)", lines[0]);
+        assert!(
+            lines[4].starts_with("| md-a | 3 | 0.011 | 0.011 | 0.001 | 0.010 | 0.012 | `"),
+            "{}",
+            md
+        );
+        assert!(lines[9].starts_with("| md-b/md-a | 1.909 |"), "{}", md);
+        assert!(lines[9].ends_with("| md-b is 90.9% slower |"), "{}", md);
+        assert_eq!("| | mean ratio | 95% conf | p (Welch) | |", lines[7]);
+        let lines_99: Vec<&str> = md_99.lines().collect();
+        assert_eq!("| | mean ratio | 99% conf | p (Welch) | |", lines_99[7]);
+        assert_ne!(lines[9], lines_99[9]);
+        let lines_median: Vec<&str> = md_median.lines().collect();
+        assert_eq!(
+            "| | median ratio | 95% conf | p (Welch) | |",
+            lines_median[7]
+        );
+        assert!(
+            lines_median[9].starts_with("| md-b/md-a | 1.909 |"),
+            "{}",
+            md_median
+        );
+    }
+
+    #[test]
+    fn header_and_raw_data() {
+        let mut tests = ExperimentMap::default();
+        let name = ExperimentName::intern("md-pipe");
+        let script = "ls | wc -l\ntrue".to_owned();
+        tests.insert(name, Experiment::new(name, String::new(), script));
+        let md = markdown_header(&tests, &Metadata::default()).unwrap();
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(format!("<sub>absh {}</sub>", version_long()), lines[0]);
+        assert_eq!("| md-pipe | `ls \\| wc -l; true` |", lines[4]);
+        tests[name].metadata.owner = Some("a|b".to_owned());
+        let suite = Metadata {
+            links: vec!["https://example.com/1".to_owned()],
+            ..Metadata::default()
+        };
+        let md = markdown_header(&tests, &suite).unwrap();
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!("https://example.com/1", lines[2]);
+        assert_eq!("| variant | script | about |", lines[4]);
+        assert_eq!(
+            "| md-pipe | `ls \\| wc -l; true` | owner: a\\|b |",
+            lines[6]
+        );
+        let md = markdown_raw_data("variant,measure,sample,value\n").unwrap();
+        assert!(
+            md.contains("```csv\nvariant,measure,sample,value\n```\n"),
+            "{}",
+            md
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use rand::Rng;
+
+use crate::math::ratio::RatioInterval;
+use crate::student::ConfInterval;
+
+/// Percentile bootstrap confidence intervals of B/A.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BootstrapRatio {
+    pub mean: RatioInterval,
+    pub median: RatioInterval,
+}
+
+fn mean(xs: &[u64]) -> f64 {
+    xs.iter().map(|&x| x as f64).sum::<f64>() / xs.len() as f64
+}
+
+/// Median, reorders `xs`.
+fn median(xs: &mut [u64]) -> f64 {
+    let odd = xs.len() % 2 == 1;
+    let (lower, &mut hi, _) = xs.select_nth_unstable(xs.len() / 2);
+    if odd {
+        hi as f64
+    } else {
+        let lo = *lower.iter().max().unwrap();
+        (lo as f64 + hi as f64) / 2.0
+    }
+}
+
+fn resample<T: Copy>(rng: &mut impl Rng, xs: &[T], buf: &mut Vec<T>) {
+    buf.clear();
+    buf.extend((0..xs.len()).map(|_| xs[rng.gen_range(0, xs.len())]));
+}
+
+/// Percentiles around the middle `conf` of `ratios`, like 2.5 and 97.5 for 95%.
+fn percentile_interval(ratio: f64, mut ratios: Vec<f64>, conf: ConfInterval) -> RatioInterval {
+    ratios.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let last = (ratios.len() - 1) as f64;
+    let tail = (1.0 - conf.level()) / 2.0;
+    RatioInterval {
+        ratio,
+        min: ratios[(last * tail).floor() as usize],
+        max: ratios[(last * (1.0 - tail)).ceil() as usize],
+    }
+}
+
+/// Resample both variants `resamples` times for intervals at level `conf`,
+/// `None` if A mean or median is zero.
+pub fn bootstrap_ratio(
+    a: &[u64],
+    b: &[u64],
+    resamples: u32,
+    conf: ConfInterval,
+    rng: &mut impl Rng,
+) -> Option<BootstrapRatio> {
+    if a.is_empty() || b.is_empty() || resamples == 0 {
+        return None;
+    }
+    let (mean_a, median_a) = (mean(a), median(&mut a.to_vec()));
+    if mean_a == 0.0 || median_a == 0.0 {
+        return None;
+    }
+
+    let mut mean_ratios = Vec::with_capacity(resamples as usize);
+    let mut median_ratios = Vec::with_capacity(resamples as usize);
+    let mut buf_a = Vec::with_capacity(a.len());
+    let mut buf_b = Vec::with_capacity(b.len());
+    for _ in 0..resamples {
+        resample(rng, a, &mut buf_a);
+        resample(rng, b, &mut buf_b);
+        // Resampled A can be all zeros even if the original is not.
+        mean_ratios.push(mean(&buf_b) / mean(&buf_a));
+        median_ratios.push(median(&mut buf_b) / median(&mut buf_a));
+    }
+    mean_ratios.retain(|r| r.is_finite());
+    median_ratios.retain(|r| r.is_finite());
+    if mean_ratios.is_empty() || median_ratios.is_empty() {
+        return None;
+    }
+
+    Some(BootstrapRatio {
+        mean: percentile_interval(mean(b) / mean_a, mean_ratios, conf),
+        median: percentile_interval(median(&mut b.to_vec()) / median_a, median_ratios, conf),
+    })
+}
+
+/// Geometric mean of `ratios` with a percentile bootstrap interval at level `conf` over
+/// the ratios, `None` if there are none or some are not positive.
+pub fn bootstrap_geomean(
+    ratios: &[f64],
+    resamples: u32,
+    conf: ConfInterval,
+    rng: &mut impl Rng,
+) -> Option<RatioInterval> {
+    if ratios.is_empty() || resamples == 0 || ratios.iter().any(|&r| r.is_nan() || r <= 0.0) {
+        return None;
+    }
+    let logs: Vec<f64> = ratios.iter().map(|r| r.ln()).collect();
+    let geomean = |logs: &[f64]| (logs.iter().sum::<f64>() / logs.len() as f64).exp();
+
+    let mut buf = Vec::with_capacity(logs.len());
+    let geomeans = (0..resamples)
+        .map(|_| {
+            resample(rng, &logs, &mut buf);
+            geomean(&buf)
+        })
+        .collect();
+    Some(percentile_interval(geomean(&logs), geomeans, conf))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::math::bootstrap::bootstrap_geomean;
+    use crate::math::bootstrap::bootstrap_ratio;
+    use crate::math::bootstrap::median;
+    use crate::math::ratio::Verdict;
+    use crate::student::ConfInterval;
+    use crate::student::TWO_SIDED_95;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(2.0, median(&mut [3, 1, 2]));
+        assert_eq!(2.5, median(&mut [4, 1, 3, 2]));
+    }
+
+    #[test]
+    fn test_bootstrap_ratio() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a: Vec<u64> = (0..50).map(|i| 1000 + i * 10).collect();
+        // Heavy tail in B does not move the median.
+        let b: Vec<u64> = (0..50)
+            .map(|i| if i % 10 == 0 { 100_000 } else { 2000 + i * 20 })
+            .collect();
+        let r = bootstrap_ratio(&a, &b, 2000, TWO_SIDED_95, &mut rng).unwrap();
+        assert!(r.mean.min <= r.mean.ratio && r.mean.ratio <= r.mean.max);
+        assert!(r.median.min <= r.median.ratio && r.median.ratio <= r.median.max);
+        assert_eq!(Verdict::Higher, r.median.verdict());
+        assert!(r.median.max - r.median.min < r.mean.max - r.mean.min);
+        let wider = bootstrap_ratio(&a, &b, 2000, ConfInterval::C_99_95_99_9, &mut rng).unwrap();
+        assert!(wider.mean.max - wider.mean.min > r.mean.max - r.mean.min);
+
+        assert_eq!(
+            None,
+            bootstrap_ratio(&[0, 0], &[1, 2], 100, TWO_SIDED_95, &mut rng)
+        );
+        assert_eq!(
+            None,
+            bootstrap_ratio(&[], &[1, 2], 100, TWO_SIDED_95, &mut rng)
+        );
+    }
+
+    #[test]
+    fn test_bootstrap_geomean() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let r =
+            bootstrap_geomean(&[1.1, 1.2, 1.15, 1.05, 1.3], 2000, TWO_SIDED_95, &mut rng).unwrap();
+        assert!((r.ratio - 1.157).abs() < 0.001, "{:?}", r);
+        assert!(r.min <= r.ratio && r.ratio <= r.max);
+        assert_eq!(Verdict::Higher, r.verdict());
+
+        let r = bootstrap_geomean(&[0.5, 2.0], 2000, TWO_SIDED_95, &mut rng).unwrap();
+        assert_eq!(Verdict::NoDifference, r.verdict());
+
+        assert_eq!(None, bootstrap_geomean(&[], 100, TWO_SIDED_95, &mut rng));
+        assert_eq!(
+            None,
+            bootstrap_geomean(&[1.0, 0.0], 100, TWO_SIDED_95, &mut rng)
+        );
+    }
+}
@@ -0,0 +1,41 @@
+/// Pearson correlation coefficient of two equally long series.
+///
+/// `None` if there are fewer than two points or either series is constant.
+pub fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    assert_eq!(xs.len(), ys.len());
+    if xs.len() < 2 {
+        return None;
+    }
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::correlation::pearson;
+
+    #[test]
+    fn test_pearson() {
+        let r = pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]).unwrap();
+        assert!((r - 1.0).abs() < 1e-9);
+        let r = pearson(&[1.0, 2.0, 3.0], &[3.0, 1.0, -1.0]).unwrap();
+        assert!((r + 1.0).abs() < 1e-9);
+        let r = pearson(&[1.0, 2.0, 3.0, 4.0], &[1.0, 3.0, 3.0, 1.0]).unwrap();
+        assert!(r.abs() < 1e-9);
+        assert_eq!(None, pearson(&[1.0, 2.0], &[5.0, 5.0]));
+        assert_eq!(None, pearson(&[1.0], &[5.0]));
+    }
+}
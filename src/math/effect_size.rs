@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::math::sorted::NumbersSorted;
+use crate::math::stats::Stats;
+
+/// How large a difference is, regardless of whether it is significant.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Magnitude {
+    Negligible,
+    Small,
+    Medium,
+    Large,
+}
+
+impl Magnitude {
+    fn from_thresholds(effect: f64, thresholds: [f64; 3]) -> Magnitude {
+        let effect = effect.abs();
+        if effect < thresholds[0] {
+            Magnitude::Negligible
+        } else if effect < thresholds[1] {
+            Magnitude::Small
+        } else if effect < thresholds[2] {
+            Magnitude::Medium
+        } else {
+            Magnitude::Large
+        }
+    }
+
+    /// Cohen's conventional 0.2, 0.5 and 0.8.
+    pub fn of_cohens_d(d: f64) -> Magnitude {
+        Magnitude::from_thresholds(d, [0.2, 0.5, 0.8])
+    }
+
+    /// Thresholds of Romano et al. (2006), matching Cohen's for normal samples.
+    pub fn of_cliffs_delta(delta: f64) -> Magnitude {
+        Magnitude::from_thresholds(delta, [0.147, 0.33, 0.474])
+    }
+}
+
+impl fmt::Display for Magnitude {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Magnitude::Negligible => write!(f, "negligible"),
+            Magnitude::Small => write!(f, "small"),
+            Magnitude::Medium => write!(f, "medium"),
+            Magnitude::Large => write!(f, "large"),
+        }
+    }
+}
+
+/// Difference of means in pooled standard deviations, `None` if both samples have no variance.
+pub fn cohens_d(a: &Stats<u64>, b: &Stats<u64>) -> Option<f64> {
+    let (na, nb) = (a.count as f64, b.count as f64);
+    let pooled_var = ((na - 1.0) * a.sigma_sq() + (nb - 1.0) * b.sigma_sq()) / (na + nb - 2.0);
+    if pooled_var.is_nan() || pooled_var <= 0.0 {
+        return None;
+    }
+    Some((b.mean as f64 - a.mean as f64) / pooled_var.sqrt())
+}
+
+/// Probability that a run of B is higher than a run of A, minus the probability that it is
+/// lower, `None` if a sample is empty.
+///
+/// Nonparametric, like the Kolmogorov–Smirnov test: only the order of values matters.
+pub fn cliffs_delta(a: NumbersSorted, b: NumbersSorted) -> Option<f64> {
+    let (a, b) = (a.0, b.0);
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let dominance: i64 = b
+        .iter()
+        .map(|&x| {
+            let lower = a.partition_point(|&y| y < x);
+            let higher = a.len() - a.partition_point(|&y| y <= x);
+            lower as i64 - higher as i64
+        })
+        .sum();
+    Some(dominance as f64 / (a.len() * b.len()) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::effect_size::Magnitude;
+    use crate::math::effect_size::cliffs_delta;
+    use crate::math::effect_size::cohens_d;
+    use crate::math::numbers::Numbers;
+    use crate::math::sorted::NumbersSorted;
+
+    #[test]
+    fn cliffs() {
+        let delta = |a: &[u64], b: &[u64]| cliffs_delta(NumbersSorted(a), NumbersSorted(b));
+        assert_eq!(Some(1.0), delta(&[1, 2, 3], &[4, 5]));
+        assert_eq!(Some(-1.0), delta(&[4, 5], &[1, 2, 3]));
+        assert_eq!(Some(0.0), delta(&[1, 2, 3], &[1, 2, 3]));
+        // Of 4 pairs, B is higher in 2, lower in 1 and equal in 1.
+        assert_eq!(Some(0.25), delta(&[1, 3], &[2, 3]));
+        assert_eq!(None, delta(&[], &[1]));
+    }
+
+    #[test]
+    fn cohens() {
+        let stats = |values: &[u64]| {
+            let mut numbers = Numbers::default();
+            for &v in values {
+                numbers.push(v);
+            }
+            numbers.stats().unwrap()
+        };
+        let a = stats(&[1000, 1100, 1200, 1300]);
+        let b = stats(&[1100, 1200, 1300, 1400]);
+        let d = cohens_d(&a, &b).unwrap();
+        assert!((d - 0.775).abs() < 0.01, "{}", d);
+        assert_eq!(Magnitude::Medium, Magnitude::of_cohens_d(d));
+        assert_eq!(None, cohens_d(&stats(&[5, 5]), &stats(&[6, 6])));
+    }
+
+    #[test]
+    fn magnitude() {
+        assert_eq!(Magnitude::Negligible, Magnitude::of_cliffs_delta(-0.1));
+        assert_eq!(Magnitude::Small, Magnitude::of_cliffs_delta(0.2));
+        assert_eq!(Magnitude::Large, Magnitude::of_cliffs_delta(-0.9));
+        assert_eq!(Magnitude::Large, Magnitude::of_cohens_d(2.0));
+    }
+}
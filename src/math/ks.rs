@@ -0,0 +1,75 @@
+use crate::math::sorted::NumbersSorted;
+use crate::math::special::kolmogorov_sf;
+
+/// Two-sample Kolmogorov–Smirnov test for the difference of distributions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KsTest {
+    /// Largest distance between empirical distribution functions.
+    pub d: f64,
+    /// Asymptotic p-value.
+    pub p: f64,
+}
+
+/// Test B is distributed differently from A, `None` if a sample is empty.
+///
+/// Unlike tests of means, this detects changes of shape, like a new mode in B.
+pub fn ks_test(a: NumbersSorted, b: NumbersSorted) -> Option<KsTest> {
+    let (a, b) = (a.0, b.0);
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut d: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let x = u64::min(a[i], b[j]);
+        while i < a.len() && a[i] == x {
+            i += 1;
+        }
+        while j < b.len() && b[j] == x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+    // Effective sample size with Stephens' correction for small samples.
+    let ne = f64::sqrt(n * m / (n + m));
+    let p = kolmogorov_sf((ne + 0.12 + 0.11 / ne) * d);
+    Some(KsTest { d, p })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ks::ks_test;
+    use crate::math::sorted::NumbersSorted;
+
+    fn ks(a: &[u64], b: &[u64]) -> (f64, f64) {
+        let test = ks_test(NumbersSorted(a), NumbersSorted(b)).unwrap();
+        (test.d, test.p)
+    }
+
+    #[test]
+    fn same() {
+        assert_eq!((0.0, 1.0), ks(&[1, 2, 3, 4], &[1, 2, 3, 4]));
+        assert_eq!(None, ks_test(NumbersSorted(&[]), NumbersSorted(&[1])));
+    }
+
+    #[test]
+    fn shifted() {
+        let a: Vec<u64> = (0..20).collect();
+        let b: Vec<u64> = (100..120).collect();
+        let (d, p) = ks(&a, &b);
+        assert_eq!(1.0, d);
+        assert!(p < 1e-6, "{}", p);
+    }
+
+    #[test]
+    fn bimodal() {
+        // Same mean, but B has two modes.
+        let a: Vec<u64> = (95..=105).flat_map(|x| [x, x, x]).collect();
+        let mut b: Vec<u64> = (0..33).map(|i| if i % 2 == 0 { 80 } else { 120 }).collect();
+        b.sort();
+        let (d, p) = ks(&a, &b);
+        assert!(d > 0.45, "{}", d);
+        assert!(p < 0.001, "{}", p);
+    }
+}
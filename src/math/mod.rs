@@ -1,3 +1,14 @@
+pub mod bootstrap;
+pub mod correlation;
+pub mod effect_size;
+pub mod ks;
 pub mod numbers;
+pub mod permutation;
+pub mod proportion;
+pub mod ratio;
+pub mod sample_size;
+pub mod significance;
 pub mod sorted;
+pub mod special;
 pub mod stats;
+pub mod welch;
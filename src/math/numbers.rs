@@ -1,6 +1,10 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
 use crate::math::sorted::NumbersSorted;
-use crate::math::stats::stats;
+use crate::math::stats::Percentiles;
 use crate::math::stats::Stats;
+use crate::math::stats::stats;
 
 pub struct Distr {
     pub counts: Vec<u64>,
@@ -16,22 +20,47 @@ impl Distr {
     }
 }
 
-#[derive(Default)]
+/// Samples in insertion order and sorted.
+///
+/// Storage is shared with snapshots, `push` copies it only while a snapshot is alive.
+#[derive(Default, Clone)]
 pub struct Numbers {
-    raw: Vec<u64>,
-    sorted: Vec<u64>,
+    raw: Arc<Vec<u64>>,
+    sorted: Arc<Vec<u64>>,
+}
+
+/// Immutable view of [`Numbers`] at some point, cheap to clone and to send to other threads.
+#[derive(Clone)]
+pub struct NumbersSnapshot(Numbers);
+
+impl Deref for NumbersSnapshot {
+    type Target = Numbers;
+
+    fn deref(&self) -> &Numbers {
+        &self.0
+    }
 }
 
 impl Numbers {
+    pub fn new() -> Numbers {
+        Numbers::default()
+    }
+
     pub fn push(&mut self, d: u64) {
-        self.raw.push(d.clone());
-        let idx = self.sorted.binary_search(&d).unwrap_or_else(|x| x);
-        self.sorted.insert(idx, d);
+        Arc::make_mut(&mut self.raw).push(d);
+        let sorted = Arc::make_mut(&mut self.sorted);
+        let idx = sorted.binary_search(&d).unwrap_or_else(|x| x);
+        sorted.insert(idx, d);
     }
 
     pub fn clear(&mut self) {
-        self.raw.clear();
-        self.sorted.clear();
+        self.raw = Arc::default();
+        self.sorted = Arc::default();
+    }
+
+    /// Current samples, not affected by later changes.
+    pub fn snapshot(&self) -> NumbersSnapshot {
+        NumbersSnapshot(self.clone())
     }
 
     pub fn raw(&self) -> &[u64] {
@@ -42,6 +71,10 @@ impl Numbers {
         self.raw.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
     pub fn med(&self) -> Option<u64> {
         self.sorted().med()
     }
@@ -70,18 +103,16 @@ impl Numbers {
         self.raw.iter().cloned()
     }
 
-    pub fn sorted(&self) -> NumbersSorted {
+    pub fn sorted(&self) -> NumbersSorted<'_> {
         NumbersSorted(&self.sorted)
     }
 
     pub fn distr(&self, n: usize, min: u64, max: u64) -> Distr {
         let mut counts = vec![0; n];
         if min != max {
-            for d in &self.raw {
-                let bucket = (((d.clone() - min.clone()) as f64)
-                    / ((max.clone() - min.clone()) as f64)
-                    * ((n - 1) as f64))
-                    .round() as usize;
+            for d in self.raw.iter() {
+                let bucket =
+                    (((d - min) as f64) / ((max - min) as f64) * ((n - 1) as f64)).round() as usize;
                 counts[bucket.clamp(0, n - 1)] += 1;
             }
         }
@@ -91,6 +122,27 @@ impl Numbers {
     pub fn stats(&self) -> Option<Stats<u64>> {
         stats(self)
     }
+
+    /// Statistics with values at `percentiles`.
+    pub fn stats_with_percentiles(&self, percentiles: &Percentiles) -> Option<Stats<u64>> {
+        let mut stats = self.stats()?;
+        for &p in &percentiles.0 {
+            stats.percentiles.push((p, self.sorted().percentile(p)?));
+        }
+        Some(stats)
+    }
+
+    /// Tukey fences: values further than 1.5 interquartile ranges outside of the quartiles
+    /// are outliers. `None` with fewer than 4 samples.
+    pub fn tukey_fences(&self) -> Option<(f64, f64)> {
+        if self.len() < 4 {
+            return None;
+        }
+        let q1 = self.sorted().percentile_f64(25.0)?;
+        let q3 = self.sorted().percentile_f64(75.0)?;
+        let iqr = q3 - q1;
+        Some((q1 - 1.5 * iqr, q3 + 1.5 * iqr))
+    }
 }
 
 #[cfg(test)]
@@ -113,6 +165,20 @@ mod test {
         assert_eq!(10, ds.min().unwrap());
     }
 
+    #[test]
+    fn snapshot() {
+        let mut ds = Numbers::default();
+        ds.push(20);
+        let snapshot = ds.snapshot();
+        ds.push(10);
+        assert_eq!(&[20], snapshot.raw());
+        assert_eq!(Some(20), snapshot.min());
+        assert_eq!(&[20, 10], ds.raw());
+        assert_eq!(Some(10), ds.min());
+        ds.clear();
+        assert_eq!(1, snapshot.len());
+    }
+
     #[test]
     fn distr_1() {
         let mut ds = Numbers::default();
@@ -132,6 +198,18 @@ mod test {
         assert_eq!(&[2, 3], &ds.distr(2, 10, 20).counts[..]);
     }
 
+    #[test]
+    fn tukey_fences() {
+        let mut ds = Numbers::default();
+        for d in [10, 11, 12, 13, 14] {
+            ds.push(d);
+        }
+        assert_eq!(Some((8.0, 16.0)), ds.tukey_fences());
+        ds.clear();
+        ds.push(10);
+        assert_eq!(None, ds.tukey_fences());
+    }
+
     #[test]
     fn sum() {
         let mut ds = Numbers::default();
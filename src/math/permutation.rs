@@ -0,0 +1,180 @@
+//! Permutation test for the difference of means or medians, without assumptions on
+//! distributions, for small samples where t-tests are not reliable.
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::math::ratio::Stat;
+
+/// Outcome of a permutation test.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PermutationTest {
+    /// Observed difference of the statistic, B minus A.
+    pub difference: f64,
+    /// Two-sided p-value: share of relabelings of runs with a difference at least as large.
+    pub p: f64,
+    /// All relabelings were enumerated, rather than sampled.
+    pub exact: bool,
+}
+
+/// Test the statistic of B differs from A, `None` if a sample is empty.
+///
+/// Enumerates all relabelings of runs as A or B when there are at most `resamples` of
+/// them, otherwise samples `resamples` random relabelings.
+pub fn permutation_test(
+    a: &[u64],
+    b: &[u64],
+    stat: Stat,
+    resamples: u32,
+    rng: &mut impl Rng,
+) -> Option<PermutationTest> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let pooled: Vec<u64> = a.iter().chain(b).copied().collect();
+    let difference = statistic(stat, b) - statistic(stat, a);
+    // Relabelings with an equal difference but different rounding count as extreme.
+    let threshold = difference.abs() * (1.0 - 1e-9);
+    let is_extreme = |relabeled_a: &[u64], relabeled_b: &[u64]| {
+        (statistic(stat, relabeled_b) - statistic(stat, relabeled_a)).abs() >= threshold
+    };
+
+    match relabelings(pooled.len(), a.len()).filter(|&n| n <= resamples as u64) {
+        Some(total) => {
+            let mut extreme = 0;
+            let mut relabeled_a = Vec::with_capacity(a.len());
+            let mut relabeled_b = Vec::with_capacity(b.len());
+            for_each_combination(pooled.len(), a.len(), |in_a| {
+                relabeled_a.clear();
+                relabeled_b.clear();
+                for (&value, &is_a) in pooled.iter().zip(in_a) {
+                    match is_a {
+                        true => relabeled_a.push(value),
+                        false => relabeled_b.push(value),
+                    }
+                }
+                if is_extreme(&relabeled_a, &relabeled_b) {
+                    extreme += 1;
+                }
+            });
+            Some(PermutationTest {
+                difference,
+                p: extreme as f64 / total as f64,
+                exact: true,
+            })
+        }
+        None => {
+            let mut pooled = pooled;
+            let mut extreme = 0;
+            for _ in 0..resamples {
+                let (relabeled_a, relabeled_b) = pooled.partial_shuffle(rng, a.len());
+                if is_extreme(relabeled_a, relabeled_b) {
+                    extreme += 1;
+                }
+            }
+            Some(PermutationTest {
+                difference,
+                // Counting the observed labeling too, so the p-value is never zero.
+                p: (extreme + 1) as f64 / (resamples + 1) as f64,
+                exact: false,
+            })
+        }
+    }
+}
+
+fn statistic(stat: Stat, values: &[u64]) -> f64 {
+    match stat {
+        Stat::Mean => values.iter().map(|&v| v as f64).sum::<f64>() / values.len() as f64,
+        Stat::Median => {
+            let mut sorted = values.to_vec();
+            sorted.sort_unstable();
+            let mid = sorted.len() / 2;
+            match sorted.len() % 2 {
+                0 => (sorted[mid - 1] as f64 + sorted[mid] as f64) / 2.0,
+                _ => sorted[mid] as f64,
+            }
+        }
+    }
+}
+
+/// Number of ways to choose `k` of `n` runs, `None` if it does not fit in `u64`.
+fn relabelings(n: usize, k: usize) -> Option<u64> {
+    let k = k.min(n - k) as u64;
+    let mut r: u64 = 1;
+    for i in 0..k {
+        r = r.checked_mul(n as u64 - i)? / (i + 1);
+    }
+    Some(r)
+}
+
+/// Call `f` with every choice of `k` of `n` positions, as flags of chosen positions.
+fn for_each_combination(n: usize, k: usize, mut f: impl FnMut(&[bool])) {
+    let mut chosen: Vec<usize> = (0..k).collect();
+    let mut flags = vec![false; n];
+    loop {
+        flags.iter_mut().for_each(|flag| *flag = false);
+        for &i in &chosen {
+            flags[i] = true;
+        }
+        f(&flags);
+        // Advance the rightmost position which can move right.
+        let mut i = k;
+        loop {
+            if i == 0 {
+                return;
+            }
+            i -= 1;
+            if chosen[i] < n - k + i {
+                break;
+            }
+        }
+        chosen[i] += 1;
+        for j in i + 1..k {
+            chosen[j] = chosen[j - 1] + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::math::permutation::permutation_test;
+    use crate::math::permutation::relabelings;
+    use crate::math::ratio::Stat;
+
+    #[test]
+    fn exact() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a = [1, 2, 3, 4, 5];
+        let b = [6, 7, 8, 9, 10];
+        let test = permutation_test(&a, &b, Stat::Mean, 1000, &mut rng).unwrap();
+        // 2 of 252 relabelings are as extreme: the observed one and its mirror.
+        assert_eq!(2.0 / 252.0, test.p);
+        assert_eq!(5.0, test.difference);
+        assert!(test.exact);
+        let test = permutation_test(&a, &b, Stat::Median, 1000, &mut rng).unwrap();
+        assert!(test.p < 0.1, "{:?}", test);
+        let test = permutation_test(&[1, 3, 5, 7], &[2, 4, 6, 8], Stat::Mean, 1000, &mut rng);
+        assert!(test.unwrap().p > 0.5);
+        assert_eq!(None, permutation_test(&[], &[1], Stat::Mean, 10, &mut rng));
+    }
+
+    #[test]
+    fn sampled() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a: Vec<u64> = (0..20).collect();
+        let b: Vec<u64> = (10..30).collect();
+        let test = permutation_test(&a, &b, Stat::Mean, 2000, &mut rng).unwrap();
+        assert!(!test.exact);
+        assert!(test.p < 0.01, "{:?}", test);
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!(Some(252), relabelings(10, 5));
+        assert_eq!(Some(1), relabelings(3, 3));
+        assert_eq!(None, relabelings(200, 100));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::math::special::chi_squared_1_sf;
+use crate::math::special::ln_choose;
+
+/// Number of successes out of total trials.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Proportion {
+    pub successes: u64,
+    pub total: u64,
+}
+
+impl Proportion {
+    pub fn ratio(&self) -> f64 {
+        self.successes as f64 / self.total as f64
+    }
+
+    fn failures(&self) -> u64 {
+        self.total - self.successes
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProportionTest {
+    ChiSquared { statistic: f64, p: f64 },
+    FisherExact { p: f64 },
+}
+
+impl ProportionTest {
+    pub fn p(&self) -> f64 {
+        match self {
+            ProportionTest::ChiSquared { p, .. } => *p,
+            ProportionTest::FisherExact { p } => *p,
+        }
+    }
+}
+
+/// Pearson's chi-squared test for 2x2 contingency table.
+///
+/// `None` if any row or column of the table is empty.
+pub fn chi_squared_2x2(a: Proportion, b: Proportion) -> Option<f64> {
+    let n = (a.total + b.total) as f64;
+    let successes = (a.successes + b.successes) as f64;
+    let failures = (a.failures() + b.failures()) as f64;
+    let denominator = a.total as f64 * b.total as f64 * successes * failures;
+    if denominator == 0.0 {
+        return None;
+    }
+    let det = a.successes as f64 * b.failures() as f64 - a.failures() as f64 * b.successes as f64;
+    Some(n * det * det / denominator)
+}
+
+/// Two-sided Fisher's exact test p-value for 2x2 contingency table.
+pub fn fisher_exact_2x2(a: Proportion, b: Proportion) -> f64 {
+    let n = a.total + b.total;
+    let successes = a.successes + b.successes;
+    let ln_p = |x: u64| {
+        ln_choose(a.total, x) + ln_choose(b.total, successes - x) - ln_choose(n, successes)
+    };
+    let observed = ln_p(a.successes);
+    let lo = successes.saturating_sub(b.total);
+    let hi = u64::min(a.total, successes);
+    let p: f64 = (lo..=hi)
+        .map(ln_p)
+        .filter(|&p| p <= observed + 1e-7)
+        .map(f64::exp)
+        .sum();
+    p.min(1.0)
+}
+
+/// Compare two proportions, using Fisher's exact test for small expected counts.
+pub fn compare_proportions(a: Proportion, b: Proportion) -> Option<ProportionTest> {
+    if a.total == 0 || b.total == 0 {
+        return None;
+    }
+    let n = (a.total + b.total) as f64;
+    let successes = (a.successes + b.successes) as f64;
+    let failures = n - successes;
+    let min_expected = [a.total, b.total]
+        .iter()
+        .flat_map(|&t| [t as f64 * successes / n, t as f64 * failures / n])
+        .fold(f64::INFINITY, f64::min);
+    if min_expected < 5.0 {
+        return Some(ProportionTest::FisherExact {
+            p: fisher_exact_2x2(a, b),
+        });
+    }
+    let statistic = chi_squared_2x2(a, b)?;
+    Some(ProportionTest::ChiSquared {
+        statistic,
+        p: chi_squared_1_sf(statistic),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::proportion::Proportion;
+    use crate::math::proportion::ProportionTest;
+    use crate::math::proportion::chi_squared_2x2;
+    use crate::math::proportion::compare_proportions;
+    use crate::math::proportion::fisher_exact_2x2;
+
+    fn p(successes: u64, total: u64) -> Proportion {
+        Proportion { successes, total }
+    }
+
+    #[test]
+    fn chi_squared() {
+        // 2x2 table [[20, 30], [30, 20]].
+        let x = chi_squared_2x2(p(20, 50), p(30, 50)).unwrap();
+        assert!((x - 4.0).abs() < 1e-9);
+        assert_eq!(None, chi_squared_2x2(p(0, 10), p(0, 10)));
+    }
+
+    #[test]
+    fn fisher() {
+        // Lady tasting tea: [[3, 1], [1, 3]].
+        let p = fisher_exact_2x2(p(3, 4), p(1, 4));
+        assert!((p - 0.485714).abs() < 1e-5, "{}", p);
+    }
+
+    #[test]
+    fn compare() {
+        match compare_proportions(p(1, 5), p(4, 5)).unwrap() {
+            ProportionTest::FisherExact { .. } => {}
+            t => panic!("{:?}", t),
+        }
+        match compare_proportions(p(20, 50), p(30, 50)).unwrap() {
+            ProportionTest::ChiSquared { p, .. } => assert!((p - 0.0455).abs() < 1e-3),
+            t => panic!("{:?}", t),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::str::FromStr;
+
+use crate::math::numbers::Numbers;
+use crate::math::stats::Stats;
+use crate::student::ConfInterval;
+use crate::student::t_table;
+
+/// Whether B is lower or higher than A.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Lower,
+    Higher,
+    NoDifference,
+}
+
+/// Statistic variants are compared by.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Stat {
+    #[default]
+    Mean,
+    Median,
+}
+
+impl FromStr for Stat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Stat> {
+        match s {
+            "mean" => Ok(Stat::Mean),
+            "median" => Ok(Stat::Median),
+            s => Err(anyhow::anyhow!(
+                "unknown stat: `{}`, expecting `mean` or `median`",
+                s
+            )),
+        }
+    }
+}
+
+impl Stat {
+    /// Name like `mean`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stat::Mean => "mean",
+            Stat::Median => "median",
+        }
+    }
+}
+
+/// Monotonic map of finite floats to integers, so that binary search can run over floats.
+fn order_key(x: f64) -> u64 {
+    let bits = x.to_bits();
+    match bits >> 63 {
+        1 => !bits,
+        _ => bits | 1 << 63,
+    }
+}
+
+fn from_order_key(key: u64) -> f64 {
+    f64::from_bits(match key >> 63 {
+        1 => key & !(1 << 63),
+        _ => !key,
+    })
+}
+
+/// `k`-th smallest, from zero, of differences `y - x` of all pairs of sorted `xs` and `ys`.
+///
+/// Binary search over values, counting pairs under a value in linear time, so it takes
+/// O((m + n) log) time instead of materializing all m·n pairs.
+fn kth_difference(xs: &[f64], ys: &[f64], k: usize) -> f64 {
+    let pairs_at_most = |t: f64| {
+        let mut j = 0;
+        let mut count = 0;
+        for &x in xs {
+            while j < ys.len() && ys[j] - x <= t {
+                j += 1;
+            }
+            count += j;
+        }
+        count
+    };
+    let mut lo = order_key(ys[0] - xs[xs.len() - 1]);
+    let mut hi = order_key(ys[ys.len() - 1] - xs[0]);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pairs_at_most(from_order_key(mid)) > k {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    from_order_key(lo)
+}
+
+/// Ratio B/A with confidence interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RatioInterval {
+    pub ratio: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl RatioInterval {
+    /// Confidence interval of the ratio of means at level `conf`.
+    pub fn mean_ratio_at(
+        stats_a: &Stats<u64>,
+        stats_b: &Stats<u64>,
+        conf: ConfInterval,
+    ) -> RatioInterval {
+        let degrees_of_freedom = u64::min(stats_a.count - 1, stats_b.count - 1);
+        let t_star = t_table(degrees_of_freedom, conf);
+
+        // Half of a confidence interval
+        let conf_h = t_star
+            * f64::sqrt(
+                stats_a.sigma_sq() / (stats_a.count - 1) as f64
+                    + stats_b.sigma_sq() / (stats_b.count - 1) as f64,
+            );
+
+        // Quarter of a confidence interval
+        let conf_q = conf_h / 2.0;
+
+        RatioInterval {
+            ratio: stats_b.mean as f64 / stats_a.mean as f64,
+            min: (stats_b.mean as f64 - conf_q) / (stats_a.mean as f64 + conf_q),
+            max: (stats_b.mean as f64 + conf_q) / (stats_a.mean as f64 - conf_q),
+        }
+    }
+
+    /// Confidence interval of the ratio of medians at level `conf`.
+    ///
+    /// The interval is that of the Hodges-Lehmann estimator of the shift between
+    /// logarithms of samples, with the normal approximation of the Mann-Whitney U
+    /// distribution. It is `0..inf` when there are too few samples.
+    pub fn median_ratio(a: &Numbers, b: &Numbers, conf: ConfInterval) -> RatioInterval {
+        let ln = |numbers: &Numbers| -> Vec<f64> {
+            let mut logs: Vec<f64> = numbers
+                .iter()
+                .map(|x| (u64::max(x, 1) as f64).ln())
+                .collect();
+            logs.sort_by(|x, y| x.partial_cmp(y).unwrap());
+            logs
+        };
+        let (xs, ys) = (ln(a), ln(b));
+        let (m, n) = (a.len() as f64, b.len() as f64);
+        let z = t_table(u64::MAX, conf);
+        let k = (m * n / 2.0 - z * (m * n * (m + n + 1.0) / 12.0).sqrt()).floor();
+        let ratio = b.med().unwrap() as f64 / a.med().unwrap() as f64;
+        if k < 0.0 {
+            return RatioInterval {
+                ratio,
+                min: 0.0,
+                max: f64::INFINITY,
+            };
+        }
+        let k = k as usize;
+        RatioInterval {
+            ratio,
+            min: kth_difference(&xs, &ys, k).exp(),
+            max: kth_difference(&xs, &ys, xs.len() * ys.len() - 1 - k).exp(),
+        }
+    }
+
+    /// Confidence interval of the ratio of `stat` at level `conf`.
+    pub fn compare(stat: Stat, conf: ConfInterval, a: &Numbers, b: &Numbers) -> RatioInterval {
+        match stat {
+            Stat::Mean => {
+                RatioInterval::mean_ratio_at(&a.stats().unwrap(), &b.stats().unwrap(), conf)
+            }
+            Stat::Median => RatioInterval::median_ratio(a, b, conf),
+        }
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        if self.max < self.min {
+            // Interval of A mean includes zero, so the ratio is unbounded.
+            Verdict::NoDifference
+        } else if self.max < 1.0 {
+            Verdict::Lower
+        } else if self.min > 1.0 {
+            Verdict::Higher
+        } else {
+            Verdict::NoDifference
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::numbers::Numbers;
+    use crate::math::ratio::RatioInterval;
+    use crate::math::ratio::Verdict;
+    use crate::math::ratio::kth_difference;
+    use crate::student::TWO_SIDED_95;
+
+    #[test]
+    fn verdict() {
+        let r = |min, max| RatioInterval {
+            ratio: 1.0,
+            min,
+            max,
+        };
+        assert_eq!(Verdict::Lower, r(0.8, 0.9).verdict());
+        assert_eq!(Verdict::Higher, r(1.1, 1.2).verdict());
+        assert_eq!(Verdict::NoDifference, r(0.9, 1.1).verdict());
+        assert_eq!(Verdict::NoDifference, r(-0.6, -1.5).verdict());
+    }
+
+    #[test]
+    fn median_ratio() {
+        let numbers = |xs: &[u64]| {
+            let mut numbers = Numbers::new();
+            for &x in xs {
+                numbers.push(x);
+            }
+            numbers
+        };
+        // Rare slow runs of B move the mean but not the median.
+        let a = numbers(&[100, 101, 99, 100, 102, 98, 100, 101, 99, 100]);
+        let b = numbers(&[110, 111, 109, 110, 112, 108, 110, 111, 109, 900]);
+        let interval = RatioInterval::median_ratio(&a, &b, TWO_SIDED_95);
+        assert_eq!(1.1, interval.ratio);
+        assert!(interval.min > 1.05 && interval.max < 1.15, "{:?}", interval);
+        assert_eq!(Verdict::Higher, interval.verdict());
+
+        let few = RatioInterval::median_ratio(&numbers(&[1, 2]), &numbers(&[3, 4]), TWO_SIDED_95);
+        assert_eq!((0.0, f64::INFINITY), (few.min, few.max));
+    }
+
+    #[test]
+    fn kth_difference_matches_all_pairs() {
+        let xs = [-1.5, 0.0, 0.0, 0.25, 3.0, 7.5];
+        let ys = [-2.0, 0.0, 0.5, 0.5, 1.0, 4.0, 10.0];
+        let mut diffs: Vec<f64> = ys
+            .iter()
+            .flat_map(|y| xs.iter().map(move |x| y - x))
+            .collect();
+        diffs.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        for (k, &d) in diffs.iter().enumerate() {
+            assert_eq!(d, kth_difference(&xs, &ys, k), "k={}", k);
+        }
+    }
+}
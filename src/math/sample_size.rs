@@ -0,0 +1,71 @@
+use crate::math::stats::Stats;
+use crate::student::ConfInterval;
+use crate::student::t_table;
+
+/// Number of iterations after which the B/A interval printed by absh at `conf` is
+/// expected to be within `precision` of the ratio, estimated from pilot statistics.
+pub fn iterations_for_precision(
+    a: &Stats<u64>,
+    b: &Stats<u64>,
+    precision: f64,
+    conf: ConfInterval,
+) -> u64 {
+    let degrees_of_freedom = u64::min(a.count, b.count).max(2) - 1;
+    let t_star = t_table(degrees_of_freedom, conf);
+    let sigma = f64::sqrt(a.sigma_sq() + b.sigma_sq());
+    // Half of the printed interval is a quarter of the confidence interval,
+    // see `RatioInterval::mean_ratio_at`.
+    let n = (t_star * sigma / (2.0 * precision * a.mean as f64)).powi(2);
+    (n.ceil() as u64 + 1).max(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::sample_size::iterations_for_precision;
+    use crate::math::stats::Stats;
+    use crate::student::ConfInterval;
+    use crate::student::TWO_SIDED_95;
+
+    fn stats(count: u64, mean: u64, std: u64) -> Stats<u64> {
+        Stats {
+            count,
+            mean,
+            med: mean,
+            min: mean,
+            max: mean,
+            std,
+            se: 0,
+            percentiles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn more_noise_needs_more_iterations() {
+        let quiet = iterations_for_precision(
+            &stats(10, 1000, 10),
+            &stats(10, 1000, 10),
+            0.01,
+            TWO_SIDED_95,
+        );
+        let noisy = iterations_for_precision(
+            &stats(10, 1000, 100),
+            &stats(10, 1000, 100),
+            0.01,
+            TWO_SIDED_95,
+        );
+        assert!(quiet < noisy, "{} {}", quiet, noisy);
+        assert_eq!(
+            2,
+            iterations_for_precision(&stats(10, 1000, 0), &stats(10, 1000, 0), 0.01, TWO_SIDED_95)
+        );
+    }
+
+    #[test]
+    fn higher_confidence_needs_more_iterations() {
+        let a = stats(10, 1000, 50);
+        let b = stats(10, 1100, 50);
+        let at_95 = iterations_for_precision(&a, &b, 0.01, TWO_SIDED_95);
+        let at_99 = iterations_for_precision(&a, &b, 0.01, ConfInterval::C_99_5_99_0);
+        assert!(at_95 < at_99, "{} {}", at_95, at_99);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::math::numbers::Numbers;
+use crate::math::permutation::permutation_test;
+use crate::math::ratio::Stat;
+use crate::math::special::normal_cdf;
+use crate::math::special::student_t_two_sided_p;
+use crate::math::stats::Stats;
+use crate::math::welch::welch_t_test;
+
+/// Relabelings of the permutation test.
+pub const PERMUTATIONS: u32 = 10000;
+
+/// Hypothesis test of p-values of comparisons, given with `--significance-test`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SignificanceTest {
+    /// Student's t-test for the difference of means, assuming equal variances.
+    Student,
+    /// Welch's t-test for the difference of means.
+    #[default]
+    Welch,
+    /// Mann–Whitney U test: whether runs of B tend to be higher or lower, by ranks only.
+    MannWhitney,
+    /// Permutation test for the difference of means, exact for small samples.
+    Permutation,
+}
+
+impl SignificanceTest {
+    /// Short name in outputs, like `p=0.0100 (Welch)`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SignificanceTest::Student => "Student",
+            SignificanceTest::Welch => "Welch",
+            SignificanceTest::MannWhitney => "Mann-Whitney",
+            SignificanceTest::Permutation => "permutation",
+        }
+    }
+
+    /// Name in sentences.
+    pub fn long_name(&self) -> &'static str {
+        match self {
+            SignificanceTest::Student => "Student's t-test",
+            SignificanceTest::Welch => "Welch's t-test",
+            SignificanceTest::MannWhitney => "Mann-Whitney U test",
+            SignificanceTest::Permutation => "permutation test",
+        }
+    }
+
+    /// Two-sided p-value of B differing from A, `None` if samples are too small or do not vary.
+    pub fn p_value(&self, a: &Numbers, b: &Numbers) -> Option<f64> {
+        match self {
+            SignificanceTest::Student => student_t_test(&a.stats()?, &b.stats()?),
+            SignificanceTest::Welch => welch_t_test(&a.stats()?, &b.stats()?).map(|t| t.p),
+            SignificanceTest::MannWhitney => mann_whitney_u_test(a.raw(), b.raw()),
+            SignificanceTest::Permutation => {
+                let mut rng = rand::thread_rng();
+                permutation_test(a.raw(), b.raw(), Stat::Mean, PERMUTATIONS, &mut rng).map(|t| t.p)
+            }
+        }
+    }
+}
+
+impl FromStr for SignificanceTest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<SignificanceTest> {
+        match s {
+            "t" => Ok(SignificanceTest::Student),
+            "welch" => Ok(SignificanceTest::Welch),
+            "mannwhitney" => Ok(SignificanceTest::MannWhitney),
+            "permutation" => Ok(SignificanceTest::Permutation),
+            s => Err(anyhow::anyhow!(
+                "unknown significance test: `{}`, expecting `t`, `welch`, `mannwhitney` \
+                 or `permutation`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SignificanceTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignificanceTest::Student => write!(f, "t"),
+            SignificanceTest::Welch => write!(f, "welch"),
+            SignificanceTest::MannWhitney => write!(f, "mannwhitney"),
+            SignificanceTest::Permutation => write!(f, "permutation"),
+        }
+    }
+}
+
+/// Student's t-test p-value with pooled variance, `None` if both samples have no variance.
+pub fn student_t_test(a: &Stats<u64>, b: &Stats<u64>) -> Option<f64> {
+    let (na, nb) = (a.count as f64, b.count as f64);
+    let df = na + nb - 2.0;
+    let pooled_var = ((na - 1.0) * a.sigma_sq() + (nb - 1.0) * b.sigma_sq()) / df;
+    if df < 1.0 || pooled_var.is_nan() || pooled_var <= 0.0 {
+        return None;
+    }
+    let t = (b.mean as f64 - a.mean as f64) / f64::sqrt(pooled_var * (1.0 / na + 1.0 / nb));
+    Some(student_t_two_sided_p(t, df))
+}
+
+/// Mann–Whitney U test p-value by the normal approximation with tie and continuity
+/// corrections, `None` if a sample is empty or all values are equal.
+pub fn mann_whitney_u_test(a: &[u64], b: &[u64]) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let n = na + nb;
+    // Values with `true` for B, in order.
+    let mut all: Vec<(u64, bool)> = a.iter().map(|&v| (v, false)).collect();
+    all.extend(b.iter().map(|&v| (v, true)));
+    all.sort_unstable();
+
+    let mut rank_sum_b = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let j = i + all[i..].iter().take_while(|(v, _)| *v == all[i].0).count();
+        // Ranks are 1-based, tied values get the mean of their ranks.
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_b += rank * all[i..j].iter().filter(|(_, is_b)| *is_b).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+
+    let u = rank_sum_b - nb * (nb + 1.0) / 2.0;
+    let mean = na * nb / 2.0;
+    let var = na * nb / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if var.is_nan() || var <= 0.0 {
+        return None;
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / var.sqrt();
+    Some((2.0 * (1.0 - normal_cdf(z))).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::numbers::Numbers;
+    use crate::math::significance::SignificanceTest;
+    use crate::math::significance::mann_whitney_u_test;
+
+    #[test]
+    fn mann_whitney() {
+        // U=0 with n=5/5: z=(12.5-0.5)/4.787=2.507.
+        let p = mann_whitney_u_test(&[1, 2, 3, 4, 5], &[6, 7, 8, 9, 10]).unwrap();
+        assert!((p - 0.0122).abs() < 0.001, "{}", p);
+        let p = mann_whitney_u_test(&[1, 3, 5, 7], &[2, 4, 6, 8]).unwrap();
+        assert!(p > 0.5, "{}", p);
+        assert_eq!(None, mann_whitney_u_test(&[5, 5], &[5, 5]));
+        assert_eq!(None, mann_whitney_u_test(&[], &[5]));
+    }
+
+    #[test]
+    fn p_value() {
+        let numbers = |values: &[u64]| {
+            let mut numbers = Numbers::default();
+            for &v in values {
+                numbers.push(v);
+            }
+            numbers
+        };
+        let a = numbers(&[1000, 1200, 1400, 1600]);
+        let b = numbers(&[2000, 2300, 2600, 2900]);
+        for s in ["t", "welch", "mannwhitney", "permutation"].iter() {
+            let test: SignificanceTest = s.parse().unwrap();
+            assert_eq!(*s, test.to_string());
+            let p = test.p_value(&a, &b).unwrap();
+            assert!(p < 0.05, "{} {}", s, p);
+        }
+        assert!("ks".parse::<SignificanceTest>().is_err());
+    }
+}
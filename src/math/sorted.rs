@@ -29,21 +29,34 @@ impl<'a> NumbersSorted<'a> {
         if self.is_empty() {
             None
         } else {
-            if self.len() % 2 == 0 {
-                let xy: u64 = self.0[self.len() / 2 - 1].clone() + self.0[self.len() / 2].clone();
+            if self.len().is_multiple_of(2) {
+                let xy: u64 = self.0[self.len() / 2 - 1] + self.0[self.len() / 2];
                 Some(xy / 2)
             } else {
-                Some(self.0[self.len() / 2].clone())
+                Some(self.0[self.len() / 2])
             }
         }
     }
 
+    /// Linearly interpolated percentile, `p` in `0..=100`.
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        Some(self.percentile_f64(p)?.round() as u64)
+    }
+
+    /// Linearly interpolated percentile, `p` in `0..=100`, not rounded.
+    pub fn percentile_f64(&self, p: f64) -> Option<f64> {
+        let last = self.len().checked_sub(1)?;
+        let pos = p / 100.0 * last as f64;
+        let (lo, hi) = (self.0[pos.floor() as usize], self.0[pos.ceil() as usize]);
+        Some(lo as f64 + (hi as f64 - lo as f64) * pos.fract())
+    }
+
     pub fn sum(&self) -> u64 {
         self.0.iter().cloned().sum()
     }
 
     pub fn mean(&self) -> Option<u64> {
-        if self.len() == 0 {
+        if self.is_empty() {
             None
         } else {
             Some(self.sum() / self.len() as u64)
@@ -119,4 +132,16 @@ mod tests {
         );
         assert_eq!(NumbersSorted(&[3, 3, 4, 4]), nums.filter(FilterCond::Gt, 2));
     }
+
+    #[test]
+    fn test_percentile() {
+        let nums: Vec<u64> = (1..=101).collect();
+        let nums = NumbersSorted(&nums);
+        assert_eq!(Some(1), nums.percentile(0.0));
+        assert_eq!(Some(91), nums.percentile(90.0));
+        assert_eq!(Some(100), nums.percentile(99.0));
+        assert_eq!(Some(101), nums.percentile(100.0));
+        assert_eq!(Some(15), NumbersSorted(&[10, 20]).percentile(50.0));
+        assert_eq!(None, NumbersSorted(&[]).percentile(50.0));
+    }
 }
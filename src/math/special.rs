@@ -0,0 +1,215 @@
+//! Special functions needed for p-values.
+
+use std::f64::consts::PI;
+
+/// Complementary error function, absolute error below `1.2e-7`.
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * f64::exp(
+        -z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))),
+    );
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+/// Cumulative distribution function of the standard normal distribution.
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / f64::sqrt(2.0))
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+pub fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFS: [f64; 9] = [
+        0.999_999_999_999_81,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        f64::ln(PI / f64::sin(PI * x)) - ln_gamma(1.0 - x)
+    } else {
+        let x = x - 1.0;
+        let mut a = COEFS[0];
+        let t = x + G + 0.5;
+        for (i, c) in COEFS.iter().enumerate().skip(1) {
+            a += c / (x + i as f64);
+        }
+        0.5 * f64::ln(2.0 * PI) + (x + 0.5) * f64::ln(t) - t + f64::ln(a)
+    }
+}
+
+/// Logarithm of the binomial coefficient.
+pub fn ln_choose(n: u64, k: u64) -> f64 {
+    assert!(k <= n);
+    ln_gamma(n as f64 + 1.0) - ln_gamma(k as f64 + 1.0) - ln_gamma((n - k) as f64 + 1.0)
+}
+
+/// Survival function of the chi-squared distribution with one degree of freedom.
+pub fn chi_squared_1_sf(x: f64) -> f64 {
+    erfc(f64::sqrt(x / 2.0))
+}
+
+/// Continued fraction for the incomplete beta function.
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        let aa = m * (b - m) * x / ((a + m2 - 1.0) * (a + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+        let aa = -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`.
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        f64::exp(ln_front) * beta_cf(a, b, x) / a
+    } else {
+        1.0 - f64::exp(ln_front) * beta_cf(b, a, 1.0 - x) / b
+    }
+}
+
+/// Probability that Student's t with `df` degrees of freedom is at least `|t|` in absolute value.
+pub fn student_t_two_sided_p(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// Survival function of the Kolmogorov distribution, `P(K > lambda)`.
+pub fn kolmogorov_sf(lambda: f64) -> f64 {
+    // The series converges slowly for small lambda, where the value is 1 anyway.
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    let mut sign = 1.0;
+    for k in 1..=100 {
+        let k = k as f64;
+        let term = f64::exp(-2.0 * k * k * lambda * lambda);
+        sum += sign * term;
+        if term < 1e-12 {
+            break;
+        }
+        sign = -sign;
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::special::chi_squared_1_sf;
+    use crate::math::special::erfc;
+    use crate::math::special::incomplete_beta;
+    use crate::math::special::kolmogorov_sf;
+    use crate::math::special::ln_choose;
+    use crate::math::special::ln_gamma;
+    use crate::math::special::normal_cdf;
+    use crate::math::special::student_t_two_sided_p;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-6,
+            "expected {}, actual {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_erfc() {
+        assert_close(1.0, erfc(0.0));
+        assert_close(0.157299, erfc(1.0));
+        assert_close(1.842701, erfc(-1.0));
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert_close(0.5, normal_cdf(0.0));
+        assert_close(0.975002, normal_cdf(1.96));
+    }
+
+    #[test]
+    fn test_ln_gamma() {
+        assert_close(0.0, ln_gamma(1.0));
+        assert_close(f64::ln(24.0), ln_gamma(5.0));
+        assert_close(f64::ln(10.0), ln_choose(5, 2));
+    }
+
+    #[test]
+    fn test_incomplete_beta() {
+        assert_close(0.5, incomplete_beta(2.0, 2.0, 0.5));
+        assert_close(0.0523, incomplete_beta(2.0, 3.0, 0.1));
+        assert_close(1.0, incomplete_beta(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_student_t_two_sided_p() {
+        assert_close(1.0, student_t_two_sided_p(0.0, 5.0));
+        // Two-sided 95% critical values.
+        assert_close(0.05, student_t_two_sided_p(2.570582, 5.0));
+        assert_close(0.05, student_t_two_sided_p(2.228139, 10.0));
+    }
+
+    #[test]
+    fn test_chi_squared_1_sf() {
+        assert_close(0.05, chi_squared_1_sf(3.841459));
+    }
+
+    #[test]
+    fn test_kolmogorov_sf() {
+        assert_close(1.0, kolmogorov_sf(0.0));
+        assert_close(0.05, kolmogorov_sf(1.358099));
+        assert_close(0.01, kolmogorov_sf(1.627624));
+    }
+}
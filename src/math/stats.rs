@@ -1,5 +1,6 @@
 use std::fmt;
 use std::fmt::Display;
+use std::str::FromStr;
 
 use crate::experiment_map::ExperimentMap;
 use crate::math::numbers::Numbers;
@@ -12,6 +13,36 @@ pub struct Stats<A> {
     pub max: A,
     pub std: A,
     pub se: A,
+    /// Values at percentiles, like `(90.0, p90)`.
+    pub percentiles: Vec<(f64, A)>,
+}
+
+/// Percentiles printed with statistics, given like `75,90,99`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Percentiles(pub Vec<f64>);
+
+impl Default for Percentiles {
+    fn default() -> Percentiles {
+        Percentiles(vec![75.0, 90.0, 99.0])
+    }
+}
+
+impl FromStr for Percentiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Percentiles> {
+        let mut percentiles = Vec::new();
+        for p in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let p: f64 = p
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid percentile: `{}`", p))?;
+            if !(0.0..=100.0).contains(&p) {
+                return Err(anyhow::anyhow!("percentile {} is not in 0..100", p));
+            }
+            percentiles.push(p);
+        }
+        Ok(Percentiles(percentiles))
+    }
 }
 
 impl<A> Stats<A> {
@@ -24,6 +55,11 @@ impl<A> Stats<A> {
             max: f(self.max),
             std: f(self.std),
             se: f(self.se),
+            percentiles: self
+                .percentiles
+                .into_iter()
+                .map(|(p, v)| (p, f(v)))
+                .collect(),
         }
     }
 }
@@ -85,6 +121,10 @@ impl<A: Display + Copy> Stats<A> {
                 self.append_column("min=", |s| s.min)?;
                 self.append_column("max=", |s| s.max)?;
                 self.append_column("med=", |s| s.med)?;
+                let percentiles = &self.stats.values().next().unwrap().percentiles;
+                for (i, (p, _)) in percentiles.iter().enumerate() {
+                    self.append_column(&format!("p{}=", p), |s| s.percentiles[i].1)?;
+                }
                 Ok(())
             }
         }
@@ -111,12 +151,14 @@ pub(crate) fn stats(numbers: &Numbers) -> Option<Stats<u64>> {
         max: numbers.max()?,
         std,
         se,
+        percentiles: Vec::new(),
     })
 }
 
 #[cfg(test)]
 mod test {
     use crate::math::numbers::Numbers;
+    use crate::math::stats::Percentiles;
     use crate::math::stats::stats;
 
     #[test]
@@ -130,4 +172,15 @@ mod test {
         let stats = stats(&numbers).unwrap();
         assert_eq!(4, stats.se);
     }
+
+    #[test]
+    fn percentiles() {
+        assert_eq!(
+            vec![90.0, 99.9],
+            "90, 99.9".parse::<Percentiles>().unwrap().0
+        );
+        assert!("".parse::<Percentiles>().unwrap().0.is_empty());
+        assert!("90,x".parse::<Percentiles>().is_err());
+        assert!("101".parse::<Percentiles>().is_err());
+    }
 }
@@ -0,0 +1,58 @@
+use crate::math::special::student_t_two_sided_p;
+use crate::math::stats::Stats;
+
+/// Welch's unequal variances t-test for the difference of means.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WelchTest {
+    pub t: f64,
+    /// Welch–Satterthwaite degrees of freedom.
+    pub df: f64,
+    /// Two-sided p-value.
+    pub p: f64,
+}
+
+/// Test B mean is different from A mean, `None` if both samples have no variance.
+pub fn welch_t_test(a: &Stats<u64>, b: &Stats<u64>) -> Option<WelchTest> {
+    // Squared standard error of the mean.
+    let var_mean = |s: &Stats<u64>| s.sigma_sq() / s.count as f64;
+    let (va, vb) = (var_mean(a), var_mean(b));
+    if va + vb == 0.0 {
+        return None;
+    }
+    let t = (b.mean as f64 - a.mean as f64) / f64::sqrt(va + vb);
+    let df =
+        (va + vb).powi(2) / (va.powi(2) / (a.count - 1) as f64 + vb.powi(2) / (b.count - 1) as f64);
+    Some(WelchTest {
+        t,
+        df,
+        p: student_t_two_sided_p(t, df),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::numbers::Numbers;
+    use crate::math::stats::Stats;
+    use crate::math::welch::welch_t_test;
+
+    fn stats(values: &[u64]) -> Stats<u64> {
+        let mut numbers = Numbers::default();
+        for &v in values {
+            numbers.push(v);
+        }
+        numbers.stats().unwrap()
+    }
+
+    #[test]
+    fn welch() {
+        let a = stats(&[1000, 1200, 1400, 1600]);
+        let b = stats(&[2000, 2300, 2600, 2900]);
+        let test = welch_t_test(&a, &b).unwrap();
+        // Exact values are t=4.941, df=5.227, stats are rounded to integers.
+        assert!((test.t - 4.945).abs() < 0.001, "{:?}", test);
+        assert!((test.df - 5.227).abs() < 0.001, "{:?}", test);
+        // Between two-sided 0.005 and 0.002 critical values for 5 degrees of freedom.
+        assert!(test.p > 0.002 && test.p < 0.005, "{:?}", test);
+        assert_eq!(None, welch_t_test(&stats(&[5, 5]), &stats(&[7, 7])));
+    }
+}
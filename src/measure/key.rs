@@ -2,15 +2,36 @@
 pub enum MeasureKey {
     WallTime,
     MaxRss,
+    /// User CPU time of the script and its children.
+    UserTime,
+    /// System CPU time of the script and its children.
+    SysTime,
 }
 
 impl MeasureKey {
-    pub const ALL: &'static [MeasureKey] = &[MeasureKey::WallTime, MeasureKey::MaxRss];
+    pub const ALL: &'static [MeasureKey] = &[
+        MeasureKey::WallTime,
+        MeasureKey::MaxRss,
+        MeasureKey::UserTime,
+        MeasureKey::SysTime,
+    ];
 
     pub fn index(&self) -> usize {
         match self {
             MeasureKey::WallTime => 0,
             MeasureKey::MaxRss => 1,
+            MeasureKey::UserTime => 2,
+            MeasureKey::SysTime => 3,
+        }
+    }
+
+    /// Identifier used in raw data file names.
+    pub fn id(&self) -> &'static str {
+        match self {
+            MeasureKey::WallTime => "wall-time",
+            MeasureKey::MaxRss => "max-rss",
+            MeasureKey::UserTime => "user-time",
+            MeasureKey::SysTime => "sys-time",
         }
     }
 
@@ -18,6 +39,8 @@ impl MeasureKey {
         match index {
             0 => MeasureKey::WallTime,
             1 => MeasureKey::MaxRss,
+            2 => MeasureKey::UserTime,
+            3 => MeasureKey::SysTime,
             _ => panic!("invalid index"),
         }
     }
@@ -1,13 +1,38 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
+use std::fmt::Write as _;
 
+use once_cell::sync::Lazy;
+
+use crate::distr_plot::DistrPlots;
+use crate::distr_plot::PlotMode;
 use crate::distr_plot::make_distr_plots;
 use crate::duration::Duration;
 use crate::experiment::Experiment;
 use crate::experiment_map::ExperimentMap;
+use crate::explain::explain_measure;
+use crate::json_report::JsonMeasure;
+use crate::json_report::json_measure;
+use crate::json_report::samples_csv;
+use crate::markdown_report::markdown_header;
+use crate::markdown_report::markdown_measure;
+use crate::markdown_report::markdown_proportions;
+use crate::markdown_report::markdown_raw_data;
+use crate::math::numbers::Numbers;
+use crate::math::stats::Percentiles;
 use crate::math::stats::Stats;
 use crate::measure::key::MeasureKey;
 use crate::mem_usage::MemUsage;
+use crate::metadata::Metadata;
+use crate::render_stats::PrintoutStats;
+use crate::render_stats::RenderOptions;
+use crate::render_stats::printout_stats;
+use crate::render_stats::render_changes;
+use crate::render_stats::render_proportions;
+use crate::render_stats::render_proportions_summary;
 use crate::render_stats::render_stats;
+use crate::render_stats::render_summary;
 use crate::run_log::RunLog;
 
 pub(crate) trait Measure {
@@ -15,10 +40,20 @@ pub(crate) trait Measure {
 
     fn number_to_display(&self, number: u64) -> Self::NumberDisplay;
 
-    fn key(&self) -> MeasureKey;
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers;
 
     fn name(&self) -> &str;
     fn id(&self) -> &str;
+
+    /// Words describing B being lower or higher than A.
+    fn verdict_words(&self) -> (&'static str, &'static str) {
+        ("lower", "higher")
+    }
+
+    /// Values are booleans, compare proportions rather than means.
+    fn is_proportion(&self, _tests: &ExperimentMap<Experiment>) -> bool {
+        false
+    }
 }
 
 pub struct WallTime;
@@ -31,16 +66,66 @@ impl Measure for WallTime {
         Duration::from_nanos(number)
     }
 
-    fn key(&self) -> MeasureKey {
-        MeasureKey::WallTime
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers {
+        &test.measures[MeasureKey::WallTime]
     }
 
     fn name(&self) -> &str {
         "Time (in seconds)"
     }
 
+    fn verdict_words(&self) -> (&'static str, &'static str) {
+        ("faster", "slower")
+    }
+
     fn id(&self) -> &str {
-        "wall-time"
+        MeasureKey::WallTime.id()
+    }
+}
+
+pub struct UserTime;
+
+impl Measure for UserTime {
+    /// Nanoseconds.
+    type NumberDisplay = Duration;
+
+    fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
+        Duration::from_nanos(number)
+    }
+
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers {
+        &test.measures[MeasureKey::UserTime]
+    }
+
+    fn name(&self) -> &str {
+        "User CPU time (in seconds)"
+    }
+
+    fn id(&self) -> &str {
+        MeasureKey::UserTime.id()
+    }
+}
+
+pub struct SysTime;
+
+impl Measure for SysTime {
+    /// Nanoseconds.
+    type NumberDisplay = Duration;
+
+    fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
+        Duration::from_nanos(number)
+    }
+
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers {
+        &test.measures[MeasureKey::SysTime]
+    }
+
+    fn name(&self) -> &str {
+        "System CPU time (in seconds)"
+    }
+
+    fn id(&self) -> &str {
+        MeasureKey::SysTime.id()
     }
 }
 
@@ -54,8 +139,8 @@ impl Measure for MaxRss {
         MemUsage::from_bytes(number).mib()
     }
 
-    fn key(&self) -> MeasureKey {
-        MeasureKey::MaxRss
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers {
+        &test.measures[MeasureKey::MaxRss]
     }
 
     fn name(&self) -> &str {
@@ -63,23 +148,118 @@ impl Measure for MaxRss {
     }
 
     fn id(&self) -> &str {
-        "max-rss"
+        MeasureKey::MaxRss.id()
+    }
+}
+
+/// Prefix of ids of raw files with run indices of metric samples, followed by metric name.
+pub const METRIC_RUNS_PREFIX: &str = "runs-metric-";
+
+/// Metric reported by the script with `absh-metric: name=value` line.
+pub struct Metric {
+    name: String,
+    id: String,
+    /// Declared with `--proportion`: values are `0` or `1` outcomes.
+    proportion: bool,
+}
+
+impl Metric {
+    pub fn new(name: &str, proportion: bool) -> Metric {
+        Metric {
+            name: format!("Metric {}", name),
+            id: format!("metric-{}", name),
+            proportion,
+        }
+    }
+
+    fn metric_name(&self) -> &str {
+        &self.id["metric-".len()..]
+    }
+}
+
+impl Measure for Metric {
+    type NumberDisplay = u64;
+
+    fn number_to_display(&self, number: u64) -> Self::NumberDisplay {
+        number
+    }
+
+    fn numbers<'a>(&self, test: &'a Experiment) -> &'a Numbers {
+        static EMPTY: Lazy<Numbers> = Lazy::new(Numbers::new);
+        test.metrics.get(self.metric_name()).unwrap_or(&EMPTY)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn is_proportion(&self, _tests: &ExperimentMap<Experiment>) -> bool {
+        self.proportion
     }
 }
 
 pub trait MeasureDyn {
     fn name(&self) -> &str;
+    fn id(&self) -> &str;
+    /// Enough samples in every experiment to compute statistics.
+    fn has_stats(&self, tests: &ExperimentMap<Experiment>) -> bool;
     fn make_distr_plots(
         &self,
         tests: &ExperimentMap<Experiment>,
         width: usize,
-    ) -> anyhow::Result<ExperimentMap<String>>;
-    fn display_stats(&self, tests: &ExperimentMap<Experiment>) -> ExperimentMap<String>;
+        mode: PlotMode,
+        mark: Option<u64>,
+    ) -> anyhow::Result<DistrPlots>;
+    fn display_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        percentiles: &Percentiles,
+    ) -> ExperimentMap<String>;
+    /// Number in measure units for humans, like seconds for wall time.
+    fn display_number(&self, number: u64) -> String;
+    /// Signed difference of two numbers.
+    fn display_delta(&self, delta: i64) -> String;
     fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String>;
+    fn render_summary(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String>;
+    fn json(&self, tests: &ExperimentMap<Experiment>, opts: &RenderOptions) -> JsonMeasure;
+    fn render_markdown(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String>;
+    /// Plain-English explanation of comparisons, empty for proportions.
+    fn render_explain(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String>;
+    /// Statistics to compare the next printout with, `None` for proportions.
+    fn printout_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> Option<PrintoutStats>;
+    /// Changes of means and ratios since `prev`.
+    fn render_changes(
+        &self,
+        prev: &PrintoutStats,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
     ) -> anyhow::Result<String>;
+    /// Samples without statistics.
+    fn render_raw(&self, tests: &ExperimentMap<Experiment>) -> anyhow::Result<String>;
     fn write_raw(&self, tests: &ExperimentMap<Experiment>, log: &mut RunLog) -> anyhow::Result<()>;
 }
 
@@ -88,38 +268,159 @@ impl<M: Measure> MeasureDyn for M {
         self.name()
     }
 
+    fn id(&self) -> &str {
+        Measure::id(self)
+    }
+
+    fn has_stats(&self, tests: &ExperimentMap<Experiment>) -> bool {
+        tests.values().all(|t| self.numbers(t).len() >= 2)
+    }
+
     fn make_distr_plots(
         &self,
         tests: &ExperimentMap<Experiment>,
         width: usize,
-    ) -> anyhow::Result<ExperimentMap<String>> {
-        make_distr_plots(tests, width, |t| &t.measures[self.key()])
+        mode: PlotMode,
+        mark: Option<u64>,
+    ) -> anyhow::Result<DistrPlots> {
+        make_distr_plots(tests, width, mode, mark, |t| self.numbers(t))
     }
 
-    fn display_stats(&self, tests: &ExperimentMap<Experiment>) -> ExperimentMap<String> {
+    fn display_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        percentiles: &Percentiles,
+    ) -> ExperimentMap<String> {
         let stats: ExperimentMap<_> = tests.map(|t| {
-            t.measures[self.key()]
-                .stats()
+            self.numbers(t)
+                .stats_with_percentiles(percentiles)
                 .unwrap()
                 .map(|n| self.number_to_display(n))
         });
         Stats::display_stats_new(&stats)
     }
 
+    fn display_number(&self, number: u64) -> String {
+        self.number_to_display(number).to_string()
+    }
+
+    fn display_delta(&self, delta: i64) -> String {
+        let sign = if delta < 0 { '-' } else { '+' };
+        format!("{}{}", sign, self.number_to_display(delta.unsigned_abs()))
+    }
+
     fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        if self.is_proportion(tests) {
+            render_proportions(tests, self, |t| self.numbers(t))
+        } else {
+            render_stats(tests, opts, self, |t| self.numbers(t))
+        }
+    }
+
+    fn render_summary(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
     ) -> anyhow::Result<String> {
-        render_stats(tests, include_distr, self, |t| &t.measures[self.key()])
+        if self.is_proportion(tests) {
+            render_proportions_summary(tests, self, opts.conf, |t| self.numbers(t))
+        } else {
+            render_summary(tests, self, self.verdict_words(), opts, |t| self.numbers(t))
+        }
+    }
+
+    fn json(&self, tests: &ExperimentMap<Experiment>, opts: &RenderOptions) -> JsonMeasure {
+        json_measure(
+            self.id(),
+            self.name(),
+            self.is_proportion(tests),
+            tests,
+            opts,
+            |t| self.numbers(t),
+        )
+    }
+
+    fn render_markdown(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        if self.is_proportion(tests) {
+            markdown_proportions(self.name(), tests, |t| self.numbers(t))
+        } else {
+            markdown_measure(
+                self.name(),
+                self.verdict_words(),
+                tests,
+                opts,
+                |t| self.numbers(t),
+                |n| self.number_to_display(n),
+            )
+        }
+    }
+
+    fn render_explain(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        if self.is_proportion(tests) {
+            return Ok(String::new());
+        }
+        explain_measure(
+            self.name(),
+            self.verdict_words(),
+            tests,
+            opts,
+            |t| self.numbers(t),
+            |n| self.number_to_display(n),
+        )
+    }
+
+    fn printout_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> Option<PrintoutStats> {
+        match self.is_proportion(tests) {
+            true => None,
+            false => Some(printout_stats(tests, opts, |t| self.numbers(t))),
+        }
+    }
+
+    fn render_changes(
+        &self,
+        prev: &PrintoutStats,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        render_changes(prev, tests, self, opts, |t| self.numbers(t))
+    }
+
+    fn render_raw(&self, tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+        let mut r = String::new();
+        writeln!(r, "{}:", self.name())?;
+        for (name, test) in tests.iter() {
+            let values: Vec<String> = self
+                .numbers(test)
+                .iter()
+                .map(|n| self.number_to_display(n).to_string())
+                .collect();
+            writeln!(r, "{}: {}", name.name_colored(), values.join(" "))?;
+        }
+        Ok(r)
     }
 
     fn write_raw(&self, tests: &ExperimentMap<Experiment>, log: &mut RunLog) -> anyhow::Result<()> {
         log.write_raw(
             self.id(),
             &tests
-                .values()
-                .map(|t| &t.measures[self.key()])
+                .iter()
+                .map(|(n, t)| (n, self.numbers(t)))
                 .collect::<Vec<_>>(),
         )
     }
@@ -128,17 +429,126 @@ impl<M: Measure> MeasureDyn for M {
 pub struct AllMeasures(pub Vec<Box<dyn MeasureDyn>>);
 
 impl AllMeasures {
+    /// Measures for given keys followed by metrics reported by scripts,
+    /// with `proportions` naming the metrics declared with `--proportion`.
+    pub fn new(
+        keys: &[MeasureKey],
+        proportions: &[String],
+        tests: &ExperimentMap<Experiment>,
+    ) -> AllMeasures {
+        let mut measures: Vec<Box<dyn MeasureDyn>> = keys
+            .iter()
+            .map(|key| -> Box<dyn MeasureDyn> {
+                match key {
+                    MeasureKey::WallTime => Box::new(WallTime),
+                    MeasureKey::MaxRss => Box::new(MaxRss),
+                    MeasureKey::UserTime => Box::new(UserTime),
+                    MeasureKey::SysTime => Box::new(SysTime),
+                }
+            })
+            .collect();
+        let metric_names: BTreeSet<&String> =
+            tests.values().flat_map(|t| t.metrics.keys()).collect();
+        for name in metric_names {
+            measures.push(Box::new(Metric::new(name, proportions.contains(name))));
+        }
+        AllMeasures(measures)
+    }
+
     pub fn render_stats(
         &self,
         tests: &ExperimentMap<Experiment>,
-        include_distr: bool,
+        opts: &RenderOptions,
     ) -> anyhow::Result<String> {
         let mut s = String::new();
-        for (i, measure) in self.0.iter().enumerate() {
-            if i != 0 {
-                s.push_str("\n");
+        for measure in self.0.iter().filter(|m| m.has_stats(tests)) {
+            if !s.is_empty() {
+                s.push('\n');
             }
-            s.push_str(&measure.render_stats(tests, include_distr)?);
+            s.push_str(&measure.render_stats(tests, opts)?);
+        }
+        Ok(s)
+    }
+
+    pub fn render_summary(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        let mut s = String::new();
+        for measure in self.0.iter().filter(|m| m.has_stats(tests)) {
+            s.push_str(&measure.render_summary(tests, opts)?);
+        }
+        Ok(s)
+    }
+
+    pub fn render_markdown(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        suite: &Metadata,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        let mut s = markdown_header(tests, suite)?;
+        let mut json = Vec::new();
+        for measure in self.0.iter().filter(|m| m.has_stats(tests)) {
+            s.push('\n');
+            s.push_str(&measure.render_markdown(tests, opts)?);
+            json.push(measure.json(tests, opts));
+        }
+        s.push('\n');
+        s.push_str(&markdown_raw_data(&samples_csv(&json))?);
+        Ok(s)
+    }
+
+    pub fn render_explain(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        let mut s = String::new();
+        for measure in self.0.iter().filter(|m| m.has_stats(tests)) {
+            s.push_str(&measure.render_explain(tests, opts)?);
+        }
+        Ok(s)
+    }
+
+    /// Statistics of measures with statistics, by measure id, to pass to `render_changes`
+    /// at the next printout.
+    pub fn printout_stats(
+        &self,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> BTreeMap<String, PrintoutStats> {
+        self.0
+            .iter()
+            .filter(|m| m.has_stats(tests))
+            .filter_map(|m| Some((m.id().to_owned(), m.printout_stats(tests, opts)?)))
+            .collect()
+    }
+
+    /// How statistics moved since the `prev` printout, empty if nothing is comparable.
+    pub fn render_changes(
+        &self,
+        prev: &BTreeMap<String, PrintoutStats>,
+        tests: &ExperimentMap<Experiment>,
+        opts: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        let mut s = String::new();
+        for measure in self.0.iter().filter(|m| m.has_stats(tests)) {
+            if let Some(prev) = prev.get(measure.id()) {
+                s.push_str(&measure.render_changes(prev, tests, opts)?);
+            }
+        }
+        if !s.is_empty() {
+            s.insert_str(0, "\nChanges since previous printout:\n");
+        }
+        Ok(s)
+    }
+
+    pub fn render_raw(&self, tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+        let mut s = String::new();
+        for measure in &self.0 {
+            s.push_str(&measure.render_raw(tests)?);
         }
         Ok(s)
     }
@@ -151,6 +561,15 @@ impl AllMeasures {
         for measure in &self.0 {
             measure.write_raw(tests, log)?;
         }
+        let metrics: BTreeSet<&String> =
+            tests.values().flat_map(|t| t.metric_runs.keys()).collect();
+        for metric in metrics {
+            let rows = tests.iter().map(|(name, t)| {
+                let runs = t.metric_runs.get(metric).map_or(&[][..], |r| &r[..]);
+                (name, runs)
+            });
+            log.write_raw_rows(&format!("{}{}", METRIC_RUNS_PREFIX, metric), rows)?;
+        }
         Ok(())
     }
 }
@@ -2,8 +2,11 @@ use std::fmt;
 use std::iter::Sum;
 use std::ops::Add;
 use std::ops::Sub;
+use std::str::FromStr;
 
-#[derive(Copy, Clone, Default, PartialOrd, Ord, PartialEq, Eq)]
+use anyhow::Context;
+
+#[derive(Copy, Clone, Default, PartialOrd, Ord, PartialEq, Eq, Debug)]
 pub struct MemUsage {
     bytes: u64,
 }
@@ -55,3 +58,44 @@ impl fmt::Display for MemUsage {
         write!(f, "{}", self.bytes)
     }
 }
+
+impl FromStr for MemUsage {
+    type Err = anyhow::Error;
+
+    /// Parse size like `512M`, `1.5GiB` or `4096` (bytes). Units are binary.
+    fn from_str(s: &str) -> anyhow::Result<MemUsage> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("invalid size: {:?}", s))?;
+        let shift = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 0,
+            "K" | "KB" | "KIB" => 10,
+            "M" | "MB" | "MIB" => 20,
+            "G" | "GB" | "GIB" => 30,
+            "T" | "TB" | "TIB" => 40,
+            _ => return Err(anyhow::anyhow!("unknown size unit in {:?}", s)),
+        };
+        Ok(MemUsage::from_bytes(
+            (number * (1u64 << shift) as f64) as u64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mem_usage::MemUsage;
+
+    #[test]
+    fn parse() {
+        assert_eq!(MemUsage::from_bytes(4096), "4096".parse().unwrap());
+        assert_eq!(MemUsage::from_bytes(512 << 20), "512M".parse().unwrap());
+        assert_eq!(MemUsage::from_bytes(3 << 29), "1.5GiB".parse().unwrap());
+        assert_eq!(MemUsage::from_bytes(1 << 10), "1kb".parse().unwrap());
+        assert!("1X".parse::<MemUsage>().is_err());
+    }
+}
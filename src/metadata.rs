@@ -0,0 +1,92 @@
+//! Descriptions, owners and issue links of variants and of the whole benchmark, given in
+//! the config and copied into the run log and reports.
+
+use serde::Serialize;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Metadata {
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    pub links: Vec<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.owner.is_none() && self.links.is_empty()
+    }
+
+    /// Run log lines like `PREFIXdescription: ...`, one `PREFIXlink: ...` per link.
+    pub fn log_lines(&self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(description) = &self.description {
+            // Newlines would end the log line.
+            lines.push(format!(
+                "{}description: {}",
+                prefix,
+                description.replace('\n', " ")
+            ));
+        }
+        if let Some(owner) = &self.owner {
+            lines.push(format!("{}owner: {}", prefix, owner));
+        }
+        for link in &self.links {
+            lines.push(format!("{}link: {}", prefix, link));
+        }
+        lines
+    }
+
+    /// Metadata from run log lines written by [`Metadata::log_lines`].
+    pub fn from_log(log: &str, prefix: &str) -> Metadata {
+        let field = |name: &str| {
+            let prefix = format!("{}{}: ", prefix, name);
+            log.lines()
+                .filter_map(move |l| l.strip_prefix(&prefix).map(str::to_owned))
+                .collect::<Vec<String>>()
+        };
+        Metadata {
+            description: field("description").into_iter().next(),
+            owner: field("owner").into_iter().next(),
+            links: field("link"),
+        }
+    }
+
+    /// Description, owner and links in one line, like
+    /// `LTO build (owner: alice; https://example.com/1)`.
+    pub fn summary(&self) -> String {
+        let mut details: Vec<String> = self.owner.iter().map(|o| format!("owner: {}", o)).collect();
+        details.extend(self.links.iter().cloned());
+        let description = self.description.clone().unwrap_or_default();
+        match (description.is_empty(), details.is_empty()) {
+            (_, true) => description,
+            (true, false) => details.join("; "),
+            (false, false) => format!("{} ({})", description, details.join("; ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::Metadata;
+
+    #[test]
+    fn log_round_trip() {
+        let metadata = Metadata {
+            description: Some("LTO\nbuild".to_owned()),
+            owner: Some("alice".to_owned()),
+            links: vec![
+                "https://example.com/1".to_owned(),
+                "https://example.com/2".to_owned(),
+            ],
+        };
+        let log = metadata.log_lines("B.").join("\n");
+        assert!(log.starts_with("B.description: LTO build\n"), "{}", log);
+        let read = Metadata::from_log(&log, "B.");
+        assert_eq!(Some("LTO build"), read.description.as_deref());
+        assert_eq!(metadata.links, read.links);
+        assert!(Metadata::from_log(&log, "").is_empty());
+        assert_eq!(
+            "LTO build (owner: alice; https://example.com/1; https://example.com/2)",
+            read.summary()
+        );
+    }
+}
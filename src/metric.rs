@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Prefix of lines scripts print to report a metric, like `absh-metric: hit=1`.
+pub const METRIC_PREFIX: &str = "absh-metric:";
+
+/// Prefix of lines scripts print when a phase starts, like `absh-phase: link`.
+pub const PHASE_PREFIX: &str = "absh-phase:";
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Name of the metric reported as a bare value, like `absh-metric: 12345`.
+pub const DEFAULT_METRIC_NAME: &str = "metric";
+
+/// Environment variable with the file scripts can write metrics to with `--custom-metric`.
+pub const METRIC_FILE_ENV: &str = "ABSH_METRIC_FILE";
+
+/// Environment variable with the descriptor scripts write metric and phase lines to
+/// with `--metrics-fd`.
+pub const METRICS_FD_ENV: &str = "ABSH_METRICS_FD";
+
+/// Parse metric like `hit=1` or bare value like `12345`.
+fn parse_metric(s: &str) -> Option<(String, u64)> {
+    let (name, value) = s.split_once('=').unwrap_or((DEFAULT_METRIC_NAME, s));
+    let name = name.trim();
+    if !is_valid_name(name) {
+        return None;
+    }
+    let value = match value.trim() {
+        "true" => 1,
+        "false" => 0,
+        value => value.parse().ok()?,
+    };
+    Some((name.to_owned(), value))
+}
+
+/// Parse metric reported by a script.
+///
+/// Values are non-negative integers, `true` or `false`.
+/// Value without a name is reported as metric `metric`.
+pub fn parse_metric_line(line: &str) -> Option<(String, u64)> {
+    parse_metric(line.trim().strip_prefix(METRIC_PREFIX)?)
+}
+
+/// Parse metrics written by a script to `$ABSH_METRIC_FILE`, one per line,
+/// like `hit=1` or `12345`. Lines which are not metrics are ignored.
+pub fn parse_metric_file(content: &str) -> Vec<(String, u64)> {
+    content.lines().filter_map(parse_metric).collect()
+}
+
+/// Parse phase marker printed by a script.
+pub fn parse_phase_line(line: &str) -> Option<String> {
+    let name = line.trim().strip_prefix(PHASE_PREFIX)?.trim();
+    if !is_valid_name(name) {
+        return None;
+    }
+    Some(name.to_owned())
+}
+
+/// Copy script output to `w` while collecting reported metrics.
+pub fn copy_collecting_metrics(r: impl Read, w: impl Write) -> io::Result<Vec<(String, u64)>> {
+    Ok(copy_collecting_metrics_timed(r, w, Instant::now(), true)?.metrics)
+}
+
+/// Script output summary.
+pub struct CopiedOutput {
+    /// Reported metrics in order.
+    pub metrics: Vec<(String, u64)>,
+    /// When each line was read, relative to start.
+    pub line_times: Vec<Duration>,
+    /// Phase markers and when they were read, relative to start.
+    pub phases: Vec<(String, Duration)>,
+}
+
+/// Copy script output to `w` while collecting reported metrics
+/// and times when lines were read relative to `start`.
+///
+/// Without `protocol`, metric and phase lines are copied as any other output.
+pub fn copy_collecting_metrics_timed(
+    r: impl Read,
+    mut w: impl Write,
+    start: Instant,
+    protocol: bool,
+) -> io::Result<CopiedOutput> {
+    let mut r = BufReader::new(r);
+    let mut metrics = Vec::new();
+    let mut line_times = Vec::new();
+    let mut phases = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if r.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let time = start.elapsed();
+        line_times.push(time);
+        w.write_all(&line)?;
+        if !protocol {
+            continue;
+        }
+        let line = String::from_utf8_lossy(&line);
+        if let Some(metric) = parse_metric_line(&line) {
+            metrics.push(metric);
+        }
+        if let Some(phase) = parse_phase_line(&line) {
+            phases.push((phase, time));
+        }
+    }
+    w.flush()?;
+    Ok(CopiedOutput {
+        metrics,
+        line_times,
+        phases,
+    })
+}
+
+/// Metrics describing when output lines appeared, in microseconds since start:
+/// first line, half and 90% of lines, last line; and the number of lines.
+pub fn line_latency_metrics(line_times: &[Duration]) -> Vec<(String, u64)> {
+    let micros = |i: usize| line_times[i].as_micros() as u64;
+    let n = line_times.len();
+    if n == 0 {
+        return vec![("lines".to_owned(), 0)];
+    }
+    let pct = |p: usize| micros((n * p).div_ceil(100).max(1) - 1);
+    vec![
+        ("lines".to_owned(), n as u64),
+        ("line_first_us".to_owned(), micros(0)),
+        ("line_p50_us".to_owned(), pct(50)),
+        ("line_p90_us".to_owned(), pct(90)),
+        ("line_last_us".to_owned(), micros(n - 1)),
+    ]
+}
+
+/// Duration of each phase in microseconds, as metrics like `phase_link_us`.
+///
+/// A phase lasts from its marker to the next marker or to the script `end`.
+/// Time before the first marker is not attributed to any phase.
+/// Durations of a phase entered several times are summed.
+pub fn phase_metrics(phases: &[(String, Duration)], end: Duration) -> Vec<(String, u64)> {
+    let mut r: Vec<(String, u64)> = Vec::new();
+    for (i, (name, start)) in phases.iter().enumerate() {
+        let next = phases.get(i + 1).map_or(end, |(_, t)| *t);
+        let micros = next.saturating_sub(*start).as_micros() as u64;
+        let name = format!("phase_{}_us", name);
+        match r.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += micros,
+            None => r.push((name, micros)),
+        }
+    }
+    r
+}
+
+/// How to combine values of a metric reported several times in one run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetricAgg {
+    Last,
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Count,
+}
+
+impl MetricAgg {
+    /// Aggregate non-empty list of values.
+    pub fn aggregate(self, values: &[u64]) -> u64 {
+        match self {
+            MetricAgg::Last => *values.last().unwrap(),
+            MetricAgg::Sum => values.iter().sum(),
+            MetricAgg::Mean => values.iter().sum::<u64>() / values.len() as u64,
+            MetricAgg::Min => *values.iter().min().unwrap(),
+            MetricAgg::Max => *values.iter().max().unwrap(),
+            MetricAgg::Count => values.len() as u64,
+        }
+    }
+}
+
+impl FromStr for MetricAgg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<MetricAgg> {
+        Ok(match s {
+            "last" => MetricAgg::Last,
+            "sum" => MetricAgg::Sum,
+            "mean" => MetricAgg::Mean,
+            "min" => MetricAgg::Min,
+            "max" => MetricAgg::Max,
+            "count" => MetricAgg::Count,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown aggregation: `{}`, expecting one of last, sum, mean, min, max, count",
+                    s
+                ));
+            }
+        })
+    }
+}
+
+/// Aggregation for a metric, like `query_ms=max`.
+#[derive(Clone, Debug)]
+pub struct MetricAggSpec {
+    pub name: String,
+    pub agg: MetricAgg,
+}
+
+impl FromStr for MetricAggSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<MetricAggSpec> {
+        let (name, agg) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expecting `name=agg`: `{}`", s))?;
+        Ok(MetricAggSpec {
+            name: name.trim().to_owned(),
+            agg: agg.trim().parse()?,
+        })
+    }
+}
+
+/// Combine values reported in one run into one value per metric.
+///
+/// Metrics without an aggregation in `specs` keep the last reported value.
+pub fn aggregate_metrics(
+    reported: Vec<(String, u64)>,
+    specs: &[MetricAggSpec],
+) -> BTreeMap<String, u64> {
+    let mut values: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for (name, value) in reported {
+        values.entry(name).or_default().push(value);
+    }
+    values
+        .into_iter()
+        .map(|(name, values)| {
+            let agg = specs
+                .iter()
+                .rev()
+                .find(|s| s.name == name)
+                .map_or(MetricAgg::Last, |s| s.agg);
+            let value = agg.aggregate(&values);
+            (name, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use crate::metric::MetricAgg;
+    use crate::metric::MetricAggSpec;
+    use crate::metric::aggregate_metrics;
+    use crate::metric::copy_collecting_metrics;
+    use crate::metric::line_latency_metrics;
+    use crate::metric::parse_metric_file;
+    use crate::metric::parse_metric_line;
+    use crate::metric::parse_phase_line;
+    use crate::metric::phase_metrics;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Some(("hit".to_owned(), 1)),
+            parse_metric_line("absh-metric: hit=1")
+        );
+        assert_eq!(
+            Some(("query_ms".to_owned(), 17)),
+            parse_metric_line("  absh-metric:query_ms = 17 ")
+        );
+        assert_eq!(
+            Some(("ok".to_owned(), 0)),
+            parse_metric_line("absh-metric: ok=false")
+        );
+        assert_eq!(None, parse_metric_line("absh-metric: hit"));
+        assert_eq!(
+            Some(("metric".to_owned(), 12345)),
+            parse_metric_line("absh-metric: 12345")
+        );
+        assert_eq!(None, parse_metric_line("absh-metric: a b=1"));
+        assert_eq!(None, parse_metric_line("absh-metric: hit=-1"));
+        assert_eq!(None, parse_metric_line("metric: hit=1"));
+    }
+
+    #[test]
+    fn parse_file() {
+        assert_eq!(
+            vec![("metric".to_owned(), 10), ("ops".to_owned(), 20)],
+            parse_metric_file("10\nops=20\n\ngarbage\n")
+        );
+    }
+
+    #[test]
+    fn copy() {
+        let mut out = Vec::new();
+        let metrics =
+            copy_collecting_metrics(&b"x\nabsh-metric: hit=1\nabsh-metric: n=3"[..], &mut out)
+                .unwrap();
+        assert_eq!(b"x\nabsh-metric: hit=1\nabsh-metric: n=3", &out[..]);
+        assert_eq!(vec![("hit".to_owned(), 1), ("n".to_owned(), 3)], metrics);
+    }
+
+    #[test]
+    fn line_latency() {
+        let times: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(
+            vec![
+                ("lines".to_owned(), 10),
+                ("line_first_us".to_owned(), 1000),
+                ("line_p50_us".to_owned(), 5000),
+                ("line_p90_us".to_owned(), 9000),
+                ("line_last_us".to_owned(), 10000),
+            ],
+            line_latency_metrics(&times)
+        );
+        assert_eq!(vec![("lines".to_owned(), 0)], line_latency_metrics(&[]));
+    }
+
+    #[test]
+    fn phases() {
+        assert_eq!(
+            Some("link".to_owned()),
+            parse_phase_line("absh-phase: link\n")
+        );
+        assert_eq!(None, parse_phase_line("absh-phase: a b"));
+        let ms = Duration::from_millis;
+        let phases = vec![
+            ("compile".to_owned(), ms(1)),
+            ("link".to_owned(), ms(4)),
+            ("compile".to_owned(), ms(5)),
+        ];
+        assert_eq!(
+            vec![
+                ("phase_compile_us".to_owned(), 6000),
+                ("phase_link_us".to_owned(), 1000),
+            ],
+            phase_metrics(&phases, ms(8))
+        );
+        assert!(phase_metrics(&[], ms(8)).is_empty());
+    }
+
+    #[test]
+    fn aggregate() {
+        let values = [3, 1, 5, 2];
+        assert_eq!(2, MetricAgg::Last.aggregate(&values));
+        assert_eq!(11, MetricAgg::Sum.aggregate(&values));
+        assert_eq!(2, MetricAgg::Mean.aggregate(&values));
+        assert_eq!(1, MetricAgg::Min.aggregate(&values));
+        assert_eq!(5, MetricAgg::Max.aggregate(&values));
+        assert_eq!(4, MetricAgg::Count.aggregate(&values));
+        assert!("median".parse::<MetricAgg>().is_err());
+    }
+
+    #[test]
+    fn aggregate_metrics_by_spec() {
+        let reported = vec![
+            ("q".to_owned(), 10),
+            ("hit".to_owned(), 1),
+            ("q".to_owned(), 30),
+            ("hit".to_owned(), 0),
+        ];
+        let specs = vec!["q=max".parse::<MetricAggSpec>().unwrap()];
+        let expected: BTreeMap<String, u64> = vec![("hit".to_owned(), 0), ("q".to_owned(), 30)]
+            .into_iter()
+            .collect();
+        assert_eq!(expected, aggregate_metrics(reported, &specs));
+        assert!("q".parse::<MetricAggSpec>().is_err());
+    }
+}
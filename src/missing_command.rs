@@ -0,0 +1,161 @@
+//! Failing early when a script starts with a command which is not installed, rather than
+//! failing every run.
+
+use std::env;
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::Context;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::sh::Shell;
+
+/// Name of the command the first line of `script` runs, `None` if it is not a plain name,
+/// like a path or a name with expansions.
+pub fn first_command(script: &str) -> Option<&str> {
+    let line = script
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'))?;
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || "-_.+".contains(c);
+    for word in line.split_whitespace() {
+        let is_assignment = match word.split_once('=') {
+            Some((name, _)) => {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            None => false,
+        };
+        if is_assignment {
+            continue;
+        }
+        let end = word.find(|c| !is_name_char(c)).unwrap_or(word.len());
+        let name = &word[..end];
+        let ends_command = word[end..]
+            .chars()
+            .next()
+            .is_none_or(|c| ";&|)<>".contains(c));
+        return match !name.is_empty() && !name.starts_with('-') && ends_command {
+            true => Some(name),
+            false => None,
+        };
+    }
+    None
+}
+
+/// Whether `dir` contains executable file `name`.
+#[cfg(unix)]
+fn is_executable(dir: &Path, name: &str) -> bool {
+    fs::metadata(dir.join(name))
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Whether `dir` contains executable file `name`, which may omit the `.exe` extension.
+#[cfg(windows)]
+fn is_executable(dir: &Path, name: &str) -> bool {
+    [dir.join(name), dir.join(format!("{}.exe", name))]
+        .iter()
+        .any(|path| path.is_file())
+}
+
+/// Whether `name` is a command the shell can run, with the environment of the variant.
+///
+/// Always true for shells not understanding POSIX `command -v`.
+pub fn command_exists(
+    shell: &Shell,
+    name: &str,
+    dir: Option<&Path>,
+    env: &[(String, String)],
+) -> anyhow::Result<bool> {
+    match shell {
+        Shell::Path(path) if shell.is_posix() => {
+            let mut command = std::process::Command::new(path);
+            command
+                .args(["-c", "command -v \"$1\"", "sh", name])
+                .envs(env.iter().cloned())
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            if let Some(dir) = dir {
+                command.current_dir(dir);
+            }
+            let status = command
+                .status()
+                .with_context(|| format!("running shell {} to find commands", path))?;
+            Ok(status.success())
+        }
+        Shell::Path(_) => Ok(true),
+        Shell::Exec => {
+            let path = match env.iter().rev().find(|(k, _)| k == "PATH") {
+                Some((_, path)) => path.into(),
+                None => env::var_os("PATH").unwrap_or_default(),
+            };
+            Ok(env::split_paths(&path).any(|dir| is_executable(&dir, name)))
+        }
+    }
+}
+
+/// Fail if a script of a variant starts with a command which cannot be found.
+pub fn check_commands(
+    shell: &Shell,
+    experiments: &ExperimentMap<Experiment>,
+) -> anyhow::Result<()> {
+    for (name, test) in experiments.iter() {
+        let scripts = [
+            ("setup", &test.setup),
+            ("condition", &test.when),
+            ("warmup", &test.warmup),
+            ("script", &test.run),
+            ("teardown", &test.teardown),
+        ];
+        for (what, script) in scripts.iter() {
+            let command = match first_command(script) {
+                Some(command) => command,
+                None => continue,
+            };
+            if !command_exists(shell, command, test.dir.as_deref(), &test.env)? {
+                return Err(anyhow::anyhow!(
+                    "command `{}` not found for variant {} {}",
+                    command,
+                    name,
+                    what
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::missing_command::command_exists;
+    use crate::missing_command::first_command;
+    use crate::sh::Shell;
+
+    #[test]
+    fn first() {
+        assert_eq!(Some("make"), first_command("\n# build\n  make -j8\nls"));
+        assert_eq!(Some("cargo"), first_command("RUSTFLAGS=-g cargo build"));
+        assert_eq!(Some("true"), first_command("true; sleep 1"));
+        assert_eq!(Some("python3.11"), first_command("python3.11 x.py"));
+        assert_eq!(None, first_command("./target/release/x"));
+        assert_eq!(None, first_command("$CC main.c"));
+        assert_eq!(None, first_command("\"my tool\" x"));
+        assert_eq!(None, first_command("{ echo; }"));
+        assert_eq!(None, first_command(""));
+    }
+
+    #[test]
+    fn exists() {
+        for shell in [Shell::default(), Shell::Exec].iter() {
+            assert!(command_exists(shell, "true", None, &[]).unwrap());
+            assert!(!command_exists(shell, "absh-no-such-command", None, &[]).unwrap());
+        }
+        assert!(command_exists(&Shell::default(), "cd", None, &[]).unwrap());
+    }
+}
@@ -0,0 +1,191 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::hint::black_box;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::process;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::thread;
+
+use anyhow::Context;
+
+/// Name of the hidden subcommand executed in the noise process.
+pub const NOISE_SUBCOMMAND: &str = "internal-noise";
+
+const READY: &str = "ready";
+
+/// Background load generated during all runs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NoiseSpec {
+    /// Busy loop in this many threads.
+    Cpu(u32),
+    /// Write and sync a temporary file.
+    Io,
+    /// Send data over a loopback TCP connection.
+    Net,
+}
+
+impl FromStr for NoiseSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<NoiseSpec> {
+        match s {
+            "io" => Ok(NoiseSpec::Io),
+            "net" => Ok(NoiseSpec::Net),
+            s => match s.strip_prefix("cpu:") {
+                Some(n) => match n.parse() {
+                    Ok(n) if n > 0 => Ok(NoiseSpec::Cpu(n)),
+                    _ => Err(anyhow::anyhow!("expecting positive thread count: `{}`", s)),
+                },
+                None => Err(anyhow::anyhow!(
+                    "unknown noise: `{}`, expecting `cpu:N`, `io` or `net`",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for NoiseSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoiseSpec::Cpu(n) => write!(f, "cpu:{}", n),
+            NoiseSpec::Io => write!(f, "io"),
+            NoiseSpec::Net => write!(f, "net"),
+        }
+    }
+}
+
+/// Helper process generating noise while it is alive.
+///
+/// The helper exits when its stdin is closed, so it does not outlive absh
+/// even if absh is killed.
+pub struct Noise {
+    child: Child,
+}
+
+impl Noise {
+    pub fn spawn(spec: NoiseSpec) -> anyhow::Result<Noise> {
+        let mut child = Command::new(env::current_exe()?)
+            .arg(NOISE_SUBCOMMAND)
+            .arg(spec.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("spawning noise process")?;
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line)?;
+        if line.trim() != READY {
+            let _ = child.kill();
+            let status = child.wait()?;
+            return Err(anyhow::anyhow!(
+                "noise process {} failed to start: {}",
+                spec,
+                status
+            ));
+        }
+        Ok(Noise { child })
+    }
+}
+
+impl Drop for Noise {
+    fn drop(&mut self) {
+        // Closing stdin tells the helper to exit, kill it in case it is stuck.
+        drop(self.child.stdin.take());
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn cpu_noise() {
+    let mut x = 0u64;
+    loop {
+        x = black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+    }
+}
+
+fn io_noise(mut file: File) -> io::Result<()> {
+    let buf = vec![0x5a; 1 << 20];
+    loop {
+        for _ in 0..16 {
+            file.write_all(&buf)?;
+        }
+        file.sync_all()?;
+        file.set_len(0)?;
+        file.rewind()?;
+    }
+}
+
+fn net_noise(listener: TcpListener) -> io::Result<()> {
+    let mut client = TcpStream::connect(listener.local_addr()?)?;
+    let (mut server, _) = listener.accept()?;
+    thread::spawn(move || {
+        let mut buf = vec![0; 1 << 16];
+        while let Ok(n) = server.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    });
+    let buf = vec![0x5a; 1 << 16];
+    loop {
+        client.write_all(&buf)?;
+    }
+}
+
+/// Body of the noise process: generate noise until stdin is closed.
+pub fn make_noise(spec: NoiseSpec) -> anyhow::Result<()> {
+    match spec {
+        NoiseSpec::Cpu(n) => {
+            for _ in 0..n {
+                thread::spawn(cpu_noise);
+            }
+        }
+        NoiseSpec::Io => {
+            let path = env::temp_dir().join(format!("absh-noise-{}", process::id()));
+            let file =
+                File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+            // Open file keeps the data, and nothing is left behind even if the process is killed.
+            fs::remove_file(&path)?;
+            thread::spawn(move || io_noise(file));
+        }
+        NoiseSpec::Net => {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            thread::spawn(move || net_noise(listener));
+        }
+    }
+    println!("{}", READY);
+    io::stdout().flush()?;
+
+    let mut buf = [0; 1];
+    while io::stdin().read(&mut buf)? != 0 {}
+    // Noise threads never finish, exit without joining them.
+    process::exit(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noise::NoiseSpec;
+
+    #[test]
+    fn parse() {
+        assert_eq!(NoiseSpec::Cpu(4), "cpu:4".parse().unwrap());
+        assert_eq!(NoiseSpec::Io, "io".parse().unwrap());
+        assert_eq!(NoiseSpec::Net, "net".parse().unwrap());
+        assert!("cpu:0".parse::<NoiseSpec>().is_err());
+        assert!("cpu".parse::<NoiseSpec>().is_err());
+        assert!("disk".parse::<NoiseSpec>().is_err());
+        assert_eq!("cpu:2", NoiseSpec::Cpu(2).to_string());
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::ExitStatus;
+
+use crate::cgroup::own_cgroup;
+
+const SIGKILL: i32 = 9;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OomKill {
+    /// Kernel reported OOM kill in our cgroup or in the kernel log.
+    Confirmed,
+    /// Killed by `SIGKILL`, but no OOM kill evidence found.
+    Suspected,
+}
+
+impl OomKill {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            OomKill::Confirmed => "killed by the OOM killer",
+            OomKill::Suspected => "killed by SIGKILL, possibly out of memory",
+        }
+    }
+}
+
+/// Cgroup v2 `memory.events` file of the current process.
+fn memory_events_path() -> Option<PathBuf> {
+    let path = own_cgroup().ok()?.join("memory.events");
+    if path.exists() { Some(path) } else { None }
+}
+
+fn read_oom_kills(path: &PathBuf) -> Option<u64> {
+    let events = fs::read_to_string(path).ok()?;
+    events
+        .lines()
+        .find_map(|l| l.strip_prefix("oom_kill "))
+        .and_then(|n| n.trim().parse().ok())
+}
+
+/// Seconds since boot, the clock of kernel log timestamps.
+#[cfg(unix)]
+fn secs_since_boot() -> f64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    time.tv_sec as f64 + time.tv_nsec as f64 / 1e9
+}
+
+#[cfg(not(unix))]
+fn secs_since_boot() -> f64 {
+    0.0
+}
+
+/// Time in seconds since boot and pid of an OOM kill in a kernel log line like
+/// `[ 5678.123456] Out of memory: Killed process 4321 (python3) total-vm:...`
+/// or `[ 5678.123400] oom-kill:constraint=CONSTRAINT_NONE,...,task=python3,pid=4321,uid=1000`.
+fn parse_oom_kill(line: &str) -> Option<(f64, u32)> {
+    let (time, message) = line.strip_prefix('[')?.split_once(']')?;
+    let time = time.trim().parse().ok()?;
+    let pid = if let Some((_, killed)) = message.split_once("Killed process ") {
+        killed.split(' ').next()?
+    } else {
+        let (_, fields) = message.split_once("oom-kill:")?;
+        fields.split(',').find_map(|f| f.strip_prefix("pid="))?
+    };
+    Some((time, pid.trim().parse().ok()?))
+}
+
+/// Kernel log mentions OOM kill of a process since `since` seconds after boot.
+/// Usually requires privileges.
+fn dmesg_mentions_oom_kill(pid: u32, since: f64) -> bool {
+    let output = match Command::new("dmesg").output() {
+        Ok(output) if output.status.success() => output.stdout,
+        _ => return false,
+    };
+    String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(parse_oom_kill)
+        .any(|(time, killed)| killed == pid && time >= since)
+}
+
+#[cfg(unix)]
+fn killed_by_signal(status: ExitStatus, signal: i32) -> bool {
+    status.signal() == Some(signal)
+}
+
+#[cfg(not(unix))]
+fn killed_by_signal(_status: ExitStatus, _signal: i32) -> bool {
+    false
+}
+
+/// Snapshot of OOM kill counters taken before running a process.
+pub struct OomWatch {
+    events_path: Option<PathBuf>,
+    oom_kills_before: Option<u64>,
+    /// Seconds since boot when the process was started.
+    started: f64,
+}
+
+impl OomWatch {
+    pub fn start() -> OomWatch {
+        let events_path = memory_events_path();
+        let oom_kills_before = events_path.as_ref().and_then(read_oom_kills);
+        OomWatch {
+            events_path,
+            oom_kills_before,
+            started: secs_since_boot(),
+        }
+    }
+
+    /// Check whether the process which exited with `status` was likely OOM-killed.
+    pub fn check(&self, pid: u32, status: ExitStatus) -> Option<OomKill> {
+        // `sh` exits with `128 + signal` when a command it runs is killed.
+        let killed = killed_by_signal(status, SIGKILL) || status.code() == Some(128 + SIGKILL);
+        if !killed {
+            return None;
+        }
+        let oom_kills_after = self.events_path.as_ref().and_then(read_oom_kills);
+        match (self.oom_kills_before, oom_kills_after) {
+            (Some(before), Some(after)) if after > before => return Some(OomKill::Confirmed),
+            _ => {}
+        }
+        if dmesg_mentions_oom_kill(pid, self.started) {
+            return Some(OomKill::Confirmed);
+        }
+        Some(OomKill::Suspected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oom::parse_oom_kill;
+
+    #[test]
+    fn oom_kill_lines() {
+        assert_eq!(
+            Some((5678.123456, 4321)),
+            parse_oom_kill(
+                "[ 5678.123456] Out of memory: Killed process 4321 (python3) \
+                 total-vm:16493140kB, anon-rss:15861432kB, file-rss:1476kB, shmem-rss:0kB, \
+                 UID:1000 pgtables:31128kB oom_score_adj:0"
+            )
+        );
+        assert_eq!(
+            Some((812.5, 77)),
+            parse_oom_kill(
+                "[  812.500000] Memory cgroup out of memory: Killed process 77 (stress) \
+                 total-vm:1054176kB, anon-rss:1048712kB, file-rss:1280kB, shmem-rss:0kB, \
+                 UID:0 pgtables:2100kB oom_score_adj:0"
+            )
+        );
+        assert_eq!(
+            Some((5678.1234, 4321)),
+            parse_oom_kill(
+                "[ 5678.123400] oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,\
+                 mems_allowed=0,global_oom,task_memcg=/user.slice/user-1000.slice/session-12.scope,\
+                 task=python3,pid=4321,uid=1000"
+            )
+        );
+    }
+
+    #[test]
+    fn other_lines() {
+        // Pids and numbers which merely contain the pid.
+        assert_eq!(
+            None,
+            parse_oom_kill("[   12.345678] systemd[1]: Started Journal Service (pid 12).")
+        );
+        assert_eq!(
+            None,
+            parse_oom_kill(
+                "[ 5678.123390] python3 invoked oom-killer: gfp_mask=0x140dca, order=0, \
+                 oom_score_adj=0"
+            )
+        );
+        // No timestamp, so the time of the kill is unknown.
+        assert_eq!(
+            None,
+            parse_oom_kill("Out of memory: Killed process 4321 (python3) total-vm:16493140kB")
+        );
+    }
+}
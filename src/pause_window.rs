@@ -0,0 +1,136 @@
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(windows)]
+use windows_sys::Win32::Foundation::SYSTEMTIME;
+#[cfg(windows)]
+use windows_sys::Win32::System::SystemInformation::GetLocalTime;
+
+const SECS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Daily local time window like `02:00-03:00` during which nothing is measured.
+///
+/// Window may cross midnight, like `23:30-01:00`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PauseWindow {
+    /// Seconds since local midnight.
+    start: u32,
+    end: u32,
+}
+
+fn parse_time_of_day(s: &str) -> anyhow::Result<u32> {
+    let err = || anyhow::anyhow!("expecting `HH:MM`: `{}`", s);
+    let (h, m) = s.split_once(':').ok_or_else(err)?;
+    let h: u32 = h.parse().map_err(|_| err())?;
+    let m: u32 = m.parse().map_err(|_| err())?;
+    if h >= 24 || m >= 60 {
+        return Err(err());
+    }
+    Ok((h * 60 + m) * 60)
+}
+
+impl PauseWindow {
+    /// Whether the window includes this many seconds since local midnight.
+    pub fn contains(&self, secs_of_day: u32) -> bool {
+        if self.start < self.end {
+            self.start <= secs_of_day && secs_of_day < self.end
+        } else {
+            secs_of_day >= self.start || secs_of_day < self.end
+        }
+    }
+
+    /// Time from `secs_of_day` to the end of the window.
+    pub fn remaining(&self, secs_of_day: u32) -> Duration {
+        Duration::from_secs(((self.end + SECS_PER_DAY - secs_of_day) % SECS_PER_DAY) as u64)
+    }
+}
+
+impl FromStr for PauseWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<PauseWindow> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("expecting `HH:MM-HH:MM`: `{}`", s))?;
+        let window = PauseWindow {
+            start: parse_time_of_day(start)?,
+            end: parse_time_of_day(end)?,
+        };
+        if window.start == window.end {
+            return Err(anyhow::anyhow!("empty pause window: `{}`", s));
+        }
+        Ok(window)
+    }
+}
+
+impl fmt::Display for PauseWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hm = |s: u32| format!("{:02}:{:02}", s / 3600, s / 60 % 60);
+        write!(f, "{}-{}", hm(self.start), hm(self.end))
+    }
+}
+
+/// Seconds since local midnight now.
+#[cfg(unix)]
+pub fn local_secs_of_day() -> u32 {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = MaybeUninit::<libc::tm>::zeroed();
+        if libc::localtime_r(&now, tm.as_mut_ptr()).is_null() {
+            // Fall back to UTC.
+            return (now as u64 % SECS_PER_DAY as u64) as u32;
+        }
+        let tm = tm.assume_init();
+        (tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec) as u32
+    }
+}
+
+/// Seconds since local midnight now.
+#[cfg(windows)]
+pub fn local_secs_of_day() -> u32 {
+    let mut time = MaybeUninit::<SYSTEMTIME>::zeroed();
+    unsafe { GetLocalTime(time.as_mut_ptr()) };
+    let time = unsafe { time.assume_init() };
+    time.wHour as u32 * 3600 + time.wMinute as u32 * 60 + time.wSecond as u32
+}
+
+/// Window which includes `secs_of_day`, if any.
+pub fn active_window(windows: &[PauseWindow], secs_of_day: u32) -> Option<PauseWindow> {
+    windows.iter().copied().find(|w| w.contains(secs_of_day))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::pause_window::PauseWindow;
+
+    #[test]
+    fn parse() {
+        let w: PauseWindow = "02:00-03:30".parse().unwrap();
+        assert_eq!("02:00-03:30", w.to_string());
+        assert!("02:00".parse::<PauseWindow>().is_err());
+        assert!("02:00-24:00".parse::<PauseWindow>().is_err());
+        assert!("02:60-03:00".parse::<PauseWindow>().is_err());
+        assert!("02:00-02:00".parse::<PauseWindow>().is_err());
+    }
+
+    #[test]
+    fn contains() {
+        let h = |h: u32| h * 3600;
+        let w: PauseWindow = "02:00-03:00".parse().unwrap();
+        assert!(!w.contains(h(1)));
+        assert!(w.contains(h(2)));
+        assert!(!w.contains(h(3)));
+        assert_eq!(Duration::from_secs(1800), w.remaining(h(2) + 1800));
+
+        let w: PauseWindow = "23:00-01:00".parse().unwrap();
+        assert!(w.contains(h(23) + 1));
+        assert!(w.contains(h(0)));
+        assert!(!w.contains(h(1)));
+        assert!(!w.contains(h(12)));
+        assert_eq!(Duration::from_secs(7200), w.remaining(h(23)));
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Fraction given as `2%` or `0.02`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Percent(pub f64);
+
+impl FromStr for Percent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Percent> {
+        let s = s.trim();
+        let fraction = match s.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f64>()? / 100.0,
+            None => s.parse()?,
+        };
+        if !(fraction > 0.0 && fraction < 1.0) {
+            return Err(anyhow::anyhow!(
+                "expecting value between 0% and 100%: `{}`",
+                s
+            ));
+        }
+        Ok(Percent(fraction))
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0 * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::percent::Percent;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Percent(0.02), "2%".parse().unwrap());
+        assert_eq!(Percent(0.5), "0.5".parse().unwrap());
+        assert_eq!("2.5%", "2.5%".parse::<Percent>().unwrap().to_string());
+        assert!("0%".parse::<Percent>().is_err());
+        assert!("150%".parse::<Percent>().is_err());
+        assert!("x".parse::<Percent>().is_err());
+    }
+}
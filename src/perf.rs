@@ -0,0 +1,130 @@
+//! Hardware counters measured with `perf stat` when running with `--perf`.
+
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+use anyhow::Context;
+
+use crate::shell::shell_quote_args;
+
+/// Counted events, reported as metrics with the same names.
+pub const PERF_EVENTS: &[&str] = &["instructions", "cycles", "cache-misses", "branch-misses"];
+
+fn perf_stat_args(output: &Path) -> Vec<String> {
+    vec![
+        "perf".to_owned(),
+        "stat".to_owned(),
+        "-x,".to_owned(),
+        "-e".to_owned(),
+        PERF_EVENTS.join(","),
+        "-o".to_owned(),
+        output.display().to_string(),
+        "--".to_owned(),
+    ]
+}
+
+/// Script running `command` under `perf stat`, which writes counters to `output`.
+pub fn perf_script(command: &[String], output: &Path) -> String {
+    let mut args = perf_stat_args(output);
+    args.extend(command.iter().cloned());
+    shell_quote_args(args)
+}
+
+/// Check that `perf` is installed and allowed to count events.
+pub fn check_perf() -> anyhow::Result<()> {
+    let output = Command::new("perf")
+        .args(perf_stat_args(Path::new("/dev/null")))
+        .arg("true")
+        .stdin(Stdio::null())
+        .output()
+        .context("`--perf` needs `perf` in PATH, usually in `linux-tools` package")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "`perf stat` failed, check `/proc/sys/kernel/perf_event_paranoid`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Event name without PMU and modifiers, like `instructions` for `cpu_core/instructions/u`.
+fn event_name(event: &str) -> &str {
+    let event = match event.split('/').nth(1) {
+        Some(name) if !name.is_empty() => name,
+        _ => event,
+    };
+    event.split(':').next().unwrap()
+}
+
+/// Parse counters written by `perf stat -x,`.
+///
+/// Counts of the same event from several PMUs, like on hybrid CPUs, are summed.
+/// Events which were not counted are omitted.
+pub fn parse_perf_output(content: &str) -> Vec<(String, u64)> {
+    let mut r: Vec<(String, u64)> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        let (value, event) = match fields[..] {
+            [value, _unit, event, ..] => (value, event_name(event)),
+            _ => continue,
+        };
+        let value: u64 = match value.parse() {
+            Ok(value) => value,
+            // `<not counted>` or `<not supported>`.
+            Err(_) => continue,
+        };
+        if !PERF_EVENTS.contains(&event) {
+            continue;
+        }
+        match r.iter_mut().find(|(n, _)| n == event) {
+            Some((_, total)) => *total += value,
+            None => r.push((event.to_owned(), value)),
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::perf::parse_perf_output;
+    use crate::perf::perf_script;
+    use crate::sh::Shell;
+
+    #[test]
+    fn parse() {
+        let output = "\
+# started on Mon Jan  1 00:00:00 2024
+
+1000,,instructions:u,500,100.00,,
+300,,cycles:u,500,100.00,,
+<not supported>,,cache-misses:u,0,100.00,,
+20,,cpu_core/branch-misses/u,500,100.00,,
+5,,cpu_atom/branch-misses/u,500,100.00,,
+";
+        assert_eq!(
+            vec![
+                ("instructions".to_owned(), 1000),
+                ("cycles".to_owned(), 300),
+                ("branch-misses".to_owned(), 25),
+            ],
+            parse_perf_output(output)
+        );
+    }
+
+    #[test]
+    fn script() {
+        assert_eq!(
+            "perf stat -x, -e instructions,cycles,cache-misses,branch-misses -o /tmp/p -- /bin/sh -ec 'make x'",
+            perf_script(
+                &Shell::default().argv("make x").unwrap(),
+                Path::new("/tmp/p")
+            )
+        );
+    }
+}
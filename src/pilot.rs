@@ -0,0 +1,97 @@
+//! Pilot runs with `--pilot`: a few iterations estimate how many are needed for
+//! the ratios to reach `--precision`.
+
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::io;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::input::PinnedInputs;
+use crate::math::sample_size::iterations_for_precision;
+use crate::measure::key::MeasureKey;
+use crate::percent::Percent;
+use crate::run_log::RunLog;
+use crate::runner::Runner;
+use crate::student::ConfInterval;
+
+/// Run `pilot` iterations and return the number of iterations to run in total,
+/// asking the user unless `auto`.
+pub fn run_pilot(
+    log: &mut RunLog,
+    runner: &mut Runner,
+    inputs: &PinnedInputs,
+    pilot: u32,
+    precision: Percent,
+    conf: ConfInterval,
+    auto: bool,
+) -> anyhow::Result<u32> {
+    while runner.min_runs() < pilot as usize {
+        inputs.verify()?;
+        runner.run_iteration(log)?;
+    }
+    let recommended = recommend_iterations(&runner.experiments, precision, conf);
+    writeln!(log.both_log_and_stderr())?;
+    writeln!(
+        log.both_log_and_stderr(),
+        "Pilot: about {} iterations are needed for B/A time within {} at {} confidence",
+        recommended,
+        precision,
+        conf
+    )?;
+    let n = match auto {
+        true => recommended,
+        false => ask_iterations(recommended)?,
+    };
+    writeln!(log.log_only(), "iterations: {}", n)?;
+    Ok(n.max(pilot))
+}
+
+/// Iterations for the wall time ratio of every variant to A to reach `precision` at `conf`.
+pub fn recommend_iterations(
+    experiments: &ExperimentMap<Experiment>,
+    precision: Percent,
+    conf: ConfInterval,
+) -> u32 {
+    let stats: Vec<_> = experiments
+        .values()
+        .map(|t| t.measures[MeasureKey::WallTime].stats().unwrap())
+        .collect();
+    let a = &stats[0];
+    let others = if stats.len() == 1 {
+        &stats[..]
+    } else {
+        &stats[1..]
+    };
+    let n = others
+        .iter()
+        .map(|b| iterations_for_precision(a, b, precision.0, conf))
+        .max()
+        .unwrap();
+    n.try_into().unwrap_or(u32::MAX)
+}
+
+/// Ask user how many iterations to run after the pilot.
+fn ask_iterations(recommended: u32) -> anyhow::Result<u32> {
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(anyhow::anyhow!(
+            "stdin is not a terminal, use `--auto` to continue after the pilot"
+        ));
+    }
+    loop {
+        eprint!(
+            "Continue with {} iterations? [Y/n/number of iterations] ",
+            recommended
+        );
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        match answer.trim() {
+            "" | "y" | "Y" => return Ok(recommended),
+            "n" | "N" => return Err(anyhow::anyhow!("stopped after the pilot")),
+            answer => match answer.parse() {
+                Ok(n) => return Ok(n),
+                Err(_) => eprintln!("expecting y, n or a number"),
+            },
+        }
+    }
+}
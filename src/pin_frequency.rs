@@ -0,0 +1,279 @@
+//! Pinning CPU frequency with `--pin-frequency`: the `performance` governor and no turbo
+//! boost while the benchmark runs, Linux only and needs root.
+
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::fs_util::write_using_temp;
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+/// Directory only writable by root, with the settings to restore in `STATE_FILE`, so
+/// a benchmark which was killed before restoring them is cleaned up by the next one.
+const STATE_DIR: &str = "/run/absh";
+const STATE_FILE: &str = "pinned-frequency";
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_owned())
+}
+
+/// Files under `cpu_dir` which exist, with values pinning the frequency.
+fn pinned_settings(cpu_dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut settings = Vec::new();
+    let mut cpus: Vec<PathBuf> = match fs::read_dir(cpu_dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with("cpu"))
+            .map(|e| e.path().join("cpufreq/scaling_governor"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    cpus.sort();
+    for governor in cpus {
+        settings.push((governor, "performance".to_owned()));
+    }
+    settings.push((cpu_dir.join("intel_pstate/no_turbo"), "1".to_owned()));
+    settings.push((cpu_dir.join("cpufreq/boost"), "0".to_owned()));
+    settings.retain(|(path, _)| path.is_file());
+    settings
+}
+
+/// Fail unless `path` is owned by the current user, is not a symlink and is not
+/// writable by others, so nobody else could have written the settings it holds.
+#[cfg(unix)]
+fn check_owned(path: &Path) -> anyhow::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.file_type().is_symlink() {
+        return Err(anyhow::anyhow!("{} is a symlink", path.display()));
+    }
+    if meta.uid() != unsafe { libc::geteuid() } {
+        return Err(anyhow::anyhow!(
+            "{} is not owned by the current user",
+            path.display()
+        ));
+    }
+    if meta.mode() & 0o022 != 0 {
+        return Err(anyhow::anyhow!(
+            "{} is writable by other users",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_owned(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn create_state_dir(dir: &Path) -> anyhow::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder
+        .create(dir)
+        .with_context(|| format!("creating {}", dir.display()))
+}
+
+fn write_settings(settings: &[(PathBuf, String)]) -> anyhow::Result<()> {
+    for (path, value) in settings {
+        fs::write(path, value)
+            .with_context(|| format!("writing `{}` to {}", value, path.display()))?;
+    }
+    Ok(())
+}
+
+/// Restore settings saved in `state` by a benchmark which did not restore them.
+///
+/// Only files which `--pin-frequency` changes under `cpu_dir` are written.
+fn restore_stale(state: &Path, cpu_dir: &Path) -> anyhow::Result<()> {
+    match fs::symlink_metadata(state) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    check_owned(state)?;
+    let known: Vec<PathBuf> = pinned_settings(cpu_dir)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    let mut saved = Vec::new();
+    for line in fs::read_to_string(state)?.lines() {
+        let (path, value) = line
+            .split_once('\t')
+            .with_context(|| format!("malformed line in {}: {:?}", state.display(), line))?;
+        let path = PathBuf::from(path);
+        if !known.contains(&path) {
+            return Err(anyhow::anyhow!(
+                "refusing to restore {}: not a CPU frequency setting in {}",
+                path.display(),
+                cpu_dir.display()
+            ));
+        }
+        saved.push((path, value.to_owned()));
+    }
+    write_settings(&saved)?;
+    fs::remove_file(state)?;
+    Ok(())
+}
+
+/// CPU frequency settings changed by `--pin-frequency`, restored when dropped.
+pub struct PinnedFrequency {
+    /// Files with their previous values.
+    saved: Vec<(PathBuf, String)>,
+    state: PathBuf,
+}
+
+impl PinnedFrequency {
+    /// Set the `performance` governor on all CPUs and disable turbo boost.
+    pub fn pin() -> anyhow::Result<PinnedFrequency> {
+        if !cfg!(target_os = "linux") {
+            return Err(anyhow::anyhow!(
+                "--pin-frequency is only supported on Linux"
+            ));
+        }
+        let state_dir = Path::new(STATE_DIR);
+        create_state_dir(state_dir)?;
+        PinnedFrequency::pin_at(Path::new(CPU_DIR), &state_dir.join(STATE_FILE))
+    }
+
+    fn pin_at(cpu_dir: &Path, state: &Path) -> anyhow::Result<PinnedFrequency> {
+        if let Some(state_dir) = state.parent() {
+            check_owned(state_dir)?;
+        }
+        restore_stale(state, cpu_dir)
+            .context("restoring CPU frequency settings of a killed benchmark")?;
+        let settings = pinned_settings(cpu_dir);
+        if settings.is_empty() {
+            return Err(anyhow::anyhow!(
+                "--pin-frequency found no CPU frequency settings in {}",
+                cpu_dir.display()
+            ));
+        }
+        let saved: Vec<(PathBuf, String)> = settings
+            .iter()
+            .filter_map(|(path, _)| Some((path.clone(), read_trimmed(path)?)))
+            .collect();
+        let contents: String = saved
+            .iter()
+            .map(|(path, value)| format!("{}\t{}\n", path.display(), value))
+            .collect();
+        write_using_temp(state, contents)?;
+        // Created before writing, so settings written before a failure are restored too.
+        let pinned = PinnedFrequency {
+            saved,
+            state: state.to_owned(),
+        };
+        write_settings(&settings)
+            .context("--pin-frequency needs root to change CPU frequency settings")?;
+        Ok(pinned)
+    }
+
+    /// Settings and their values while pinned, for the run log.
+    pub fn describe(&self) -> Vec<String> {
+        self.saved
+            .iter()
+            .map(|(path, old)| {
+                let new = read_trimmed(path).unwrap_or_default();
+                format!("{}: {} (was {})", path.display(), new, old)
+            })
+            .collect()
+    }
+}
+
+impl Drop for PinnedFrequency {
+    fn drop(&mut self) {
+        match write_settings(&self.saved) {
+            Ok(()) => {
+                let _ = fs::remove_file(&self.state);
+            }
+            Err(e) => eprintln!(
+                "restoring CPU frequency settings failed, saved in {}: {:#}",
+                self.state.display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::pin_frequency::PinnedFrequency;
+
+    #[test]
+    fn pin_and_restore() {
+        let dir = env::temp_dir().join(format!("absh-pin-freq-test-{}", std::process::id()));
+        let cpu_dir = dir.join("cpu");
+        let state = dir.join("state");
+        for path in ["cpu0/cpufreq", "cpu1/cpufreq", "intel_pstate"].iter() {
+            fs::create_dir_all(cpu_dir.join(path)).unwrap();
+        }
+        let governor = |cpu: &str| cpu_dir.join(cpu).join("cpufreq/scaling_governor");
+        let no_turbo = cpu_dir.join("intel_pstate/no_turbo");
+        fs::write(governor("cpu0"), "powersave\n").unwrap();
+        fs::write(governor("cpu1"), "schedutil\n").unwrap();
+        fs::write(&no_turbo, "0\n").unwrap();
+        let read = |path| fs::read_to_string(path).unwrap().trim().to_owned();
+
+        let pinned = PinnedFrequency::pin_at(&cpu_dir, &state).unwrap();
+        assert_eq!("performance", read(governor("cpu1")));
+        assert_eq!("1", read(no_turbo.clone()));
+        assert!(pinned.describe()[0].ends_with("scaling_governor: performance (was powersave)"));
+        // As if absh was killed: the next benchmark restores the settings first.
+        std::mem::forget(pinned);
+        fs::write(governor("cpu0"), "ondemand\n").unwrap();
+        let pinned = PinnedFrequency::pin_at(&cpu_dir, &state).unwrap();
+        assert!(pinned.describe()[0].ends_with("(was powersave)"));
+        drop(pinned);
+        assert_eq!("powersave", read(governor("cpu0")));
+        assert_eq!("schedutil", read(governor("cpu1")));
+        assert_eq!("0", read(no_turbo.clone()));
+        assert!(!state.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn planted_state_is_rejected() {
+        let dir = env::temp_dir().join(format!("absh-pin-freq-planted-{}", std::process::id()));
+        let cpu_dir = dir.join("cpu");
+        let state = dir.join("state");
+        fs::create_dir_all(cpu_dir.join("cpu0/cpufreq")).unwrap();
+        let governor = cpu_dir.join("cpu0/cpufreq/scaling_governor");
+        fs::write(&governor, "powersave\n").unwrap();
+        let victim = dir.join("victim");
+        fs::write(&victim, "secret\n").unwrap();
+
+        fs::write(&state, format!("{}\tpwned\n", victim.display())).unwrap();
+        assert!(PinnedFrequency::pin_at(&cpu_dir, &state).is_err());
+        // A file under the cpu dir which is not a frequency setting.
+        let other = cpu_dir.join("cpu0/online");
+        fs::write(&other, "1\n").unwrap();
+        fs::write(&state, format!("{}\t0\n", other.display())).unwrap();
+        assert!(PinnedFrequency::pin_at(&cpu_dir, &state).is_err());
+        assert_eq!("secret\n", fs::read_to_string(&victim).unwrap());
+        assert_eq!("1\n", fs::read_to_string(&other).unwrap());
+        assert_eq!("powersave\n", fs::read_to_string(&governor).unwrap());
+
+        #[cfg(unix)]
+        {
+            let target = dir.join("target");
+            fs::write(&target, format!("{}\tperformance\n", governor.display())).unwrap();
+            fs::remove_file(&state).unwrap();
+            std::os::unix::fs::symlink(&target, &state).unwrap();
+            assert!(PinnedFrequency::pin_at(&cpu_dir, &state).is_err());
+            assert_eq!("powersave\n", fs::read_to_string(&governor).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! Reading files into the page cache before measured runs with `--prime-files`.
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+
+use crate::experiment_name::ExperimentName;
+use crate::sh::sh_command;
+
+/// Files declared with `--prime-files [VARIANT:]GLOB`.
+#[derive(Clone, Debug)]
+pub struct PrimeFiles {
+    /// Variant priming the files, all variants if `None`.
+    pub variant: Option<String>,
+    /// Shell glob, like `data/*.bin`.
+    pub glob: String,
+}
+
+impl PrimeFiles {
+    pub fn used_by(&self, name: ExperimentName) -> bool {
+        match &self.variant {
+            Some(variant) => name.is_named(variant),
+            None => true,
+        }
+    }
+}
+
+impl FromStr for PrimeFiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<PrimeFiles> {
+        // Prefix is a variant only if it is a valid name, so paths with `:` still work.
+        let (variant, glob) = match s.split_once(':') {
+            Some((variant, glob)) if ExperimentName::validate(variant).is_ok() => {
+                (Some(variant.to_owned()), glob)
+            }
+            _ => (None, s),
+        };
+        if glob.is_empty() {
+            return Err(anyhow::anyhow!("expecting `[VARIANT:]GLOB`: `{}`", s));
+        }
+        Ok(PrimeFiles {
+            variant,
+            glob: glob.to_owned(),
+        })
+    }
+}
+
+/// Regular files matching shell glob.
+pub fn expand_glob(glob: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let script = format!(
+        r#"for f in {}; do if [ -f "$f" ]; then printf '%s\n' "$f"; fi; done"#,
+        glob
+    );
+    let output = sh_command(&script)
+        .output()
+        .with_context(|| format!("expanding {}", glob))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "expanding {}: {}",
+            glob,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Read files to the end, so they are in the page cache. Returns the number of bytes read.
+pub fn prime_files(paths: &[PathBuf]) -> anyhow::Result<u64> {
+    let mut total = 0;
+    for path in paths {
+        let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        total += io::copy(&mut file, &mut io::sink())
+            .with_context(|| format!("reading {}", path.display()))?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::experiment_name::ExperimentName;
+    use crate::prime::PrimeFiles;
+    use crate::prime::expand_glob;
+    use crate::prime::prime_files;
+
+    #[test]
+    fn parse() {
+        let spec: PrimeFiles = "data/*.bin".parse().unwrap();
+        assert_eq!(None, spec.variant);
+        assert_eq!("data/*.bin", spec.glob);
+        let spec: PrimeFiles = "b:data/*.bin".parse().unwrap();
+        assert_eq!("data/*.bin", spec.glob);
+        assert!(spec.used_by(ExperimentName::letter(1)));
+        assert!(!spec.used_by(ExperimentName::letter(0)));
+        let spec: PrimeFiles = "/mnt/a:b/*".parse().unwrap();
+        assert_eq!(None, spec.variant);
+        assert!("".parse::<PrimeFiles>().is_err());
+    }
+
+    #[test]
+    fn expand_and_prime() {
+        let dir = env::temp_dir().join(format!("absh-prime-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("x.bin"), "abc").unwrap();
+        fs::write(dir.join("y.bin"), "de").unwrap();
+        fs::write(dir.join("z.txt"), "f").unwrap();
+        let paths = expand_glob(&format!("{}/*.bin {}/sub", dir.display(), dir.display())).unwrap();
+        assert_eq!(vec![dir.join("x.bin"), dir.join("y.bin")], paths);
+        assert_eq!(5, prime_files(&paths).unwrap());
+        let none = expand_glob(&format!("{}/*.none", dir.display())).unwrap();
+        assert!(none.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
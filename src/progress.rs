@@ -0,0 +1,102 @@
+//! Progress line with ETA printed after each iteration when the number of iterations
+//! is fixed, like with `-n`.
+
+use std::time;
+use std::time::Instant;
+
+use crate::experiment_name::ExperimentName;
+
+/// Width of the bar in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Progress towards a number of runs of each variant.
+pub struct Progress {
+    total: usize,
+    /// Runs of the slowest variant when measuring started.
+    first: usize,
+    start: Instant,
+}
+
+impl Progress {
+    /// Progress towards `total` runs of each variant, of which `done` are already recorded.
+    pub fn new(total: u32, done: usize) -> Progress {
+        Progress {
+            total: total as usize,
+            first: done,
+            start: Instant::now(),
+        }
+    }
+
+    /// Line like `[########------------] A 20/50, B 19/50, ETA 1m 05s` given `runs` of each
+    /// variant, with the ETA from the mean iteration time since the start.
+    pub fn line(&self, runs: &[(ExperimentName, usize)]) -> String {
+        self.line_after(runs, self.start.elapsed())
+    }
+
+    fn line_after(&self, runs: &[(ExperimentName, usize)], elapsed: time::Duration) -> String {
+        let done = runs
+            .iter()
+            .map(|&(_, n)| n)
+            .min()
+            .unwrap_or(0)
+            .min(self.total);
+        let filled = BAR_WIDTH * done / self.total.max(1);
+        let mut line = format!("[{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled));
+        for (i, (name, n)) in runs.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            line.push_str(&format!(
+                "{}{} {}/{}",
+                sep,
+                name.name_colored(),
+                n,
+                self.total
+            ));
+        }
+        if done > self.first {
+            let per_iteration = elapsed.as_secs_f64() / (done - self.first) as f64;
+            let eta = per_iteration * (self.total - done) as f64;
+            line.push_str(&format!(", ETA {}", format_eta(eta.round() as u64)));
+        }
+        line
+    }
+}
+
+/// Seconds like `1h 05m`, `3m 07s` or `12s`.
+fn format_eta(secs: u64) -> String {
+    match secs {
+        s if s >= 3600 => format!("{}h {:02}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m {:02}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time;
+
+    use crate::ansi::strip_csi;
+    use crate::experiment_name::ExperimentName;
+    use crate::progress::Progress;
+    use crate::progress::format_eta;
+
+    #[test]
+    fn line() {
+        let (a, b) = (ExperimentName::letter(0), ExperimentName::letter(1));
+        let progress = Progress::new(50, 10);
+        let line = progress.line_after(&[(a, 20), (b, 19)], time::Duration::from_secs(9));
+        // 9 iterations took 9 seconds, 31 are left.
+        assert_eq!(
+            "[#######-------------] A 20/50, B 19/50, ETA 31s",
+            strip_csi(&line)
+        );
+        let line = progress.line_after(&[(a, 10), (b, 10)], time::Duration::from_secs(1));
+        assert_eq!("[####----------------] A 10/50, B 10/50", strip_csi(&line));
+    }
+
+    #[test]
+    fn eta() {
+        assert_eq!("12s", format_eta(12));
+        assert_eq!("3m 07s", format_eta(187));
+        assert_eq!("1h 05m", format_eta(3900));
+    }
+}
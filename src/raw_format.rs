@@ -0,0 +1,164 @@
+//! Format of `raw-*.txt` files in the run log.
+//!
+//! Versions:
+//! * 0: one line of numbers per experiment, experiments in order
+//! * 1: lines like `A: 1 2 3`
+//! * 2: like 1, with `# absh-raw-format: 2` header line
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::experiment_name::ExperimentName;
+use crate::fs_util::write_using_temp;
+
+/// Version written by this absh.
+pub const RAW_FORMAT_VERSION: u32 = 2;
+
+const HEADER_PREFIX: &str = "# absh-raw-format: ";
+
+/// Numbers per experiment, in file order.
+pub type RawRows = Vec<(ExperimentName, Vec<u64>)>;
+
+/// Format version and lines after the header.
+fn split_header(content: &str) -> anyhow::Result<(u32, &str)> {
+    if let Some(rem) = content.strip_prefix(HEADER_PREFIX) {
+        let (version, rem) = rem.split_once('\n').unwrap_or((rem, ""));
+        let version = version
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid raw format version: {:?}", version))?;
+        return Ok((version, rem));
+    }
+    let first = content.lines().next().unwrap_or("");
+    Ok((if first.contains(':') { 1 } else { 0 }, content))
+}
+
+fn parse_line(
+    line: &str,
+    version: u32,
+    index: usize,
+) -> anyhow::Result<(ExperimentName, Vec<u64>)> {
+    let (name, numbers) = match version {
+        0 => (ExperimentName::letter(index), line),
+        _ => {
+            let (name, numbers) = line
+                .split_once(':')
+                .with_context(|| format!("expecting `name: numbers`: {:?}", line))?;
+            let name = name.trim();
+            ExperimentName::validate(name)?;
+            (ExperimentName::intern(name), numbers)
+        }
+    };
+    let numbers = numbers
+        .split_whitespace()
+        .map(|n| {
+            n.parse()
+                .with_context(|| format!("invalid number: {:?}", n))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((name, numbers))
+}
+
+/// Parse raw file of any known version.
+pub fn parse_raw(content: &str) -> anyhow::Result<RawRows> {
+    let (version, content) = split_header(content)?;
+    if version > RAW_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "raw format version {} is written by a newer absh, this absh supports up to {}",
+            version,
+            RAW_FORMAT_VERSION
+        ));
+    }
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| parse_line(line, version, i))
+        .collect()
+}
+
+/// Format raw file in the current version.
+pub fn format_raw<'a>(rows: impl IntoIterator<Item = (ExperimentName, &'a [u64])>) -> String {
+    let mut r = format!("{}{}\n", HEADER_PREFIX, RAW_FORMAT_VERSION);
+    for (name, numbers) in rows {
+        r.push_str(name.name());
+        r.push(':');
+        for n in numbers {
+            r.push(' ');
+            r.push_str(&n.to_string());
+        }
+        r.push('\n');
+    }
+    r
+}
+
+/// Rewrite raw files in the log directory in the current version.
+///
+/// Returns the files rewritten.
+pub fn upgrade_raw_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut upgraded = Vec::new();
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if file_name.starts_with("raw-") && file_name.ends_with(".txt") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    for path in paths {
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let (version, _) = split_header(&content)?;
+        if version == RAW_FORMAT_VERSION {
+            continue;
+        }
+        let rows = parse_raw(&content).with_context(|| format!("in {}", path.display()))?;
+        let upgraded_content = format_raw(rows.iter().map(|(n, v)| (*n, &v[..])));
+        write_using_temp(&path, upgraded_content)?;
+        upgraded.push(path);
+    }
+    Ok(upgraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::experiment_name::ExperimentName;
+    use crate::raw_format::format_raw;
+    use crate::raw_format::parse_raw;
+
+    #[test]
+    fn parse_all_versions() {
+        let expected = vec![
+            (ExperimentName::letter(0), vec![1, 2]),
+            (ExperimentName::letter(1), vec![3]),
+        ];
+        assert_eq!(expected, parse_raw("1 2\n3\n").unwrap());
+        assert_eq!(expected, parse_raw("A: 1 2\nB: 3\n").unwrap());
+        assert_eq!(
+            expected,
+            parse_raw("# absh-raw-format: 2\nA: 1 2\nB: 3\n").unwrap()
+        );
+        assert!(parse_raw("# absh-raw-format: 99\nA: 1\n").is_err());
+    }
+
+    #[test]
+    fn format_roundtrip() {
+        let a = [10, 20];
+        let b: [u64; 0] = [];
+        let content = format_raw(vec![
+            (ExperimentName::letter(0), &a[..]),
+            (ExperimentName::letter(1), &b[..]),
+        ]);
+        assert_eq!("# absh-raw-format: 2\nA: 10 20\nB:\n", content);
+        assert_eq!(
+            vec![
+                (ExperimentName::letter(0), vec![10, 20]),
+                (ExperimentName::letter(1), vec![])
+            ],
+            parse_raw(&content).unwrap()
+        );
+    }
+}
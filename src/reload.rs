@@ -0,0 +1,254 @@
+//! Changes of the config picked up by a running benchmark with `--reload-config`.
+
+use std::fmt::Write as _;
+
+use crate::ansi;
+use crate::cargo_compare::CargoCompare;
+use crate::config::Config;
+use crate::danger::find_destructive_in;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::input::PinnedInputs;
+use crate::run_log::RunLog;
+
+/// Settings of the benchmark which apply to variants added from the config.
+pub struct ReloadOptions<'a> {
+    pub cargo_compare: Option<CargoCompare>,
+    pub no_safety_check: bool,
+    pub inputs: &'a PinnedInputs,
+    /// Value of `ABSH_SCALE` given with `--calibrate-to`.
+    pub scale: Option<u64>,
+}
+
+/// Add variants added to the changed config and remove variants removed from it.
+///
+/// Changes of existing variants are ignored, so their samples stay comparable. The log
+/// records `NAME.added` and `NAME.removed` with the first iteration affected.
+pub fn reload_config(
+    log: &mut RunLog,
+    config: anyhow::Result<Config>,
+    experiments: &mut ExperimentMap<Experiment>,
+    next_iteration: u64,
+    opts: &ReloadOptions,
+) -> anyhow::Result<()> {
+    let warn = |log: &mut RunLog, message: String| {
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}{message}{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            warn(log, format!("Ignoring changed config: {:#}", e))?;
+            return Ok(());
+        }
+    };
+    let names: Vec<ExperimentName> = config
+        .test
+        .iter()
+        .map(|t| ExperimentName::intern(&t.name))
+        .collect();
+    let baseline = experiments.keys().next().unwrap();
+    if names[0] != baseline {
+        let message = format!("the first test must stay `{}`", baseline);
+        warn(log, format!("Ignoring changed config: {}", message))?;
+        return Ok(());
+    }
+    if let Some(name) = names
+        .iter()
+        .find(|n| names.iter().filter(|m| m == n).count() > 1)
+    {
+        let message = format!("test `{}` is given more than once", name);
+        warn(log, format!("Ignoring changed config: {}", message))?;
+        return Ok(());
+    }
+
+    let removed: Vec<ExperimentName> = experiments.keys().filter(|n| !names.contains(n)).collect();
+    for name in removed {
+        let test = experiments.remove(name).unwrap();
+        writeln!(
+            log.log_only(),
+            "{}.removed: iteration={}",
+            name,
+            next_iteration
+        )?;
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "Removed {} from the config, dropping its {} runs",
+            name.name_colored(),
+            test.runs()
+        )?;
+    }
+
+    for (test, &name) in config.test.iter().zip(&names) {
+        let warmup = test.warmup.clone().unwrap_or_default();
+        let when = test.when.clone().unwrap_or_default();
+        if let Some(existing) = experiments.get(name) {
+            let scripts = (
+                &existing.run,
+                &existing.warmup,
+                &existing.when,
+                &existing.dir,
+            );
+            if scripts != (&test.run, &warmup, &when, &test.dir) {
+                warn(
+                    log,
+                    format!(
+                        "Ignoring changed scripts of {}, rename the test to measure them anew",
+                        name
+                    ),
+                )?;
+            }
+            continue;
+        }
+
+        let mut experiment = Experiment::new(name, warmup, test.run.clone());
+        experiment.when = when;
+        if let Some(cargo) = opts.cargo_compare {
+            experiment.setup = cargo.wrap_setup("");
+        }
+        experiment.dir = test.dir.clone();
+        experiment.metadata = test.metadata();
+        experiment.env = opts
+            .cargo_compare
+            .map_or(Vec::new(), |cargo| cargo.env(name));
+        experiment
+            .env
+            .extend(test.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for (spec, _) in opts
+            .inputs
+            .inputs
+            .iter()
+            .filter(|(spec, _)| spec.used_by(name))
+        {
+            experiment
+                .env
+                .push((spec.env_var(), spec.path.display().to_string()));
+        }
+        if let Some(scale) = opts.scale {
+            experiment
+                .env
+                .push(("ABSH_SCALE".to_owned(), scale.to_string()));
+        }
+        if let Some(dir) = experiment.dir.as_ref().filter(|d| !d.is_dir()) {
+            let message = format!("working directory is not a directory: {}", dir.display());
+            warn(log, format!("Not adding {}, {}", name, message))?;
+            continue;
+        }
+        if !find_destructive_in(&experiment).is_empty() && !opts.no_safety_check {
+            warn(
+                log,
+                format!("Not adding {}, its scripts look destructive", name),
+            )?;
+            continue;
+        }
+        writeln!(
+            log.log_only(),
+            "{}.added: iteration={}",
+            name,
+            next_iteration
+        )?;
+        log.write_experiment(&experiment)?;
+        writeln!(log.both_log_and_stderr())?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "Added {} from the config",
+            name.name_colored()
+        )?;
+        experiments.insert(name, experiment);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::cargo_compare::CargoCompare;
+    use crate::config::Config;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::input::PinnedInputs;
+    use crate::reload::ReloadOptions;
+    use crate::reload::reload_config;
+    use crate::run_log::RunLog;
+
+    #[test]
+    fn reload() {
+        let dir = env::temp_dir().join(format!("absh-reload-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("absh.toml");
+        let mut log = RunLog::open(&dir.join("logs"));
+        let inputs = PinnedInputs { inputs: Vec::new() };
+        let opts = ReloadOptions {
+            cargo_compare: Some(CargoCompare::Clean),
+            no_safety_check: false,
+            inputs: &inputs,
+            scale: Some(4),
+        };
+        let mut experiments = ExperimentMap::default();
+        for name in ["base", "old"] {
+            let name = ExperimentName::intern(name);
+            experiments.insert(
+                name,
+                Experiment::new(name, String::new(), "./run".to_owned()),
+            );
+        }
+        let names = |experiments: &ExperimentMap<Experiment>| -> Vec<String> {
+            experiments.keys().map(|n| n.name().to_owned()).collect()
+        };
+        let mut reload = |config: &str, experiments: &mut ExperimentMap<Experiment>| {
+            fs::write(&path, config).unwrap();
+            reload_config(&mut log, Config::load(&path), experiments, 3, &opts).unwrap();
+        };
+
+        // Rejected as a whole: unparsable, another first test, a test given twice.
+        let base = "[[test]]\nname = \"base\"\nrun = \"./run\"\n";
+        for config in [
+            "[[test]]\nname = \"base\"\n".to_owned(),
+            "[[test]]\nname = \"new\"\nrun = \"./run\"\n".to_owned(),
+            format!("{}{}", base, base),
+        ] {
+            reload(&config, &mut experiments);
+            assert_eq!(vec!["base", "old"], names(&experiments));
+        }
+
+        // Variants which look destructive or have no working directory are not added.
+        reload(
+            &format!(
+                "{}[[test]]\nname = \"rm\"\nrun = \"rm -rf ~/\"\n\
+                 [[test]]\nname = \"nodir\"\nrun = \"./run\"\ndir = \"missing\"\n",
+                base
+            ),
+            &mut experiments,
+        );
+        assert_eq!(vec!["base"], names(&experiments));
+
+        // Changed scripts of an existing variant are ignored.
+        reload(
+            "[[test]]\nname = \"base\"\nrun = \"./run2\"\n\
+             [[test]]\nname = \"new\"\nrun = \"./new\"\n",
+            &mut experiments,
+        );
+        assert_eq!(vec!["base", "new"], names(&experiments));
+        assert_eq!("./run", experiments[ExperimentName::intern("base")].run);
+        let new = &experiments[ExperimentName::intern("new")];
+        assert_eq!("cargo clean --quiet", new.setup);
+        assert!(new.env.contains(&("ABSH_SCALE".to_owned(), "4".to_owned())));
+
+        let text = fs::read_to_string(log.name().join("log")).unwrap();
+        assert!(text.contains("old.removed: iteration=3\n"), "{}", text);
+        assert!(text.contains("new.added: iteration=3\n"), "{}", text);
+        assert!(text.contains("new.run: ./new\n"), "{}", text);
+        assert!(!text.contains("rm.added"), "{}", text);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,130 @@
 use std::fmt::Write;
+use std::str::FromStr;
 
 use crate::ansi;
+use crate::buckets::Buckets;
+use crate::delta_plot::make_delta_plot;
+use crate::delta_plot::paired_deltas;
+use crate::delta_plot::paired_deltas_by_iteration;
+use crate::distr_plot::PlotMark;
+use crate::distr_plot::PlotMode;
 use crate::experiment::Experiment;
 use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::bootstrap::bootstrap_ratio;
+use crate::math::correlation::pearson;
+use crate::math::effect_size::Magnitude;
+use crate::math::effect_size::cliffs_delta;
+use crate::math::effect_size::cohens_d;
+use crate::math::ks::ks_test;
 use crate::math::numbers::Numbers;
+use crate::math::proportion::Proportion;
+use crate::math::proportion::ProportionTest;
+use crate::math::proportion::compare_proportions;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Stat;
+use crate::math::ratio::Verdict;
+use crate::math::significance::SignificanceTest;
+use crate::math::stats::Percentiles;
+use crate::math::stats::Stats;
+use crate::measure::key::MeasureKey;
 use crate::measure::tr::MeasureDyn;
-use crate::student::t_table;
-use crate::student::TWO_SIDED_95;
+use crate::student::ConfInterval;
+
+/// How to render statistics.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// Include distribution plots.
+    pub include_distr: bool,
+    /// Make lines fit this width.
+    pub max_width: Option<usize>,
+    /// Include distribution of per-iteration differences.
+    pub paired: bool,
+    /// Which distribution plots to include.
+    pub plot: PlotMode,
+    /// Also compute bootstrap intervals of mean and median ratios with this many resamples.
+    pub bootstrap: Option<u32>,
+    /// Order of variants, as given if `None`.
+    pub sort_by: Option<SortBy>,
+    /// Statistic of the B/A ratio intervals.
+    pub stat: Stat,
+    /// Level of the B/A ratio intervals.
+    pub conf: ConfInterval,
+    /// Percentile columns of the statistics.
+    pub percentiles: Percentiles,
+    /// Test of p-values of comparisons.
+    pub significance: SignificanceTest,
+    /// Reference values to draw under distribution plots.
+    pub marks: Vec<PlotMark>,
+    /// Wall time thresholds of the share of runs under them in the summary.
+    pub buckets: Buckets,
+}
+
+/// Order of variants in statistics and plots.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SortBy {
+    /// Alphabetically by name.
+    Name,
+    /// Lowest mean first.
+    Mean,
+    /// Lowest median first.
+    Median,
+    /// Baseline first, then lowest ratio to it.
+    Ratio,
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<SortBy> {
+        match s {
+            "name" => Ok(SortBy::Name),
+            "mean" => Ok(SortBy::Mean),
+            "median" => Ok(SortBy::Median),
+            "ratio" => Ok(SortBy::Ratio),
+            s => Err(anyhow::anyhow!(
+                "unknown sort order: `{}`, expecting `name`, `mean`, `median` or `ratio`",
+                s
+            )),
+        }
+    }
+}
+
+/// Line with `^` at `column` of a plot starting after `prefix` characters, labelled on
+/// the side with more room.
+fn mark_line(prefix: usize, column: usize, plot_width: usize, label: &str) -> String {
+    if column < plot_width / 2 {
+        format!("{}^ {}", " ".repeat(prefix + column), label)
+    } else {
+        let pad = (prefix + column).saturating_sub(label.len() + 1);
+        format!("{}{} ^", " ".repeat(pad), label)
+    }
+}
+
+/// Names of variants in the order to display them.
+fn display_order(
+    stats: &ExperimentMap<Stats<u64>>,
+    sort_by: Option<SortBy>,
+    stat: Stat,
+) -> Vec<ExperimentName> {
+    let mut names: Vec<ExperimentName> = stats.keys().collect();
+    match sort_by {
+        None => {}
+        Some(SortBy::Name) => names.sort_by_key(|n| n.name()),
+        Some(SortBy::Mean) => names.sort_by_key(|&n| stats[n].mean),
+        Some(SortBy::Median) => names.sort_by_key(|&n| stats[n].med),
+        // Ratios to the same baseline are in the order of the compared statistic.
+        Some(SortBy::Ratio) => match stat {
+            Stat::Mean => names[1..].sort_by_key(|&n| stats[n].mean),
+            Stat::Median => names[1..].sort_by_key(|&n| stats[n].med),
+        },
+    }
+    names
+}
 
 pub(crate) fn render_stats(
     tests: &ExperimentMap<Experiment>,
-    include_distr: bool,
+    opts: &RenderOptions,
     measure: &dyn MeasureDyn,
     numbers: impl Fn(&Experiment) -> &Numbers,
 ) -> anyhow::Result<String> {
@@ -18,14 +132,32 @@ pub(crate) fn render_stats(
 
     let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
 
-    let stats_str: ExperimentMap<String> = measure.display_stats(tests);
+    let stats_str: ExperimentMap<String> = measure.display_stats(tests, &opts.percentiles);
 
     let stats_width = stats_str.values().map(|s| s.len()).max().unwrap();
 
-    let distr_plots = measure.make_distr_plots(&tests, stats_width - 8)?;
+    let mut plot_width = stats_width - 8;
+    if let Some(max_width) = opts.max_width {
+        let name_width = tests.keys().map(|n| n.name().len()).max().unwrap();
+        // `A: distr=[` and `]`.
+        let decoration = name_width + 10;
+        plot_width = usize::min(plot_width, max_width.saturating_sub(decoration).max(1));
+    }
+
+    let mark = opts
+        .marks
+        .iter()
+        .find(|m| m.measure == measure.id())
+        .map(|m| m.value);
+    let distr_plots = measure.make_distr_plots(tests, plot_width, opts.plot, mark)?;
+
+    let order = display_order(&stats, opts.sort_by, opts.stat);
+    let a_name = tests.keys().next().unwrap();
+    let others = || order.iter().copied().filter(|&n| n != a_name);
 
     writeln!(r, "{}:", measure.name())?;
-    for (_name, test, stats) in tests.zip(&stats_str) {
+    for &name in &order {
+        let (test, stats) = (&tests[name], &stats_str[name]);
         writeln!(
             r,
             "{color}{name}{reset}: {stats}",
@@ -34,45 +166,697 @@ pub(crate) fn render_stats(
             reset = ansi::RESET,
         )?;
     }
-    for (_name, test, plot) in tests.zip(&distr_plots) {
-        if include_distr {
+    for plots in &distr_plots.plots {
+        for &name in &order {
+            let (test, plot) = (&tests[name], &plots[name]);
+            if opts.include_distr {
+                writeln!(
+                    r,
+                    "{color}{name}{reset}: distr=[{plot}]",
+                    name = test.name,
+                    color = test.name.color(),
+                    reset = ansi::RESET,
+                )?;
+            }
+        }
+    }
+    if let (Some(mark), Some(column), true) = (mark, distr_plots.mark_column, opts.include_distr) {
+        // Aligned with plots of the first variant, after `A: distr=[`.
+        let prefix = order[0].name().len() + ": distr=[".len();
+        let label = format!("mark {}", measure.display_number(mark));
+        writeln!(r, "{}", mark_line(prefix, column, plot_width, &label))?;
+    }
+
+    let stats_a = &stats[a_name];
+    for b_name in others() {
+        let stats_b = &stats[b_name];
+        let interval = RatioInterval::compare(
+            opts.stat,
+            opts.conf,
+            numbers(&tests[a_name]),
+            numbers(&tests[b_name]),
+        );
+        write!(
+            r,
+            "{b_name}/{a_name}: {b_a:.3} {b_a_min:.3}..{b_a_max:.3}",
+            b_a = interval.ratio,
+            b_a_min = interval.min,
+            b_a_max = interval.max,
+        )?;
+        match opts.stat {
+            Stat::Mean => {
+                let p = opts
+                    .significance
+                    .p_value(numbers(&tests[a_name]), numbers(&tests[b_name]));
+                write!(
+                    r,
+                    " ({conf} conf) p={p} ({test})",
+                    conf = opts.conf,
+                    p = p.map_or("?".to_owned(), |p| format!("{:.4}", p)),
+                    test = opts.significance.name(),
+                )?;
+            }
+            Stat::Median => write!(r, " ({} conf of medians)", opts.conf)?,
+        }
+        let sorted = |name: ExperimentName| numbers(tests.get(name).unwrap()).sorted();
+        match ks_test(sorted(a_name), sorted(b_name)) {
+            Some(ks) => writeln!(r, ", D={:.3} p={:.4} (KS)", ks.d, ks.p)?,
+            None => writeln!(r)?,
+        }
+        let cohen = cohens_d(stats_a, stats_b)
+            .map(|d| format!("d={:.2} ({}, Cohen)", d, Magnitude::of_cohens_d(d)));
+        let cliff = cliffs_delta(sorted(a_name), sorted(b_name)).map(|delta| {
+            format!(
+                "δ={:.2} ({}, Cliff)",
+                delta,
+                Magnitude::of_cliffs_delta(delta)
+            )
+        });
+        let effects: Vec<String> = cohen.into_iter().chain(cliff).collect();
+        if !effects.is_empty() {
+            writeln!(r, "{b_name}/{a_name} effect size: {}", effects.join(", "))?;
+        }
+    }
+
+    if let Some(resamples) = opts.bootstrap {
+        let a = &tests[a_name];
+        for b in others().map(|n| &tests[n]) {
+            let bootstrap = match bootstrap_ratio(
+                numbers(a).raw(),
+                numbers(b).raw(),
+                resamples,
+                opts.conf,
+                &mut rand::thread_rng(),
+            ) {
+                Some(bootstrap) => bootstrap,
+                None => continue,
+            };
             writeln!(
                 r,
-                "{color}{name}{reset}: distr=[{plot}]",
-                name = test.name,
-                color = test.name.color(),
-                reset = ansi::RESET,
+                "{b_name}/{a_name}: mean {mean:.3} {mean_min:.3}..{mean_max:.3}, median {med:.3} {med_min:.3}..{med_max:.3} ({conf} bootstrap)",
+                b_name = b.name,
+                a_name = a.name,
+                mean = bootstrap.mean.ratio,
+                mean_min = bootstrap.mean.min,
+                mean_max = bootstrap.mean.max,
+                med = bootstrap.median.ratio,
+                med_min = bootstrap.median.min,
+                med_max = bootstrap.median.max,
+                conf = opts.conf,
+            )?;
+        }
+    }
+
+    if opts.paired {
+        let a = &tests[a_name];
+        for b in others().map(|n| &tests[n]) {
+            let aligned = |t: &Experiment| numbers(t).len() == t.iterations.len();
+            let deltas = match aligned(a) && aligned(b) {
+                true => {
+                    paired_deltas_by_iteration(&a.iterations, numbers(a), &b.iterations, numbers(b))
+                }
+                // Metrics may be missing in some runs and loaded logs have no iterations.
+                false => paired_deltas(numbers(a), numbers(b)),
+            };
+            if deltas.is_empty() {
+                continue;
+            }
+            let mean = deltas.iter().sum::<i64>() / deltas.len() as i64;
+            writeln!(
+                r,
+                "{b_name}-{a_name}: n={n} mean={mean} delta=[{plot}]",
+                b_name = b.name,
+                a_name = a.name,
+                n = deltas.len(),
+                mean = measure.display_delta(mean),
+                plot = make_delta_plot(&deltas, plot_width),
             )?;
         }
     }
 
+    Ok(r)
+}
+
+pub(crate) fn render_verdict(
+    name: ExperimentName,
+    verdict: Verdict,
+    ratio: f64,
+    words: (&str, &str),
+) -> String {
+    match verdict {
+        Verdict::Lower => format!(
+            "{green}{name} is {pct:.1}% {word}{reset}",
+            green = ansi::GREEN,
+            pct = (1.0 - ratio) * 100.0,
+            word = words.0,
+            reset = ansi::RESET,
+        ),
+        Verdict::Higher => format!(
+            "{red}{name} is {pct:.1}% {word}{reset}",
+            red = ansi::RED,
+            pct = (ratio - 1.0) * 100.0,
+            word = words.1,
+            reset = ansi::RESET,
+        ),
+        Verdict::NoDifference => format!(
+            "{yellow}no significant difference{reset}",
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        ),
+    }
+}
+
+/// Verdicts and ratios only.
+pub(crate) fn render_summary(
+    tests: &ExperimentMap<Experiment>,
+    measure: &dyn MeasureDyn,
+    words: (&str, &str),
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "{}:", measure.name())?;
+
+    let stats: ExperimentMap<_> = tests.map(|t| numbers(t).stats().unwrap());
     let mut stats_iter = stats.iter();
     let (a_name, stats_a) = stats_iter.next().unwrap();
     for (b_name, stats_b) in stats_iter {
-        let degrees_of_freedom = u64::min(stats_a.count as u64 - 1, stats_b.count as u64 - 1);
-        let t_star = t_table(degrees_of_freedom, TWO_SIDED_95);
+        let interval = RatioInterval::compare(
+            opts.stat,
+            opts.conf,
+            numbers(&tests[a_name]),
+            numbers(&tests[b_name]),
+        );
+        writeln!(
+            r,
+            "{b_name}/{a_name}: {b_a:.3} {b_a_min:.3}..{b_a_max:.3} ({conf} conf{of}) n={n_a}/{n_b}: {verdict}",
+            b_a = interval.ratio,
+            b_a_min = interval.min,
+            b_a_max = interval.max,
+            conf = opts.conf,
+            of = match opts.stat {
+                Stat::Mean => "",
+                Stat::Median => " of medians",
+            },
+            n_a = stats_a.count,
+            n_b = stats_b.count,
+            verdict = render_verdict(b_name, interval.verdict(), interval.ratio, words),
+        )?;
+    }
+    Ok(r)
+}
+
+/// Arrow and absolute change, or `=` if the change is not visible at display precision.
+fn render_change(delta: f64, display: impl Fn(f64) -> String) -> String {
+    let abs = display(delta.abs());
+    if abs == display(0.0) {
+        "=".to_owned()
+    } else if delta > 0.0 {
+        format!("↑{}", abs)
+    } else {
+        format!("↓{}", abs)
+    }
+}
+
+/// Statistics of a measure at a printout, kept to show how they moved by the next one
+/// without holding on to the samples.
+#[derive(Clone, Debug)]
+pub struct PrintoutStats {
+    /// Mean or median of each variant, by `--stat`.
+    values: Vec<(ExperimentName, f64)>,
+    /// Ratio interval of each variant after the first to the first one.
+    intervals: Vec<RatioInterval>,
+}
+
+pub(crate) fn printout_stats(
+    tests: &ExperimentMap<Experiment>,
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> PrintoutStats {
+    let values = tests
+        .iter()
+        .map(|(name, t)| {
+            let stats = numbers(t).stats().unwrap();
+            let value = match opts.stat {
+                Stat::Mean => stats.mean as f64,
+                Stat::Median => stats.med as f64,
+            };
+            (name, value)
+        })
+        .collect();
+    let a = tests.values().next().unwrap();
+    let intervals = tests
+        .values()
+        .skip(1)
+        .map(|b| RatioInterval::compare(opts.stat, opts.conf, numbers(a), numbers(b)))
+        .collect();
+    PrintoutStats { values, intervals }
+}
+
+/// How means (or medians), ratios and confidence interval widths moved since the `prev`
+/// printout.
+pub(crate) fn render_changes(
+    prev: &PrintoutStats,
+    tests: &ExperimentMap<Experiment>,
+    measure: &dyn MeasureDyn,
+    opts: &RenderOptions,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    let now = printout_stats(tests, opts, numbers);
+    let names = |stats: &PrintoutStats| -> Vec<ExperimentName> {
+        stats.values.iter().map(|&(name, _)| name).collect()
+    };
+    if names(prev) != names(&now) {
+        return Ok(r);
+    }
+    let display = |n: f64| measure.display_number(n.round() as u64);
+    let ratio = |n: f64| format!("{:.3}", n);
 
-        // Half of a confidence interval
-        let conf_h = t_star
-            * f64::sqrt(
-                stats_a.sigma_sq() / (stats_a.count - 1) as f64
-                    + stats_b.sigma_sq() / (stats_b.count - 1) as f64,
-            );
+    write!(r, "{}:", measure.name())?;
+    for (i, (&(name, prev), &(_, value))) in prev.values.iter().zip(&now.values).enumerate() {
+        write!(
+            r,
+            "{sep} {color}{name}{reset} {stat} {change}",
+            sep = if i == 0 { "" } else { "," },
+            name = name,
+            color = name.color(),
+            reset = ansi::RESET,
+            stat = opts.stat.name(),
+            change = render_change(value - prev, display),
+        )?;
+    }
+    let a_name = now.values[0].0;
+    let intervals = prev.intervals.iter().zip(&now.intervals);
+    for (&(name, _), (prev, interval)) in now.values[1..].iter().zip(intervals) {
+        // Interval is unbounded when A varies too much.
+        let width = match (prev.max >= prev.min, interval.max >= interval.min) {
+            (true, true) => format!(
+                "{:.3} {}",
+                interval.max - interval.min,
+                render_change((interval.max - interval.min) - (prev.max - prev.min), ratio)
+            ),
+            (false, true) => format!("{:.3}", interval.max - interval.min),
+            (_, false) => "unbounded".to_owned(),
+        };
+        write!(
+            r,
+            ", {name}/{a_name} {change}, conf width {width}",
+            name = name,
+            a_name = a_name,
+            change = render_change(interval.ratio - prev.ratio, ratio),
+            width = width,
+        )?;
+    }
+    writeln!(r)?;
+    Ok(r)
+}
 
-        // Quarter of a confidence interval
-        let conf_q = conf_h / 2.0;
+/// Difference of rates of `b` and `a` in percentage points, like `+12.5pp`.
+///
+/// Rates are compared by difference rather than ratio, which is undefined when `a` is zero.
+pub(crate) fn render_rate_difference(a: Proportion, b: Proportion) -> String {
+    format!("{:+.1}pp", (b.ratio() - a.ratio()) * 100.0)
+}
 
-        let b_a_min = (stats_b.mean as f64 - conf_q) / (stats_a.mean as f64 + conf_q);
-        let b_a_max = (stats_b.mean as f64 + conf_q) / (stats_a.mean as f64 - conf_q);
+pub(crate) fn render_proportions_summary(
+    tests: &ExperimentMap<Experiment>,
+    measure: &dyn MeasureDyn,
+    conf: ConfInterval,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "{} (proportion):", measure.name())?;
 
+    let proportions: ExperimentMap<Proportion> = tests.map(|t| Proportion {
+        successes: numbers(t).sum(),
+        total: numbers(t).len() as u64,
+    });
+    let mut iter = proportions.iter();
+    let (a_name, a) = iter.next().unwrap();
+    for (b_name, b) in iter {
+        let p = compare_proportions(*a, *b).map(|t| t.p());
+        let difference = render_rate_difference(*a, *b);
+        let verdict = match p {
+            Some(p) if p < conf.alpha() && b.ratio() < a.ratio() => {
+                format!("{}{} rate is lower{}", ansi::GREEN, b_name, ansi::RESET)
+            }
+            Some(p) if p < conf.alpha() => {
+                format!("{}{} rate is higher{}", ansi::RED, b_name, ansi::RESET)
+            }
+            _ => format!("{}no significant difference{}", ansi::YELLOW, ansi::RESET),
+        };
         writeln!(
             r,
-            "{b_name}/{a_name}: {b_a:.3} {b_a_min:.3}..{b_a_max:.3} (95% conf)",
-            b_a = stats_b.mean as f64 / stats_a.mean as f64,
-            b_a_min = b_a_min,
-            b_a_max = b_a_max,
+            "{b_name}-{a_name}: {difference} (p={p}) n={n_a}/{n_b}: {verdict}",
+            p = p.map_or("?".to_owned(), |p| format!("{:.4}", p)),
+            n_a = a.total,
+            n_b = b.total,
         )?;
     }
+    Ok(r)
+}
 
+pub(crate) fn render_proportions(
+    tests: &ExperimentMap<Experiment>,
+    measure: &dyn MeasureDyn,
+    numbers: impl Fn(&Experiment) -> &Numbers,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+
+    let proportions: ExperimentMap<Proportion> = tests.map(|t| Proportion {
+        successes: numbers(t).sum(),
+        total: numbers(t).len() as u64,
+    });
+
+    writeln!(r, "{} (proportion):", measure.name())?;
+    for (_name, test, p) in tests.zip(&proportions) {
+        writeln!(
+            r,
+            "{color}{name}{reset}: {successes}/{total} ({percent:.1}%)",
+            name = test.name,
+            color = test.name.color(),
+            reset = ansi::RESET,
+            successes = p.successes,
+            total = p.total,
+            percent = p.ratio() * 100.0,
+        )?;
+    }
+
+    let mut iter = proportions.iter();
+    let (a_name, a) = iter.next().unwrap();
+    for (b_name, b) in iter {
+        let test = match compare_proportions(*a, *b) {
+            Some(ProportionTest::ChiSquared { statistic, p }) => {
+                format!("chi-squared={:.3} p={:.4}", statistic, p)
+            }
+            Some(ProportionTest::FisherExact { p }) => format!("Fisher exact p={:.4}", p),
+            None => "no test".to_owned(),
+        };
+        writeln!(
+            r,
+            "{b_name}-{a_name}: {difference} ({test})",
+            difference = render_rate_difference(*a, *b),
+        )?;
+    }
+
+    Ok(r)
+}
+
+/// Failure and skip counts per experiment, empty if nothing failed or was skipped.
+pub fn render_failures(tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+    if tests.values().all(|t| t.failures == 0) {
+        return render_skips(tests);
+    }
+    let mut r = String::new();
+    writeln!(r)?;
+    writeln!(r, "Failures:")?;
+    for test in tests.values() {
+        write!(
+            r,
+            "{color}{name}{reset}: {failures} failed",
+            name = test.name,
+            color = test.name.color(),
+            reset = ansi::RESET,
+            failures = test.failures,
+        )?;
+        if test.oom_kills != 0 {
+            write!(
+                r,
+                ", {red}{oom} out of memory{reset}",
+                red = ansi::RED,
+                oom = test.oom_kills,
+                reset = ansi::RESET,
+            )?;
+        }
+        if test.suspected_oom_kills != 0 {
+            write!(
+                r,
+                ", {yellow}{killed} killed by SIGKILL (possibly out of memory){reset}",
+                yellow = ansi::YELLOW,
+                killed = test.suspected_oom_kills,
+                reset = ansi::RESET,
+            )?;
+        }
+        if test.timeouts != 0 {
+            write!(
+                r,
+                ", {red}{timeouts} timed out{reset}",
+                red = ansi::RED,
+                timeouts = test.timeouts,
+                reset = ansi::RESET,
+            )?;
+        }
+        writeln!(r)?;
+    }
+    r.push_str(&render_skips(tests)?);
     Ok(r)
 }
+
+fn render_skips(tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+    let mut r = String::new();
+    if tests.values().all(|t| t.skips == 0) {
+        return Ok(r);
+    }
+    writeln!(r)?;
+    writeln!(r, "Skipped by condition:")?;
+    for test in tests.values().filter(|t| !t.when.is_empty()) {
+        writeln!(
+            r,
+            "{color}{name}{reset}: {skips} iterations skipped",
+            name = test.name,
+            color = test.name.color(),
+            reset = ansi::RESET,
+            skips = test.skips,
+        )?;
+    }
+    Ok(r)
+}
+
+/// Number of runs excluded from statistics as outliers, per experiment.
+pub fn render_outliers(
+    tests: &ExperimentMap<Experiment>,
+    filtered: &ExperimentMap<Experiment>,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r)?;
+    write!(r, "Outliers excluded (Tukey fences):")?;
+    for (i, (_name, test, filtered)) in tests.zip(filtered).enumerate() {
+        write!(
+            r,
+            "{sep} {color}{name}{reset}: {excluded}/{runs}",
+            sep = if i == 0 { "" } else { "," },
+            name = test.name,
+            color = test.name.color(),
+            reset = ansi::RESET,
+            excluded = test.runs() - filtered.runs(),
+            runs = test.runs(),
+        )?;
+    }
+    writeln!(r)?;
+    Ok(r)
+}
+
+/// Temperature and frequency ranges and correlation of run time with temperature,
+/// empty if sensors were not recorded.
+pub fn render_sensors(tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+    let mut r = String::new();
+    if tests.values().all(|t| t.sensors.is_empty()) {
+        return Ok(r);
+    }
+    writeln!(r)?;
+    writeln!(r, "Sensors:")?;
+    for test in tests.values() {
+        write!(
+            r,
+            "{color}{name}{reset}:",
+            name = test.name,
+            color = test.name.color(),
+            reset = ansi::RESET,
+        )?;
+        let temps: Vec<f64> = test.sensors.iter().filter_map(|s| s.temp()).collect();
+        let freqs: Vec<u64> = test
+            .sensors
+            .iter()
+            .filter_map(|s| s.cpu_freq_mhz())
+            .collect();
+        if temps.is_empty() && freqs.is_empty() {
+            writeln!(r, " not available")?;
+            continue;
+        }
+        if !temps.is_empty() {
+            let min = temps.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = temps.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            write!(r, " temp={:.1}..{:.1}C", min, max)?;
+        }
+        if let (Some(min), Some(max)) = (freqs.iter().min(), freqs.iter().max()) {
+            write!(r, " freq={}..{}MHz", min, max)?;
+        }
+        // Sensors are recorded only for successful runs, as are durations.
+        let durations = test.measures[MeasureKey::WallTime].raw();
+        if temps.len() == durations.len() {
+            let durations: Vec<f64> = durations.iter().map(|&d| d as f64).collect();
+            if let Some(corr) = pearson(&durations, &temps) {
+                write!(r, " time/temp r={:.2}", corr)?;
+                if corr.abs() >= 0.5 {
+                    write!(
+                        r,
+                        " {yellow}(possible thermal effect){reset}",
+                        yellow = ansi::YELLOW,
+                        reset = ansi::RESET,
+                    )?;
+                }
+            }
+        }
+        writeln!(r)?;
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::strip_csi;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::math::numbers::Numbers;
+    use crate::math::ratio::Stat;
+    use crate::measure::key::MeasureKey;
+    use crate::measure::tr::Metric;
+    use crate::measure::tr::WallTime;
+    use crate::render_stats::RenderOptions;
+    use crate::render_stats::SortBy;
+    use crate::render_stats::display_order;
+    use crate::render_stats::printout_stats;
+    use crate::render_stats::render_changes;
+    use crate::render_stats::render_proportions_summary;
+    use crate::student::ConfInterval;
+    use crate::student::TWO_SIDED_95;
+
+    fn experiments(samples: [&[u64]; 2]) -> ExperimentMap<Experiment> {
+        let mut tests = ExperimentMap::default();
+        for (name, samples) in ["changes-a", "changes-b"].iter().zip(samples.iter()) {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for &t in samples.iter() {
+                test.measures[MeasureKey::WallTime].push(t * 1_000_000);
+            }
+            tests.insert(name, test);
+        }
+        tests
+    }
+
+    #[test]
+    fn changes() {
+        let prev = experiments([&[100, 102, 98], &[200, 210, 190]]);
+        let tests = experiments([&[100, 102, 98, 100], &[200, 210, 190, 220]]);
+        let opts = RenderOptions::default();
+        fn numbers(t: &Experiment) -> &Numbers {
+            &t.measures[MeasureKey::WallTime]
+        }
+        let prev = printout_stats(&prev, &opts, numbers);
+        let changes = render_changes(&prev, &tests, &WallTime, &opts, numbers).unwrap();
+        assert_eq!(
+            "Time (in seconds): changes-a mean =, changes-b mean ↑0.005, \
+             changes-b/changes-a ↑0.050, conf width 0.740 ↓0.214\n",
+            strip_csi(&changes)
+        );
+    }
+
+    #[test]
+    fn sort_by() {
+        let mut stats = ExperimentMap::default();
+        for (name, samples) in [
+            ("sort-c", [10, 10, 70]),
+            ("sort-b", [40, 40, 40]),
+            ("sort-a", [0, 30, 30]),
+            ("sort-d", [35, 35, 90]),
+        ] {
+            let mut numbers = Numbers::new();
+            for t in samples {
+                numbers.push(t);
+            }
+            stats.insert(ExperimentName::intern(name), numbers.stats().unwrap());
+        }
+        let order_by_stat = |sort_by, stat| -> Vec<&str> {
+            display_order(&stats, sort_by, stat)
+                .iter()
+                .map(|n| n.name())
+                .collect()
+        };
+        let order = |sort_by| order_by_stat(sort_by, Stat::Mean);
+        assert_eq!(vec!["sort-c", "sort-b", "sort-a", "sort-d"], order(None));
+        assert_eq!(
+            vec!["sort-a", "sort-b", "sort-c", "sort-d"],
+            order(Some(SortBy::Name))
+        );
+        assert_eq!(
+            vec!["sort-a", "sort-c", "sort-b", "sort-d"],
+            order(Some(SortBy::Mean))
+        );
+        assert_eq!(
+            vec!["sort-c", "sort-a", "sort-d", "sort-b"],
+            order(Some(SortBy::Median))
+        );
+        assert_eq!(
+            vec!["sort-c", "sort-a", "sort-b", "sort-d"],
+            order(Some(SortBy::Ratio))
+        );
+        assert_eq!(
+            vec!["sort-c", "sort-a", "sort-d", "sort-b"],
+            order_by_stat(Some(SortBy::Ratio), Stat::Median)
+        );
+    }
+
+    #[test]
+    fn proportions_from_zero() {
+        let mut tests = ExperimentMap::default();
+        for (name, hits) in [("rate-a", [0; 20]), ("rate-b", [1; 20])] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for h in hits {
+                test.metrics.entry("hit".to_owned()).or_default().push(h);
+            }
+            tests.insert(name, test);
+        }
+        let metric = Metric::new("hit", true);
+        let summary =
+            render_proportions_summary(&tests, &metric, TWO_SIDED_95, |t| &t.metrics["hit"])
+                .unwrap();
+        assert_eq!(
+            "Metric hit (proportion):\n\
+             rate-b-rate-a: +100.0pp (p=0.0000) n=20/20: rate-b rate is higher\n",
+            strip_csi(&summary)
+        );
+    }
+
+    #[test]
+    fn proportions_at_confidence() {
+        let mut tests = ExperimentMap::default();
+        for (name, hits) in [("level-a", 10), ("level-b", 20)] {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            for i in 0..40 {
+                let hit = (i < hits) as u64;
+                test.metrics.entry("hit".to_owned()).or_default().push(hit);
+            }
+            tests.insert(name, test);
+        }
+        let metric = Metric::new("hit", true);
+        let summary = |conf| {
+            let summary =
+                render_proportions_summary(&tests, &metric, conf, |t| &t.metrics["hit"]).unwrap();
+            strip_csi(&summary)
+        };
+        assert_eq!(
+            "Metric hit (proportion):\n\
+             level-b-level-a: +25.0pp (p=0.0209) n=40/40: level-b rate is higher\n",
+            summary(TWO_SIDED_95)
+        );
+        assert_eq!(
+            "Metric hit (proportion):\n\
+             level-b-level-a: +25.0pp (p=0.0209) n=40/40: no significant difference\n",
+            summary(ConfInterval::C_99_5_99_0)
+        );
+    }
+}
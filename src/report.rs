@@ -0,0 +1,394 @@
+//! Reports of finished benchmarks and of recorded logs, and wall time ratios which
+//! decide when a running benchmark stops.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::slice;
+
+use crate::ansi;
+use crate::buckets::Buckets;
+use crate::buckets::render_buckets;
+use crate::console_writer::ConsoleWriter;
+use crate::distr_plot::PlotMark;
+use crate::distr_plot::PlotMode;
+use crate::environment::describe_environment;
+use crate::exit_code::ExitCode;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Stat;
+use crate::math::significance::SignificanceTest;
+use crate::math::stats::Percentiles;
+use crate::measure::key::MeasureKey;
+use crate::measure::tr::AllMeasures;
+use crate::render_stats::RenderOptions;
+use crate::render_stats::SortBy;
+use crate::render_stats::render_failures;
+use crate::render_stats::render_outliers;
+use crate::render_stats::render_sensors;
+use crate::run_log_reader::LoadedRunLog;
+use crate::run_log_reader::compare_run_logs;
+use crate::run_log_reader::merge_run_logs;
+use crate::run_log_reader::read_run_logs;
+use crate::run_order::render_run_order;
+use crate::student::ConfInterval;
+use crate::sweep::SWEEP_RESAMPLES;
+use crate::sweep::render_sweep;
+use crate::sweep::sweep_points;
+use crate::wrap::terminal_width;
+use crate::wrap::wrap_text;
+
+/// Short summary of verdicts, optionally followed by all the statistics.
+pub fn render_final_report(
+    measures: &AllMeasures,
+    experiments: &ExperimentMap<Experiment>,
+    render_opts: &RenderOptions,
+    details: bool,
+    explain: bool,
+    environment: bool,
+) -> anyhow::Result<String> {
+    let mut r = String::new();
+    writeln!(r, "Summary:")?;
+    r.push_str(&measures.render_summary(experiments, render_opts)?);
+    r.push_str(&render_buckets(
+        experiments,
+        &render_opts.buckets,
+        render_opts.conf,
+    )?);
+    r.push_str(&render_failures(experiments)?);
+    if explain {
+        writeln!(r)?;
+        writeln!(r, "Explanation:")?;
+        r.push_str(&measures.render_explain(experiments, render_opts)?);
+    }
+    if details {
+        writeln!(r)?;
+        writeln!(r, "Details:")?;
+        r.push_str(&measures.render_stats(experiments, render_opts)?);
+        r.push_str(&render_sensors(experiments)?);
+        r.push_str(&render_run_order(experiments)?);
+        if environment {
+            writeln!(r)?;
+            writeln!(r, "Environment:")?;
+            for (key, value) in describe_environment() {
+                writeln!(r, "{}: {}", key, value)?;
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// Shards missing from logs of a sharded benchmark, empty if none are missing.
+pub fn render_missing_shards(logs: &[LoadedRunLog]) -> anyhow::Result<String> {
+    let mut r = String::new();
+    let shards: Vec<_> = logs.iter().filter_map(|l| l.shard).collect();
+    if let Some(shard) = shards.first() {
+        let missing: Vec<String> = (1..=shard.count)
+            .filter(|&index| !shards.iter().any(|s| s.index == index))
+            .map(|index| format!("{}/{}", index, shard.count))
+            .collect();
+        if !missing.is_empty() {
+            writeln!(
+                r,
+                "{yellow}Missing shards: {missing}{reset}",
+                yellow = ansi::YELLOW,
+                missing = missing.join(", "),
+                reset = ansi::RESET,
+            )?;
+        }
+    }
+    Ok(r)
+}
+
+/// Report of a recorded log, as markdown if `md`.
+///
+/// Without `md`, outliers are listed after the report and text is wrapped to
+/// `opts.max_width`.
+pub fn render_log_report(
+    log: &LoadedRunLog,
+    opts: &RenderOptions,
+    filter_outliers: bool,
+    md: bool,
+    details: bool,
+    explain: bool,
+) -> anyhow::Result<String> {
+    for (name, experiment) in log.experiments.iter() {
+        if experiment.runs() < 2 {
+            return Err(anyhow::anyhow!(
+                "not enough runs of {} to compute statistics",
+                name
+            ));
+        }
+    }
+
+    let filtered;
+    let (experiments, outliers) = match filter_outliers {
+        true => {
+            filtered = log.experiments.map(|t| t.without_outliers(&log.measures));
+            (&filtered, render_outliers(&log.experiments, &filtered)?)
+        }
+        false => (&log.experiments, String::new()),
+    };
+
+    let measures = AllMeasures::new(&log.measures, &log.proportions, experiments);
+    if md {
+        return measures.render_markdown(experiments, &log.suite, opts);
+    }
+    let report =
+        render_final_report(&measures, experiments, opts, details, explain, false)? + &outliers;
+    Ok(wrap_text(&report, opts.max_width))
+}
+
+/// Wall time ratio intervals of every variant to A.
+pub fn wall_time_intervals(
+    experiments: &ExperimentMap<Experiment>,
+    stat: Stat,
+    conf: ConfInterval,
+) -> Vec<(ExperimentName, RatioInterval)> {
+    let numbers = experiments.map(|t| &t.measures[MeasureKey::WallTime]);
+    let mut numbers_iter = numbers.iter();
+    let (_, a) = numbers_iter.next().unwrap();
+    numbers_iter
+        .map(|(name, b)| (name, RatioInterval::compare(stat, conf, a, b)))
+        .collect()
+}
+
+/// First variant whose wall time ratio to A is certainly above `limit`.
+pub fn ratio_above(
+    experiments: &ExperimentMap<Experiment>,
+    stat: Stat,
+    conf: ConfInterval,
+    limit: f64,
+) -> Option<(ExperimentName, RatioInterval)> {
+    wall_time_intervals(experiments, stat, conf)
+        .into_iter()
+        // `max < min` when the interval is unbounded.
+        .find(|(_, interval)| interval.min > limit && interval.max >= interval.min)
+}
+
+/// Width of the widest wall time ratio interval, `None` if there is only one variant.
+pub fn widest_interval(
+    experiments: &ExperimentMap<Experiment>,
+    stat: Stat,
+    conf: ConfInterval,
+) -> Option<f64> {
+    wall_time_intervals(experiments, stat, conf)
+        .iter()
+        .map(|(_, interval)| match interval.max >= interval.min {
+            true => interval.max - interval.min,
+            false => f64::INFINITY,
+        })
+        .reduce(f64::max)
+}
+
+/// Options of `absh report`, `analyze`, `compare` and `sweep`.
+#[derive(clap::Args, Debug)]
+pub struct ReportOpts {
+    #[clap(long, help = "Combine shards recorded with `--shard` into one report")]
+    pub merge: bool,
+    #[clap(long, help = "Print full statistics and plots after the summary")]
+    pub details: bool,
+    #[clap(
+        long,
+        help = "Explain in plain English what the interval, p-value and n mean for each result, with caveats"
+    )]
+    pub explain: bool,
+    #[clap(long, help = "Also plot per-iteration differences against A")]
+    pub paired: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(100..),
+        help = "Also print percentile bootstrap intervals of mean and median ratios, computed from N resamples"
+    )]
+    pub bootstrap: Option<u32>,
+    #[clap(
+        long,
+        default_value = "auto",
+        value_name = "auto|halves|full|both",
+        help = "Distribution plots to print: halves plot for few samples, full plot, both, or pick by bar height"
+    )]
+    pub plot: PlotMode,
+    #[clap(
+        long,
+        value_name = "MEASURE=VALUE",
+        help = "Mark a reference value under distribution plots, like `time=1.5s`, `rss=200M` or `metric-NAME=N`, may be given many times"
+    )]
+    pub mark: Vec<PlotMark>,
+    #[clap(
+        long,
+        value_name = "DURATIONS",
+        help = "Report the share of runs of each variant with wall time under each threshold, like `100ms,500ms,1s`, and whether it differs from the first variant"
+    )]
+    pub buckets: Option<Buckets>,
+    #[clap(
+        long,
+        value_name = "METRIC",
+        help = "Report a metric as the rate of runs reporting `1` in addition to those declared when running, may be given many times"
+    )]
+    pub proportion: Vec<String>,
+    #[clap(
+        long,
+        value_name = "name|mean|median|ratio",
+        help = "Order of variants in statistics and plots, lowest first; the baseline stays first with `ratio`"
+    )]
+    pub sort_by: Option<SortBy>,
+    #[clap(
+        long,
+        default_value = "mean",
+        value_name = "mean|median",
+        help = "Compare variants by mean, or by median which rare slow runs do not move"
+    )]
+    pub stat: Stat,
+    #[clap(
+        long,
+        default_value = "75,90,99",
+        value_name = "P,...",
+        help = "Percentiles printed with statistics, empty for none"
+    )]
+    pub percentiles: Percentiles,
+    #[clap(
+        long,
+        default_value = "0.95",
+        value_name = "LEVEL",
+        help = "Confidence level of intervals: 0.90, 0.95, 0.99 or 0.999"
+    )]
+    pub confidence: ConfInterval,
+    #[clap(
+        long,
+        default_value = "welch",
+        value_name = "t|welch|mannwhitney|permutation",
+        help = "Hypothesis test of p-values of comparisons: Student's t, Welch's t, Mann-Whitney U or permutation test"
+    )]
+    pub significance_test: SignificanceTest,
+    #[clap(
+        long,
+        help = "Exclude runs outside of Tukey fences from statistics, raw data in the log keeps them"
+    )]
+    pub filter_outliers: bool,
+    #[clap(
+        long,
+        help = "Print statistics tables, ratios and distribution sparklines as GitHub-flavored markdown"
+    )]
+    pub md: bool,
+    #[clap(required = true, help = "Run log directories")]
+    pub dirs: Vec<PathBuf>,
+}
+
+/// Report of one log, or of shards combined with `--merge`.
+pub fn report_logs(opts: &ReportOpts) -> anyhow::Result<()> {
+    if !opts.merge && opts.dirs.len() != 1 {
+        return Err(anyhow::anyhow!("use `--merge` to combine several logs"));
+    }
+    print_report(&mut ConsoleWriter::auto(), opts, &opts.dirs, opts.details)
+}
+
+/// Full report of every log, or of all logs combined with `--merge`.
+pub fn analyze_logs(opts: &ReportOpts) -> anyhow::Result<()> {
+    let mut out = ConsoleWriter::auto();
+    if opts.merge {
+        return print_report(&mut out, opts, &opts.dirs, true);
+    }
+    for (i, dir) in opts.dirs.iter().enumerate() {
+        if opts.dirs.len() > 1 {
+            if i != 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "{}:", dir.display())?;
+        }
+        print_report(&mut out, opts, slice::from_ref(dir), true)?;
+    }
+    Ok(())
+}
+
+/// Compare the first variants of separately recorded logs as A, B, ...
+pub fn compare_logs(opts: &ReportOpts) -> anyhow::Result<()> {
+    if opts.merge {
+        return Err(anyhow::anyhow!("`--merge` cannot be used with `compare`"));
+    }
+    if !(2..=26).contains(&opts.dirs.len()) {
+        return Err(anyhow::anyhow!("`compare` needs from 2 to 26 logs"));
+    }
+    let logs = read_run_logs(&opts.dirs)?;
+    let mut out = ConsoleWriter::auto();
+    for (i, log) in logs.iter().enumerate() {
+        writeln!(
+            out,
+            "{}: {}",
+            ExperimentName::letter(i).name_colored(),
+            log.dir.display()
+        )?;
+    }
+    writeln!(out)?;
+    print_log_report(&mut out, opts, compare_run_logs(logs)?, opts.details)
+}
+
+/// Ratios of logs recorded at different parameter values, and their geometric means.
+pub fn sweep_logs(opts: &ReportOpts) -> anyhow::Result<ExitCode> {
+    if opts.merge {
+        return Err(anyhow::anyhow!("`--merge` cannot be used with `sweep`"));
+    }
+    if opts.dirs.len() < 2 {
+        return Err(anyhow::anyhow!("`sweep` needs at least 2 logs"));
+    }
+    let points = sweep_points(read_run_logs(&opts.dirs)?, opts.filter_outliers)?;
+
+    let render_opts = RenderOptions {
+        stat: opts.stat,
+        conf: opts.confidence,
+        ..RenderOptions::default()
+    };
+    let resamples = opts.bootstrap.unwrap_or(SWEEP_RESAMPLES);
+    let (text, combined) = render_sweep(&points, &render_opts, resamples)?;
+    let mut out = ConsoleWriter::auto();
+    write!(out, "{}", wrap_text(&text, terminal_width()))?;
+    Ok(ExitCode::from_verdicts(
+        combined.iter().map(|interval| interval.verdict()),
+    ))
+}
+
+/// Report of logs in `dirs` combined, with full statistics if `details`.
+fn print_report(
+    out: &mut ConsoleWriter,
+    opts: &ReportOpts,
+    dirs: &[PathBuf],
+    details: bool,
+) -> anyhow::Result<()> {
+    let logs = read_run_logs(dirs)?;
+    write!(out, "{}", render_missing_shards(&logs)?)?;
+    print_log_report(out, opts, merge_run_logs(logs)?, details)
+}
+
+fn print_log_report(
+    out: &mut ConsoleWriter,
+    opts: &ReportOpts,
+    mut log: LoadedRunLog,
+    details: bool,
+) -> anyhow::Result<()> {
+    log.proportions.extend(opts.proportion.iter().cloned());
+    let render_opts = RenderOptions {
+        include_distr: true,
+        max_width: terminal_width(),
+        paired: opts.paired,
+        bootstrap: opts.bootstrap,
+        plot: opts.plot,
+        sort_by: opts.sort_by,
+        stat: opts.stat,
+        percentiles: opts.percentiles.clone(),
+        conf: opts.confidence,
+        significance: opts.significance_test,
+        marks: opts.mark.clone(),
+        buckets: opts.buckets.clone().unwrap_or_default(),
+    };
+    let report = render_log_report(
+        &log,
+        &render_opts,
+        opts.filter_outliers,
+        opts.md,
+        details,
+        opts.explain,
+    )?;
+    write!(out, "{}", report)?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::sh::SH;
+use crate::sh::SH_FLAGS;
+use crate::shell::shell_quote;
+use crate::shell::shell_quote_args;
+
+fn invocation(test: &Experiment, script: &str) -> String {
+    let env = &test.env;
+    let mut args: Vec<String> = Vec::new();
+    if !env.is_empty() {
+        args.push("env".to_owned());
+        args.extend(env.iter().map(|(k, v)| format!("{}={}", k, v)));
+    }
+    args.extend([SH.to_owned(), SH_FLAGS.to_owned(), script.to_owned()]);
+    let command = format!("{} </dev/null", shell_quote_args(args));
+    match &test.dir {
+        Some(dir) => format!(
+            "(cd {} && {})",
+            shell_quote(&dir.to_string_lossy()),
+            command
+        ),
+        None => command,
+    }
+}
+
+/// Shell script running the first measured run of each experiment the way absh does.
+pub fn repro_script(experiments: &ExperimentMap<Experiment>, cwd: &Path) -> String {
+    let mut r = String::new();
+    writeln!(r, "#!/bin/sh").unwrap();
+    writeln!(
+        r,
+        "# Runs warmup and script of each variant once, like the first absh iteration."
+    )
+    .unwrap();
+    writeln!(r, "set -e").unwrap();
+    writeln!(r, "cd {}", shell_quote(&cwd.to_string_lossy())).unwrap();
+    for (name, test) in experiments.iter() {
+        writeln!(r).unwrap();
+        writeln!(r, "# {}", name).unwrap();
+        if !test.warmup.is_empty() {
+            writeln!(r, "{}", invocation(test, &test.warmup)).unwrap();
+        }
+        writeln!(r, "{}", invocation(test, &test.run)).unwrap();
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::repro::repro_script;
+
+    #[test]
+    fn script() {
+        let mut experiments = ExperimentMap::default();
+        let name = ExperimentName::intern("repro");
+        let mut test = Experiment::new(name, "make".to_owned(), "./bench 1".to_owned());
+        test.env.push(("ABSH_SCALE".to_owned(), "3".to_owned()));
+        experiments.insert(name, test);
+        let name = ExperimentName::intern("repro-dir");
+        let mut test = Experiment::new(name, String::new(), "./bench".to_owned());
+        test.dir = Some(PathBuf::from("build dir"));
+        experiments.insert(name, test);
+        assert_eq!(
+            "#!/bin/sh\n\
+             # Runs warmup and script of each variant once, like the first absh iteration.\n\
+             set -e\n\
+             cd '/tmp/my dir'\n\
+             \n\
+             # repro\n\
+             env ABSH_SCALE=3 /bin/sh -ec make </dev/null\n\
+             env ABSH_SCALE=3 /bin/sh -ec './bench 1' </dev/null\n\
+             \n\
+             # repro-dir\n\
+             (cd 'build dir' && /bin/sh -ec ./bench </dev/null)\n",
+            repro_script(&experiments, Path::new("/tmp/my dir"))
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! `absh review`: step through outlier samples of a run log and exclude some of them.
+//!
+//! Excluded samples are listed in `excluded.txt` of the log directory as
+//! `NAME INDEX` lines, where index is the position in raw data files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::duration::Duration;
+use crate::fs_util::write_using_temp;
+use crate::measure::key::MeasureKey;
+use crate::run_log_reader::LoadedRunLog;
+
+/// File in the run log directory listing excluded samples.
+pub const EXCLUDED_FILE: &str = "excluded.txt";
+
+/// What the run log says about one sample.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SampleInfo {
+    pub iteration: u64,
+    /// Unix time when the run finished.
+    pub time: u64,
+    /// Last lines of the script output.
+    pub output: Vec<String>,
+}
+
+/// Sample details logged after each successful run, by variant name and sample index.
+///
+/// Samples of the iteration excluded with `-i` are logged with the same indices
+/// as the first kept samples, later lines win.
+pub fn parse_sample_info(log: &str) -> BTreeMap<(String, usize), SampleInfo> {
+    let mut samples = BTreeMap::new();
+    let mut last: BTreeMap<String, usize> = BTreeMap::new();
+    for line in log.lines() {
+        if let Some((name, rest)) = line.split_once(".sample: ") {
+            let mut fields = rest.split(' ');
+            let index = fields.next().and_then(|i| i.parse().ok());
+            let mut info = SampleInfo::default();
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("iteration", n)) => info.iteration = n.parse().unwrap_or(0),
+                    Some(("time", n)) => info.time = n.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+            if let Some(index) = index {
+                samples.insert((name.to_owned(), index), info);
+                last.insert(name.to_owned(), index);
+            }
+        } else if let Some((name, output)) = line.split_once(".output: ") {
+            if let Some(&index) = last.get(name) {
+                let info = samples.get_mut(&(name.to_owned(), index)).unwrap();
+                info.output.push(output.to_owned());
+            }
+        }
+    }
+    samples
+}
+
+/// Samples excluded in previous reviews, empty if there were none.
+pub fn read_excluded(dir: &Path) -> anyhow::Result<Vec<(String, usize)>> {
+    let path = dir.join(EXCLUDED_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let (name, index) = l
+                .split_once(' ')
+                .with_context(|| format!("invalid line in {}: {:?}", path.display(), l))?;
+            let index = index
+                .parse()
+                .with_context(|| format!("invalid line in {}: {:?}", path.display(), l))?;
+            Ok((name.to_owned(), index))
+        })
+        .collect()
+}
+
+pub fn write_excluded(dir: &Path, excluded: &[(String, usize)]) -> anyhow::Result<()> {
+    let mut content = String::new();
+    for (name, index) in excluded {
+        content.push_str(&format!("{} {}\n", name, index));
+    }
+    write_using_temp(dir.join(EXCLUDED_FILE), content)
+}
+
+/// Ask about every wall time sample outside of Tukey fences whether to exclude it.
+///
+/// Empty answer keeps the current decision, `q` keeps it for all remaining samples.
+/// Returns all excluded samples.
+pub fn review(
+    log: &LoadedRunLog,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> anyhow::Result<Vec<(String, usize)>> {
+    let infos = fs::read_to_string(log.dir.join("log"))
+        .map(|l| parse_sample_info(&l))
+        .unwrap_or_default();
+    let first_time = infos.values().map(|i| i.time).min();
+
+    let mut excluded = log.excluded.clone();
+    let mut candidates = 0;
+    'review: for (name, test) in log.experiments.iter() {
+        let numbers = &test.measures[MeasureKey::WallTime];
+        let (lo, hi) = match numbers.tukey_fences() {
+            Some(fences) => fences,
+            None => continue,
+        };
+        let median = Duration::from_nanos(numbers.med().unwrap());
+        for (index, n) in numbers.iter().enumerate() {
+            if lo <= n as f64 && n as f64 <= hi {
+                continue;
+            }
+            candidates += 1;
+            let key = (name.name().to_owned(), index);
+            let is_excluded = excluded.contains(&key);
+            writeln!(out)?;
+            write!(
+                out,
+                "{} sample {}: {:3} s, median {:3} s",
+                name.name_colored(),
+                index,
+                Duration::from_nanos(n),
+                median
+            )?;
+            if let Some(info) = infos.get(&key) {
+                write!(out, ", iteration {}", info.iteration)?;
+                if let Some(first_time) = first_time {
+                    write!(out, ", {} s after the first sample", info.time - first_time)?;
+                }
+                writeln!(out)?;
+                for line in &info.output {
+                    writeln!(out, "    {}", line)?;
+                }
+            } else {
+                writeln!(out)?;
+            }
+            loop {
+                match is_excluded {
+                    true => write!(out, "Exclude? [Y/n/q] ")?,
+                    false => write!(out, "Exclude? [y/N/q] ")?,
+                }
+                out.flush()?;
+                let mut answer = String::new();
+                if input.read_line(&mut answer)? == 0 {
+                    break 'review;
+                }
+                let exclude = match answer.trim() {
+                    "" => is_excluded,
+                    "y" | "Y" => true,
+                    "n" | "N" => false,
+                    "q" | "Q" => break 'review,
+                    _ => {
+                        writeln!(out, "expecting y, n or q")?;
+                        continue;
+                    }
+                };
+                excluded.retain(|e| e != &key);
+                if exclude {
+                    excluded.push(key);
+                }
+                break;
+            }
+        }
+    }
+    if candidates == 0 {
+        writeln!(out, "No outliers in {}", log.dir.display())?;
+    }
+    excluded.sort();
+    Ok(excluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+    use crate::metadata::Metadata;
+    use crate::review::SampleInfo;
+    use crate::review::parse_sample_info;
+    use crate::review::review;
+    use crate::run_log_reader::LoadedRunLog;
+
+    #[test]
+    fn sample_info() {
+        let log = "\
+A.sample: 0 iteration=1 time=100
+A.output: ignored
+A.sample: 0 iteration=2 time=105
+A.output: hello
+B.sample: 0 iteration=2 time=107
+A.output: world
+";
+        let infos = parse_sample_info(log);
+        assert_eq!(
+            Some(&SampleInfo {
+                iteration: 2,
+                time: 105,
+                output: vec!["hello".to_owned(), "world".to_owned()],
+            }),
+            infos.get(&("A".to_owned(), 0))
+        );
+        assert_eq!(2, infos.len());
+    }
+
+    #[test]
+    fn review_outliers() {
+        let name = ExperimentName::intern("review-a");
+        let mut test = Experiment::new(name, String::new(), String::new());
+        for n in [100, 101, 102, 103, 104, 500] {
+            test.measures[MeasureKey::WallTime].push(n * 1_000_000);
+        }
+        let mut experiments = ExperimentMap::default();
+        experiments.insert(name, test);
+        let log = LoadedRunLog {
+            dir: PathBuf::from("/nonexistent"),
+            shard: None,
+            experiments,
+            suite: Metadata::default(),
+            measures: vec![MeasureKey::WallTime],
+            proportions: Vec::new(),
+            excluded: Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        let excluded = review(&log, &mut &b"x\ny\n"[..], &mut out).unwrap();
+        assert_eq!(vec![("review-a".to_owned(), 5)], excluded);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("sample 5: 0.500 s, median 0.102 s"), "{}", out);
+    }
+}
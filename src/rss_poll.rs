@@ -0,0 +1,241 @@
+//! Peak RSS of a script's process tree sampled from `/proc`, for systems where
+//! `wait4` does not report max RSS, or of its process group with `--mem-tree`.
+//! Thread and file descriptor counts are sampled along for `--threads-fds`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time;
+
+/// Time between samples.
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// `VmRSS` in bytes from `/proc/<pid>/status` content.
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line["VmRSS:".len()..]
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// `Threads` from `/proc/<pid>/status` content.
+fn parse_threads(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("Threads:"))?;
+    line["Threads:".len()..].trim().parse().ok()
+}
+
+/// Pids in `/proc/<pid>/task/<pid>/children` content.
+fn parse_children(children: &str) -> Vec<u32> {
+    children
+        .split_whitespace()
+        .filter_map(|p| p.parse().ok())
+        .collect()
+}
+
+/// Parent pid and process group from `/proc/<pid>/stat` content.
+fn parse_stat(stat: &str) -> Option<(u32, u32)> {
+    // The command name in parentheses may contain spaces and parentheses.
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    let ppid = fields.nth(1)?.parse().ok()?;
+    let pgrp = fields.next()?.parse().ok()?;
+    Some((ppid, pgrp))
+}
+
+/// Pid, parent pid and process group of every process.
+fn scan_processes() -> Vec<(u32, u32, u32)> {
+    let mut processes = Vec::new();
+    for entry in fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let pid = match entry.file_name().to_str().and_then(|p| p.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let stat = fs::read_to_string(entry.path().join("stat")).unwrap_or_default();
+        if let Some((ppid, pgrp)) = parse_stat(&stat) {
+            processes.push((pid, ppid, pgrp));
+        }
+    }
+    processes
+}
+
+/// Children of every process, for kernels without `/proc/<pid>/task/<tid>/children`.
+fn scan_children() -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, ppid, _) in scan_processes() {
+        children.entry(ppid).or_default().push(pid);
+    }
+    children
+}
+
+/// Sum of peaks of processes sampled together.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ProcPeaks {
+    /// Bytes.
+    pub rss: u64,
+    pub threads: u64,
+    /// Open file descriptors, zero unless counted.
+    pub fds: u64,
+}
+
+impl ProcPeaks {
+    fn max(self, other: ProcPeaks) -> ProcPeaks {
+        ProcPeaks {
+            rss: u64::max(self.rss, other.rss),
+            threads: u64::max(self.threads, other.threads),
+            fds: u64::max(self.fds, other.fds),
+        }
+    }
+}
+
+/// Current usage summed over `pids`, skipping processes which are gone.
+fn usage(pids: &[u32], count_fds: bool) -> ProcPeaks {
+    let mut total = ProcPeaks::default();
+    for &pid in pids {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+        total.rss += parse_vm_rss(&status).unwrap_or(0);
+        total.threads += parse_threads(&status).unwrap_or(0);
+        if count_fds {
+            total.fds += fs::read_dir(format!("/proc/{}/fd", pid)).map_or(0, |d| d.count() as u64);
+        }
+    }
+    total
+}
+
+/// Processes in group `pgid`, including orphaned grandchildren of the group
+/// leader, but not processes which started their own group.
+fn group_pids(pgid: u32) -> Vec<u32> {
+    scan_processes()
+        .into_iter()
+        .filter(|&(_, _, pgrp)| pgrp == pgid)
+        .map(|(pid, _, _)| pid)
+        .collect()
+}
+
+/// `root` and its descendants.
+///
+/// Children started by threads other than the main one are not found.
+fn tree_pids(root: u32) -> Vec<u32> {
+    let scanned = match Path::new(&format!("/proc/{}/task/{}/children", root, root)).exists() {
+        true => None,
+        false => Some(scan_children()),
+    };
+    let mut tree = Vec::new();
+    let mut pids = vec![root];
+    while let Some(pid) = pids.pop() {
+        tree.push(pid);
+        match &scanned {
+            Some(scanned) => pids.extend(scanned.get(&pid).into_iter().flatten()),
+            None => {
+                let children = fs::read_to_string(format!("/proc/{}/task/{}/children", pid, pid))
+                    .unwrap_or_default();
+                pids.extend(parse_children(&children));
+            }
+        }
+    }
+    tree
+}
+
+/// Thread sampling usage of a process tree until finished.
+pub struct RssPoller {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<ProcPeaks>,
+}
+
+impl RssPoller {
+    /// `/proc` has process status.
+    pub fn available() -> bool {
+        Path::new("/proc/self/status").exists()
+    }
+
+    /// Start sampling the tree of `pid` in a background thread, counting open
+    /// file descriptors with `count_fds`.
+    pub fn start(pid: u32, count_fds: bool) -> RssPoller {
+        RssPoller::spawn(move || usage(&tree_pids(pid), count_fds))
+    }
+
+    /// Start sampling process group `pgid` in a background thread.
+    pub fn start_group(pgid: u32, count_fds: bool) -> RssPoller {
+        RssPoller::spawn(move || usage(&group_pids(pgid), count_fds))
+    }
+
+    fn spawn(sample: impl Fn() -> ProcPeaks + Send + 'static) -> RssPoller {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut peaks = ProcPeaks::default();
+            loop {
+                peaks = peaks.max(sample());
+                match stopped.recv_timeout(POLL_INTERVAL) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    _ => return peaks,
+                }
+            }
+        });
+        RssPoller { stop, thread }
+    }
+
+    /// Peaks, zero if the processes exited before the first sample.
+    pub fn finish(self) -> ProcPeaks {
+        let _ = self.stop.send(());
+        self.thread.join().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::os::unix::process::CommandExt;
+    #[cfg(unix)]
+    use std::process::Command;
+
+    #[cfg(unix)]
+    use crate::rss_poll::RssPoller;
+    use crate::rss_poll::parse_children;
+    use crate::rss_poll::parse_stat;
+    use crate::rss_poll::parse_threads;
+    use crate::rss_poll::parse_vm_rss;
+
+    #[test]
+    fn parse() {
+        let status = "Name:\tsh\nVmHWM:\t    1664 kB\nVmRSS:\t    1536 kB\nThreads:\t1\n";
+        assert_eq!(Some(1536 * 1024), parse_vm_rss(status));
+        assert_eq!(None, parse_vm_rss("Name:\tkthreadd\n"));
+        assert_eq!(Some(1), parse_threads(status));
+        assert_eq!(vec![12, 345], parse_children("12 345 "));
+        assert!(parse_children("").is_empty());
+        assert_eq!(Some((7, 40)), parse_stat("42 (a) b)) S 7 40 40 0 -1"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn poll() {
+        if !RssPoller::available() {
+            return;
+        }
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 0.1; :"])
+            .spawn()
+            .unwrap();
+        let poller = RssPoller::start(child.id(), true);
+        child.wait().unwrap();
+        let peaks = poller.finish();
+        assert!(peaks.rss > 0);
+        assert!(peaks.threads > 0);
+        assert!(peaks.fds > 0);
+
+        // The orphaned `sleep` stays in the group after the shell exits.
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 0.3 & exit"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        child.wait().unwrap();
+        assert_eq!(0, RssPoller::start(child.id(), false).finish().rss);
+        assert!(RssPoller::start_group(child.id(), false).finish().rss > 0);
+    }
+}
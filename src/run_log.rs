@@ -3,6 +3,7 @@ use std::fmt;
 use std::fmt::Write as _;
 use std::fs;
 use std::fs::File;
+#[cfg(unix)]
 use std::os::unix;
 use std::path::Path;
 use std::path::PathBuf;
@@ -10,20 +11,31 @@ use std::time::SystemTime;
 
 use crate::ansi::strip_csi;
 use crate::console_writer::ConsoleWriter;
+use crate::durability::Durability;
+use crate::experiment::Experiment;
+use crate::experiment_name::ExperimentName;
+use crate::fs_util::sync_dir_of;
 use crate::fs_util::write_using_temp;
+use crate::fs_util::write_using_temp_synced;
+use crate::input::PinnedInputs;
 use crate::math::numbers::Numbers;
 use crate::maybe_strip_csi_writer::MaybeStripCsiWriter;
+use crate::raw_format::format_raw;
+use crate::shard::Shard;
 use crate::shell::shell_quote_args;
+use crate::version::version_long;
 
 pub struct RunLog {
     name: PathBuf,
     last: Option<PathBuf>,
     file: File,
     console_writer: ConsoleWriter,
+    durability: Durability,
 }
 
 pub struct BothLogAndStderr<'a> {
     log: &'a mut RunLog,
+    stderr: bool,
 }
 
 impl RunLog {
@@ -35,11 +47,22 @@ impl RunLog {
         self.last.as_deref()
     }
 
-    pub fn open() -> RunLog {
+    /// Directory for run logs when `--log-dir` is not given: `ABSH_LOG_DIR`,
+    /// then `$XDG_STATE_HOME/absh/logs`, then `~/.absh/logs`.
+    pub fn default_logs_dir() -> PathBuf {
+        let non_empty = |var| env::var_os(var).filter(|v| !v.is_empty());
+        if let Some(dir) = non_empty("ABSH_LOG_DIR") {
+            return PathBuf::from(dir);
+        }
+        if let Some(state) = non_empty("XDG_STATE_HOME") {
+            return Path::new(&state).join("absh/logs");
+        }
         let home_dir = dirs::home_dir().expect("home_dir not found");
-        let mut absh_logs_dir = home_dir.clone();
-        absh_logs_dir.push(".absh/logs");
-        let mut name = absh_logs_dir.clone();
+        home_dir.join(".absh/logs")
+    }
+
+    pub fn open(absh_logs_dir: &Path) -> RunLog {
+        let mut name = absh_logs_dir.to_owned();
         let id = format!(
             "{}",
             SystemTime::now()
@@ -49,16 +72,18 @@ impl RunLog {
         );
         name.push(&id);
 
-        fs::create_dir_all(&name).expect(&format!("failed to create dir {}", name.display()));
+        fs::create_dir_all(&name)
+            .unwrap_or_else(|e| panic!("failed to create dir {}: {}", name.display(), e));
 
         let mut log = name.clone();
         log.push("log");
 
-        let file = File::create(&log).expect(&format!("failed to create {}", log.display()));
+        let file = File::create(&log)
+            .unwrap_or_else(|e| panic!("failed to create {}: {}", log.display(), e));
 
         #[cfg(unix)]
         let last = {
-            let mut last = absh_logs_dir.clone();
+            let mut last = absh_logs_dir.to_owned();
             last.push("last");
 
             let _ = fs::remove_file(&last);
@@ -73,11 +98,20 @@ impl RunLog {
             name,
             file,
             last,
+            durability: Durability::default(),
+        }
+    }
+
+    pub fn both_log_and_stderr(&mut self) -> BothLogAndStderr<'_> {
+        BothLogAndStderr {
+            log: self,
+            stderr: true,
         }
     }
 
-    pub fn both_log_and_stderr(&mut self) -> BothLogAndStderr {
-        BothLogAndStderr { log: self }
+    /// Like `both_log_and_stderr`, but to the log only unless `stderr` is true.
+    pub fn log_and_stderr_if(&mut self, stderr: bool) -> BothLogAndStderr<'_> {
+        BothLogAndStderr { log: self, stderr }
     }
 
     pub fn log_only(&mut self) -> impl fmt::Write + '_ {
@@ -91,35 +125,94 @@ impl RunLog {
         &mut self.console_writer
     }
 
-    pub fn write_raw(&mut self, id: &str, durations: &[&Numbers]) -> anyhow::Result<()> {
-        let mut content = String::new();
-        fn join(r: &mut String, ds: &Numbers) -> anyhow::Result<()> {
-            for (i, d) in ds.iter().enumerate() {
-                if i != 0 {
-                    write!(r, " ")?;
-                }
-                write!(r, "{}", d)?;
-            }
-            write!(r, "\n")?;
-            Ok(())
+    /// Record scripts, settings and metadata of the experiment.
+    pub fn write_experiment(&mut self, t: &Experiment) -> anyhow::Result<()> {
+        let metadata = t.metadata.log_lines(&format!("{}.", t.name));
+        for line in t.log_lines().into_iter().chain(metadata) {
+            writeln!(self.log_only(), "{}", line)?;
         }
+        Ok(())
+    }
+
+    /// Write the log to disk, so its writeback does not overlap the next run.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// How far `write_raw` and `sync` write samples.
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
 
-        for d in durations {
-            join(&mut content, d)?;
+    /// Write the log to disk as far as the durability requires, after `write_raw`.
+    pub fn sync(&mut self) -> anyhow::Result<()> {
+        match self.durability {
+            Durability::None => {}
+            Durability::Flush => self.file.sync_data()?,
+            Durability::Fsync => {
+                self.file.sync_all()?;
+                sync_dir_of(&self.name.join("log"))?;
+            }
         }
+        Ok(())
+    }
+
+    pub fn write_raw(
+        &mut self,
+        id: &str,
+        durations: &[(ExperimentName, &Numbers)],
+    ) -> anyhow::Result<()> {
+        self.write_raw_rows(id, durations.iter().map(|(name, d)| (*name, d.raw())))
+    }
 
-        write_using_temp(self.name.join(format!("raw-{}.txt", id)), content)?;
+    /// Write numbers which are not samples, like run indices, in the raw data format.
+    pub fn write_raw_rows<'a>(
+        &mut self,
+        id: &str,
+        rows: impl IntoIterator<Item = (ExperimentName, &'a [u64])>,
+    ) -> anyhow::Result<()> {
+        let content = format_raw(rows);
+        let path = self.name.join(format!("raw-{}.txt", id));
+        match self.durability {
+            Durability::None => write_using_temp(path, content)?,
+            Durability::Flush | Durability::Fsync => write_using_temp_synced(path, content)?,
+        }
         Ok(())
     }
 
+    /// Save stdout and stderr of a run of `name` in `output/NAME-ITERATION.stdout` and
+    /// `.stderr`, skipping empty ones, and return the paths written.
+    pub fn write_output(
+        &mut self,
+        name: ExperimentName,
+        iteration: u64,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = self.name.join("output");
+        let mut paths = Vec::new();
+        for (ext, contents) in [("stdout", stdout), ("stderr", stderr)] {
+            if contents.is_empty() {
+                continue;
+            }
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{}-{}.{}", name, iteration, ext));
+            fs::write(&path, contents)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
     pub fn write_graph(&mut self, graph: &str) -> anyhow::Result<()> {
         write_using_temp(self.name.join("graph.txt"), graph)?;
         write_using_temp(self.name.join("graph-bw.txt"), strip_csi(graph))?;
 
         let report_md = format!(
-            "```\n{}\n```\n```\n{}```\n",
+            "```\n{}\n```\n```\n{}```\n\nabsh {}\n",
             Self::args_str(),
             strip_csi(graph),
+            version_long(),
         );
         write_using_temp(self.name.join("report.md"), report_md)?;
         Ok(())
@@ -129,17 +222,51 @@ impl RunLog {
         shell_quote_args(env::args())
     }
 
-    pub fn write_args(&mut self) -> anyhow::Result<()> {
-        let mut args = Self::args_str();
-        args.push_str("\n");
+    /// Record the command line, `args` when the benchmark was started by another command.
+    pub fn write_args(&mut self, args: Option<&[String]>) -> anyhow::Result<()> {
+        let mut args = match args {
+            Some(args) => shell_quote_args(args),
+            None => Self::args_str(),
+        };
+        args.push('\n');
         write_using_temp(self.name.join("args.txt"), args)?;
         Ok(())
     }
+
+    pub fn write_shard(&mut self, shard: Shard) -> anyhow::Result<()> {
+        write_using_temp(self.name.join("shard.txt"), format!("{}\n", shard))?;
+        writeln!(self.log_only(), "shard: {}", shard)?;
+        Ok(())
+    }
+
+    pub fn write_inputs(&mut self, inputs: &PinnedInputs) -> anyhow::Result<()> {
+        if inputs.inputs.is_empty() {
+            return Ok(());
+        }
+        let mut content = String::new();
+        for line in inputs.lines() {
+            writeln!(self.log_only(), "input: {}", line)?;
+            writeln!(content, "{}", line)?;
+        }
+        write_using_temp(self.name.join("inputs.txt"), content)?;
+        Ok(())
+    }
+
+    pub fn write_version(&mut self) -> anyhow::Result<()> {
+        write_using_temp(
+            self.name.join("version.txt"),
+            format!("{}\n", version_long()),
+        )?;
+        writeln!(self.log_only(), "absh_version: {}", version_long())?;
+        Ok(())
+    }
 }
 
 impl fmt::Write for BothLogAndStderr<'_> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.log.console_writer.write_str(s)?;
+        if self.stderr {
+            self.log.console_writer.write_str(s)?;
+        }
         write!(self.log.log_only(), "{}", s)?;
         Ok(())
     }
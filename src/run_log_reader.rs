@@ -0,0 +1,332 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::measure::key::MeasureKey;
+use crate::measure::tr::METRIC_RUNS_PREFIX;
+use crate::metadata::Metadata;
+use crate::raw_format::parse_raw;
+use crate::review::read_excluded;
+use crate::shard::Shard;
+
+/// Run log previously written by [`RunLog`](crate::run_log::RunLog).
+pub struct LoadedRunLog {
+    pub dir: PathBuf,
+    pub shard: Option<Shard>,
+    pub experiments: ExperimentMap<Experiment>,
+    /// Description, owner and links of the whole benchmark.
+    pub suite: Metadata,
+    /// Measures which have raw data in the log.
+    pub measures: Vec<MeasureKey>,
+    /// Metrics declared with `--proportion`.
+    pub proportions: Vec<String>,
+    /// Samples excluded in `absh review`, by variant name and index in raw data.
+    pub excluded: Vec<(String, usize)>,
+}
+
+impl LoadedRunLog {
+    /// Drop samples excluded in `absh review` from experiments.
+    pub fn without_excluded(mut self) -> LoadedRunLog {
+        let excluded = &self.excluded;
+        self.experiments = self.experiments.map(|t| {
+            let indices: Vec<usize> = excluded
+                .iter()
+                .filter(|(name, _)| name == t.name.name())
+                .map(|&(_, index)| index)
+                .collect();
+            t.without_runs(&indices)
+        });
+        self.excluded.clear();
+        self
+    }
+}
+
+fn read_scripts(log: &str, name: ExperimentName) -> (String, String) {
+    let field = |suffix: &str| {
+        let prefix = format!("{}.{}: ", name, suffix);
+        log.lines()
+            .find_map(|l| l.strip_prefix(&prefix))
+            .unwrap_or("")
+            .to_owned()
+    };
+    (field("warmup"), field("run"))
+}
+
+pub fn read_run_log(dir: &Path) -> anyhow::Result<LoadedRunLog> {
+    let mut experiments: ExperimentMap<Experiment> = ExperimentMap::default();
+    let mut measures = Vec::new();
+    let mut raw_files = Vec::new();
+    let log = fs::read_to_string(dir.join("log")).unwrap_or_default();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let file_name = entry?.file_name();
+        if let Some(id) = file_name
+            .to_str()
+            .and_then(|n| n.strip_prefix("raw-"))
+            .and_then(|n| n.strip_suffix(".txt"))
+        {
+            raw_files.push(id.to_owned());
+        }
+    }
+    raw_files.sort();
+
+    for id in raw_files {
+        if let Some(metric) = id.strip_prefix(METRIC_RUNS_PREFIX) {
+            let path = dir.join(format!("raw-{}.txt", id));
+            let content =
+                fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            let rows = parse_raw(&content).with_context(|| format!("in {}", path.display()))?;
+            for (name, runs) in rows {
+                if let Some(experiment) = experiments.get_mut(name) {
+                    experiment.metric_runs.insert(metric.to_owned(), runs);
+                }
+            }
+            continue;
+        }
+        let key = MeasureKey::ALL.iter().copied().find(|k| k.id() == id);
+        let metric = id.strip_prefix("metric-");
+        if key.is_none() && metric.is_none() {
+            continue;
+        }
+        if let Some(key) = key {
+            measures.push(key);
+        }
+
+        let path = dir.join(format!("raw-{}.txt", id));
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        let rows = parse_raw(&content).with_context(|| format!("in {}", path.display()))?;
+        for (name, numbers) in rows {
+            if experiments.get(name).is_none() {
+                let (warmup, run) = read_scripts(&log, name);
+                let mut experiment = Experiment::new(name, warmup, run);
+                experiment.metadata = Metadata::from_log(&log, &format!("{}.", name));
+                experiments.insert(name, experiment);
+            }
+            let experiment = &mut experiments[name];
+            let target = match (key, metric) {
+                (Some(key), _) => &mut experiment.measures[key],
+                (None, Some(metric)) => experiment.metrics.entry(metric.to_owned()).or_default(),
+                (None, None) => unreachable!(),
+            };
+            for n in numbers {
+                target.push(n);
+            }
+        }
+    }
+    if measures.is_empty() {
+        return Err(anyhow::anyhow!("no raw data in {}", dir.display()));
+    }
+
+    let shard = match fs::read_to_string(dir.join("shard.txt")) {
+        Ok(s) => Some(s.trim().parse()?),
+        Err(_) => None,
+    };
+
+    Ok(LoadedRunLog {
+        dir: dir.to_owned(),
+        shard,
+        experiments,
+        suite: Metadata::from_log(&log, ""),
+        measures,
+        proportions: log
+            .lines()
+            .filter_map(|l| l.strip_prefix("proportion: ").map(str::to_owned))
+            .collect(),
+        excluded: read_excluded(dir)?,
+    })
+}
+
+/// Read logs in every one of `dirs`.
+pub fn read_run_logs(dirs: &[PathBuf]) -> anyhow::Result<Vec<LoadedRunLog>> {
+    dirs.iter().map(|dir| read_run_log(dir)).collect()
+}
+
+/// Combine logs of shards of the same benchmark into one, without samples excluded in review.
+pub fn merge_run_logs(logs: Vec<LoadedRunLog>) -> anyhow::Result<LoadedRunLog> {
+    let mut logs = logs.into_iter().map(LoadedRunLog::without_excluded);
+    let mut merged = logs.next().context("no logs to merge")?;
+    let mut shards: Vec<Shard> = merged.shard.into_iter().collect();
+    for log in logs {
+        let names: Vec<_> = log.experiments.keys().collect();
+        if names != merged.experiments.keys().collect::<Vec<_>>() {
+            return Err(anyhow::anyhow!(
+                "{} and {} have different experiments",
+                merged.dir.display(),
+                log.dir.display()
+            ));
+        }
+        if let Some(shard) = log.shard {
+            if shards.contains(&shard) {
+                return Err(anyhow::anyhow!(
+                    "shard {} is given more than once ({})",
+                    shard,
+                    log.dir.display()
+                ));
+            }
+            if shards.iter().any(|s| s.count != shard.count) {
+                return Err(anyhow::anyhow!(
+                    "shard {} of {} has different shard count",
+                    shard,
+                    log.dir.display()
+                ));
+            }
+            shards.push(shard);
+        }
+        merged.measures.retain(|k| log.measures.contains(k));
+        for (name, exp) in log.experiments.iter() {
+            merged.experiments[name].append_samples(exp, &merged.measures);
+        }
+    }
+    merged.shard = None;
+    Ok(merged)
+}
+
+/// Combine the first variant of each log into one log, as variants A, B, ...
+///
+/// Only measures and metrics recorded in all logs are kept.
+pub fn compare_run_logs(logs: Vec<LoadedRunLog>) -> anyhow::Result<LoadedRunLog> {
+    let logs: Vec<LoadedRunLog> = logs
+        .into_iter()
+        .map(LoadedRunLog::without_excluded)
+        .collect();
+    let first = logs.first().context("no logs to compare")?;
+    let mut measures = first.measures.clone();
+    let mut experiments = ExperimentMap::default();
+    for (i, log) in logs.iter().enumerate() {
+        measures.retain(|k| log.measures.contains(k));
+        let exp = log
+            .experiments
+            .values()
+            .next()
+            .with_context(|| format!("no variants in {}", log.dir.display()))?;
+        let name = ExperimentName::letter(i);
+        let mut exp = exp.snapshot();
+        exp.name = name;
+        experiments.insert(name, exp);
+    }
+    let metrics: Vec<String> = experiments
+        .values()
+        .flat_map(|t| t.metrics.keys())
+        .filter(|m| experiments.values().all(|t| t.metrics.contains_key(*m)))
+        .cloned()
+        .collect();
+    for exp in experiments.values_mut() {
+        exp.metrics.retain(|m, _| metrics.contains(m));
+        exp.metric_runs.retain(|m, _| metrics.contains(m));
+    }
+    Ok(LoadedRunLog {
+        dir: first.dir.clone(),
+        shard: None,
+        experiments,
+        suite: Metadata::default(),
+        measures,
+        proportions: first.proportions.clone(),
+        excluded: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use crate::experiment::Experiment;
+    use crate::experiment_name::ExperimentName;
+    use crate::measure::key::MeasureKey;
+    use crate::run_log::RunLog;
+    use crate::run_log_reader::LoadedRunLog;
+    use crate::run_log_reader::merge_run_logs;
+    use crate::run_log_reader::read_run_log;
+    use crate::shard::Shard;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("absh-{}-{}", name, std::process::id()))
+    }
+
+    /// Write a run log under `logs_dir` like a benchmark does, returns its directory.
+    fn write_log(logs_dir: &Path, shard: Option<&str>, runs: &[(&str, &[u64])]) -> PathBuf {
+        let mut log = RunLog::open(logs_dir);
+        for (name, _) in runs {
+            let name = ExperimentName::intern(name);
+            let test = Experiment::new(name, "make".to_owned(), format!("./bench-{}", name));
+            log.write_experiment(&test).unwrap();
+        }
+        let rows = runs.iter().map(|(n, r)| (ExperimentName::intern(n), *r));
+        log.write_raw_rows(MeasureKey::WallTime.id(), rows).unwrap();
+        if let Some(shard) = shard {
+            log.write_shard(shard.parse::<Shard>().unwrap()).unwrap();
+        }
+        log.name().to_owned()
+    }
+
+    fn wall_times(log: &LoadedRunLog, name: &str) -> Vec<u64> {
+        log.experiments[ExperimentName::intern(name)].measures[MeasureKey::WallTime]
+            .raw()
+            .to_vec()
+    }
+
+    #[test]
+    fn read_back() {
+        let dir = temp_dir("reader-read-back");
+        let path = write_log(&dir, Some("1/2"), &[("A", &[1, 2, 3]), ("B", &[4, 5])]);
+        let log = read_run_log(&path).unwrap();
+        assert_eq!(vec![MeasureKey::WallTime], log.measures);
+        assert_eq!(Some("1/2".parse().unwrap()), log.shard);
+        assert_eq!(vec![1, 2, 3], wall_times(&log, "A"));
+        assert_eq!(vec![4, 5], wall_times(&log, "B"));
+        let a = &log.experiments[ExperimentName::intern("A")];
+        assert_eq!(("make", "./bench-A"), (&*a.warmup, &*a.run));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_raw_format() {
+        let dir = temp_dir("reader-newer");
+        let path = write_log(&dir, None, &[("A", &[1])]);
+        fs::write(
+            path.join("raw-wall-time.txt"),
+            "# absh-raw-format: 99\nA: 1\n",
+        )
+        .unwrap();
+        let e = read_run_log(&path).err().unwrap();
+        assert!(format!("{:#}", e).contains("newer absh"), "{:#}", e);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_shards() {
+        let dir = temp_dir("reader-merge");
+        let log = |shard: &str, runs: &[(&str, &[u64])]| {
+            // Logs opened in the same second need separate parent directories.
+            let logs_dir = dir.join(shard.replace('/', "-"));
+            read_run_log(&write_log(&logs_dir, Some(shard), runs)).unwrap()
+        };
+        let merged = merge_run_logs(vec![
+            log("1/2", &[("A", &[1, 2]), ("B", &[3])]),
+            log("2/2", &[("A", &[4]), ("B", &[5, 6])]),
+        ])
+        .unwrap();
+        assert_eq!(None, merged.shard);
+        assert_eq!(vec![1, 2, 4], wall_times(&merged, "A"));
+        assert_eq!(vec![3, 5, 6], wall_times(&merged, "B"));
+
+        let merge = |other: &str, runs: &[(&str, &[u64])]| {
+            let first = log("1/2", &[("A", &[1]), ("B", &[2])]);
+            merge_run_logs(vec![first, log(other, runs)])
+                .err()
+                .map(|e| e.to_string())
+                .unwrap()
+        };
+        assert!(merge("1/2", &[("A", &[1]), ("B", &[2])]).contains("more than once"));
+        assert!(merge("2/3", &[("A", &[1]), ("B", &[2])]).contains("different shard count"));
+        assert!(merge("2/2", &[("A", &[1]), ("C", &[2])]).contains("different experiments"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! How often each variant ran at each position within iterations, to check that random
+//! order did not happen to favour some variant.
+
+use std::fmt::Write as _;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::math::special::ln_choose;
+
+/// Probability of any count in random order being as unbalanced as a reported one.
+const UNBALANCED_P: f64 = 0.05;
+
+/// Ordinal like `1st`, `2nd` or `11th`.
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Two-sided p-value of `count` successes in `total` trials with probability `p`.
+fn binomial_p(count: u64, total: u64, p: f64) -> f64 {
+    let pmf = |k: u64| {
+        f64::exp(ln_choose(total, k) + k as f64 * p.ln() + (total - k) as f64 * (1.0 - p).ln())
+    };
+    let below: f64 = (0..=count).map(pmf).sum();
+    let above: f64 = (count..=total).map(pmf).sum();
+    f64::min(1.0, 2.0 * below.min(above))
+}
+
+/// Variant and position which ran more or less often than random order explains, like
+/// `A ran 1st in 10 of 10 iterations, expected about 5`, the worst one if several.
+pub fn unbalanced_order(tests: &ExperimentMap<Experiment>) -> Option<String> {
+    let variants = tests.count();
+    if variants < 2 {
+        return None;
+    }
+    // Every variant at every position is tested.
+    let threshold = UNBALANCED_P / (variants * variants) as f64;
+    let mut worst: Option<(f64, String)> = None;
+    for test in tests.values() {
+        let total: u64 = test.positions.iter().sum();
+        let expected = total as f64 / variants as f64;
+        for position in 0..variants {
+            let count = test.positions.get(position).copied().unwrap_or(0);
+            let p = binomial_p(count, total, 1.0 / variants as f64);
+            // Running less often somewhere means running more often somewhere else.
+            if count as f64 <= expected || p >= threshold {
+                continue;
+            }
+            if worst.as_ref().is_none_or(|(worst_p, _)| p < *worst_p) {
+                let warning = format!(
+                    "{} ran {} in {} of {} iterations, expected about {:.0}",
+                    test.name,
+                    ordinal(position + 1),
+                    count,
+                    total,
+                    expected
+                );
+                worst = Some((p, warning));
+            }
+        }
+    }
+    worst.map(|(_, warning)| warning)
+}
+
+/// Runs of each variant at each position within iterations.
+pub fn render_run_order(tests: &ExperimentMap<Experiment>) -> anyhow::Result<String> {
+    let mut r = String::new();
+    if tests.count() < 2 || tests.values().all(|t| t.positions.is_empty()) {
+        return Ok(r);
+    }
+    writeln!(r)?;
+    writeln!(r, "Run order:")?;
+    for test in tests.values() {
+        let counts: Vec<String> = (0..tests.count())
+            .map(|i| {
+                let count = test.positions.get(i).copied().unwrap_or(0);
+                format!("{} {}", ordinal(i + 1), count)
+            })
+            .collect();
+        writeln!(r, "{}: {}", test.name.name_colored(), counts.join(", "))?;
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::strip_csi;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::run_order::binomial_p;
+    use crate::run_order::ordinal;
+    use crate::run_order::render_run_order;
+    use crate::run_order::unbalanced_order;
+
+    fn tests(a: &[u64], b: &[u64]) -> ExperimentMap<Experiment> {
+        let mut tests = ExperimentMap::default();
+        for (name, positions) in [("order-a", a), ("order-b", b)].iter() {
+            let name = ExperimentName::intern(name);
+            let mut test = Experiment::new(name, String::new(), String::new());
+            test.positions = positions.to_vec();
+            tests.insert(name, test);
+        }
+        tests
+    }
+
+    #[test]
+    fn ordinals() {
+        let ordinals: Vec<String> = [1, 2, 3, 4, 11, 12, 21, 102]
+            .iter()
+            .map(|&n| ordinal(n))
+            .collect();
+        assert_eq!(
+            vec!["1st", "2nd", "3rd", "4th", "11th", "12th", "21st", "102nd"],
+            ordinals
+        );
+    }
+
+    #[test]
+    fn binomial() {
+        assert!((binomial_p(10, 10, 0.5) - 2.0 / 1024.0).abs() < 1e-9);
+        assert!((binomial_p(5, 10, 0.5) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert_eq!(None, unbalanced_order(&tests(&[6, 4], &[4, 6])));
+        assert_eq!(None, unbalanced_order(&tests(&[4, 0], &[0, 4])));
+        assert_eq!(None, unbalanced_order(&tests(&[8, 2], &[2, 8])));
+        assert_eq!(
+            Some("order-a ran 1st in 10 of 10 iterations, expected about 5".to_owned()),
+            unbalanced_order(&tests(&[10, 0], &[0, 10]))
+        );
+    }
+
+    #[test]
+    fn render() {
+        assert_eq!(
+            "\nRun order:\norder-a: 1st 6, 2nd 4\norder-b: 1st 4, 2nd 6\n",
+            strip_csi(&render_run_order(&tests(&[6, 4], &[4, 6])).unwrap())
+        );
+    }
+}
@@ -0,0 +1,925 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::ExitStatus;
+use std::thread;
+use std::time;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use rand::Rng;
+
+use crate::alert::AlertThreshold;
+use crate::ansi;
+use crate::cpu_pinning::CpuList;
+use crate::drop_caches::drop_caches;
+use crate::duration::Duration;
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::experiment_name::ExperimentName;
+use crate::failure_policy::FailureAbort;
+use crate::failure_policy::FailurePolicy;
+use crate::interrupt::Interrupted;
+use crate::interrupt::check_interrupted;
+use crate::interrupt::interrupted;
+use crate::math::stats::Stats;
+use crate::measure::key::MeasureKey;
+use crate::mem_usage::MemUsage;
+use crate::metric::METRIC_FILE_ENV;
+use crate::metric::MetricAggSpec;
+use crate::metric::aggregate_metrics;
+use crate::metric::parse_metric_file;
+use crate::oom::OomKill;
+use crate::oom::OomWatch;
+use crate::pause_window::PauseWindow;
+use crate::pause_window::active_window;
+use crate::pause_window::local_secs_of_day;
+use crate::perf::parse_perf_output;
+use crate::perf::perf_script;
+use crate::prime::PrimeFiles;
+use crate::prime::expand_glob;
+use crate::prime::prime_files;
+use crate::rss_poll::RssPoller;
+use crate::run_log::RunLog;
+use crate::scheduling::Scheduling;
+use crate::sensors::SensorReading;
+use crate::sensors::SensorSample;
+use crate::sh::ScriptOptions;
+use crate::sh::ScriptRun;
+use crate::sh::Shell;
+use crate::sh::run_script;
+use crate::sh::with_body_start;
+use crate::verbosity::Verbosity;
+use crate::warmup_mode::WarmupMode;
+use crate::worker::Worker;
+use crate::wrap::truncate_line;
+
+/// How to run experiments.
+#[derive(Clone, Debug, Default)]
+pub struct RunnerOptions {
+    /// Order of runs of experiments.
+    pub scheduling: Scheduling,
+    /// Which runs of each experiment run its warmup script first.
+    pub warmup_mode: WarmupMode,
+    /// What to print while running.
+    pub verbosity: Verbosity,
+    /// What to do when a run fails.
+    pub on_failure: FailurePolicy,
+    /// Stop when more runs than this failed in total.
+    pub max_failures: Option<u64>,
+    /// Truncate printed scripts to this width.
+    pub max_line_width: Option<usize>,
+    /// How to combine metrics reported several times in one run.
+    pub metric_agg: Vec<MetricAggSpec>,
+    /// Metrics declared with `--proportion`, which must be `0` or `1`.
+    pub proportions: Vec<String>,
+    /// Collect metrics scripts write to `$ABSH_METRIC_FILE`.
+    pub custom_metric: bool,
+    /// Run scripts under `perf stat` and record hardware counters as metrics.
+    pub perf: bool,
+    /// Files to read into the page cache before each measured run.
+    pub prime_files: Vec<PrimeFiles>,
+    /// Sync and drop the page cache before each run, before its setup and warmup.
+    pub drop_caches: bool,
+    /// Record CPU frequency and temperature around each run.
+    pub record_sensors: bool,
+    /// Record when script output lines appear.
+    pub line_latency: bool,
+    /// Run scripts from a separate worker process.
+    pub worker: bool,
+    /// Run scripts in transient cgroups for resource accounting.
+    pub cgroup: bool,
+    /// Shell running scripts, or none with `Shell::Exec`.
+    pub shell: Shell,
+    /// Record shell setup and script body time separately.
+    pub body_time: bool,
+    /// Sample peak RSS from `/proc`, set by `check_max_rss`.
+    pub poll_rss: bool,
+    /// Sample peak RSS of the script's whole process group.
+    pub mem_tree: bool,
+    /// Record peak thread and file descriptor counts as metrics.
+    pub threads_fds: bool,
+    /// Read reported metrics from `$ABSH_METRICS_FD` rather than script output.
+    pub metrics_fd: bool,
+    /// Pin scripts to these CPUs.
+    pub cpus: Option<CpuList>,
+    /// Niceness of scripts.
+    pub nice: Option<i32>,
+    /// Do not start iterations during these daily windows.
+    pub pause_windows: Vec<PauseWindow>,
+    /// Kill warmup or run scripts running longer than this.
+    pub timeout: Option<Duration>,
+    /// Highlight runs much slower than the running median.
+    pub alert_if_above: Option<AlertThreshold>,
+    /// Wait for Enter after highlighting a slow run.
+    pub pause_on_alert: bool,
+    /// Idle time between the end of one run and the start of the next.
+    pub gap: Duration,
+}
+
+impl RunnerOptions {
+    fn script_options(&self) -> ScriptOptions {
+        ScriptOptions {
+            line_latency: self.line_latency,
+            timeout: self.timeout,
+            cgroup: self.cgroup,
+            shell: self.shell.clone(),
+            body_time: self.body_time,
+            poll_rss: self.poll_rss,
+            mem_tree: self.mem_tree,
+            threads_fds: self.threads_fds,
+            metrics_fd: self.metrics_fd,
+            cpus: self.cpus.clone(),
+            nice: self.nice,
+            capture_stderr: true,
+            quiet_stderr: self.verbosity == Verbosity::Quiet,
+        }
+    }
+
+    /// Check that runs report max RSS, and sample it from `/proc` if they do not.
+    pub fn check_max_rss(&mut self) -> anyhow::Result<MaxRssSource> {
+        let probe = run_script("true", None, &[], io::sink(), &self.script_options())?;
+        if probe.max_rss != 0 {
+            Ok(MaxRssSource::Reported)
+        } else if RssPoller::available() {
+            self.poll_rss = true;
+            Ok(MaxRssSource::Polled)
+        } else {
+            Ok(MaxRssSource::Unavailable)
+        }
+    }
+}
+
+/// How max RSS of runs is measured.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MaxRssSource {
+    /// By `wait4`, or by the Job Object of the script on Windows.
+    Reported,
+    /// By sampling `/proc`, may miss short peaks.
+    Polled,
+    /// Not at all, runs have zero max RSS.
+    Unavailable,
+}
+
+/// Runs experiments in iterations and collects their samples.
+///
+/// ```ignore
+/// let mut runner = Runner::new(experiments, RunnerOptions::default())?;
+/// while runner.min_runs() < 10 {
+///     runner.run_iteration(&mut log)?;
+/// }
+/// let stats = runner.wall_time_stats();
+/// ```
+pub struct Runner {
+    pub experiments: ExperimentMap<Experiment>,
+    pub options: RunnerOptions,
+    worker: Option<Worker>,
+    iteration: u64,
+    last_run_end: Option<time::Instant>,
+}
+
+impl Runner {
+    pub fn new(
+        experiments: ExperimentMap<Experiment>,
+        options: RunnerOptions,
+    ) -> anyhow::Result<Runner> {
+        if experiments.count() == 0 {
+            return Err(anyhow::anyhow!("no experiments"));
+        }
+        let worker = match options.worker {
+            true => Some(Worker::spawn()?),
+            false => None,
+        };
+        Ok(Runner {
+            experiments,
+            options,
+            worker,
+            iteration: 0,
+            last_run_end: None,
+        })
+    }
+
+    /// Number of iterations run so far.
+    pub fn iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    /// Number iterations after `iterations` recorded earlier, when extending a run.
+    pub fn skip_iterations(&mut self, iterations: u64) {
+        self.iteration += iterations;
+    }
+
+    /// Smallest number of successful runs of any experiment.
+    pub fn min_runs(&self) -> usize {
+        self.experiments.values().map(|t| t.runs()).min().unwrap()
+    }
+
+    /// Run every experiment once, or for a block of iterations with blocked scheduling.
+    ///
+    /// Returns `Interrupted` error after Ctrl-C, without recording the interrupted run.
+    pub fn run_iteration(&mut self, log: &mut RunLog) -> anyhow::Result<()> {
+        wait_pause_windows(log, &self.options.pause_windows)?;
+        let names: Vec<ExperimentName> = self.experiments.keys().collect();
+        let first = self.iteration + 1;
+        let runs = self
+            .options
+            .scheduling
+            .schedule(&names, first, &mut rand::thread_rng());
+        // Runs of each iteration so far, blocked scheduling runs several at once.
+        let mut started: BTreeMap<u64, usize> = BTreeMap::new();
+        for (name, iteration) in runs {
+            if iteration > self.iteration {
+                self.iteration = iteration;
+                writeln!(log.log_only(), "iteration: {}", self.iteration)?;
+            }
+            check_interrupted()?;
+            self.wait_gap(log)?;
+            let position = started.entry(iteration).or_default();
+            let test = self.experiments.get_mut(name).unwrap();
+            if test.positions.len() <= *position {
+                test.positions.resize(*position + 1, 0);
+            }
+            test.positions[*position] += 1;
+            *position += 1;
+            self.run_with_retries(log, name, iteration)?;
+            self.check_failures(name)?;
+        }
+        Ok(())
+    }
+
+    /// Run an experiment once, and again while it fails and `--on-failure` allows it.
+    fn run_with_retries(
+        &mut self,
+        log: &mut RunLog,
+        name: ExperimentName,
+        iteration: u64,
+    ) -> anyhow::Result<()> {
+        let max_retries = self.options.on_failure.max_retries();
+        let mut retries = 0;
+        loop {
+            let test = self.experiments.get_mut(name).unwrap();
+            let failures = test.failures;
+            let result = run_test(log, &self.options, &mut self.worker, test, iteration);
+            self.last_run_end = Some(time::Instant::now());
+            result?;
+            if self.experiments[name].failures == failures || retries == max_retries {
+                return Ok(());
+            }
+            retries += 1;
+            writeln!(
+                log.both_log_and_stderr(),
+                "retrying {}, retry {} of {}",
+                name.name_colored(),
+                retries,
+                max_retries
+            )?;
+            check_interrupted()?;
+            self.wait_gap(log)?;
+        }
+    }
+
+    /// Stop with `FailureAbort` when failures of `name` or in total are not tolerated.
+    fn check_failures(&self, name: ExperimentName) -> anyhow::Result<()> {
+        let test = &self.experiments[name];
+        if self.options.on_failure == FailurePolicy::Abort && test.failures != 0 {
+            return Err(FailureAbort(format!("{} failed with `--on-failure abort`", name)).into());
+        }
+        let failures: u64 = self.experiments.values().map(|t| t.failures).sum();
+        match self.options.max_failures {
+            Some(max) if failures > max => Err(FailureAbort(format!(
+                "{} failed runs, more than `--max-failures {}`",
+                failures, max
+            ))
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Flush the log and sleep until `gap` has passed since the previous run ended.
+    ///
+    /// Time spent rendering statistics between iterations counts towards the gap.
+    fn wait_gap(&mut self, log: &mut RunLog) -> anyhow::Result<()> {
+        let last_run_end = match self.last_run_end {
+            Some(end) => end,
+            None => return Ok(()),
+        };
+        log.flush()?;
+        let gap = time::Duration::from_nanos(self.options.gap.nanos());
+        loop {
+            let remaining = gap.saturating_sub(last_run_end.elapsed());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            // Wake up regularly to stop promptly on Ctrl-C.
+            thread::sleep(remaining.min(time::Duration::from_millis(50)));
+            check_interrupted()?;
+        }
+    }
+
+    /// Forget samples of all iterations run so far.
+    pub fn clear_samples(&mut self) {
+        for test in self.experiments.values_mut() {
+            test.clear_samples();
+        }
+    }
+
+    /// Wall time statistics of each experiment, `None` with fewer than two samples.
+    pub fn wall_time_stats(&self) -> ExperimentMap<Option<Stats<Duration>>> {
+        self.experiments.map(|t| {
+            let numbers = &t.measures[MeasureKey::WallTime];
+            if numbers.len() < 2 {
+                return None;
+            }
+            Some(numbers.stats()?.map(Duration::from_nanos))
+        })
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Sleep while the local time is in any of the windows, and record the pause in the log.
+fn wait_pause_windows(log: &mut RunLog, windows: &[PauseWindow]) -> anyhow::Result<()> {
+    let start = unix_secs();
+    let mut paused = false;
+    while let Some(window) = active_window(windows, local_secs_of_day()) {
+        if !paused {
+            writeln!(log.both_log_and_stderr())?;
+            writeln!(
+                log.both_log_and_stderr(),
+                "{yellow}Pausing measurements during {}{reset}",
+                window,
+                yellow = ansi::YELLOW,
+                reset = ansi::RESET,
+            )?;
+            paused = true;
+        }
+        // Wake up at least every minute in case the clock is changed.
+        let remaining = window.remaining(local_secs_of_day());
+        thread::sleep(remaining.clamp(time::Duration::from_secs(1), time::Duration::from_secs(60)));
+    }
+    if paused {
+        let end = unix_secs();
+        writeln!(log.log_only(), "pause: {}..{}", start, end)?;
+        writeln!(
+            log.both_log_and_stderr(),
+            "Resuming measurements after {} s pause",
+            end - start
+        )?;
+    }
+    Ok(())
+}
+
+/// Lines of script output kept in the log for `absh review`.
+const OUTPUT_TAIL_LINES: usize = 3;
+
+/// Writer passing output through and keeping all of it.
+struct CapturedOutput<W: Write> {
+    inner: W,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write> CapturedOutput<W> {
+    fn new(inner: W) -> CapturedOutput<W> {
+        CapturedOutput {
+            inner,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Last lines, including the unterminated one.
+    fn tail_lines(&self) -> Vec<String> {
+        let output = String::from_utf8_lossy(&self.bytes);
+        let lines: Vec<&str> = output.lines().collect();
+        let skip = lines.len().saturating_sub(OUTPUT_TAIL_LINES);
+        lines[skip..].iter().map(|l| l.to_string()).collect()
+    }
+}
+
+impl<W: Write> Write for CapturedOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn alert_slow_run(
+    log: &mut RunLog,
+    test: &Experiment,
+    duration: Duration,
+    ratio: f64,
+    pause: bool,
+) -> anyhow::Result<()> {
+    writeln!(
+        log.both_log_and_stderr(),
+        "{red}{} took {:3} s, {:.1}x the running median, did something change on the machine?{reset}",
+        test.name,
+        duration,
+        ratio,
+        red = ansi::RED,
+        reset = ansi::RESET,
+    )?;
+    if pause {
+        eprint!("Press Enter to continue ");
+        io::stdin().read_line(&mut String::new())?;
+    }
+    Ok(())
+}
+
+fn record_failure(
+    log: &mut RunLog,
+    test: &mut Experiment,
+    what: &str,
+    status: ExitStatus,
+    oom: Option<OomKill>,
+) -> anyhow::Result<()> {
+    test.failures += 1;
+    match oom {
+        Some(oom) => {
+            match oom {
+                OomKill::Confirmed => test.oom_kills += 1,
+                OomKill::Suspected => test.suspected_oom_kills += 1,
+            }
+            writeln!(
+                log.both_log_and_stderr(),
+                "{red}{} failed: {}, {}{reset}",
+                what,
+                status,
+                oom.describe(),
+                red = ansi::RED,
+                reset = ansi::RESET,
+            )?;
+        }
+        None => {
+            writeln!(log.both_log_and_stderr(), "{} failed: {}", what, status)?;
+        }
+    }
+    Ok(())
+}
+
+fn record_timeout(
+    log: &mut RunLog,
+    test: &mut Experiment,
+    what: &str,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    test.failures += 1;
+    test.timeouts += 1;
+    writeln!(
+        log.both_log_and_stderr(),
+        "{red}{} timed out after {:3} s, killed{reset}",
+        what,
+        timeout,
+        red = ansi::RED,
+        reset = ansi::RESET,
+    )?;
+    Ok(())
+}
+
+/// Count a run reporting a `--proportion` metric other than 0 or 1 as failed.
+fn record_invalid_proportion(
+    log: &mut RunLog,
+    test: &mut Experiment,
+    name: &str,
+    value: u64,
+) -> anyhow::Result<()> {
+    test.failures += 1;
+    writeln!(
+        log.both_log_and_stderr(),
+        "{red}script failed: metric `{}` is declared with --proportion, but {} reported {}{reset}",
+        name,
+        test.name,
+        value,
+        red = ansi::RED,
+        reset = ansi::RESET,
+    )?;
+    Ok(())
+}
+
+/// Read files given with `--prime-files` for the experiment into the page cache.
+fn prime_test_files(
+    log: &mut RunLog,
+    opts: &RunnerOptions,
+    test: &Experiment,
+) -> anyhow::Result<()> {
+    let mut paths = Vec::new();
+    for spec in opts.prime_files.iter().filter(|p| p.used_by(test.name)) {
+        paths.extend(expand_glob(&spec.glob)?);
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let bytes = prime_files(&paths)?;
+    writeln!(
+        log.log_and_stderr_if(opts.verbosity.prints_runs()),
+        "primed {} files, {} MiB",
+        paths.len(),
+        MemUsage::from_bytes(bytes).mib()
+    )?;
+    Ok(())
+}
+
+/// Empty temporary file for a script to write to, removed when dropped.
+struct ScratchFile {
+    path: PathBuf,
+}
+
+impl ScratchFile {
+    /// Create a file with a random name, never opening a file or symlink which someone
+    /// else put at a predictable path in a shared temporary directory.
+    fn create(kind: &str) -> anyhow::Result<ScratchFile> {
+        let mut rng = rand::thread_rng();
+        loop {
+            let name = format!("absh-{}-{}-{:016x}", kind, process::id(), rng.gen::<u64>());
+            let path = env::temp_dir().join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(ScratchFile { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).with_context(|| format!("creating {}", path.display())),
+            }
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<String> {
+        fs::read_to_string(&self.path).with_context(|| format!("reading {}", self.path.display()))
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Run script in the worker process if there is one, or directly.
+fn run_script_in(
+    worker: &mut Option<Worker>,
+    script: &str,
+    dir: Option<&Path>,
+    env: &[(String, String)],
+    out: impl Write,
+    opts: &ScriptOptions,
+) -> anyhow::Result<ScriptRun> {
+    let run = match worker {
+        Some(worker) => worker.run(script, dir, env, out, opts),
+        None => run_script(script, dir, env, out, opts),
+    };
+    // Script killed by Ctrl-C is not a failure of the script.
+    match interrupted() {
+        true => Err(Interrupted.into()),
+        false => run,
+    }
+}
+
+fn run_test(
+    log: &mut RunLog,
+    opts: &RunnerOptions,
+    worker: &mut Option<Worker>,
+    test: &mut Experiment,
+    iteration: u64,
+) -> anyhow::Result<()> {
+    let verbose = opts.verbosity.prints_runs();
+    writeln!(log.log_and_stderr_if(verbose))?;
+    writeln!(
+        log.log_and_stderr_if(verbose),
+        "running test: {}",
+        test.name.name_colored()
+    )?;
+
+    let prev_env = test.prev_sample_env();
+    if !prev_env.is_empty() {
+        let env_str = prev_env
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(log.log_only(), "{}.env: {}", test.name, env_str)?;
+    }
+    let env: Vec<(String, String)> = test.env.iter().cloned().chain(prev_env).collect();
+
+    if !test.when.is_empty() {
+        let mut command = opts.shell.command(&test.when)?;
+        if let Some(dir) = &test.dir {
+            command.current_dir(dir);
+        }
+        let status = command
+            .envs(env.iter().cloned())
+            .status()
+            .context("running condition script")?;
+        check_interrupted()?;
+        if !status.success() {
+            writeln!(
+                log.both_log_and_stderr(),
+                "{} skipped, condition script failed: {}",
+                test.name.name_colored(),
+                status
+            )?;
+            writeln!(
+                log.log_only(),
+                "{}.skipped: iteration={}",
+                test.name,
+                iteration
+            )?;
+            test.skips += 1;
+            return Ok(());
+        }
+    }
+
+    if opts.drop_caches {
+        writeln!(log.log_and_stderr_if(verbose), "dropping caches")?;
+        drop_caches()?;
+    }
+    if !test.setup.is_empty() {
+        let setup = test.setup.clone();
+        if !run_hook(log, opts, worker, test, "setup", &setup, &env)? {
+            return Ok(());
+        }
+    }
+    let result = run_warmup_and_script(log, opts, worker, test, iteration, env.clone());
+    if !test.teardown.is_empty() {
+        let teardown = test.teardown.clone();
+        run_hook(log, opts, worker, test, "teardown", &teardown, &env)?;
+    }
+    result
+}
+
+/// Print a script before running it, to the log only with `--quiet`.
+fn print_script(
+    log: &mut RunLog,
+    opts: &RunnerOptions,
+    what: &str,
+    script: &str,
+) -> anyhow::Result<()> {
+    let width = opts.max_line_width.unwrap_or(0);
+    let stderr = opts.verbosity.prints_runs();
+    writeln!(log.log_and_stderr_if(stderr), "running {}:", what)?;
+    for line in script.lines() {
+        writeln!(
+            log.log_and_stderr_if(stderr),
+            "{}",
+            truncate_line(&format!("    {}", line), width)
+        )?;
+    }
+    Ok(())
+}
+
+/// Where script output goes: stdout with `--verbose`, discarded otherwise.
+fn script_output(opts: &RunnerOptions) -> Box<dyn Write> {
+    match opts.verbosity.streams_output() {
+        true => Box::new(io::stdout()),
+        false => Box::new(io::sink()),
+    }
+}
+
+/// Run the setup or teardown script of `test`, returns whether it succeeded.
+///
+/// Failed setup counts as a failed run, failed teardown is only reported.
+fn run_hook(
+    log: &mut RunLog,
+    opts: &RunnerOptions,
+    worker: &mut Option<Worker>,
+    test: &mut Experiment,
+    what: &str,
+    script: &str,
+    env: &[(String, String)],
+) -> anyhow::Result<bool> {
+    print_script(log, opts, &format!("{} script", what), script)?;
+    let oom_watch = OomWatch::start();
+    let run = run_script_in(
+        worker,
+        script,
+        test.dir.as_deref(),
+        env,
+        script_output(opts),
+        &ScriptOptions {
+            line_latency: false,
+            body_time: false,
+            ..opts.script_options()
+        },
+    )?;
+    if run.status.success() && !run.timed_out {
+        return Ok(true);
+    }
+    if what != "setup" {
+        writeln!(
+            log.both_log_and_stderr(),
+            "{yellow}{} failed: {}{reset}",
+            what,
+            run.status,
+            yellow = ansi::YELLOW,
+            reset = ansi::RESET,
+        )?;
+        return Ok(false);
+    }
+    match (run.timed_out, opts.timeout) {
+        (true, Some(timeout)) => record_timeout(log, test, what, timeout)?,
+        _ => {
+            let oom = oom_watch.check(run.pid, run.status);
+            record_failure(log, test, what, run.status, oom)?;
+        }
+    }
+    Ok(false)
+}
+
+/// Run the warmup script when the warmup mode asks for it, then the measured script,
+/// and record the run.
+fn run_warmup_and_script(
+    log: &mut RunLog,
+    opts: &RunnerOptions,
+    worker: &mut Option<Worker>,
+    test: &mut Experiment,
+    iteration: u64,
+    mut env: Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    if opts.warmup_mode.runs_warmup(test.warmups) && !test.warmup.is_empty() {
+        print_script(log, opts, "warmup script", &test.warmup)?;
+
+        let oom_watch = OomWatch::start();
+        let warmup = run_script_in(
+            worker,
+            &test.warmup,
+            test.dir.as_deref(),
+            &env,
+            script_output(opts),
+            &ScriptOptions {
+                line_latency: false,
+                body_time: false,
+                ..opts.script_options()
+            },
+        )?;
+        if let (true, Some(timeout)) = (warmup.timed_out, opts.timeout) {
+            return record_timeout(log, test, "warmup", timeout);
+        }
+        if !warmup.status.success() {
+            let oom = oom_watch.check(warmup.pid, warmup.status);
+            return record_failure(log, test, "warmup", warmup.status, oom);
+        }
+        test.warmups += 1;
+    }
+
+    prime_test_files(log, opts, test)?;
+
+    print_script(log, opts, "script", &test.run)?;
+
+    let metric_file = match opts.custom_metric {
+        true => Some(ScratchFile::create("metric")?),
+        false => None,
+    };
+    if let Some(metric_file) = &metric_file {
+        env.push((
+            METRIC_FILE_ENV.to_owned(),
+            metric_file.path.display().to_string(),
+        ));
+    }
+
+    let oom_watch = OomWatch::start();
+    let sensors_start = opts.record_sensors.then(SensorReading::read);
+    let mut output = CapturedOutput::new(script_output(opts));
+    let perf_file = match opts.perf {
+        true => Some(ScratchFile::create("perf")?),
+        false => None,
+    };
+    let script = match opts.body_time {
+        true => with_body_start(&test.run),
+        false => test.run.clone(),
+    };
+    let script = match &perf_file {
+        Some(perf_file) => perf_script(&opts.shell.argv(&script)?, &perf_file.path),
+        None => script,
+    };
+    let mut run = run_script_in(
+        worker,
+        &script,
+        test.dir.as_deref(),
+        &env,
+        &mut output,
+        &opts.script_options(),
+    )?;
+    let saved = log.write_output(test.name, iteration, &output.bytes, &run.stderr)?;
+    let output = output.tail_lines();
+    if let Some(metric_file) = metric_file {
+        run.metrics.extend(parse_metric_file(&metric_file.read()?));
+    }
+    if let Some(perf_file) = perf_file {
+        run.metrics.extend(parse_perf_output(&perf_file.read()?));
+    }
+    let sensors = sensors_start.map(|start| SensorSample {
+        start,
+        end: SensorReading::read(),
+    });
+    if !run.status.success() || run.timed_out {
+        // Output was not shown while running, show how it ended.
+        if !opts.verbosity.streams_output() {
+            for line in &output {
+                writeln!(log.stderr_only(), "    {}", line)?;
+            }
+        }
+        for path in &saved {
+            writeln!(
+                log.both_log_and_stderr(),
+                "output saved to {}",
+                path.display()
+            )?;
+        }
+    }
+    if let (true, Some(timeout)) = (run.timed_out, opts.timeout) {
+        return record_timeout(log, test, "script", timeout);
+    }
+    if !run.status.success() {
+        let oom = oom_watch.check(run.pid, run.status);
+        return record_failure(log, test, "script", run.status, oom);
+    }
+    let metrics = aggregate_metrics(run.metrics, &opts.metric_agg);
+    let invalid = metrics
+        .iter()
+        .find(|&(name, &value)| value > 1 && opts.proportions.contains(name));
+    if let Some((name, value)) = invalid {
+        return record_invalid_proportion(log, test, name, *value);
+    }
+
+    let duration = run.duration;
+    let max_rss = MemUsage::from_bytes(run.max_rss);
+
+    writeln!(
+        log.log_and_stderr_if(opts.verbosity.prints_runs()),
+        "{} finished in {:3} s, max rss {} MiB",
+        test.name.name_colored(),
+        duration,
+        max_rss.mib(),
+    )?;
+
+    // Sample details for `absh review`, index is the position in raw data files.
+    writeln!(
+        log.log_only(),
+        "{}.sample: {} iteration={} time={}",
+        test.name,
+        test.measures[MeasureKey::WallTime].len(),
+        iteration,
+        unix_secs()
+    )?;
+    for line in output {
+        writeln!(log.log_only(), "{}.output: {}", test.name, line)?;
+    }
+
+    if let Some(threshold) = opts.alert_if_above {
+        let previous = &test.measures[MeasureKey::WallTime];
+        if let Some(ratio) = threshold.check(duration.nanos(), previous.med(), previous.len()) {
+            alert_slow_run(log, test, duration, ratio, opts.pause_on_alert)?;
+        }
+    }
+
+    test.iterations.push(iteration);
+    test.measures[MeasureKey::WallTime].push(duration.nanos());
+    test.measures[MeasureKey::MaxRss].push(max_rss.bytes());
+    test.measures[MeasureKey::UserTime].push(run.user_time.nanos());
+    test.measures[MeasureKey::SysTime].push(run.sys_time.nanos());
+    for (name, value) in metrics {
+        test.push_metric(name, value);
+    }
+    if let Some(sensors) = sensors {
+        writeln!(
+            log.log_only(),
+            "{}.sensors: start {} end {}",
+            test.name,
+            sensors.start,
+            sensors.end
+        )?;
+        test.sensors.push(sensors);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::runner::ScratchFile;
+
+    #[test]
+    fn scratch_file() {
+        let a = ScratchFile::create("test").unwrap();
+        let b = ScratchFile::create("test").unwrap();
+        assert_ne!(a.path, b.path);
+        fs::write(&a.path, "x").unwrap();
+        assert_eq!("x", a.read().unwrap());
+        let path = a.path.clone();
+        drop(a);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand::prelude::SliceRandom;
+
+use crate::experiment_name::ExperimentName;
+
+/// Block size of `blocked` scheduling without `:N`.
+const DEFAULT_BLOCK: u64 = 5;
+
+/// Order of runs of variants, given with `--scheduling`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Scheduling {
+    /// `ABAB`: every iteration runs variants in the same order.
+    #[default]
+    Interleaved,
+    /// `AABB`: runs of a variant for this many iterations back to back.
+    Blocked(u64),
+    /// Every iteration rotates the order, so each variant runs at each position equally often.
+    LatinSquare,
+    /// Every iteration runs variants in random order.
+    Random,
+}
+
+impl Scheduling {
+    /// Iterations scheduled at once.
+    pub fn iterations(&self) -> u64 {
+        match *self {
+            Scheduling::Blocked(block) => block,
+            _ => 1,
+        }
+    }
+
+    /// Variant and iteration of each run of iterations starting with `first`.
+    pub fn schedule(
+        &self,
+        names: &[ExperimentName],
+        first: u64,
+        rng: &mut impl Rng,
+    ) -> Vec<(ExperimentName, u64)> {
+        match *self {
+            Scheduling::Interleaved => names.iter().map(|&n| (n, first)).collect(),
+            Scheduling::Blocked(block) => names
+                .iter()
+                .flat_map(|&n| (first..first + block).map(move |i| (n, i)))
+                .collect(),
+            Scheduling::LatinSquare => {
+                let shift = (first - 1) as usize % names.len();
+                let (head, tail) = names.split_at(shift);
+                tail.iter().chain(head).map(|&n| (n, first)).collect()
+            }
+            Scheduling::Random => {
+                let mut names = names.to_vec();
+                names.shuffle(rng);
+                names.into_iter().map(|n| (n, first)).collect()
+            }
+        }
+    }
+}
+
+impl FromStr for Scheduling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Scheduling> {
+        let blocked = |block: &str| -> anyhow::Result<Scheduling> {
+            match block.parse() {
+                Ok(block) if block > 0 => Ok(Scheduling::Blocked(block)),
+                _ => Err(anyhow::anyhow!("invalid block size: `{}`", block)),
+            }
+        };
+        match s {
+            "interleaved" => Ok(Scheduling::Interleaved),
+            "blocked" => Ok(Scheduling::Blocked(DEFAULT_BLOCK)),
+            "latin-square" => Ok(Scheduling::LatinSquare),
+            "random" => Ok(Scheduling::Random),
+            s => match s.strip_prefix("blocked:") {
+                Some(block) => blocked(block),
+                None => Err(anyhow::anyhow!(
+                    "unknown scheduling: `{}`, expecting `interleaved`, `blocked[:N]`, \
+                     `latin-square` or `random`",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Scheduling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheduling::Interleaved => write!(f, "interleaved"),
+            Scheduling::Blocked(block) => write!(f, "blocked:{}", block),
+            Scheduling::LatinSquare => write!(f, "latin-square"),
+            Scheduling::Random => write!(f, "random"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::experiment_name::ExperimentName;
+    use crate::scheduling::Scheduling;
+
+    fn order(scheduling: &str, first: u64) -> String {
+        let names: Vec<_> = ["sched-a", "sched-b", "sched-c"]
+            .iter()
+            .map(|n| ExperimentName::intern(n))
+            .collect();
+        let scheduling: Scheduling = scheduling.parse().unwrap();
+        scheduling
+            .schedule(&names, first, &mut StdRng::seed_from_u64(1))
+            .iter()
+            .map(|(n, i)| format!("{}{}", &n.name()[6..], i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn schedule() {
+        assert_eq!("a1 b1 c1", order("interleaved", 1));
+        assert_eq!("a3 a4 b3 b4 c3 c4", order("blocked:2", 3));
+        assert_eq!("a1 b1 c1", order("latin-square", 1));
+        assert_eq!("b2 c2 a2", order("latin-square", 2));
+        assert_eq!("c3 a3 b3", order("latin-square", 3));
+        assert_eq!("a4 b4 c4", order("latin-square", 4));
+        let random = order("random", 1);
+        assert_eq!(3, random.split(' ').count());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ok(Scheduling::Blocked(5)),
+            "blocked".parse().map_err(|_| ())
+        );
+        assert_eq!(
+            Ok(Scheduling::Blocked(3)),
+            "blocked:3".parse().map_err(|_| ())
+        );
+        assert!("blocked:0".parse::<Scheduling>().is_err());
+        assert!("ABAB".parse::<Scheduling>().is_err());
+        for s in ["interleaved", "blocked:7", "latin-square", "random"].iter() {
+            assert_eq!(*s, s.parse::<Scheduling>().unwrap().to_string());
+        }
+    }
+}
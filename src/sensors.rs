@@ -0,0 +1,120 @@
+use std::fmt;
+use std::fs;
+use std::process::Command;
+
+/// CPU frequency and temperature at some moment.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SensorReading {
+    /// Mean current frequency of all CPUs, in MHz.
+    pub cpu_freq_mhz: Option<u64>,
+    /// Highest temperature reported by any sensor, in millidegrees Celsius.
+    pub temp_millicelsius: Option<u64>,
+}
+
+/// Sensor readings before and after a run.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SensorSample {
+    pub start: SensorReading,
+    pub end: SensorReading,
+}
+
+impl SensorSample {
+    /// Mean of start and end temperature, in degrees Celsius.
+    pub fn temp(&self) -> Option<f64> {
+        let start = self.start.temp_millicelsius?;
+        let end = self.end.temp_millicelsius?;
+        Some((start + end) as f64 / 2000.0)
+    }
+
+    /// Mean of start and end frequency, in MHz.
+    pub fn cpu_freq_mhz(&self) -> Option<u64> {
+        let start = self.start.cpu_freq_mhz?;
+        let end = self.end.cpu_freq_mhz?;
+        Some((start + end) / 2)
+    }
+}
+
+fn read_u64(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Read numbers from all files matching `dir/<entry prefix>*/<file>`.
+fn read_all(dir: &str, entry_prefix: &str, file: &str) -> Vec<u64> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(entry_prefix))
+        .filter_map(|e| read_u64(&format!("{}/{}", e.path().display(), file)))
+        .collect()
+}
+
+fn sysctl(name: &str) -> Option<String> {
+    let output = Command::new("sysctl").arg("-n").arg(name).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+fn read_cpu_freq_mhz() -> Option<u64> {
+    let khz = read_all("/sys/devices/system/cpu", "cpu", "cpufreq/scaling_cur_freq");
+    if !khz.is_empty() {
+        return Some(khz.iter().sum::<u64>() / khz.len() as u64 / 1000);
+    }
+    // FreeBSD.
+    sysctl("dev.cpu.0.freq")?.parse().ok()
+}
+
+fn read_temp_millicelsius() -> Option<u64> {
+    let mut temps = Vec::new();
+    for entry in fs::read_dir("/sys/class/hwmon")
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        for input in fs::read_dir(entry.path()).into_iter().flatten().flatten() {
+            let name = input.file_name().to_string_lossy().into_owned();
+            if name.starts_with("temp") && name.ends_with("_input") {
+                temps.extend(read_u64(&input.path().display().to_string()));
+            }
+        }
+    }
+    if temps.is_empty() {
+        temps = read_all("/sys/class/thermal", "thermal_zone", "temp");
+    }
+    if let Some(max) = temps.into_iter().max() {
+        return Some(max);
+    }
+    // FreeBSD, like `45.0C`.
+    let celsius: f64 = sysctl("dev.cpu.0.temperature")?
+        .trim_end_matches('C')
+        .parse()
+        .ok()?;
+    Some((celsius * 1000.0) as u64)
+}
+
+impl SensorReading {
+    /// Read sensors, missing values are `None`.
+    pub fn read() -> SensorReading {
+        SensorReading {
+            cpu_freq_mhz: read_cpu_freq_mhz(),
+            temp_millicelsius: read_temp_millicelsius(),
+        }
+    }
+}
+
+impl fmt::Display for SensorReading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cpu_freq_mhz {
+            Some(mhz) => write!(f, "freq={}MHz", mhz)?,
+            None => write!(f, "freq=?")?,
+        }
+        match self.temp_millicelsius {
+            Some(t) => write!(f, " temp={}.{}C", t / 1000, t % 1000 / 100),
+            None => write!(f, " temp=?"),
+        }
+    }
+}
@@ -1,10 +1,680 @@
+use std::convert::TryInto;
+use std::io;
+use std::io::PipeReader;
+use std::io::Read;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::os::unix::io::FromRawFd;
+#[cfg(unix)]
+use std::os::unix::io::OwnedFd;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::Path;
 use std::process::Child;
 use std::process::Command;
+use std::process::ExitStatus;
 use std::process::Stdio;
+#[cfg(windows)]
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time;
+use std::time::Instant;
+
+use wait4::Wait4;
+
+use crate::cgroup::Cgroup;
+use crate::cpu_pinning::CpuList;
+use crate::cpu_pinning::pin_on_spawn;
+use crate::duration::Duration;
+#[cfg(unix)]
+use crate::interrupt::kill_group_on_interrupt;
+#[cfg(windows)]
+use crate::job::Job;
+use crate::metric::METRICS_FD_ENV;
+use crate::metric::copy_collecting_metrics_timed;
+use crate::metric::line_latency_metrics;
+use crate::metric::phase_metrics;
+use crate::rss_poll::RssPoller;
+use crate::shell::shell_split;
+
+/// POSIX shell running helper scripts, and variant scripts by default on Unix.
+pub const SH: &str = "/bin/sh";
+/// Shell flags preceding the script.
+pub const SH_FLAGS: &str = "-ec";
+/// `cmd.exe` flag preceding the script.
+const CMD_FLAGS: &str = "/C";
+
+/// Shell running variant scripts unless `--shell` is given.
+#[cfg(not(windows))]
+const DEFAULT_SHELL: &str = SH;
+#[cfg(windows)]
+const DEFAULT_SHELL: &str = "cmd.exe";
+
+pub fn sh_command(script: &str) -> Command {
+    let mut command = Command::new(SH);
+    command.args([SH_FLAGS, script]).stdin(Stdio::null());
+    command
+}
 
 pub fn spawn_sh(script: &str) -> anyhow::Result<Child> {
-    Ok(Command::new("/bin/sh")
-        .args(&["-ec", &script])
-        .stdin(Stdio::null())
-        .spawn()?)
+    Ok(sh_command(script).spawn()?)
+}
+
+/// How the run, warmup and condition scripts are started.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shell {
+    /// Pass the script to this shell.
+    Path(String),
+    /// Split the script into arguments and execute them without a shell.
+    Exec,
+}
+
+impl Default for Shell {
+    fn default() -> Shell {
+        Shell::Path(DEFAULT_SHELL.to_owned())
+    }
+}
+
+impl Shell {
+    /// Shell selected by `--shell` and `--exec`.
+    pub fn from_flags(shell: Option<&str>, exec: bool) -> Shell {
+        match (shell, exec) {
+            (_, true) => Shell::Exec,
+            (Some(path), false) => Shell::Path(path.to_owned()),
+            (None, false) => Shell::default(),
+        }
+    }
+
+    /// Flags preceding the script, picked by the shell file name.
+    fn flags(path: &str) -> &'static str {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        match name.to_ascii_lowercase().as_str() {
+            "fish" => "-c",
+            "cmd" | "cmd.exe" => CMD_FLAGS,
+            "powershell" | "powershell.exe" | "pwsh" | "pwsh.exe" => "-Command",
+            _ => SH_FLAGS,
+        }
+    }
+
+    /// Shell understands POSIX `sh` syntax.
+    pub fn is_posix(&self) -> bool {
+        match self {
+            Shell::Path(path) => Shell::flags(path) == SH_FLAGS,
+            Shell::Exec => false,
+        }
+    }
+
+    /// Command line running `script`.
+    pub fn argv(&self, script: &str) -> anyhow::Result<Vec<String>> {
+        match self {
+            Shell::Path(path) => Ok(vec![
+                path.clone(),
+                Shell::flags(path).to_owned(),
+                script.to_owned(),
+            ]),
+            Shell::Exec => {
+                let argv = shell_split(script)?;
+                if argv.is_empty() {
+                    return Err(anyhow::anyhow!("empty command with --exec"));
+                }
+                Ok(argv)
+            }
+        }
+    }
+
+    pub fn command(&self, script: &str) -> anyhow::Result<Command> {
+        let argv = self.argv(script)?;
+        let mut command = Command::new(&argv[0]);
+        match self {
+            // `cmd.exe` does not unquote arguments like other programs, so the script
+            // is passed as is.
+            #[cfg(windows)]
+            Shell::Path(path) if Shell::flags(path) == CMD_FLAGS => {
+                command.arg(CMD_FLAGS).raw_arg(script);
+            }
+            _ => {
+                command.args(&argv[1..]);
+            }
+        }
+        command.stdin(Stdio::null());
+        Ok(command)
+    }
+}
+
+/// Descriptor the shell reports the start of the script body to.
+const BODY_START_FD: libc::c_int = 9;
+
+/// Descriptor scripts write metric and phase lines to with `ScriptOptions::metrics_fd`.
+pub const METRICS_FD: libc::c_int = 8;
+
+/// `script` for a POSIX shell which first reports that the body starts.
+///
+/// The shell writes a byte to the pipe set up by `ScriptOptions::body_time`
+/// and closes it, so processes started by the script do not keep it open.
+pub fn with_body_start(script: &str) -> String {
+    format!(
+        "printf x >&{fd}; exec {fd}>&-; {}",
+        script,
+        fd = BODY_START_FD
+    )
+}
+
+/// Pipe whose writer the spawned process inherits as descriptor `target`.
+///
+/// The returned writer must be dropped after spawning.
+#[cfg(unix)]
+fn inherited_pipe(command: &mut Command, target: libc::c_int) -> io::Result<(PipeReader, OwnedFd)> {
+    let (reader, writer) = io::pipe()?;
+    // Above the targets, so `dup2` of one pipe neither closes another nor keeps close-on-exec.
+    let fd = unsafe { libc::fcntl(writer.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 10) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let writer = unsafe { OwnedFd::from_raw_fd(fd) };
+    unsafe {
+        command.pre_exec(move || match libc::dup2(fd, target) {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    Ok((reader, writer))
+}
+
+#[cfg(not(unix))]
+fn inherited_pipe(
+    _command: &mut Command,
+    _target: libc::c_int,
+) -> io::Result<(PipeReader, io::PipeWriter)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "passing descriptors to scripts is only supported on Unix",
+    ))
+}
+
+/// Reader of a pipe the script writes to, which ends at EOF or once the shell exited
+/// and what was written until then is read.
+///
+/// A background process started by the script may keep the pipe open after the
+/// shell exited, so EOF alone could never come. Windows pipes cannot be polled,
+/// there the pipe is read until EOF.
+struct UntilExit<R> {
+    pipe: R,
+    /// Reaches EOF when the shell exited.
+    #[cfg(unix)]
+    exited: PipeReader,
+    /// Bytes in the pipe when the shell exited which are not read yet.
+    #[cfg(unix)]
+    remaining: Option<usize>,
+}
+
+impl<R> UntilExit<R> {
+    #[cfg(unix)]
+    fn new(pipe: R, exited: &PipeReader) -> io::Result<UntilExit<R>> {
+        Ok(UntilExit {
+            pipe,
+            exited: exited.try_clone()?,
+            remaining: None,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn new(pipe: R, _exited: &PipeReader) -> io::Result<UntilExit<R>> {
+        Ok(UntilExit { pipe })
+    }
+}
+
+#[cfg(unix)]
+impl<R: Read + AsRawFd> UntilExit<R> {
+    /// Wait until the pipe is readable, returns whether the shell exited instead.
+    fn wait_readable(&self) -> io::Result<bool> {
+        let mut fds = [self.pipe.as_raw_fd(), self.exited.as_raw_fd()].map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        while unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+        Ok(fds[1].revents != 0)
+    }
+}
+
+#[cfg(unix)]
+impl<R: Read + AsRawFd> Read for UntilExit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining.is_none() {
+            if !self.wait_readable()? {
+                return self.pipe.read(buf);
+            }
+            // Everything the shell wrote is in the pipe now.
+            let mut available: libc::c_int = 0;
+            if unsafe { libc::ioctl(self.pipe.as_raw_fd(), libc::FIONREAD, &mut available) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.remaining = Some(available as usize);
+        }
+        let remaining = self.remaining.as_mut().unwrap();
+        if *remaining == 0 {
+            return Ok(0);
+        }
+        let len = buf.len().min(*remaining);
+        let n = self.pipe.read(&mut buf[..len])?;
+        *remaining = if n == 0 { 0 } else { *remaining - n };
+        Ok(n)
+    }
+}
+
+#[cfg(not(unix))]
+impl<R: Read> Read for UntilExit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.pipe.read(buf)
+    }
+}
+
+/// Time the byte written by `with_body_start` arrives, `None` if the pipe is closed without it.
+fn wait_body_start(mut reader: impl Read) -> Option<Instant> {
+    let mut buf = [0];
+    match reader.read(&mut buf) {
+        Ok(1) => Some(Instant::now()),
+        _ => None,
+    }
+}
+
+/// Outcome of a finished script.
+pub struct ScriptRun {
+    pub status: ExitStatus,
+    pub pid: u32,
+    pub duration: Duration,
+    /// Bytes, zero if not available.
+    pub max_rss: u64,
+    /// CPU time of the script and its children.
+    pub user_time: Duration,
+    pub sys_time: Duration,
+    /// Metrics reported by the script, in order.
+    pub metrics: Vec<(String, u64)>,
+    /// Script was killed because it ran longer than the timeout.
+    pub timed_out: bool,
+    /// What the script wrote to stderr, with `ScriptOptions::capture_stderr`.
+    pub stderr: Vec<u8>,
+}
+
+/// Call `kill` unless something is sent to the returned channel before `timeout`.
+///
+/// Thread result is whether `kill` was called.
+fn kill_after(
+    kill: impl FnOnce() + Send + 'static,
+    timeout: Duration,
+) -> (mpsc::Sender<()>, thread::JoinHandle<bool>) {
+    let (done_tx, done_rx) = mpsc::channel();
+    let watchdog = thread::spawn(move || {
+        match done_rx.recv_timeout(time::Duration::from_nanos(timeout.nanos())) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                kill();
+                true
+            }
+            _ => false,
+        }
+    });
+    (done_tx, watchdog)
+}
+
+/// Kill process group `pgid`.
+#[cfg(unix)]
+fn kill_group(pgid: u32) {
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// How scripts are run.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptOptions {
+    pub shell: Shell,
+    /// Add metrics describing output line times.
+    pub line_latency: bool,
+    /// Run the script in its own process group, which is killed when the script
+    /// runs longer. On Windows the Job Object of the script is terminated.
+    pub timeout: Option<Duration>,
+    /// Run the script in a transient cgroup, and take CPU time and peak memory
+    /// of the whole process tree from it.
+    pub cgroup: bool,
+    /// Pass the script a pipe to report the start of its body to,
+    /// for scripts made with `with_body_start`.
+    pub body_time: bool,
+    /// Sample peak RSS of the process tree from `/proc`, used when `wait4`
+    /// does not report it.
+    pub poll_rss: bool,
+    /// Run the script in its own process group, and sample peak RSS of the whole
+    /// group from `/proc`, including children which outlive their parents.
+    /// On Windows peak memory of the whole Job Object is taken instead.
+    pub mem_tree: bool,
+    /// Sample peak thread and open file descriptor counts of the process tree,
+    /// added as metrics `peak_threads` and `peak_fds`.
+    pub threads_fds: bool,
+    /// Read metric and phase lines from descriptor `METRICS_FD`, passed to the script
+    /// in `$ABSH_METRICS_FD`, rather than from its output.
+    pub metrics_fd: bool,
+    /// Run the script and its children on these CPUs.
+    pub cpus: Option<CpuList>,
+    /// Niceness of the script and its children.
+    pub nice: Option<i32>,
+    /// Keep what the script writes to stderr in `ScriptRun::stderr`.
+    pub capture_stderr: bool,
+    /// Do not pass what the script writes to stderr through.
+    pub quiet_stderr: bool,
+}
+
+/// Copy `r` to stderr unless `quiet`, and return everything read.
+fn capture_stderr(mut r: impl Read, quiet: bool) -> io::Result<Vec<u8>> {
+    let mut captured = Vec::new();
+    let mut buf = [0; 8192];
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            return Ok(captured);
+        }
+        if !quiet {
+            // Nowhere to report a failed write to stderr, keep capturing.
+            let _ = io::stderr().write_all(&buf[..n]);
+        }
+        captured.extend_from_slice(&buf[..n]);
+    }
+}
+
+/// Run script to completion, copying its output to `out`.
+///
+/// Durations of phases marked by the script are added as metrics.
+/// With `cgroup`, bytes read and written are added as metrics when the io
+/// controller is enabled. With `body_time`, time until the script body starts
+/// and after it is added as metrics `setup_us` and `body_us`.
+pub fn run_script(
+    script: &str,
+    dir: Option<&Path>,
+    env: &[(String, String)],
+    out: impl Write,
+    opts: &ScriptOptions,
+) -> anyhow::Result<ScriptRun> {
+    let mut command = opts.shell.command(script)?;
+    command.envs(env.iter().cloned()).stdout(Stdio::piped());
+    if opts.capture_stderr {
+        command.stderr(Stdio::piped());
+    } else if opts.quiet_stderr {
+        command.stderr(Stdio::null());
+    }
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    #[cfg(unix)]
+    let own_group = opts.timeout.is_some() || opts.mem_tree;
+    #[cfg(unix)]
+    if own_group {
+        command.process_group(0);
+    }
+    // Every script runs in a job, which accounts for all processes it starts.
+    #[cfg(windows)]
+    let job = {
+        Job::suspend_on_spawn(&mut command);
+        Arc::new(Job::create()?)
+    };
+    let cgroup = match opts.cgroup {
+        true => Some(Cgroup::create()?),
+        false => None,
+    };
+    if let Some(cgroup) = &cgroup {
+        cgroup.join_on_spawn(&mut command)?;
+    }
+    pin_on_spawn(&mut command, opts.cpus.as_ref(), opts.nice)?;
+    let body_start = match opts.body_time {
+        true => Some(inherited_pipe(&mut command, BODY_START_FD)?),
+        false => None,
+    };
+    let metrics_pipe = match opts.metrics_fd {
+        true => {
+            command.env(METRICS_FD_ENV, METRICS_FD.to_string());
+            Some(inherited_pipe(&mut command, METRICS_FD)?)
+        }
+        false => None,
+    };
+    // Closed when the shell exited, to stop reading pipes a background process keeps open.
+    let (exited, exited_writer) = io::pipe()?;
+    // Only the script is timed, not creating the cgroup or pipes above.
+    let start = Instant::now();
+    let mut process = command.spawn()?;
+    #[cfg(windows)]
+    if let Err(e) = job.assign(&process) {
+        let _ = process.kill();
+        let _ = process.wait();
+        return Err(e);
+    }
+    let stderr = match process.stderr.take() {
+        Some(stderr) => {
+            let stderr = UntilExit::new(stderr, &exited)?;
+            let quiet = opts.quiet_stderr;
+            Some(thread::spawn(move || capture_stderr(stderr, quiet)))
+        }
+        None => None,
+    };
+    // On Windows the job reports peak memory of the whole tree.
+    let mem_tree = opts.mem_tree && cfg!(unix);
+    let rss_poller = match (mem_tree, opts.poll_rss || opts.threads_fds) {
+        (true, _) => Some(RssPoller::start_group(process.id(), opts.threads_fds)),
+        (false, true) => Some(RssPoller::start(process.id(), opts.threads_fds)),
+        (false, false) => None,
+    };
+    let body_start = match body_start {
+        Some((reader, writer)) => {
+            // Only the script holds the writer now.
+            drop(writer);
+            let reader = UntilExit::new(reader, &exited)?;
+            Some(thread::spawn(move || wait_body_start(reader)))
+        }
+        None => None,
+    };
+    let metrics_lines = match metrics_pipe {
+        Some((reader, writer)) => {
+            drop(writer);
+            let reader = UntilExit::new(reader, &exited)?;
+            Some(thread::spawn(move || {
+                copy_collecting_metrics_timed(reader, io::sink(), start, true)
+            }))
+        }
+        None => None,
+    };
+    #[cfg(unix)]
+    let _group = own_group.then(|| kill_group_on_interrupt(process.id()));
+    #[cfg(unix)]
+    let kill = {
+        let pgid = process.id();
+        move || kill_group(pgid)
+    };
+    #[cfg(windows)]
+    let kill = {
+        let job = job.clone();
+        move || job.terminate()
+    };
+    let watchdog = opts.timeout.map(|timeout| kill_after(kill, timeout));
+    let stdout = UntilExit::new(process.stdout.take().unwrap(), &exited)?;
+    let pid = process.id();
+    let (output, status) = thread::scope(|scope| {
+        let waiter = scope.spawn(|| {
+            let status = process.wait4();
+            drop(exited_writer);
+            status
+        });
+        let output = copy_collecting_metrics_timed(stdout, out, start, !opts.metrics_fd);
+        (output, waiter.join().unwrap())
+    });
+    let (output, status) = (output?, status?);
+    let elapsed = start.elapsed();
+    let stderr = match stderr {
+        Some(stderr) => stderr.join().unwrap()?,
+        None => Vec::new(),
+    };
+    let (mut metrics, phases) = match metrics_lines {
+        Some(metrics_lines) => {
+            let lines = metrics_lines.join().unwrap()?;
+            (lines.metrics, lines.phases)
+        }
+        None => (output.metrics, output.phases),
+    };
+    if opts.line_latency {
+        metrics.extend(line_latency_metrics(&output.line_times));
+    }
+    let peaks = rss_poller.map(RssPoller::finish).unwrap_or_default();
+    let timed_out = match watchdog {
+        Some((done_tx, watchdog)) => {
+            let _ = done_tx.send(());
+            watchdog.join().unwrap()
+        }
+        None => false,
+    };
+    metrics.extend(phase_metrics(&phases, elapsed));
+    if opts.threads_fds {
+        metrics.push(("peak_threads".to_owned(), peaks.threads));
+        metrics.push(("peak_fds".to_owned(), peaks.fds));
+    }
+    // Sum over the tree is only wanted when asked, `wait4` reports the largest process.
+    let polled_rss = match opts.poll_rss || mem_tree {
+        true => peaks.rss,
+        false => 0,
+    };
+    if let Some(body_start) = body_start.and_then(|t| t.join().unwrap()) {
+        // The reading thread may see the byte only after the script exited.
+        let setup = body_start.duration_since(start).min(elapsed);
+        metrics.push(("setup_us".to_owned(), setup.as_micros() as u64));
+        metrics.push(("body_us".to_owned(), (elapsed - setup).as_micros() as u64));
+    }
+    let duration = Duration::from_nanos(elapsed.as_nanos().try_into()?);
+    let mut run = ScriptRun {
+        status: status.status,
+        pid,
+        duration,
+        max_rss: u64::max(status.rusage.maxrss, polled_rss),
+        user_time: Duration::from_nanos(status.rusage.utime.as_nanos().try_into()?),
+        sys_time: Duration::from_nanos(status.rusage.stime.as_nanos().try_into()?),
+        metrics,
+        timed_out,
+        stderr,
+    };
+    #[cfg(windows)]
+    {
+        let stats = job.stats()?;
+        run.user_time = stats.user_time;
+        run.sys_time = stats.sys_time;
+        // Jobs track committed memory rather than the working set.
+        run.max_rss = match opts.mem_tree {
+            true => stats.peak_job_memory,
+            false => stats.peak_process_memory,
+        };
+    }
+    if let Some(cgroup) = cgroup {
+        let stats = cgroup.stats()?;
+        run.user_time = stats.user_time;
+        run.sys_time = stats.sys_time;
+        // Includes page cache and kernel memory, so it is not comparable with max rss.
+        if let Some(memory_peak) = stats.memory_peak {
+            run.metrics
+                .push(("cgroup_memory_peak".to_owned(), memory_peak));
+        }
+        if let Some((read, written)) = stats.io {
+            run.metrics.push(("io_read_bytes".to_owned(), read));
+            run.metrics.push(("io_write_bytes".to_owned(), written));
+        }
+    }
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::time;
+    #[cfg(unix)]
+    use std::time::Instant;
+
+    use crate::sh::ScriptOptions;
+    use crate::sh::Shell;
+    use crate::sh::run_script;
+    use crate::sh::with_body_start;
+
+    #[test]
+    fn shell_argv() {
+        let argv = |path: &str| Shell::Path(path.to_owned()).argv("x").unwrap();
+        assert_eq!(vec!["/bin/bash", "-ec", "x"], argv("/bin/bash"));
+        assert_eq!(vec!["/usr/bin/fish", "-c", "x"], argv("/usr/bin/fish"));
+        assert_eq!(
+            vec![r"C:\Windows\System32\CMD.EXE", "/C", "x"],
+            argv(r"C:\Windows\System32\CMD.EXE")
+        );
+        assert_eq!(vec!["pwsh", "-Command", "x"], argv("pwsh"));
+        assert!(!Shell::Path("cmd.exe".to_owned()).is_posix());
+        assert!(Shell::Path("/bin/dash".to_owned()).is_posix());
+    }
+
+    #[test]
+    fn metrics_fd() {
+        let script = with_body_start(
+            "echo absh-metric: fake=1; echo absh-metric: real=2 >&$ABSH_METRICS_FD; \
+             echo absh-phase: p >&$ABSH_METRICS_FD",
+        );
+        let opts = ScriptOptions {
+            metrics_fd: true,
+            body_time: true,
+            ..ScriptOptions::default()
+        };
+        let mut out = Vec::new();
+        let run = run_script(&script, None, &[], &mut out, &opts).unwrap();
+        assert!(run.status.success());
+        assert_eq!(b"absh-metric: fake=1\n", &out[..]);
+        let names: Vec<&str> = run.metrics.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["real", "phase_p_us", "setup_us", "body_us"], names);
+        assert_eq!(2, run.metrics[0].1);
+    }
+
+    #[test]
+    fn capture_stderr() {
+        let opts = ScriptOptions {
+            capture_stderr: true,
+            quiet_stderr: true,
+            ..ScriptOptions::default()
+        };
+        let mut out = Vec::new();
+        let run = run_script("echo out; echo err >&2", None, &[], &mut out, &opts).unwrap();
+        assert_eq!(b"out\n", &out[..]);
+        assert_eq!(b"err\n", &run.stderr[..]);
+    }
+
+    /// A background process keeping the output pipes open does not block the run.
+    #[cfg(unix)]
+    #[test]
+    fn background_process() {
+        let opts = ScriptOptions {
+            capture_stderr: true,
+            quiet_stderr: true,
+            metrics_fd: true,
+            ..ScriptOptions::default()
+        };
+        let start = Instant::now();
+        let mut out = Vec::new();
+        let run = run_script(
+            "sleep 5 & echo out; echo err >&2; echo absh-metric: m=1 >&$ABSH_METRICS_FD",
+            None,
+            &[],
+            &mut out,
+            &opts,
+        )
+        .unwrap();
+        assert!(start.elapsed() < time::Duration::from_secs(4));
+        assert!(run.status.success());
+        assert_eq!(b"out\n", &out[..]);
+        assert_eq!(b"err\n", &run.stderr[..]);
+        assert_eq!(vec![("m".to_owned(), 1)], run.metrics);
+    }
 }
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Context;
+
+/// Part of the iterations executed by one session, like `2/5`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Shard {
+    /// One-based shard index.
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    /// Number of iterations this shard runs out of `total`.
+    ///
+    /// Iteration `i` (zero-based) belongs to shard `i % count + 1`.
+    pub fn iterations(&self, total: u32) -> u32 {
+        (total + self.count - self.index) / self.count
+    }
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Shard> {
+        let (index, count) = s
+            .split_once('/')
+            .with_context(|| format!("shard must be `K/M`: {:?}", s))?;
+        let index: u32 = index
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid shard index: {:?}", s))?;
+        let count: u32 = count
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid shard count: {:?}", s))?;
+        if index == 0 || index > count {
+            return Err(anyhow::anyhow!(
+                "shard index must be in 1..={}: {:?}",
+                count,
+                s
+            ));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shard::Shard;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Shard { index: 2, count: 5 },
+            "2/5".parse::<Shard>().unwrap()
+        );
+        assert!("0/5".parse::<Shard>().is_err());
+        assert!("6/5".parse::<Shard>().is_err());
+        assert!("5".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn iterations() {
+        let total: u32 = (1..=5)
+            .map(|index| Shard { index, count: 5 }.iterations(12))
+            .sum();
+        assert_eq!(12, total);
+        assert_eq!(3, Shard { index: 1, count: 5 }.iterations(12));
+        assert_eq!(2, Shard { index: 5, count: 5 }.iterations(12));
+    }
+}
@@ -15,9 +15,7 @@ pub fn shell_quote(s: &str) -> String {
     }
 
     // If a string does not need escaping, just return it.
-    if !s.contains(|c| {
-        c == '"' || c == '\'' || c == '\"' || c <= ' ' || c == '\\' || c == '$' || c == '`'
-    }) {
+    if !s.contains(|c| c == '"' || c == '\'' || c <= ' ' || c == '\\' || c == '$' || c == '`') {
         return s.to_owned();
     }
 
@@ -148,10 +146,98 @@ pub fn shell_quote_args(args: impl IntoIterator<Item = impl AsRef<str>>) -> Stri
         .join(" ")
 }
 
+/// Split a command line into arguments like a POSIX shell, without expansions.
+///
+/// Supports single and double quotes, backslash escapes, and `$'...'` strings
+/// produced by [`shell_quote`].
+pub fn shell_split(s: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = s.chars().peekable();
+    let unterminated = || anyhow::anyhow!("unterminated quote in `{}`", s);
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = arg.take() {
+                    args.push(arg);
+                }
+            }
+            '\\' => {
+                let next = chars
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("trailing `\\` in `{}`", s))?;
+                // Backslash-newline is a line continuation.
+                if next != '\n' {
+                    arg.get_or_insert_with(String::new).push(next);
+                }
+            }
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or_else(unterminated)? {
+                            c @ ('"' | '\\' | '$' | '`') => arg.push(c),
+                            '\n' => {}
+                            c => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        '\\' => match chars.next().ok_or_else(unterminated)? {
+                            'n' => arg.push('\n'),
+                            'r' => arg.push('\r'),
+                            't' => arg.push('\t'),
+                            c @ '0'..='7' => {
+                                let mut code = c.to_digit(8).unwrap();
+                                for _ in 0..2 {
+                                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                                        Some(d) => {
+                                            code = code * 8 + d;
+                                            chars.next();
+                                        }
+                                        None => break,
+                                    }
+                                }
+                                arg.push(char::from(code as u8));
+                            }
+                            c => arg.push(c),
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::shell::shell_quote;
     use crate::shell::shell_quote_args;
+    use crate::shell::shell_split;
 
     #[test]
     fn test_shell_quote() {
@@ -170,6 +256,20 @@ mod tests {
 
     #[test]
     fn test_shell_quote_args() {
-        assert_eq!("echo '$A'", shell_quote_args(&["echo", "$A"]));
+        assert_eq!("echo '$A'", shell_quote_args(["echo", "$A"]));
+    }
+
+    #[test]
+    fn test_shell_split() {
+        let split = |s| shell_split(s).unwrap();
+        assert_eq!(vec!["ls", "-l", "a b"], split("  ls -l 'a b' "));
+        assert_eq!(vec!["a\"b$", "c d", ""], split(r#""a\"b$" c\ d """#));
+        assert_eq!(vec!["x\ny"], split("$'x\\ny'"));
+        assert!(split("").is_empty());
+        assert!(shell_split("'x").is_err());
+        assert!(shell_split("x\\").is_err());
+
+        let args = ["echo", "", "x y", "x'y\"z", "x\ny", "x\x01y", "$A", "a\\b"];
+        assert_eq!(args.to_vec(), split(&shell_quote_args(args)));
     }
 }
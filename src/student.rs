@@ -1,15 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
 /// Confidence interval.
 #[allow(non_camel_case_types)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ConfInterval {
     C_95_0_90_0,
     C_97_5_95_0,
+    C_99_5_99_0,
+    C_99_95_99_9,
 }
 
 /// Two-sided 95% confidence interval.
 pub const TWO_SIDED_95: ConfInterval = ConfInterval::C_97_5_95_0;
 
+impl Default for ConfInterval {
+    fn default() -> ConfInterval {
+        TWO_SIDED_95
+    }
+}
+
+/// Two-sided level, like `0.99`.
+impl FromStr for ConfInterval {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ConfInterval> {
+        match s {
+            "0.9" | "0.90" => Ok(ConfInterval::C_95_0_90_0),
+            "0.95" => Ok(ConfInterval::C_97_5_95_0),
+            "0.99" => Ok(ConfInterval::C_99_5_99_0),
+            "0.999" => Ok(ConfInterval::C_99_95_99_9),
+            s => Err(anyhow::anyhow!(
+                "unsupported confidence: `{}`, expecting `0.90`, `0.95`, `0.99` or `0.999`",
+                s
+            )),
+        }
+    }
+}
+
+impl ConfInterval {
+    /// Two-sided level, like `0.99`.
+    pub fn level(&self) -> f64 {
+        match self {
+            ConfInterval::C_95_0_90_0 => 0.90,
+            ConfInterval::C_97_5_95_0 => 0.95,
+            ConfInterval::C_99_5_99_0 => 0.99,
+            ConfInterval::C_99_95_99_9 => 0.999,
+        }
+    }
+
+    /// Significance level of tests matching the interval, `1 - level`, like `0.01`.
+    pub fn alpha(&self) -> f64 {
+        match self {
+            ConfInterval::C_95_0_90_0 => 0.10,
+            ConfInterval::C_97_5_95_0 => 0.05,
+            ConfInterval::C_99_5_99_0 => 0.01,
+            ConfInterval::C_99_95_99_9 => 0.001,
+        }
+    }
+}
+
+/// Two-sided level in percent, like `99%`.
+impl fmt::Display for ConfInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfInterval::C_95_0_90_0 => write!(f, "90%"),
+            ConfInterval::C_97_5_95_0 => write!(f, "95%"),
+            ConfInterval::C_99_5_99_0 => write!(f, "99%"),
+            ConfInterval::C_99_95_99_9 => write!(f, "99.9%"),
+        }
+    }
+}
+
 /// Query precomputed t-table.
+#[allow(clippy::approx_constant)]
 pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
     assert!(v >= 1);
     // One-sided              75%    80%    85%    90%    95%    97.5%  99%    99.5%  99.75% 99.9%  99.95%
@@ -232,7 +296,7 @@ pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
             ],
         ),
         (
-            u64::max_value(),
+            u64::MAX,
             [
                 0.674, 0.842, 1.036, 1.282, 1.645, 1.960, 2.326, 2.576, 2.807, 3.090, 3.291,
             ],
@@ -243,9 +307,27 @@ pub fn t_table(v: u64, conf_interval: ConfInterval) -> f64 {
             let index = match conf_interval {
                 ConfInterval::C_95_0_90_0 => 4,
                 ConfInterval::C_97_5_95_0 => 5,
+                ConfInterval::C_99_5_99_0 => 7,
+                ConfInterval::C_99_95_99_9 => 10,
             };
             return row.1[index];
         }
     }
     unreachable!()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::student::ConfInterval;
+    use crate::student::t_table;
+
+    #[test]
+    fn levels() {
+        let conf = |s: &str| s.parse::<ConfInterval>().unwrap();
+        assert_eq!(12.71, t_table(1, conf("0.95")));
+        assert_eq!(3.169, t_table(10, conf("0.99")));
+        assert_eq!(3.291, t_table(u64::MAX, conf("0.999")));
+        assert_eq!("90%", conf("0.90").to_string());
+        assert!("0.8".parse::<ConfInterval>().is_err());
+    }
+}
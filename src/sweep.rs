@@ -0,0 +1,188 @@
+//! Combined verdict of one benchmark recorded separately at several parameter values.
+
+use std::fmt::Write as _;
+
+use anyhow::Context;
+
+use crate::experiment::Experiment;
+use crate::experiment_map::ExperimentMap;
+use crate::math::bootstrap::bootstrap_geomean;
+use crate::math::ratio::RatioInterval;
+use crate::math::ratio::Stat;
+use crate::measure::key::MeasureKey;
+use crate::render_stats::RenderOptions;
+use crate::render_stats::render_verdict;
+use crate::run_log_reader::LoadedRunLog;
+
+/// Resamples of the geometric mean interval without `--bootstrap`.
+pub const SWEEP_RESAMPLES: u32 = 10000;
+
+/// Variants recorded at one parameter value.
+pub struct SweepPoint {
+    /// Parameter value, like the run log directory.
+    pub label: String,
+    pub experiments: ExperimentMap<Experiment>,
+}
+
+/// Points of recorded logs without samples excluded in review, checking that all of them
+/// have the same variants with enough runs.
+pub fn sweep_points(
+    logs: Vec<LoadedRunLog>,
+    filter_outliers: bool,
+) -> anyhow::Result<Vec<SweepPoint>> {
+    let mut points: Vec<SweepPoint> = Vec::new();
+    for log in logs {
+        let log = log.without_excluded();
+        let names: Vec<_> = log.experiments.keys().collect();
+        if names.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "fewer than 2 variants in {}",
+                log.dir.display()
+            ));
+        }
+        if let Some(first) = points.first() {
+            if names != first.experiments.keys().collect::<Vec<_>>() {
+                return Err(anyhow::anyhow!(
+                    "{} and {} have different experiments",
+                    first.label,
+                    log.dir.display()
+                ));
+            }
+        }
+        for (name, experiment) in log.experiments.iter() {
+            if experiment.runs() < 2 {
+                return Err(anyhow::anyhow!(
+                    "not enough runs of {} in {} to compute statistics",
+                    name,
+                    log.dir.display()
+                ));
+            }
+        }
+        let experiments = match filter_outliers {
+            true => log.experiments.map(|t| t.without_outliers(&log.measures)),
+            false => log.experiments,
+        };
+        points.push(SweepPoint {
+            label: log.dir.display().to_string(),
+            experiments,
+        });
+    }
+    Ok(points)
+}
+
+/// Wall time ratio of every variant to A at each parameter value, and their geometric mean
+/// with a percentile bootstrap interval over parameter values.
+///
+/// All points must have the same variants.
+pub fn render_sweep(
+    points: &[SweepPoint],
+    opts: &RenderOptions,
+    resamples: u32,
+) -> anyhow::Result<(String, Vec<RatioInterval>)> {
+    let first = &points.first().context("no parameter values")?.experiments;
+    let label_width = points.iter().map(|p| p.label.len()).max().unwrap();
+    let words = ("faster", "slower");
+
+    let mut r = String::new();
+    let mut combined = Vec::new();
+    writeln!(r, "Time (in seconds):")?;
+    let mut names = first.keys();
+    let a_name = names.next().unwrap();
+    for b_name in names {
+        writeln!(r, "{}/{} by parameter:", b_name, a_name)?;
+        let mut ratios = Vec::new();
+        for point in points {
+            let numbers = |name| &point.experiments[name].measures[MeasureKey::WallTime];
+            let interval =
+                RatioInterval::compare(opts.stat, opts.conf, numbers(a_name), numbers(b_name));
+            writeln!(
+                r,
+                "  {label:<label_width$} {b_a:.3} {b_a_min:.3}..{b_a_max:.3} ({conf} conf{of}) n={n_a}/{n_b}: {verdict}",
+                label = point.label,
+                label_width = label_width,
+                b_a = interval.ratio,
+                b_a_min = interval.min,
+                b_a_max = interval.max,
+                conf = opts.conf,
+                of = match opts.stat {
+                    Stat::Mean => "",
+                    Stat::Median => " of medians",
+                },
+                n_a = numbers(a_name).len(),
+                n_b = numbers(b_name).len(),
+                verdict = render_verdict(b_name, interval.verdict(), interval.ratio, words),
+            )?;
+            ratios.push(interval.ratio);
+        }
+        match bootstrap_geomean(&ratios, resamples, opts.conf, &mut rand::thread_rng()) {
+            Some(interval) => {
+                writeln!(
+                    r,
+                    "{b_name}/{a_name} geometric mean: {b_a:.3} {b_a_min:.3}..{b_a_max:.3} ({conf} bootstrap over {n} parameters): {verdict}",
+                    b_a = interval.ratio,
+                    b_a_min = interval.min,
+                    b_a_max = interval.max,
+                    conf = opts.conf,
+                    n = ratios.len(),
+                    verdict = render_verdict(b_name, interval.verdict(), interval.ratio, words),
+                )?;
+                combined.push(interval);
+            }
+            None => writeln!(
+                r,
+                "{}/{} geometric mean: undefined, some ratio is not positive",
+                b_name, a_name
+            )?,
+        }
+    }
+    Ok((r, combined))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::strip_csi;
+    use crate::experiment::Experiment;
+    use crate::experiment_map::ExperimentMap;
+    use crate::experiment_name::ExperimentName;
+    use crate::math::ratio::Verdict;
+    use crate::measure::key::MeasureKey;
+    use crate::render_stats::RenderOptions;
+    use crate::sweep::SweepPoint;
+    use crate::sweep::render_sweep;
+
+    fn point(label: &str, a: u64, b: u64) -> SweepPoint {
+        let mut experiments = ExperimentMap::default();
+        for &(name, mean) in [("sweep-a", a), ("sweep-b", b)].iter() {
+            let name = ExperimentName::intern(name);
+            let mut experiment = Experiment::new(name, String::new(), String::new());
+            for i in 0..10 {
+                experiment.measures[MeasureKey::WallTime].push(mean + i % 3);
+            }
+            experiments.insert(name, experiment);
+        }
+        SweepPoint {
+            label: label.to_owned(),
+            experiments,
+        }
+    }
+
+    #[test]
+    fn render() {
+        let points = [point("n=10", 100, 110), point("n=1000", 1000, 1200)];
+        let (text, combined) = render_sweep(&points, &RenderOptions::default(), 1000).unwrap();
+        let text = strip_csi(&text);
+        assert!(
+            text.contains("sweep-b/sweep-a by parameter:\n  n=10   1.100"),
+            "{}",
+            text
+        );
+        assert!(text.contains("  n=1000 1.200"), "{}", text);
+        assert!(
+            text.contains("sweep-b/sweep-a geometric mean: 1.149 1.100..1.200 "),
+            "{}",
+            text
+        );
+        assert_eq!(1, combined.len());
+        assert_eq!(Verdict::Higher, combined[0].verdict());
+    }
+}
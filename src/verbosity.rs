@@ -0,0 +1,45 @@
+/// How much is printed during runs, given with `-q` and `-v`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Verbosity {
+    /// Only problems, like failed runs, and the final statistics.
+    Quiet,
+    /// Scripts before they run, their stderr, results of each run and statistics
+    /// after each iteration.
+    #[default]
+    Normal,
+    /// Also stdout of scripts.
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: bool) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, true) => Verbosity::Verbose,
+            (false, false) => Verbosity::Normal,
+        }
+    }
+
+    /// Print scripts, results of each run and statistics after each iteration.
+    pub fn prints_runs(&self) -> bool {
+        *self != Verbosity::Quiet
+    }
+
+    /// Pass output of scripts through.
+    pub fn streams_output(&self) -> bool {
+        *self == Verbosity::Verbose
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verbosity::Verbosity;
+
+    #[test]
+    fn from_flags() {
+        assert_eq!(Verbosity::Normal, Verbosity::from_flags(false, false));
+        assert!(!Verbosity::from_flags(true, false).prints_runs());
+        assert!(!Verbosity::from_flags(false, false).streams_output());
+        assert!(Verbosity::from_flags(false, true).streams_output());
+    }
+}
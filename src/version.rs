@@ -0,0 +1,25 @@
+/// Crate version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Git commit absh was built from, or `unknown`.
+pub const GIT_COMMIT: &str = env!("ABSH_GIT_COMMIT");
+/// Comma-separated list of cargo features enabled at build time.
+pub const FEATURES: &str = env!("ABSH_FEATURES");
+
+pub fn features() -> Vec<&'static str> {
+    FEATURES.split(',').filter(|f| !f.is_empty()).collect()
+}
+
+/// Version string printed by `--version`.
+pub fn version_long() -> String {
+    let features = features();
+    format!(
+        "{} (commit {}, features: {})",
+        VERSION,
+        GIT_COMMIT,
+        if features.is_empty() {
+            "none".to_owned()
+        } else {
+            features.join(",")
+        }
+    )
+}
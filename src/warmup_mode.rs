@@ -0,0 +1,74 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// When warmup scripts run, given with `--warmup-mode`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum WarmupMode {
+    /// Before every run.
+    #[default]
+    Each,
+    /// Before runs until a warmup of the variant succeeds.
+    Once,
+    /// Before runs until this many warmups of the variant succeed.
+    FirstN(u64),
+}
+
+impl WarmupMode {
+    /// Whether to run the warmup after `succeeded` successful warmups of the variant.
+    pub fn runs_warmup(&self, succeeded: u64) -> bool {
+        match *self {
+            WarmupMode::Each => true,
+            WarmupMode::Once => succeeded == 0,
+            WarmupMode::FirstN(n) => succeeded < n,
+        }
+    }
+}
+
+impl FromStr for WarmupMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<WarmupMode> {
+        match s {
+            "each" => Ok(WarmupMode::Each),
+            "once" => Ok(WarmupMode::Once),
+            s => match s.strip_prefix("first-n=") {
+                Some(n) => match n.parse() {
+                    Ok(n) if n > 0 => Ok(WarmupMode::FirstN(n)),
+                    _ => Err(anyhow::anyhow!("invalid warmup count: `{}`", n)),
+                },
+                None => Err(anyhow::anyhow!(
+                    "unknown warmup mode: `{}`, expecting `each`, `once` or `first-n=N`",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for WarmupMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarmupMode::Each => write!(f, "each"),
+            WarmupMode::Once => write!(f, "once"),
+            WarmupMode::FirstN(n) => write!(f, "first-n={}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::warmup_mode::WarmupMode;
+
+    #[test]
+    fn runs_warmup() {
+        let mode = |s: &str| s.parse::<WarmupMode>().unwrap();
+        assert!(mode("each").runs_warmup(10));
+        assert!(mode("once").runs_warmup(0));
+        assert!(!mode("once").runs_warmup(1));
+        assert!(mode("first-n=3").runs_warmup(2));
+        assert!(!mode("first-n=3").runs_warmup(3));
+        assert!("first-n=0".parse::<WarmupMode>().is_err());
+        assert!("always".parse::<WarmupMode>().is_err());
+        assert_eq!("first-n=3", mode("first-n=3").to_string());
+    }
+}
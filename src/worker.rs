@@ -0,0 +1,492 @@
+//! Worker process which runs scripts on behalf of the main process.
+//!
+//! The main process sends scripts over the worker's stdin and reads
+//! script output and timings from the worker's stdout. Both directions
+//! are sequences of chunks: decimal length, newline, then the bytes.
+//!
+//! Request is the script, then the working directory (empty for the
+//! current one), then `NAME=VALUE` environment chunks, then an empty
+//! chunk, then script option chunks like `timeout-ms=500`, then an empty
+//! chunk. Options come with every request, since hooks and warmups run
+//! with other options than measured scripts. Response is any number of output chunks (tag `o`) and metric
+//! chunks (tag `m`), captured stderr (tag `e`), then a done chunk
+//! (tag `d`).
+
+use std::env;
+#[cfg(unix)]
+use std::ffi::OsString;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStringExt;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::ChildStdout;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+
+use anyhow::Context;
+
+use crate::duration::Duration;
+use crate::interrupt::install_interrupt_handler;
+use crate::metric::METRIC_PREFIX;
+use crate::metric::parse_metric_line;
+use crate::sh::ScriptOptions;
+use crate::sh::ScriptRun;
+use crate::sh::Shell;
+use crate::sh::run_script;
+
+/// Name of the hidden subcommand executed in the worker process.
+pub const WORKER_SUBCOMMAND: &str = "internal-worker";
+
+/// Request option enabling line latency metrics.
+const OPT_LINE_LATENCY: &str = "line-latency";
+
+/// Request option with script timeout in milliseconds.
+const OPT_TIMEOUT_MS: &str = "timeout-ms";
+
+/// Request option enabling cgroup accounting.
+const OPT_CGROUP: &str = "cgroup";
+
+/// Request option with the shell running scripts.
+const OPT_SHELL: &str = "shell";
+
+/// Request option running scripts without a shell.
+const OPT_EXEC: &str = "exec";
+
+/// Request option enabling body start reports.
+const OPT_BODY_TIME: &str = "body-time";
+
+/// Request option enabling RSS sampling from `/proc`.
+const OPT_POLL_RSS: &str = "poll-rss";
+
+/// Request option enabling RSS sampling of the script's process group.
+const OPT_MEM_TREE: &str = "mem-tree";
+
+/// Request option enabling thread and file descriptor sampling.
+const OPT_THREADS_FDS: &str = "threads-fds";
+
+/// Request option reading metrics from a separate descriptor.
+const OPT_METRICS_FD: &str = "metrics-fd";
+
+/// Request option with CPUs to run scripts on.
+const OPT_CPU_LIST: &str = "cpu-list";
+
+/// Request option with niceness of scripts.
+const OPT_NICE: &str = "nice";
+
+/// Request option discarding stderr of scripts.
+const OPT_QUIET_STDERR: &str = "quiet-stderr";
+
+/// Request option sending stderr of scripts back.
+const OPT_CAPTURE_STDERR: &str = "capture-stderr";
+
+const TAG_OUTPUT: u8 = b'o';
+const TAG_METRIC: u8 = b'm';
+const TAG_STDERR: u8 = b'e';
+const TAG_DONE: u8 = b'd';
+
+fn write_chunk(w: &mut impl Write, chunk: &[u8]) -> io::Result<()> {
+    writeln!(w, "{}", chunk.len())?;
+    w.write_all(chunk)
+}
+
+fn write_tagged(w: &mut impl Write, tag: u8, data: &[u8]) -> io::Result<()> {
+    writeln!(w, "{}", data.len() + 1)?;
+    w.write_all(&[tag])?;
+    w.write_all(data)
+}
+
+/// Read chunk, `None` on EOF.
+fn read_chunk(r: &mut impl BufRead) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut len = String::new();
+    if r.read_line(&mut len)? == 0 {
+        return Ok(None);
+    }
+    let len: usize = len
+        .trim_end()
+        .parse()
+        .with_context(|| format!("invalid chunk length: {:?}", len))?;
+    let mut chunk = vec![0; len];
+    r.read_exact(&mut chunk)?;
+    Ok(Some(chunk))
+}
+
+/// Script options as request chunks, `NAME` or `NAME=VALUE`.
+fn options_to_chunks(opts: &ScriptOptions) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut flag = |on: bool, name: &str| {
+        if on {
+            chunks.push(name.to_owned());
+        }
+    };
+    flag(opts.line_latency, OPT_LINE_LATENCY);
+    flag(opts.cgroup, OPT_CGROUP);
+    flag(opts.body_time, OPT_BODY_TIME);
+    flag(opts.poll_rss, OPT_POLL_RSS);
+    flag(opts.mem_tree, OPT_MEM_TREE);
+    flag(opts.threads_fds, OPT_THREADS_FDS);
+    flag(opts.metrics_fd, OPT_METRICS_FD);
+    flag(opts.quiet_stderr, OPT_QUIET_STDERR);
+    flag(opts.capture_stderr, OPT_CAPTURE_STDERR);
+    flag(opts.shell == Shell::Exec, OPT_EXEC);
+    if let Shell::Path(path) = &opts.shell {
+        chunks.push(format!("{}={}", OPT_SHELL, path));
+    }
+    if let Some(timeout) = opts.timeout {
+        chunks.push(format!("{}={}", OPT_TIMEOUT_MS, timeout.millis()));
+    }
+    if let Some(cpus) = &opts.cpus {
+        chunks.push(format!("{}={}", OPT_CPU_LIST, cpus));
+    }
+    if let Some(nice) = opts.nice {
+        chunks.push(format!("{}={}", OPT_NICE, nice));
+    }
+    chunks
+}
+
+/// Script options from request chunks made by `options_to_chunks`.
+fn options_from_chunks(chunks: &[String]) -> anyhow::Result<ScriptOptions> {
+    let mut opts = ScriptOptions {
+        shell: Shell::Exec,
+        ..ScriptOptions::default()
+    };
+    for chunk in chunks {
+        let (name, value) = match chunk.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (chunk.as_str(), None),
+        };
+        let value = || value.ok_or_else(|| anyhow::anyhow!("no value of option `{}`", name));
+        match name {
+            OPT_LINE_LATENCY => opts.line_latency = true,
+            OPT_CGROUP => opts.cgroup = true,
+            OPT_BODY_TIME => opts.body_time = true,
+            OPT_POLL_RSS => opts.poll_rss = true,
+            OPT_MEM_TREE => opts.mem_tree = true,
+            OPT_THREADS_FDS => opts.threads_fds = true,
+            OPT_METRICS_FD => opts.metrics_fd = true,
+            OPT_QUIET_STDERR => opts.quiet_stderr = true,
+            OPT_CAPTURE_STDERR => opts.capture_stderr = true,
+            OPT_EXEC => opts.shell = Shell::Exec,
+            OPT_SHELL => opts.shell = Shell::Path(value()?.to_owned()),
+            OPT_TIMEOUT_MS => opts.timeout = Some(Duration::from_millis(value()?.parse()?)),
+            OPT_CPU_LIST => opts.cpus = Some(value()?.parse()?),
+            OPT_NICE => opts.nice = Some(value()?.parse()?),
+            name => return Err(anyhow::anyhow!("unknown option: `{}`", name)),
+        }
+    }
+    Ok(opts)
+}
+
+/// Read chunks until an empty one, as strings.
+fn read_string_chunks(r: &mut impl BufRead) -> anyhow::Result<Vec<String>> {
+    let mut chunks = Vec::new();
+    loop {
+        let chunk = read_chunk(r)?.ok_or_else(|| anyhow::anyhow!("unexpected EOF in request"))?;
+        if chunk.is_empty() {
+            return Ok(chunks);
+        }
+        chunks.push(String::from_utf8(chunk)?);
+    }
+}
+
+/// Writer sending everything written as output chunks.
+struct OutputChunks<W: Write>(W);
+
+impl<W: Write> Write for OutputChunks<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_tagged(&mut self.0, TAG_OUTPUT, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Pre-spawned process running scripts.
+pub struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Worker {
+    pub fn spawn() -> anyhow::Result<Worker> {
+        let mut command = Command::new(env::current_exe()?);
+        command.arg(WORKER_SUBCOMMAND);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("spawning worker process")?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Worker {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Run script in the worker with `opts`, copying its output to `out`.
+    pub fn run(
+        &mut self,
+        script: &str,
+        dir: Option<&Path>,
+        env: &[(String, String)],
+        mut out: impl Write,
+        opts: &ScriptOptions,
+    ) -> anyhow::Result<ScriptRun> {
+        write_chunk(&mut self.stdin, script.as_bytes())?;
+        let dir = match dir {
+            Some(dir) => path_to_bytes(dir)?,
+            None => Vec::new(),
+        };
+        write_chunk(&mut self.stdin, &dir)?;
+        for (name, value) in env {
+            write_chunk(&mut self.stdin, format!("{}={}", name, value).as_bytes())?;
+        }
+        write_chunk(&mut self.stdin, &[])?;
+        for option in options_to_chunks(opts) {
+            write_chunk(&mut self.stdin, option.as_bytes())?;
+        }
+        write_chunk(&mut self.stdin, &[])?;
+        self.stdin.flush()?;
+
+        let mut metrics = Vec::new();
+        let mut stderr = Vec::new();
+        loop {
+            let chunk = read_chunk(&mut self.stdout)?
+                .ok_or_else(|| anyhow::anyhow!("worker process exited unexpectedly"))?;
+            let (tag, data) = chunk
+                .split_first()
+                .ok_or_else(|| anyhow::anyhow!("empty chunk from worker"))?;
+            match *tag {
+                TAG_OUTPUT => {
+                    out.write_all(data)?;
+                    out.flush()?;
+                }
+                TAG_METRIC => {
+                    let line = format!("{}{}", METRIC_PREFIX, String::from_utf8_lossy(data));
+                    metrics.extend(parse_metric_line(&line));
+                }
+                TAG_STDERR => stderr.extend_from_slice(data),
+                TAG_DONE => {
+                    let done = String::from_utf8_lossy(data);
+                    let fields: Vec<i64> = done
+                        .split(' ')
+                        .map(|f| f.parse())
+                        .collect::<Result<_, _>>()
+                        .with_context(|| format!("invalid done chunk: {:?}", done))?;
+                    if let [
+                        status,
+                        pid,
+                        nanos,
+                        max_rss,
+                        user_nanos,
+                        sys_nanos,
+                        timed_out,
+                    ] = fields[..]
+                    {
+                        return Ok(ScriptRun {
+                            status: ExitStatus::from_raw(status as _),
+                            pid: pid as u32,
+                            duration: Duration::from_nanos(nanos as u64),
+                            max_rss: max_rss as u64,
+                            user_time: Duration::from_nanos(user_nanos as u64),
+                            sys_time: Duration::from_nanos(sys_nanos as u64),
+                            metrics,
+                            timed_out: timed_out != 0,
+                            stderr,
+                        });
+                    }
+                    return Err(anyhow::anyhow!("invalid done chunk: {:?}", done));
+                }
+                tag => return Err(anyhow::anyhow!("unknown chunk tag: {}", tag)),
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Worker is idle between requests, so it is safe to kill it.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Working directory as sent to the worker.
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> anyhow::Result<Vec<u8>> {
+    Ok(path.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(windows)]
+fn path_to_bytes(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("directory is not valid Unicode: {}", path.display()))?;
+    Ok(path.as_bytes().to_vec())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> anyhow::Result<PathBuf> {
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(windows)]
+fn path_from_bytes(bytes: Vec<u8>) -> anyhow::Result<PathBuf> {
+    Ok(PathBuf::from(String::from_utf8(bytes)?))
+}
+
+/// Exit status as passed to `ExitStatus::from_raw`.
+#[cfg(unix)]
+fn raw_exit_status(status: ExitStatus) -> i64 {
+    status.into_raw() as i64
+}
+
+#[cfg(windows)]
+fn raw_exit_status(status: ExitStatus) -> i64 {
+    // Exit codes are `u32` on Windows, `code` always has them.
+    status.code().unwrap_or(0) as u32 as i64
+}
+
+/// Body of the worker process: run scripts until stdin is closed.
+pub fn serve_worker() -> anyhow::Result<()> {
+    // Ctrl-C is handled by the main process, the current script is finished normally.
+    install_interrupt_handler()?;
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    loop {
+        let script = match read_chunk(&mut stdin)? {
+            Some(script) => String::from_utf8(script)?,
+            None => return Ok(()),
+        };
+        let dir =
+            read_chunk(&mut stdin)?.ok_or_else(|| anyhow::anyhow!("unexpected EOF in request"))?;
+        let dir = match dir.is_empty() {
+            true => None,
+            false => Some(path_from_bytes(dir)?),
+        };
+        let mut env = Vec::new();
+        for var in read_string_chunks(&mut stdin)? {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("invalid env var: {:?}", var))?;
+            env.push((name.to_owned(), value.to_owned()));
+        }
+        let opts = options_from_chunks(&read_string_chunks(&mut stdin)?)?;
+
+        let run = run_script(
+            &script,
+            dir.as_deref(),
+            &env,
+            OutputChunks(&mut stdout),
+            &opts,
+        )?;
+        for (name, value) in &run.metrics {
+            write_tagged(
+                &mut stdout,
+                TAG_METRIC,
+                format!(" {}={}", name, value).as_bytes(),
+            )?;
+        }
+        if !run.stderr.is_empty() {
+            write_tagged(&mut stdout, TAG_STDERR, &run.stderr)?;
+        }
+        let done = format!(
+            "{} {} {} {} {} {} {}",
+            raw_exit_status(run.status),
+            run.pid,
+            run.duration.nanos(),
+            run.max_rss,
+            run.user_time.nanos(),
+            run.sys_time.nanos(),
+            run.timed_out as u8
+        );
+        write_tagged(&mut stdout, TAG_DONE, done.as_bytes())?;
+        stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::cpu_pinning::CpuList;
+    use crate::duration::Duration;
+    use crate::sh::ScriptOptions;
+    use crate::sh::Shell;
+    use crate::worker::options_from_chunks;
+    use crate::worker::options_to_chunks;
+    use crate::worker::read_chunk;
+    use crate::worker::write_chunk;
+    use crate::worker::write_tagged;
+
+    #[test]
+    fn chunks() {
+        let mut buf = Vec::new();
+        write_chunk(&mut buf, b"echo 1\n").unwrap();
+        write_chunk(&mut buf, b"").unwrap();
+        write_tagged(&mut buf, b'o', b"x").unwrap();
+        assert_eq!(b"7\necho 1\n0\n2\nox", &buf[..]);
+
+        let mut r = BufReader::new(&buf[..]);
+        assert_eq!(Some(b"echo 1\n".to_vec()), read_chunk(&mut r).unwrap());
+        assert_eq!(Some(Vec::new()), read_chunk(&mut r).unwrap());
+        assert_eq!(Some(b"ox".to_vec()), read_chunk(&mut r).unwrap());
+        assert_eq!(None, read_chunk(&mut r).unwrap());
+    }
+
+    #[test]
+    fn options() {
+        let opts = ScriptOptions {
+            shell: Shell::Path("/bin/bash".to_owned()),
+            line_latency: true,
+            timeout: Some(Duration::from_millis(1500)),
+            capture_stderr: true,
+            cpus: Some("0-1".parse::<CpuList>().unwrap()),
+            nice: Some(-5),
+            ..ScriptOptions::default()
+        };
+        let chunks = options_to_chunks(&opts);
+        assert_eq!(
+            vec![
+                "line-latency",
+                "capture-stderr",
+                "shell=/bin/bash",
+                "timeout-ms=1500",
+                "cpu-list=0-1",
+                "nice=-5",
+            ],
+            chunks
+        );
+        let parsed = options_from_chunks(&chunks).unwrap();
+        assert_eq!(format!("{:?}", opts), format!("{:?}", parsed));
+
+        let exec = ScriptOptions {
+            shell: Shell::Exec,
+            ..ScriptOptions::default()
+        };
+        let parsed = options_from_chunks(&options_to_chunks(&exec)).unwrap();
+        assert_eq!(Shell::Exec, parsed.shell);
+        assert!(!parsed.capture_stderr);
+        assert!(parsed.timeout.is_none());
+        assert!(options_from_chunks(&["fast".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::env;
+
+use crate::ansi::strip_csi;
+
+/// Width of the terminal attached to stderr, or `$COLUMNS`.
+pub fn terminal_width() -> Option<usize> {
+    #[cfg(unix)]
+    {
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        let r = unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut ws) };
+        if r == 0 && ws.ws_col != 0 {
+            return Some(ws.ws_col as usize);
+        }
+    }
+    env::var("COLUMNS").ok()?.trim().parse().ok()
+}
+
+/// Number of characters displayed, ignoring escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    strip_csi(s).chars().count()
+}
+
+/// Iterate over characters, yielding escape sequences as single items.
+fn csi_aware_chunks(s: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut rem = s;
+    std::iter::from_fn(move || {
+        if rem.is_empty() {
+            return None;
+        }
+        if let Some(after) = rem.strip_prefix("\x1b[") {
+            if let Some(end) = after.find(|c: char| c.is_ascii_alphabetic()) {
+                let (seq, r) = rem.split_at(2 + end + 1);
+                rem = r;
+                return Some((seq, true));
+            }
+        }
+        let len = rem.chars().next().unwrap().len_utf8();
+        let (c, r) = rem.split_at(len);
+        rem = r;
+        Some((c, false))
+    })
+}
+
+/// Cut the line to `width` visible characters, marking the cut with `…`.
+pub fn truncate_line(s: &str, width: usize) -> String {
+    if width == 0 || visible_width(s) <= width {
+        return s.to_owned();
+    }
+    let mut r = String::new();
+    let mut visible = 0;
+    for (chunk, is_csi) in csi_aware_chunks(s) {
+        // Keep escape sequences after the cut so colors are reset.
+        if is_csi {
+            r.push_str(chunk);
+        } else if visible < width - 1 {
+            visible += 1;
+            r.push_str(chunk);
+        } else if visible == width - 1 {
+            visible += 1;
+            r.push('…');
+        }
+    }
+    r
+}
+
+/// Wrap the line at column boundaries (spaces followed by non-space)
+/// indenting continuation lines to align with text after the first `": "`.
+pub fn wrap_line(s: &str, width: usize) -> String {
+    if width == 0 || visible_width(s) <= width {
+        return s.to_owned();
+    }
+    let indent = match strip_csi(s).find(": ") {
+        Some(i) if i + 2 < width / 2 => i + 2,
+        _ => 4,
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let bytes = s.as_bytes();
+    for i in 1..bytes.len() {
+        if bytes[i - 1] == b' ' && bytes[i] != b' ' && i - 1 > start {
+            segments.push(&s[start..i - 1]);
+            start = i;
+        }
+    }
+    segments.push(&s[start..]);
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for segment in segments {
+        if line.is_empty() {
+            line.push_str(segment);
+        } else if visible_width(&line) + 1 + visible_width(segment) <= width {
+            line.push(' ');
+            line.push_str(segment);
+        } else {
+            lines.push(line);
+            line = " ".repeat(indent);
+            line.push_str(segment);
+        }
+    }
+    lines.push(line);
+    lines
+        .iter()
+        .map(|l| truncate_line(l, width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wrap every line of the text.
+pub fn wrap_text(s: &str, width: Option<usize>) -> String {
+    match width {
+        None => s.to_owned(),
+        Some(width) => {
+            let mut r = String::new();
+            for line in s.lines() {
+                r.push_str(&wrap_line(line, width));
+                r.push('\n');
+            }
+            r
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wrap::truncate_line;
+    use crate::wrap::visible_width;
+    use crate::wrap::wrap_line;
+
+    #[test]
+    fn truncate() {
+        assert_eq!("abc", truncate_line("abc", 3));
+        assert_eq!("ab…", truncate_line("abcd", 3));
+        assert_eq!(
+            "\x1b[31mab…\x1b[0m",
+            truncate_line("\x1b[31mabcd\x1b[0m", 3)
+        );
+        assert_eq!("abcd", truncate_line("abcd", 0));
+    }
+
+    #[test]
+    fn wrap() {
+        assert_eq!("A: n=1 x=2", wrap_line("A: n=1 x=2", 20));
+        assert_eq!(
+            "A: n= 1 mean=2\n   max=3",
+            wrap_line("A: n= 1 mean=2 max=3", 14)
+        );
+        let wrapped = wrap_line("\x1b[31mA\x1b[0m: n=1 mean=2 max=3", 10);
+        assert!(
+            wrapped.lines().all(|l| visible_width(l) <= 10),
+            "{}",
+            wrapped
+        );
+    }
+}